@@ -1,6 +1,5 @@
 #! /bin/sh
 cargo build
-# cargo build --features linux_eventfd
 # cargo build --target x86_64-unknown-redox
 
 cd test_c_service
@@ -20,9 +20,21 @@ fn main() {
         
         Usage:
             rsdctl <ip-addr:port> <command> [args]
+            rsdctl <ip-addr:port> logs <unit> [-n <lines>] [--since <time>] [--until <time>] [--priority <prio>] [--follow]
+            rsdctl <ip-addr:port> <start|start-all|stop|stop-all|restart> <unit> [--job-mode <replace|fail|isolate>]
+            rsdctl <ip-addr:port> isolate <unit>
+            rsdctl <ip-addr:port> list-jobs
+            rsdctl <ip-addr:port> cancel <job-id>
+            rsdctl <ip-addr:port> load <unit> [<unit>...]
+            rsdctl <ip-addr:port> <enable|disable|mask|unmask|preset|is-enabled> <unit> [<unit>...]
         
         Example:
             rsdctl 0.0.0.0:8080 restart test.service
+            rsdctl 0.0.0.0:8080 daemon-reload
+            rsdctl 0.0.0.0:8080 daemon-reexec
+            rsdctl 0.0.0.0:8080 enable test.service
+            rsdctl 0.0.0.0:8080 logs test.service --since -5min --priority err
+            rsdctl 0.0.0.0:8080 start-all test.target --job-mode isolate
         ");
         return;
     }
@@ -34,7 +46,24 @@ fn main() {
     };
     let args = args;
 
-    let params = if args.len() == 2 {
+    let is_logs = args[0] == "logs";
+    let params = if is_logs {
+        match logs_params(&args[1..]) {
+            Ok(params) => Some(params),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if args.iter().any(|arg| arg == "--job-mode") {
+        match job_params(&args[1..]) {
+            Ok(params) => Some(params),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+    } else if args.len() == 2 {
         Some(Value::String(args[1].clone()))
     } else if args.len() > 1 {
         Some({
@@ -61,17 +90,99 @@ fn main() {
         stream.write_all(str_call.as_bytes()).unwrap();
         stream.shutdown(std::net::Shutdown::Write).unwrap();
         println!("Wait for response");
-        let resp: Value = serde_json::from_reader(&mut stream).unwrap();
-        println!("Got response");
-        println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+        read_responses(&mut stream, is_logs);
     } else {
         let mut stream = std::net::TcpStream::connect(addr).unwrap();
         println!("Write cmd: {}", str_call);
         stream.write_all(str_call.as_bytes()).unwrap();
         stream.shutdown(std::net::Shutdown::Write).unwrap();
         println!("Wait for response");
-        let resp: Value = serde_json::from_reader(&mut stream).unwrap();
-        println!("Got response");
-        println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+        read_responses(&mut stream, is_logs);
     };
 }
+
+/// Pack the cli args of the logs command into the params object
+fn logs_params(args: &[String]) -> Result<Value, String> {
+    let mut params = serde_json::Map::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value_for = |name: &str| {
+            args.next()
+                .cloned()
+                .ok_or_else(|| format!("Missing value for {}", name))
+        };
+        match arg.as_str() {
+            "-n" | "--lines" => {
+                let lines = value_for(arg)?;
+                let lines = lines
+                    .parse::<u64>()
+                    .map_err(|_| format!("Not a number: {}", lines))?;
+                params.insert("lines".into(), Value::from(lines));
+            }
+            "--since" => {
+                params.insert("since".into(), Value::String(value_for(arg)?));
+            }
+            "--until" => {
+                params.insert("until".into(), Value::String(value_for(arg)?));
+            }
+            "-p" | "--priority" => {
+                params.insert("priority".into(), Value::String(value_for(arg)?));
+            }
+            "-f" | "--follow" => {
+                params.insert("follow".into(), Value::Bool(true));
+            }
+            unit => {
+                params.insert("unit".into(), Value::String(unit.to_owned()));
+            }
+        }
+    }
+    Ok(Value::Object(params))
+}
+
+/// Pack the unit and the --job-mode of the start/stop/restart commands into the params object
+fn job_params(args: &[String]) -> Result<Value, String> {
+    let mut params = serde_json::Map::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--job-mode" => {
+                let mode = args
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| format!("Missing value for {}", arg))?;
+                params.insert("mode".into(), Value::String(mode));
+            }
+            unit => {
+                params.insert("unit".into(), Value::String(unit.to_owned()));
+            }
+        }
+    }
+    Ok(Value::Object(params))
+}
+
+/// Print the responses until the connection is closed. Log entries are printed one per line,
+/// everything else is pretty printed json.
+fn read_responses<R: std::io::Read>(stream: &mut R, is_logs: bool) {
+    for resp in serde_json::Deserializer::from_reader(stream).into_iter::<Value>() {
+        let resp = resp.unwrap();
+        match resp.get("result") {
+            Some(Value::Array(entries)) if is_logs => {
+                for entry in entries {
+                    let field = |name: &str| entry.get(name).and_then(|v| v.as_str()).unwrap_or("");
+                    println!(
+                        "{} {}[{}] <{}>: {}",
+                        field("Timestamp"),
+                        field("Identifier"),
+                        field("Pid"),
+                        field("Priority"),
+                        field("Message")
+                    );
+                }
+            }
+            _ => {
+                println!("Got response");
+                println!("{}", serde_json::to_string_pretty(&resp).unwrap());
+            }
+        }
+    }
+}
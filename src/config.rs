@@ -3,12 +3,17 @@
 //! Currently configurable:
 //! ### Logging
 //! 1. Wether or not to log to disk (and the dir to put the logs in)
+//! 1. How large the log files may grow before they are rotated and how many rotated files are kept
 //! 1. Wether or not to log to stdout
+//! 1. Wether or not to forward logs to syslog (the socket, the message format and wether to fall back to /dev/kmsg)
 //!
 //! ### General config
 //! 1. Where to find the units (one or more directories)
+//! 1. The writable unit dir in which enable/disable/mask create their symlinks and where to find preset files
 //! 1. notification-socket directory (where the unix-domain sockets are placed on which services can notify rustysd)
 //! 1. Which unit is the target that should be started
+//! 1. Default resource limits for all services (DefaultLimitNOFILE etc., the same values as the Limit*= settings)
+//! 1. Where the uids of services with DynamicUser=yes are persisted and from which range they are allocated
 
 use std::{collections::HashMap, fs::File, io::Read, path::PathBuf};
 use toml;
@@ -18,13 +23,40 @@ pub struct LoggingConfig {
     pub log_to_stdout: bool,
     pub log_to_disk: bool,
     pub log_dir: PathBuf,
+    /// Size in bytes after which the log file is rotated
+    pub log_max_file_size: u64,
+    /// How many rotated log files are kept
+    pub log_max_files: usize,
+    pub log_to_syslog: bool,
+    pub syslog_socket: PathBuf,
+    pub syslog_format: crate::logging::SyslogFormat,
+    /// Write to /dev/kmsg if the syslog socket can not be reached (yet)
+    pub syslog_kmsg_fallback: bool,
 }
 
 #[derive(Debug, Clone)]
 pub struct Config {
     pub unit_dirs: Vec<PathBuf>,
+    /// Writable unit dir for the symlinks of enable/disable/mask. If it is set the [Install] sections only take effect through these
+    /// symlinks like with systemd, otherwise WantedBy=/RequiredBy= are applied directly. It is always the first of the unit_dirs
+    pub install_dir: Option<PathBuf>,
+    /// Dirs with *.preset files for the preset command
+    pub preset_dirs: Vec<PathBuf>,
     pub target_unit: String,
     pub notification_sockets_dir: PathBuf,
+    pub dynamic_users_dir: PathBuf,
+    /// Range (inclusive) from which uids for services with DynamicUser=yes are allocated
+    pub dynamic_user_min_uid: u32,
+    pub dynamic_user_max_uid: u32,
+    /// Overridden by the Limit*= settings of the units
+    pub default_limits: crate::units::ResourceLimits,
+}
+
+impl Config {
+    /// Without an install dir WantedBy=/RequiredBy= are applied directly instead of through the symlinks of enable
+    pub fn apply_install_sections(&self) -> bool {
+        self.install_dir.is_none()
+    }
 }
 
 #[derive(Debug)]
@@ -32,6 +64,7 @@ enum SettingValue {
     Str(String),
     Array(Vec<SettingValue>),
     Boolean(bool),
+    Number(u64),
 }
 
 fn load_toml(
@@ -69,10 +102,63 @@ fn load_toml(
             settings.insert("logging.dir".to_owned(), SettingValue::Str(val.clone()));
         }
         if let Some(toml::Value::Boolean(val)) = map.get("log_to_disk") {
-            settings.insert("logging.to.disk".to_owned(), SettingValue::Boolean(*val));
+            settings.insert("logging.to_disk".to_owned(), SettingValue::Boolean(*val));
         }
         if let Some(toml::Value::Boolean(val)) = map.get("log_to_stdout") {
-            settings.insert("logging.to.stdout".to_owned(), SettingValue::Boolean(*val));
+            settings.insert("logging.to_stdout".to_owned(), SettingValue::Boolean(*val));
+        }
+        if let Some(toml::Value::Integer(val)) = map.get("log_max_file_size") {
+            settings.insert(
+                "logging.max_file_size".to_owned(),
+                SettingValue::Number(*val as u64),
+            );
+        }
+        if let Some(toml::Value::Integer(val)) = map.get("log_max_files") {
+            settings.insert(
+                "logging.max_files".to_owned(),
+                SettingValue::Number(*val as u64),
+            );
+        }
+        if let Some(toml::Value::Boolean(val)) = map.get("log_to_syslog") {
+            settings.insert("logging.to_syslog".to_owned(), SettingValue::Boolean(*val));
+        }
+        if let Some(toml::Value::String(val)) = map.get("syslog_socket") {
+            settings.insert(
+                "logging.syslog_socket".to_owned(),
+                SettingValue::Str(val.clone()),
+            );
+        }
+        if let Some(toml::Value::String(val)) = map.get("syslog_format") {
+            settings.insert(
+                "logging.syslog_format".to_owned(),
+                SettingValue::Str(val.clone()),
+            );
+        }
+        if let Some(toml::Value::Boolean(val)) = map.get("syslog_kmsg_fallback") {
+            settings.insert(
+                "logging.syslog_kmsg_fallback".to_owned(),
+                SettingValue::Boolean(*val),
+            );
+        }
+        if let Some(toml::Value::String(val)) = map.get("install_dir") {
+            settings.insert("install.dir".to_owned(), SettingValue::Str(val.clone()));
+        }
+        if let Some(toml::Value::Array(elems)) = map.get("preset_dirs") {
+            settings.insert(
+                "preset.dirs".to_owned(),
+                SettingValue::Array(
+                    elems
+                        .iter()
+                        .map(|e| {
+                            if let toml::Value::String(s) = e {
+                                SettingValue::Str(s.clone())
+                            } else {
+                                SettingValue::Str("".to_owned())
+                            }
+                        })
+                        .collect(),
+                ),
+            );
         }
         if let Some(toml::Value::String(val)) = map.get("target_unit") {
             settings.insert("target.unit".to_owned(), SettingValue::Str(val.clone()));
@@ -83,6 +169,43 @@ fn load_toml(
                 SettingValue::Str(val.clone()),
             );
         }
+        for (key, val) in map {
+            if let Some(name) = key.strip_prefix("DefaultLimit") {
+                let val = match val {
+                    toml::Value::String(s) => s.clone(),
+                    toml::Value::Integer(i) => i.to_string(),
+                    _ => continue,
+                };
+                settings.insert(
+                    format!("defaultlimit.{}", name.to_lowercase()),
+                    SettingValue::Str(val),
+                );
+            }
+        }
+        if let Some(toml::Value::String(val)) = map.get("dynamic_users_dir") {
+            settings.insert(
+                "dynamic_users.dir".to_owned(),
+                SettingValue::Str(val.clone()),
+            );
+        }
+        if let Some(val) = map
+            .get("dynamic_user_min_uid")
+            .and_then(|val| val.as_integer().map(|val| val as u64))
+        {
+            settings.insert(
+                "dynamic_users.min_uid".to_owned(),
+                SettingValue::Number(val),
+            );
+        }
+        if let Some(val) = map
+            .get("dynamic_user_max_uid")
+            .and_then(|val| val.as_integer().map(|val| val as u64))
+        {
+            settings.insert(
+                "dynamic_users.max_uid".to_owned(),
+                SettingValue::Number(val),
+            );
+        }
     }
     Ok(())
 }
@@ -124,6 +247,56 @@ fn load_json(
         if let Some(serde_json::Value::Bool(val)) = map.get("log_to_stdout") {
             settings.insert("logging.to_stdout".to_owned(), SettingValue::Boolean(*val));
         }
+        if let Some(val) = map.get("log_max_file_size").and_then(|val| val.as_u64()) {
+            settings.insert(
+                "logging.max_file_size".to_owned(),
+                SettingValue::Number(val),
+            );
+        }
+        if let Some(val) = map.get("log_max_files").and_then(|val| val.as_u64()) {
+            settings.insert("logging.max_files".to_owned(), SettingValue::Number(val));
+        }
+        if let Some(serde_json::Value::Bool(val)) = map.get("log_to_syslog") {
+            settings.insert("logging.to_syslog".to_owned(), SettingValue::Boolean(*val));
+        }
+        if let Some(serde_json::Value::String(val)) = map.get("syslog_socket") {
+            settings.insert(
+                "logging.syslog_socket".to_owned(),
+                SettingValue::Str(val.clone()),
+            );
+        }
+        if let Some(serde_json::Value::String(val)) = map.get("syslog_format") {
+            settings.insert(
+                "logging.syslog_format".to_owned(),
+                SettingValue::Str(val.clone()),
+            );
+        }
+        if let Some(serde_json::Value::Bool(val)) = map.get("syslog_kmsg_fallback") {
+            settings.insert(
+                "logging.syslog_kmsg_fallback".to_owned(),
+                SettingValue::Boolean(*val),
+            );
+        }
+        if let Some(serde_json::Value::String(val)) = map.get("install_dir") {
+            settings.insert("install.dir".to_owned(), SettingValue::Str(val.clone()));
+        }
+        if let Some(serde_json::Value::Array(elems)) = map.get("preset_dirs") {
+            settings.insert(
+                "preset.dirs".to_owned(),
+                SettingValue::Array(
+                    elems
+                        .iter()
+                        .map(|e| {
+                            if let serde_json::Value::String(s) = e {
+                                SettingValue::Str(s.clone())
+                            } else {
+                                SettingValue::Str("".to_owned())
+                            }
+                        })
+                        .collect(),
+                ),
+            );
+        }
         if let Some(serde_json::Value::String(val)) = map.get("target_unit") {
             settings.insert("target.unit".to_owned(), SettingValue::Str(val.clone()));
         }
@@ -133,6 +306,37 @@ fn load_json(
                 SettingValue::Str(val.clone()),
             );
         }
+        for (key, val) in map {
+            if let Some(name) = key.strip_prefix("DefaultLimit") {
+                let val = match val {
+                    serde_json::Value::String(s) => s.clone(),
+                    serde_json::Value::Number(n) => n.to_string(),
+                    _ => continue,
+                };
+                settings.insert(
+                    format!("defaultlimit.{}", name.to_lowercase()),
+                    SettingValue::Str(val),
+                );
+            }
+        }
+        if let Some(serde_json::Value::String(val)) = map.get("dynamic_users_dir") {
+            settings.insert(
+                "dynamic_users.dir".to_owned(),
+                SettingValue::Str(val.clone()),
+            );
+        }
+        if let Some(val) = map.get("dynamic_user_min_uid").and_then(|val| val.as_u64()) {
+            settings.insert(
+                "dynamic_users.min_uid".to_owned(),
+                SettingValue::Number(val),
+            );
+        }
+        if let Some(val) = map.get("dynamic_user_max_uid").and_then(|val| val.as_u64()) {
+            settings.insert(
+                "dynamic_users.max_uid".to_owned(),
+                SettingValue::Number(val),
+            );
+        }
     }
     Ok(())
 }
@@ -190,37 +394,124 @@ pub fn load_config(config_path: &Option<PathBuf>) -> (LoggingConfig, Result<Conf
         SettingValue::Boolean(b) => *b,
         _ => false,
     });
+    let log_max_file_size = settings
+        .get("logging.max_file_size")
+        .and_then(|val| match val {
+            SettingValue::Number(n) => Some(*n),
+            SettingValue::Str(s) => s.parse::<u64>().ok(),
+            _ => None,
+        });
+    let log_max_files = settings.get("logging.max_files").and_then(|val| match val {
+        SettingValue::Number(n) => Some(*n as usize),
+        SettingValue::Str(s) => s.parse::<usize>().ok(),
+        _ => None,
+    });
+    let log_to_syslog = settings.get("logging.to_syslog").map(|val| match val {
+        SettingValue::Boolean(b) => *b,
+        _ => false,
+    });
+    let syslog_socket = settings
+        .get("logging.syslog_socket")
+        .and_then(|val| match val {
+            SettingValue::Str(s) => Some(PathBuf::from(s)),
+            _ => None,
+        });
+    let syslog_format = match settings.get("logging.syslog_format") {
+        Some(SettingValue::Str(s)) => match s.as_str() {
+            "rfc3164" => Ok(crate::logging::SyslogFormat::Rfc3164),
+            "rfc5424" => Ok(crate::logging::SyslogFormat::Rfc5424),
+            _ => Err(format!("Unknown syslog format: {}", s)),
+        },
+        _ => Ok(crate::logging::SyslogFormat::Rfc3164),
+    };
+    let syslog_kmsg_fallback = settings
+        .get("logging.syslog_kmsg_fallback")
+        .map(|val| match val {
+            SettingValue::Boolean(b) => *b,
+            _ => false,
+        });
 
     let notification_sockets_dir = settings.get("notifications.dir").map(|dir| match dir {
         SettingValue::Str(s) => Some(PathBuf::from(s)),
         _ => None,
     });
+    let dynamic_users_dir = settings.get("dynamic_users.dir").and_then(|dir| match dir {
+        SettingValue::Str(s) => Some(PathBuf::from(s)),
+        _ => None,
+    });
+    let dynamic_user_uid = |key: &str| {
+        settings.get(key).and_then(|val| match val {
+            SettingValue::Number(n) => Some(*n as u32),
+            SettingValue::Str(s) => s.parse::<u32>().ok(),
+            _ => None,
+        })
+    };
+    let dynamic_user_min_uid = dynamic_user_uid("dynamic_users.min_uid");
+    let dynamic_user_max_uid = dynamic_user_uid("dynamic_users.max_uid");
+    // the env var for DefaultLimitNOFILE is RUSTYSD_DEFAULTLIMIT_NOFILE
+    let mut default_limits = Ok(crate::units::ResourceLimits::default());
+    for (resource, name) in &crate::units::LIMIT_NAMES {
+        let key = format!("defaultlimit.{}", name.to_lowercase());
+        if let Some(SettingValue::Str(val)) = settings.get(&key) {
+            default_limits = default_limits.and_then(|mut limits| {
+                limits.set(
+                    *resource,
+                    crate::units::parse_limit_value(*resource, val)
+                        .map_err(|e| format!("DefaultLimit{}: {}", name, e))?,
+                );
+                Ok(limits)
+            });
+        }
+    }
     let target_unit = settings.get("target.unit").map(|name| match name {
         SettingValue::Str(s) => Some(s.clone()),
         _ => None,
     });
 
-    let unit_dirs = settings.get("unit.dirs").map(|dir| match dir {
-        SettingValue::Str(s) => vec![PathBuf::from(s)],
-        SettingValue::Array(arr) => arr
-            .iter()
-            .map(|el| match el {
-                SettingValue::Str(s) => Some(PathBuf::from(s)),
-                _ => None,
-            })
-            .fold(Vec::new(), |mut acc, el| {
-                if let Some(path) = el {
-                    if path.exists() {
-                        acc.push(path)
+    let existing_dirs = |key: &str| {
+        settings.get(key).map(|dir| match dir {
+            SettingValue::Str(s) => vec![PathBuf::from(s)],
+            SettingValue::Array(arr) => arr
+                .iter()
+                .map(|el| match el {
+                    SettingValue::Str(s) => Some(PathBuf::from(s)),
+                    _ => None,
+                })
+                .fold(Vec::new(), |mut acc, el| {
+                    if let Some(path) = el {
+                        if path.exists() {
+                            acc.push(path)
+                        }
                     }
-                }
-                acc
-            }),
-        _ => Vec::new(),
-    });
+                    acc
+                }),
+            _ => Vec::new(),
+        })
+    };
+    let mut unit_dirs = existing_dirs("unit.dirs");
+    let preset_dirs = existing_dirs("preset.dirs");
+
+    let install_dir = match settings.get("install.dir") {
+        Some(SettingValue::Str(s)) => Some(PathBuf::from(s)),
+        _ => None,
+    };
+    let install_dir_err = match &install_dir {
+        Some(dir) => match std::fs::create_dir_all(dir) {
+            Ok(()) => {
+                let dirs = unit_dirs.get_or_insert_with(|| vec![PathBuf::from("./unitfiles")]);
+                dirs.retain(|other| other != dir);
+                dirs.insert(0, dir.clone());
+                None
+            }
+            Err(e) => Some(format!("Could not create install dir {:?}: {}", dir, e)),
+        },
+        None => None,
+    };
 
     let config = Config {
         unit_dirs: unit_dirs.unwrap_or_else(|| vec![PathBuf::from("./unitfiles")]),
+        install_dir,
+        preset_dirs: preset_dirs.unwrap_or_default(),
         target_unit: target_unit
             .unwrap_or(Some("default.target".to_owned()))
             .unwrap(),
@@ -228,9 +519,21 @@ pub fn load_config(config_path: &Option<PathBuf>) -> (LoggingConfig, Result<Conf
         notification_sockets_dir: notification_sockets_dir
             .unwrap_or_else(|| Some(PathBuf::from("./notifications")))
             .unwrap(),
+        dynamic_users_dir: dynamic_users_dir
+            .unwrap_or_else(|| PathBuf::from("/var/lib/rustysd/dynamic_users")),
+        // the same range systemd uses
+        dynamic_user_min_uid: dynamic_user_min_uid.unwrap_or(61184),
+        dynamic_user_max_uid: dynamic_user_max_uid.unwrap_or(65519),
+        default_limits: default_limits.clone().unwrap_or_default(),
     };
 
-    let conf = if let Some(json_conf) = json_conf {
+    let conf = if let Err(e) = &syslog_format {
+        Err(e.clone())
+    } else if let Err(e) = default_limits {
+        Err(e)
+    } else if let Some(e) = install_dir_err {
+        Err(e)
+    } else if let Some(json_conf) = json_conf {
         if toml_conf.is_some() {
             Err(format!("Found both json and toml conf!"))
         } else {
@@ -260,6 +563,12 @@ pub fn load_config(config_path: &Option<PathBuf>) -> (LoggingConfig, Result<Conf
                 .unwrap_or_else(|| PathBuf::from("./logs")),
             log_to_disk: log_to_disk.unwrap_or(false),
             log_to_stdout: log_to_stdout.unwrap_or(true),
+            log_max_file_size: log_max_file_size.unwrap_or(10 * 1024 * 1024),
+            log_max_files: log_max_files.unwrap_or(5),
+            log_to_syslog: log_to_syslog.unwrap_or(false),
+            syslog_socket: syslog_socket.unwrap_or_else(|| PathBuf::from("/dev/log")),
+            syslog_format: syslog_format.unwrap_or(crate::logging::SyslogFormat::Rfc3164),
+            syslog_kmsg_fallback: syslog_kmsg_fallback.unwrap_or(true),
         },
         conf,
     )
@@ -1,3 +1,4 @@
+use crate::jobs::*;
 use crate::runtime_info::*;
 use crate::units::*;
 
@@ -37,12 +38,39 @@ pub enum Command {
     LoadAllNew,
     LoadAllNewDry,
     Remove(String),
-    Restart(String),
-    Start(String),
-    StartAll(String),
-    Stop(String),
-    StopAll(String),
+    Restart(String, JobMode),
+    Start(String, JobMode),
+    StartAll(String, JobMode),
+    Stop(String, JobMode),
+    StopAll(String, JobMode),
+    Isolate(String),
+    DaemonReload,
+    DaemonReexec,
+    Install(InstallAction, Vec<String>),
+    IsEnabled(Vec<String>),
+    ListJobs,
+    CancelJob(JobId),
     Shutdown,
+    Logs(LogsQuery),
+}
+
+/// The calls that change the symlinks in the install dir
+#[derive(Debug, Clone, Copy)]
+pub enum InstallAction {
+    Enable,
+    Disable,
+    Mask,
+    Unmask,
+    Preset,
+}
+
+#[derive(Debug)]
+pub struct LogsQuery {
+    pub filter: crate::log_store::LogFilter,
+    /// How many of the last matching lines to return. None returns all.
+    pub lines: Option<usize>,
+    /// Keep the connection open and send new entries as they are logged
+    pub follow: bool,
 }
 
 enum ParseError {
@@ -67,58 +95,16 @@ fn parse_command(call: &super::jsonrpc2::Call) -> Result<Command, ParseError> {
             Command::Status(name)
         }
         "restart" => {
-            let name = match &call.params {
-                Some(params) => match params {
-                    Value::String(s) => s.clone(),
-                    _ => {
-                        return Err(ParseError::ParamsInvalid(format!(
-                            "Params must be a single string"
-                        )))
-                    }
-                },
-                None => {
-                    return Err(ParseError::ParamsInvalid(format!(
-                        "Params must be a single string"
-                    )))
-                }
-            };
-            Command::Restart(name)
+            let (name, mode) = parse_job_params(&call.params)?;
+            Command::Restart(name, mode)
         }
         "start" => {
-            let name = match &call.params {
-                Some(params) => match params {
-                    Value::String(s) => s.clone(),
-                    _ => {
-                        return Err(ParseError::ParamsInvalid(format!(
-                            "Params must be a single string"
-                        )))
-                    }
-                },
-                None => {
-                    return Err(ParseError::ParamsInvalid(format!(
-                        "Params must be a single string"
-                    )))
-                }
-            };
-            Command::Start(name)
+            let (name, mode) = parse_job_params(&call.params)?;
+            Command::Start(name, mode)
         }
         "start-all" => {
-            let name = match &call.params {
-                Some(params) => match params {
-                    Value::String(s) => s.clone(),
-                    _ => {
-                        return Err(ParseError::ParamsInvalid(format!(
-                            "Params must be a single string"
-                        )))
-                    }
-                },
-                None => {
-                    return Err(ParseError::ParamsInvalid(format!(
-                        "Params must be a single string"
-                    )))
-                }
-            };
-            Command::StartAll(name)
+            let (name, mode) = parse_job_params(&call.params)?;
+            Command::StartAll(name, mode)
         }
         "remove" => {
             let name = match &call.params {
@@ -139,40 +125,17 @@ fn parse_command(call: &super::jsonrpc2::Call) -> Result<Command, ParseError> {
             Command::Remove(name)
         }
         "stop" => {
-            let name = match &call.params {
-                Some(params) => match params {
-                    Value::String(s) => s.clone(),
-                    _ => {
-                        return Err(ParseError::ParamsInvalid(format!(
-                            "Params must be a single string"
-                        )))
-                    }
-                },
-                None => {
-                    return Err(ParseError::ParamsInvalid(format!(
-                        "Params must be a single string"
-                    )))
-                }
-            };
-            Command::Stop(name)
+            let (name, mode) = parse_job_params(&call.params)?;
+            Command::Stop(name, mode)
         }
         "stop-all" => {
-            let name = match &call.params {
-                Some(params) => match params {
-                    Value::String(s) => s.clone(),
-                    _ => {
-                        return Err(ParseError::ParamsInvalid(format!(
-                            "Params must be a single string"
-                        )))
-                    }
-                },
-                None => {
-                    return Err(ParseError::ParamsInvalid(format!(
-                        "Params must be a single string"
-                    )))
-                }
-            };
-            Command::StopAll(name)
+            let (name, mode) = parse_job_params(&call.params)?;
+            Command::StopAll(name, mode)
+        }
+        "isolate" => {
+            // the mode is always isolate
+            let (name, _) = parse_job_params(&call.params)?;
+            Command::Isolate(name)
         }
 
         "list-units" => {
@@ -202,49 +165,241 @@ fn parse_command(call: &super::jsonrpc2::Call) -> Result<Command, ParseError> {
             };
             Command::ListUnits(kind)
         }
+        "list-jobs" => Command::ListJobs,
+        "cancel" => {
+            let id = match &call.params {
+                Some(Value::Number(num)) => num.as_u64(),
+                Some(Value::String(s)) => s.parse::<u64>().ok(),
+                _ => None,
+            };
+            match id {
+                Some(id) => Command::CancelJob(id),
+                None => {
+                    return Err(ParseError::ParamsInvalid(
+                        "Params must be a single job id".into(),
+                    ))
+                }
+            }
+        }
         "shutdown" => Command::Shutdown,
+        "logs" => Command::Logs(parse_logs_query(&call.params)?),
         "reload" => Command::LoadAllNew,
+        "daemon-reload" => Command::DaemonReload,
+        "daemon-reexec" => Command::DaemonReexec,
         "reload-dry" => Command::LoadAllNewDry,
-        "enable" => {
-            let names = match &call.params {
-                Some(params) => match params {
-                    Value::String(s) => vec![s.clone()],
-                    Value::Array(names) => {
-                        let mut str_names = Vec::new();
-                        for name in names {
-                            if let Value::String(name) = name {
-                                str_names.push(name.clone());
-                            } else {
-                                return Err(ParseError::ParamsInvalid(format!(
-                                    "Params must be at least one string"
-                                )));
+        "load" => Command::LoadNew(parse_names(&call.params)?),
+        "enable" => Command::Install(InstallAction::Enable, parse_names(&call.params)?),
+        "disable" => Command::Install(InstallAction::Disable, parse_names(&call.params)?),
+        "mask" => Command::Install(InstallAction::Mask, parse_names(&call.params)?),
+        "unmask" => Command::Install(InstallAction::Unmask, parse_names(&call.params)?),
+        "preset" => Command::Install(InstallAction::Preset, parse_names(&call.params)?),
+        "is-enabled" => Command::IsEnabled(parse_names(&call.params)?),
+        _ => {
+            return Err(ParseError::MethodNotFound(format!(
+                "Unknown method: {}",
+                call.method
+            )))
+        }
+    };
+
+    Ok(command)
+}
+
+/// Params of the calls that take one or more unit names. Either a single string or an array of strings
+fn parse_names(params: &Option<Value>) -> Result<Vec<String>, ParseError> {
+    let invalid = || ParseError::ParamsInvalid("Params must be at least one string".into());
+    match params {
+        Some(Value::String(s)) => Ok(vec![s.clone()]),
+        Some(Value::Array(names)) => {
+            let mut str_names = Vec::new();
+            for name in names {
+                if let Value::String(name) = name {
+                    str_names.push(name.clone());
+                } else {
+                    return Err(invalid());
+                }
+            }
+            Ok(str_names)
+        }
+        _ => Err(invalid()),
+    }
+}
+
+/// Params of the calls that create jobs. Either the unit name or an object with the unit name and the job mode
+/// ("replace", "fail" or "isolate"). The mode defaults to replace.
+fn parse_job_params(params: &Option<Value>) -> Result<(String, JobMode), ParseError> {
+    match params {
+        Some(Value::String(s)) => Ok((s.clone(), JobMode::Replace)),
+        Some(Value::Object(map)) => {
+            let name = match map.get("unit") {
+                Some(Value::String(name)) => name.clone(),
+                _ => return Err(ParseError::ParamsInvalid("unit must be a string".into())),
+            };
+            let mode = match map.get("mode") {
+                Some(Value::String(mode)) => JobMode::parse(mode).ok_or_else(|| {
+                    ParseError::ParamsInvalid(format!("Unknown job mode: {}", mode))
+                })?,
+                None => JobMode::Replace,
+                _ => return Err(ParseError::ParamsInvalid("mode must be a string".into())),
+            };
+            Ok((name, mode))
+        }
+        _ => Err(ParseError::ParamsInvalid(
+            "Params must be a single string or an object".into(),
+        )),
+    }
+}
+
+/// Parse a point in time for the logs call. Either seconds since the unix epoch,
+/// "now" or a duration into the past like "-30s", "-5min", "-2h" or "-1d".
+/// Returns microseconds since the unix epoch.
+fn parse_log_time(val: &Value) -> Result<u64, String> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap()
+        .as_micros() as u64;
+    match val {
+        Value::Number(num) => match num.as_f64() {
+            Some(secs) if secs >= 0.0 => Ok((secs * 1_000_000.0) as u64),
+            _ => Err(format!("Invalid time: {}", num)),
+        },
+        Value::String(s) => {
+            if s == "now" {
+                Ok(now)
+            } else if let Some(ago) = s.strip_prefix('-') {
+                let split_pos = ago.find(|c: char| !c.is_ascii_digit()).unwrap_or(ago.len());
+                let (num, unit) = ago.split_at(split_pos);
+                let num = num
+                    .parse::<u64>()
+                    .map_err(|_| format!("Invalid time: {}", s))?;
+                let secs = match unit {
+                    "" | "s" => num,
+                    "min" => num * 60,
+                    "h" => num * 60 * 60,
+                    "d" => num * 60 * 60 * 24,
+                    _ => return Err(format!("Invalid time unit in: {}", s)),
+                };
+                Ok(now.saturating_sub(secs * 1_000_000))
+            } else {
+                match s.parse::<f64>() {
+                    Ok(secs) if secs >= 0.0 => Ok((secs * 1_000_000.0) as u64),
+                    _ => Err(format!("Invalid time: {}", s)),
+                }
+            }
+        }
+        _ => Err("Time must be a number or a string".into()),
+    }
+}
+
+fn parse_logs_query(params: &Option<Value>) -> Result<LogsQuery, ParseError> {
+    let mut query = LogsQuery {
+        filter: crate::log_store::LogFilter::default(),
+        lines: Some(10),
+        follow: false,
+    };
+    match params {
+        Some(Value::String(unit)) => {
+            query.filter.unit = Some(unit.clone());
+        }
+        Some(Value::Object(map)) => {
+            for (key, val) in map {
+                match key.as_str() {
+                    "unit" => match val {
+                        Value::String(unit) => query.filter.unit = Some(unit.clone()),
+                        _ => return Err(ParseError::ParamsInvalid("unit must be a string".into())),
+                    },
+                    "lines" => match val.as_u64() {
+                        // 0 means all lines
+                        Some(0) => query.lines = None,
+                        Some(lines) => query.lines = Some(lines as usize),
+                        None => {
+                            return Err(ParseError::ParamsInvalid(
+                                "lines must be a positive number".into(),
+                            ))
+                        }
+                    },
+                    "since" => {
+                        query.filter.since =
+                            Some(parse_log_time(val).map_err(ParseError::ParamsInvalid)?)
+                    }
+                    "until" => {
+                        query.filter.until =
+                            Some(parse_log_time(val).map_err(ParseError::ParamsInvalid)?)
+                    }
+                    "priority" => {
+                        let prio = match val {
+                            Value::String(s) => crate::log_store::parse_priority(s),
+                            Value::Number(num) => {
+                                crate::log_store::parse_priority(&num.to_string())
+                            }
+                            _ => None,
+                        };
+                        match prio {
+                            Some(prio) => query.filter.priority = Some(prio),
+                            None => {
+                                return Err(ParseError::ParamsInvalid("priority must be a number between 0 and 7 or a name like \"err\" or \"info\"".into()))
                             }
                         }
-                        str_names
                     }
+                    "follow" => match val {
+                        Value::Bool(follow) => query.follow = *follow,
+                        _ => {
+                            return Err(ParseError::ParamsInvalid(
+                                "follow must be a boolean".into(),
+                            ))
+                        }
+                    },
                     _ => {
                         return Err(ParseError::ParamsInvalid(format!(
-                            "Params must be at least one string"
+                            "Unknown param for logs: {}",
+                            key
                         )))
                     }
-                },
-                None => {
-                    return Err(ParseError::ParamsInvalid(format!(
-                        "Params must be at least one string"
-                    )))
                 }
-            };
-            Command::LoadNew(names)
+            }
         }
         _ => {
-            return Err(ParseError::MethodNotFound(format!(
-                "Unknown method: {}",
-                call.method
-            )))
+            return Err(ParseError::ParamsInvalid(
+                "Params must be a single string or an object".into(),
+            ))
         }
-    };
+    }
+    if query.filter.unit.is_none() {
+        return Err(ParseError::ParamsInvalid("A unit must be given".into()));
+    }
+    Ok(query)
+}
 
-    Ok(command)
+pub fn format_log_entry(entry: &crate::log_store::LogEntry) -> Value {
+    let mut map = serde_json::Map::new();
+    use chrono::TimeZone;
+    let time = chrono::Local.timestamp_opt(
+        (entry.timestamp / 1_000_000) as i64,
+        ((entry.timestamp % 1_000_000) * 1000) as u32,
+    );
+    map.insert(
+        "Timestamp".into(),
+        Value::String(match time.single() {
+            Some(time) => format!("{}", time.format("%Y-%m-%d %H:%M:%S%.6f")),
+            None => format!("{}", entry.timestamp),
+        }),
+    );
+    map.insert("Unit".into(), Value::String(entry.unit.clone()));
+    map.insert("Identifier".into(), Value::String(entry.identifier.clone()));
+    map.insert("Stream".into(), Value::String(entry.stream.as_str().into()));
+    map.insert(
+        "Pid".into(),
+        Value::String(match entry.pid {
+            Some(pid) => format!("{}", pid),
+            None => "".into(),
+        }),
+    );
+    map.insert(
+        "Priority".into(),
+        Value::String(crate::log_store::priority_name(entry.priority).into()),
+    );
+    map.insert("Message".into(), Value::String(entry.message.clone()));
+    Value::Object(map)
 }
 
 pub fn format_socket(socket_unit: &Unit, status: UnitStatus) -> Value {
@@ -267,6 +422,30 @@ pub fn format_socket(socket_unit: &Unit, status: UnitStatus) -> Value {
                     .collect(),
             ),
         );
+        let state = &*sock.state.read().unwrap();
+        map.insert(
+            "Triggers".into(),
+            Value::String(format!("{}", state.sock.triggers.total)),
+        );
+        map.insert(
+            "TriggersInInterval".into(),
+            Value::String(format!("{}", state.sock.triggers.in_interval)),
+        );
+        map.insert(
+            "Polls".into(),
+            Value::String(format!("{}", state.sock.polls.total)),
+        );
+        map.insert(
+            "PollingPaused".into(),
+            Value::String(format!(
+                "{}",
+                state
+                    .sock
+                    .polling_paused_until
+                    .map(|until| until > std::time::Instant::now())
+                    .unwrap_or(false)
+            )),
+        );
     }
 
     Value::Object(map)
@@ -279,6 +458,34 @@ pub fn format_target(socket_unit: &Unit, status: UnitStatus) -> Value {
     Value::Object(map)
 }
 
+/// Units whose file changed or was removed while they were running get the new config when they are stopped
+fn add_needs_restart(formatted: &mut Value, id: &UnitId, run_info: &RuntimeInfo) {
+    if let Value::Object(map) = formatted {
+        map.insert(
+            "NeedsRestart".into(),
+            Value::Bool(run_info.pending_reload.contains_key(id)),
+        );
+    }
+}
+
+/// Masked units refuse to start. Shadowed files have the same name as the unit but are in a later unit dir
+fn add_unit_files(formatted: &mut Value, unit: &Unit) {
+    if let Value::Object(map) = formatted {
+        map.insert("Masked".into(), Value::Bool(unit.common.unit.masked));
+        map.insert(
+            "ShadowedFiles".into(),
+            Value::Array(
+                unit.common
+                    .unit
+                    .shadowed_files
+                    .iter()
+                    .map(|path| Value::String(path.to_string_lossy().into_owned()))
+                    .collect(),
+            ),
+        );
+    }
+}
+
 pub fn format_service(srvc_unit: &Unit, status: UnitStatus) -> Value {
     let mut map = serde_json::Map::new();
     map.insert("Name".into(), Value::String(srvc_unit.id.name.clone()));
@@ -338,6 +545,150 @@ fn find_units_with_pattern<'a>(
     units
 }
 
+/// Use the full name of the loaded unit if the name matches exactly one. Otherwise the name is used as is
+/// so logs of units that are not loaded anymore can be read too.
+fn resolve_log_unit(query: &mut LogsQuery, unit_table: &UnitTable) {
+    if let Some(name) = &query.filter.unit {
+        let units = find_units_with_name(name, unit_table);
+        if units.len() == 1 {
+            query.filter.unit = Some(units[0].id.name.clone());
+        }
+    }
+}
+
+/// How often a follower without new entries checks whether the client hung up
+const FOLLOW_HANGUP_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// Send all new log entries that match the query until the connection is closed
+fn follow_logs(
+    query: &LogsQuery,
+    logs: std::sync::mpsc::Receiver<crate::log_store::LogEntry>,
+    id: Option<Value>,
+    sink: &mut dyn Write,
+    fd: RawFd,
+) {
+    loop {
+        let entry = match logs.recv_timeout(FOLLOW_HANGUP_CHECK_INTERVAL) {
+            Ok(entry) => entry,
+            Err(std::sync::mpsc::RecvTimeoutError::Timeout) => {
+                if client_hung_up(fd) {
+                    return;
+                }
+                continue;
+            }
+            Err(std::sync::mpsc::RecvTimeoutError::Disconnected) => {
+                // the log store drops followers that do not keep up
+                let err = super::jsonrpc2::make_error(
+                    super::jsonrpc2::SERVER_ERROR,
+                    "Stopped following the logs because the client did not keep up".into(),
+                    None,
+                );
+                let msg = super::jsonrpc2::make_error_response(id, err);
+                let response_string = serde_json::to_string_pretty(&msg).unwrap();
+                let _ = sink.write_all(response_string.as_bytes());
+                return;
+            }
+        };
+        if query.filter.matches(&entry) {
+            let msg = super::jsonrpc2::make_result_response(
+                id.clone(),
+                Value::Array(vec![format_log_entry(&entry)]),
+            );
+            let response_string = serde_json::to_string_pretty(&msg).unwrap();
+            if sink.write_all(response_string.as_bytes()).is_err() {
+                // client hung up
+                return;
+            }
+        }
+    }
+}
+
+/// A closed connection reads as EOF. Peeking leaves anything the client sent in the socket.
+fn client_hung_up(fd: RawFd) -> bool {
+    let mut buf = [0u8; 1];
+    let flags = nix::sys::socket::MsgFlags::MSG_PEEK | nix::sys::socket::MsgFlags::MSG_DONTWAIT;
+    match nix::sys::socket::recv(fd, &mut buf, flags) {
+        Ok(0) => true,
+        Ok(_) | Err(nix::Error::EAGAIN) | Err(nix::Error::EINTR) => false,
+        Err(_) => true,
+    }
+}
+
+pub fn format_job(job: &Job, waiting_for: &[JobId]) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert("Id".into(), Value::from(job.id));
+    map.insert("Unit".into(), Value::String(job.unit.name.clone()));
+    map.insert("Type".into(), Value::String(format!("{:?}", job.kind)));
+    map.insert("State".into(), Value::String(format!("{:?}", job.state)));
+    map.insert(
+        "WaitingFor".into(),
+        Value::Array(waiting_for.iter().map(|id| Value::from(*id)).collect()),
+    );
+    Value::Object(map)
+}
+
+fn find_unit_id(unit_name: &str, unit_table: &UnitTable) -> Result<UnitId, String> {
+    let units = find_units_with_name(unit_name, unit_table);
+    if units.len() > 1 {
+        let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
+        return Err(format!(
+            "More than one unit found with name: {}: {:?}",
+            unit_name, names
+        ));
+    }
+    if units.is_empty() {
+        return Err(format!("No unit found with name: {}", unit_name));
+    }
+    Ok(units[0].id.clone())
+}
+
+fn format_reload_result(result: ReloadResult) -> Value {
+    let names = |names: Vec<String>| Value::Array(names.into_iter().map(Value::String).collect());
+    let mut response_object = serde_json::Map::new();
+    response_object.insert("Added".into(), names(result.added));
+    response_object.insert("Changed".into(), names(result.changed));
+    response_object.insert("Removed".into(), names(result.removed));
+    response_object.insert("NeedsRestart".into(), names(result.needs_restart));
+    Value::Object(response_object)
+}
+
+/// Plan the jobs for the request, queue them and wait until they ran. Afterwards the changes from the last daemon-reload are applied
+/// to the units that are stopped now.
+fn run_job(
+    unit_name: &str,
+    kind: JobKind,
+    recursive: bool,
+    mode: JobMode,
+    run_info: &ArcMutRuntimeInfo,
+) -> Result<(), String> {
+    let transaction = {
+        let run_info = &*run_info.read().unwrap();
+        let id = find_unit_id(unit_name, &run_info.unit_table)?;
+
+        if kind == JobKind::Restart && run_info.pending_reload.contains_key(&id) {
+            None
+        } else {
+            let planned = plan_transaction(&id, kind, recursive, mode, run_info)?;
+            let transaction = run_info.jobs.submit(planned, mode)?;
+            trace!("Queued jobs for {}: {:?}", unit_name, transaction.jobs);
+            Some(transaction)
+        }
+    };
+    let res = match transaction {
+        Some(transaction) => run_transaction(&transaction, run_info),
+        None => {
+            // the new config can only be swapped in while the unit is stopped
+            run_job(unit_name, JobKind::Stop, false, mode, run_info)?;
+            return run_job(unit_name, JobKind::Start, false, mode, run_info);
+        }
+    };
+
+    if !run_info.read().unwrap().pending_reload.is_empty() {
+        apply_pending_reloads(&mut run_info.write().unwrap());
+    }
+    res
+}
+
 pub fn execute_command(
     cmd: Command,
     run_info: ArcMutRuntimeInfo,
@@ -347,91 +698,14 @@ pub fn execute_command(
         Command::Shutdown => {
             crate::shutdown::shutdown_sequence(run_info);
         }
-        Command::Restart(unit_name) => {
-            let run_info = &*run_info.read().unwrap();
-            let id = {
-                let unit_table = &run_info.unit_table;
-                let units = find_units_with_name(&unit_name, unit_table);
-                if units.len() > 1 {
-                    let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
-                    return Err(format!(
-                        "More than one unit found with name: {}: {:?}",
-                        unit_name, names
-                    ));
-                }
-                if units.len() == 0 {
-                    return Err(format!("No unit found with name: {}", unit_name));
-                }
-                let x = units[0].id.clone();
-                x
-            };
-
-            match crate::units::reactivate_unit(id, run_info).map_err(|e| format!("{}", e)) {
-                Err(e) => {
-                    return Err(e);
-                }
-                Ok(_) => {
-                    // Happy
-                }
-            };
+        Command::Restart(unit_name, mode) => {
+            run_job(&unit_name, JobKind::Restart, false, mode, &run_info)?;
         }
-        Command::Start(unit_name) => {
-            let run_info = &*run_info.read().unwrap();
-            let id = {
-                let unit_table = &run_info.unit_table;
-                let units = find_units_with_name(&unit_name, unit_table);
-                if units.len() > 1 {
-                    let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
-                    return Err(format!(
-                        "More than one unit found with name: {}: {:?}",
-                        unit_name, names
-                    ));
-                }
-                if units.len() == 0 {
-                    return Err(format!("No unit found with name: {}", unit_name));
-                }
-                let x = units[0].id.clone();
-                x
-            };
-
-            match crate::units::activate_unit(id, run_info, ActivationSource::Regular)
-                .map_err(|e| format!("{}", e))
-            {
-                Err(e) => {
-                    return Err(e);
-                }
-                Ok(_) => {
-                    // Happy
-                }
-            };
+        Command::Start(unit_name, mode) => {
+            run_job(&unit_name, JobKind::Start, false, mode, &run_info)?;
         }
-        Command::StartAll(unit_name) => {
-            let id = {
-                let run_info_locked = &*run_info.read().unwrap();
-                let unit_table = &run_info_locked.unit_table;
-                let units = find_units_with_name(&unit_name, unit_table);
-                if units.len() > 1 {
-                    let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
-                    return Err(format!(
-                        "More than one unit found with name: {}: {:?}",
-                        unit_name, names
-                    ));
-                }
-                if units.len() == 0 {
-                    return Err(format!("No unit found with name: {}", unit_name));
-                }
-                let x = units[0].id.clone();
-                x
-            };
-
-            let errs = crate::units::activate_needed_units(id, run_info);
-            if errs.len() > 0 {
-                let mut errstr = String::from("Errors while starting the units:");
-                for err in errs {
-                    errstr.push_str(&format!("\n{:?}", err));
-                }
-                return Err(errstr);
-            }
+        Command::StartAll(unit_name, mode) => {
+            run_job(&unit_name, JobKind::Start, true, mode, &run_info)?;
         }
         Command::Remove(unit_name) => {
             let run_info = &mut *run_info.write().unwrap();
@@ -454,61 +728,32 @@ pub fn execute_command(
             crate::units::remove_unit_with_dependencies(id, run_info)
                 .map_err(|e| format!("{}", e))?;
         }
-        Command::Stop(unit_name) => {
-            let run_info = &*run_info.read().unwrap();
-            let id = {
-                let units = find_units_with_name(&unit_name, &run_info.unit_table);
-                if units.len() > 1 {
-                    let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
-                    return Err(format!(
-                        "More than one unit found with name: {}: {:?}",
-                        unit_name, names
-                    ));
-                }
-                if units.len() == 0 {
-                    return Err(format!("No unit found with name: {}", unit_name));
-                }
-                let x = units[0].id.clone();
-                x
-            };
-
-            match crate::units::deactivate_unit(&id, run_info).map_err(|e| format!("{}", e)) {
-                Err(e) => {
-                    return Err(e);
-                }
-                Ok(_) => {
-                    // Happy
-                }
-            };
+        Command::Stop(unit_name, mode) => {
+            run_job(&unit_name, JobKind::Stop, false, mode, &run_info)?;
+        }
+        Command::StopAll(unit_name, mode) => {
+            run_job(&unit_name, JobKind::Stop, true, mode, &run_info)?;
         }
-        Command::StopAll(unit_name) => {
+        Command::Isolate(unit_name) => {
+            run_job(
+                &unit_name,
+                JobKind::Start,
+                true,
+                JobMode::Isolate,
+                &run_info,
+            )?;
+        }
+        Command::ListJobs => {
             let run_info = &*run_info.read().unwrap();
-            let id = {
-                let units = find_units_with_name(&unit_name, &run_info.unit_table);
-                if units.len() > 1 {
-                    let names: Vec<_> = units.iter().map(|unit| unit.id.name.clone()).collect();
-                    return Err(format!(
-                        "More than one unit found with name: {}: {:?}",
-                        unit_name, names
-                    ));
-                }
-                if units.len() == 0 {
-                    return Err(format!("No unit found with name: {}", unit_name));
-                }
-                let x = units[0].id.clone();
-                x
-            };
-
-            match crate::units::deactivate_unit_recursive(&id, run_info)
-                .map_err(|e| format!("{}", e))
-            {
-                Err(e) => {
-                    return Err(e);
-                }
-                Ok(_) => {
-                    // Happy
-                }
-            };
+            for (job, waiting_for) in run_info.jobs.list() {
+                result_vec
+                    .as_array_mut()
+                    .unwrap()
+                    .push(format_job(&job, &waiting_for));
+            }
+        }
+        Command::CancelJob(id) => {
+            run_info.read().unwrap().jobs.cancel(id)?;
         }
         Command::Status(unit_name) => {
             let run_info = &*run_info.read().unwrap();
@@ -519,24 +764,18 @@ pub fn execute_command(
                     let units = find_units_with_pattern(&name, unit_table);
                     for unit in units {
                         let status = { unit.common.status.read().unwrap().clone() };
-                        if name.ends_with(".service") {
-                            result_vec
-                                .as_array_mut()
-                                .unwrap()
-                                .push(format_service(&unit, status));
+                        let mut formatted = if name.ends_with(".service") {
+                            format_service(&unit, status)
                         } else if name.ends_with(".socket") {
-                            result_vec
-                                .as_array_mut()
-                                .unwrap()
-                                .push(format_socket(&unit, status));
+                            format_socket(&unit, status)
                         } else if name.ends_with(".target") {
-                            result_vec
-                                .as_array_mut()
-                                .unwrap()
-                                .push(format_target(&unit, status));
+                            format_target(&unit, status)
                         } else {
                             return Err("Name suffix not recognized".into());
-                        }
+                        };
+                        add_needs_restart(&mut formatted, &unit.id, run_info);
+                        add_unit_files(&mut formatted, unit);
+                        result_vec.as_array_mut().unwrap().push(formatted);
                     }
                 }
                 None => {
@@ -545,11 +784,14 @@ pub fn execute_command(
                         .iter()
                         .map(|(_id, unit)| {
                             let status = { unit.common.status.read().unwrap().clone() };
-                            match unit.specific {
+                            let mut formatted = match unit.specific {
                                 Specific::Socket(_) => format_socket(&unit, status),
                                 Specific::Service(_) => format_service(&unit, status),
                                 Specific::Target(_) => format_target(&unit, status),
-                            }
+                            };
+                            add_needs_restart(&mut formatted, &unit.id, run_info);
+                            add_unit_files(&mut formatted, unit);
+                            formatted
                         })
                         .collect();
                     for s in strings {
@@ -558,6 +800,20 @@ pub fn execute_command(
                 }
             }
         }
+        Command::Logs(mut query) => {
+            let run_info = &*run_info.read().unwrap();
+            let log_store = match &run_info.log_store {
+                Some(log_store) => log_store,
+                None => return Err("Logging to disk is not enabled, no logs are stored".into()),
+            };
+            resolve_log_unit(&mut query, &run_info.unit_table);
+            for entry in log_store.query(&query.filter, query.lines)? {
+                result_vec
+                    .as_array_mut()
+                    .unwrap()
+                    .push(format_log_entry(&entry));
+            }
+        }
         Command::ListUnits(kind) => {
             let run_info = &*run_info.read().unwrap();
             let unit_table = &run_info.unit_table;
@@ -588,8 +844,12 @@ pub fn execute_command(
             let run_info = &mut *run_info.write().unwrap();
             let unit_table = &run_info.unit_table;
             // get all units there are
-            let units = load_all_units(&run_info.config.unit_dirs, &run_info.config.target_unit)
-                .map_err(|e| format!("Error while loading unit definitons: {:?}", e))?;
+            let units = load_all_units(
+                &run_info.config.unit_dirs,
+                &run_info.config.target_unit,
+                run_info.config.apply_install_sections(),
+            )
+            .map_err(|e| format!("Error while loading unit definitons: {:?}", e))?;
 
             // collect all names
             let existing_names = unit_table
@@ -622,12 +882,77 @@ pub fn execute_command(
                 .unwrap()
                 .push(Value::Object(response_object));
         }
+        Command::DaemonReload => {
+            let run_info = &mut *run_info.write().unwrap();
+            let result = reload_all_units(run_info)?;
+            result_vec
+                .as_array_mut()
+                .unwrap()
+                .push(format_reload_result(result));
+        }
+        Command::Install(action, names) => {
+            let run_info = &mut *run_info.write().unwrap();
+            let mut changes = InstallChanges::default();
+            for name in &names {
+                let conf = &run_info.config;
+                match action {
+                    InstallAction::Enable => enable_unit(conf, name, &mut changes)?,
+                    InstallAction::Disable => disable_unit(conf, name, &mut changes)?,
+                    InstallAction::Mask => mask_unit(conf, name, &mut changes)?,
+                    InstallAction::Unmask => unmask_unit(conf, name, &mut changes)?,
+                    InstallAction::Preset => preset_unit(conf, name, &mut changes)?,
+                }
+            }
+            let paths = |paths: Vec<std::path::PathBuf>| {
+                Value::Array(
+                    paths
+                        .into_iter()
+                        .map(|path| Value::String(path.to_string_lossy().into_owned()))
+                        .collect(),
+                )
+            };
+            let mut response_object = serde_json::Map::new();
+            response_object.insert("Created".into(), paths(changes.created));
+            response_object.insert("Removed".into(), paths(changes.removed));
+            // like a daemon-reload, so the changes take effect
+            response_object.insert(
+                "Reload".into(),
+                format_reload_result(reload_all_units(run_info)?),
+            );
+            result_vec
+                .as_array_mut()
+                .unwrap()
+                .push(Value::Object(response_object));
+        }
+        Command::IsEnabled(names) => {
+            let run_info = &*run_info.read().unwrap();
+            for name in names {
+                let state = unit_enable_state(&run_info.config, &name)?;
+                let mut response_object = serde_json::Map::new();
+                response_object.insert("Name".into(), Value::String(name));
+                response_object.insert("State".into(), Value::String(state.as_str().into()));
+                result_vec
+                    .as_array_mut()
+                    .unwrap()
+                    .push(Value::Object(response_object));
+            }
+        }
+        Command::DaemonReexec => {
+            // keep the write lock so nothing changes between saving the state and the exec.
+            // This only returns if the exec failed, otherwise the connection is closed by the exec
+            let run_info = &*run_info.write().unwrap();
+            crate::reexec::reexec(run_info)?;
+        }
         Command::LoadAllNewDry => {
             let run_info = &mut *run_info.write().unwrap();
             let unit_table = &run_info.unit_table;
             // get all units there are
-            let units = load_all_units(&run_info.config.unit_dirs, &run_info.config.target_unit)
-                .map_err(|e| format!("Error while loading unit definitons: {:?}", e))?;
+            let units = load_all_units(
+                &run_info.config.unit_dirs,
+                &run_info.config.target_unit,
+                run_info.config.apply_install_sections(),
+            )
+            .map_err(|e| format!("Error while loading unit definitons: {:?}", e))?;
 
             // collect all names
             let existing_names = unit_table
@@ -669,7 +994,8 @@ pub fn execute_command(
 
 use std::io::Read;
 use std::io::Write;
-pub fn listen_on_commands<T: 'static + Read + Write + Send>(
+use std::os::unix::io::{AsRawFd, RawFd};
+pub fn listen_on_commands<T: 'static + Read + Write + AsRawFd + Send>(
     mut source: Box<T>,
     run_info: ArcMutRuntimeInfo,
 ) {
@@ -721,6 +1047,25 @@ pub fn listen_on_commands<T: 'static + Read + Write + Send>(
                             }
                             Ok(cmd) => {
                                 trace!("Execute command: {:?}", cmd);
+                                // subscribe before reading the existing logs so no entries are missed in between
+                                let follow = match &cmd {
+                                    Command::Logs(query) if query.follow => {
+                                        let run_info_locked = run_info.read().unwrap();
+                                        run_info_locked.log_store.as_ref().map(|log_store| {
+                                            let mut query = LogsQuery {
+                                                filter: query.filter.clone(),
+                                                lines: None,
+                                                follow: true,
+                                            };
+                                            resolve_log_unit(
+                                                &mut query,
+                                                &run_info_locked.unit_table,
+                                            );
+                                            (query, log_store.follow())
+                                        })
+                                    }
+                                    _ => None,
+                                };
                                 let msg = match execute_command(cmd, run_info.clone()) {
                                     Err(e) => {
                                         let err = super::jsonrpc2::make_error(
@@ -728,14 +1073,20 @@ pub fn listen_on_commands<T: 'static + Read + Write + Send>(
                                             e,
                                             None,
                                         );
-                                        super::jsonrpc2::make_error_response(call.id, err)
-                                    }
-                                    Ok(result) => {
-                                        super::jsonrpc2::make_result_response(call.id, result)
+                                        super::jsonrpc2::make_error_response(call.id.clone(), err)
                                     }
+                                    Ok(result) => super::jsonrpc2::make_result_response(
+                                        call.id.clone(),
+                                        result,
+                                    ),
                                 };
                                 let response_string = serde_json::to_string_pretty(&msg).unwrap();
                                 source.write_all(response_string.as_bytes()).unwrap();
+                                if let Some((query, logs)) = follow {
+                                    let fd = source.as_raw_fd();
+                                    follow_logs(&query, logs, call.id, source.as_mut(), fd);
+                                    return;
+                                }
                             }
                         }
                     }
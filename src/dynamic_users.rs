@@ -0,0 +1,152 @@
+//! Uids for services with DynamicUser=yes. They are allocated from the configured range when the service is started.
+//!
+//! The allocations are persisted by user name in `users.json` in the dynamic users dir, so a restarted service (or rustysd itself)
+//! gets the same uid again and files the service left behind in e.g. ReadWritePaths= still belong to it. The gid is always the same as the uid.
+//!
+//! The names are not added to the real /etc/passwd and /etc/group. Instead rustysd writes copies of them with the additional entries into
+//! the dynamic users dir, which are bind mounted over the originals in the mount namespace of the service.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub struct DynamicUsers {
+    dir: PathBuf,
+    min_uid: u32,
+    max_uid: u32,
+    allocated: Mutex<HashMap<String, u32>>,
+}
+
+fn load_allocations(path: &Path) -> Result<HashMap<String, u32>, String> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+    let file =
+        std::fs::File::open(path).map_err(|e| format!("Error while opening {:?}: {}", path, e))?;
+    serde_json::from_reader(file).map_err(|e| format!("Error while decoding {:?}: {}", path, e))
+}
+
+/// The host file with an additional line for the dynamic user
+fn write_with_entry(src: &Path, dst: &Path, entry: &str) -> Result<(), String> {
+    let mut content = std::fs::read_to_string(src).unwrap_or_default();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(entry);
+    content.push('\n');
+    let mut file =
+        std::fs::File::create(dst).map_err(|e| format!("Error while creating {:?}: {}", dst, e))?;
+    file.write_all(content.as_bytes())
+        .map_err(|e| format!("Error while writing {:?}: {}", dst, e))
+}
+
+impl DynamicUsers {
+    pub fn new(dir: &Path, min_uid: u32, max_uid: u32) -> Result<DynamicUsers, String> {
+        if min_uid > max_uid {
+            return Err(format!(
+                "The dynamic user range {}-{} is empty",
+                min_uid, max_uid
+            ));
+        }
+        let allocated = load_allocations(&dir.join("users.json"))?;
+        Ok(DynamicUsers {
+            dir: dir.to_owned(),
+            min_uid,
+            max_uid,
+            allocated: Mutex::new(allocated),
+        })
+    }
+
+    /// No allocations, used if the stored ones can not be loaded. The users.json is overwritten with the next allocation.
+    pub fn empty(conf: &crate::config::Config) -> DynamicUsers {
+        DynamicUsers {
+            dir: conf.dynamic_users_dir.clone(),
+            min_uid: conf.dynamic_user_min_uid,
+            max_uid: conf.dynamic_user_max_uid,
+            allocated: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn load(conf: &crate::config::Config) -> Result<DynamicUsers, String> {
+        Self::new(
+            &conf.dynamic_users_dir,
+            conf.dynamic_user_min_uid,
+            conf.dynamic_user_max_uid,
+        )
+    }
+
+    fn create_dir(&self) -> Result<(), String> {
+        if !self.dir.exists() {
+            std::fs::create_dir_all(&self.dir)
+                .map_err(|e| format!("Error while creating {:?}: {}", self.dir, e))?;
+        }
+        Ok(())
+    }
+
+    fn save(&self, allocated: &HashMap<String, u32>) -> Result<(), String> {
+        self.create_dir()?;
+        let path = self.dir.join("users.json");
+        let content = serde_json::to_string(allocated)
+            .map_err(|e| format!("Error while encoding dynamic users: {}", e))?;
+        std::fs::write(&path, content).map_err(|e| format!("Error while writing {:?}: {}", path, e))
+    }
+
+    /// Ids that are in use by other dynamic users or by entries in /etc/passwd or /etc/group can not be allocated
+    fn is_free(uid: u32, allocated: &HashMap<String, u32>) -> bool {
+        !allocated.values().any(|id| *id == uid)
+            && !matches!(
+                nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(uid)),
+                Ok(Some(_))
+            )
+            && !matches!(
+                nix::unistd::Group::from_gid(nix::unistd::Gid::from_raw(uid)),
+                Ok(Some(_))
+            )
+    }
+
+    /// The uid that was allocated for this name before, or the lowest free uid in the range
+    pub fn get_or_allocate(&self, name: &str) -> Result<u32, String> {
+        let mut allocated = self.allocated.lock().unwrap();
+        if let Some(uid) = allocated.get(name) {
+            return Ok(*uid);
+        }
+        let uid = (self.min_uid..=self.max_uid)
+            .find(|uid| Self::is_free(*uid, &allocated))
+            .ok_or_else(|| {
+                format!(
+                    "No free uid left in the dynamic user range {}-{}",
+                    self.min_uid, self.max_uid
+                )
+            })?;
+        allocated.insert(name.to_owned(), uid);
+        self.save(&allocated)?;
+        Ok(uid)
+    }
+
+    /// Write the passwd and group files that make the names resolvable in the service. Returns their paths.
+    pub fn write_user_files(
+        &self,
+        user: &str,
+        group: &str,
+        uid: u32,
+    ) -> Result<(PathBuf, PathBuf), String> {
+        self.create_dir()?;
+        let passwd = self.dir.join(format!("{}.passwd", user));
+        let group_file = self.dir.join(format!("{}.group", user));
+        write_with_entry(
+            Path::new("/etc/passwd"),
+            &passwd,
+            &format!(
+                "{}:x:{}:{}:Dynamic User:/:/usr/sbin/nologin",
+                user, uid, uid
+            ),
+        )?;
+        write_with_entry(
+            Path::new("/etc/group"),
+            &group_file,
+            &format!("{}:x:{}:", group, uid),
+        )?;
+        Ok((passwd, group_file))
+    }
+}
@@ -8,9 +8,20 @@ use std::{
 };
 
 use crate::units::UnitId;
+use serde_json::Value;
+use std::convert::TryInto;
 
 type GlobalEntry = Vec<(UnitId, String, Box<dyn AsRawFd + Send + Sync>)>;
 
+/// A filedescriptor restored after daemon-reexec. Only its number is known, it is closed like all the others by the socket unit.
+struct RestoredFd(RawFd);
+
+impl AsRawFd for RestoredFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
 #[derive(Default)]
 pub struct FDStore {
     // Indexed by unit name
@@ -90,4 +101,95 @@ impl FDStore {
             None
         }
     }
+
+    /// All filedescriptors in the store
+    pub fn all_fds(&self) -> Vec<RawFd> {
+        let mut all = Vec::new();
+        for fds in self.global_sockets.values() {
+            all.extend(fds.iter().map(|(_, _, fd)| fd.as_raw_fd()));
+        }
+        for fds in self.service_stored_sockets.values() {
+            for fds in fds.values() {
+                all.extend(fds.iter().map(|fd| **fd));
+            }
+        }
+        all
+    }
+
+    /// Save the store for daemon-reexec. Only the numbers of the fds are saved, the fds have to stay open over the exec.
+    pub fn to_json(&self) -> Value {
+        let mut global = serde_json::Map::new();
+        for (name, fds) in &self.global_sockets {
+            let fds = fds
+                .iter()
+                .map(|(id, fd_name, fd)| {
+                    let mut map = serde_json::Map::new();
+                    map.insert("unit".into(), Value::String(id.name.clone()));
+                    map.insert("name".into(), Value::String(fd_name.clone()));
+                    map.insert("fd".into(), Value::from(fd.as_raw_fd()));
+                    Value::Object(map)
+                })
+                .collect();
+            global.insert(name.clone(), Value::Array(fds));
+        }
+
+        let mut service_stored = serde_json::Map::new();
+        for (srvc_name, fds) in &self.service_stored_sockets {
+            let mut by_name = serde_json::Map::new();
+            for (fd_name, fds) in fds {
+                let fds = fds.iter().map(|fd| Value::from(**fd)).collect();
+                by_name.insert(fd_name.clone(), Value::Array(fds));
+            }
+            service_stored.insert(srvc_name.clone(), Value::Object(by_name));
+        }
+
+        let mut map = serde_json::Map::new();
+        map.insert("global".into(), Value::Object(global));
+        map.insert("service_stored".into(), Value::Object(service_stored));
+        Value::Object(map)
+    }
+
+    /// Restore a store saved with to_json
+    pub fn from_json(value: &Value) -> Result<FDStore, String> {
+        let fd_from_json = |fd: &Value| {
+            fd.as_i64()
+                .map(|fd| fd as RawFd)
+                .ok_or_else(|| format!("Not a filedescriptor: {}", fd))
+        };
+        let mut store = FDStore::default();
+
+        if let Some(global) = value.get("global").and_then(Value::as_object) {
+            for (name, fds) in global {
+                let mut entry: GlobalEntry = Vec::new();
+                for fd in fds.as_array().into_iter().flatten() {
+                    let id: UnitId = fd
+                        .get("unit")
+                        .and_then(Value::as_str)
+                        .ok_or_else(|| format!("Missing unit for fd of {}", name))?
+                        .try_into()?;
+                    let fd_name = fd
+                        .get("name")
+                        .and_then(Value::as_str)
+                        .unwrap_or_default()
+                        .to_owned();
+                    let fd = fd_from_json(fd.get("fd").unwrap_or(&Value::Null))?;
+                    entry.push((id, fd_name, Box::new(RestoredFd(fd))));
+                }
+                store.insert_global(name.clone(), entry);
+            }
+        }
+
+        if let Some(service_stored) = value.get("service_stored").and_then(Value::as_object) {
+            for (srvc_name, by_name) in service_stored {
+                for (fd_name, fds) in by_name.as_object().into_iter().flatten() {
+                    let mut new_fds = Vec::new();
+                    for fd in fds.as_array().into_iter().flatten() {
+                        new_fds.push(Box::new(fd_from_json(fd)?));
+                    }
+                    store.insert_service_stored(srvc_name.clone(), fd_name.clone(), new_fds);
+                }
+            }
+        }
+        Ok(store)
+    }
 }
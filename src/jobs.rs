@@ -0,0 +1,421 @@
+//! Jobs for the start/stop/restart requests that come in over the control interface.
+//!
+//! A request is first planned into a transaction over the dependency graph, which contains one job per unit it touches. The transaction is
+//! then submitted to the JobQueue. The jobs for one unit run one after another in the order their transactions were submitted:
+//! 1. If the last job for a unit is of the same kind, the new job is merged into it
+//! 1. If there are jobs of a different kind, the transaction is rejected with JobMode::Fail. With JobMode::Replace (and Isolate) the old jobs
+//!    are cancelled if they did not start running yet. The new job is queued behind the ones that are already running
+//! 1. If a job is cancelled the transactions that were merged into it get the job back as their own, so they do not skip the unit
+//!
+//! Transactions that touch the same units are run in the order they were submitted, transactions on unrelated units run concurrently.
+//! The jobs are removed from the queue when they are done, so the queue only shows what is pending or running right now.
+
+use crate::runtime_info::{ArcMutRuntimeInfo, RuntimeInfo};
+use crate::units::*;
+
+use log::trace;
+use std::sync::{Condvar, Mutex};
+
+pub type JobId = u64;
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum JobKind {
+    Start,
+    Stop,
+    Restart,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum JobMode {
+    /// Cancel conflicting jobs that did not start yet
+    Replace,
+    /// Reject the request if there are conflicting jobs
+    Fail,
+    /// Like replace, but additionally stop all units that are not needed by the started unit
+    Isolate,
+}
+
+impl JobMode {
+    pub fn parse(mode: &str) -> Option<JobMode> {
+        match mode {
+            "replace" => Some(JobMode::Replace),
+            "fail" => Some(JobMode::Fail),
+            "isolate" => Some(JobMode::Isolate),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum JobState {
+    Waiting,
+    Running,
+}
+
+#[derive(Clone, Debug)]
+pub struct Job {
+    pub id: JobId,
+    pub unit: UnitId,
+    pub kind: JobKind,
+    pub state: JobState,
+    transaction: u64,
+    /// The later transactions whose jobs were merged into this one
+    merged: Vec<u64>,
+}
+
+/// A submitted request. Holds the jobs that were installed for it when it was submitted and all units it touches, including the ones whose
+/// jobs were merged into jobs of earlier transactions.
+#[derive(Debug)]
+pub struct Transaction {
+    id: u64,
+    pub jobs: Vec<(JobId, UnitId, JobKind)>,
+    units: Vec<UnitId>,
+}
+
+#[derive(Default)]
+struct JobTable {
+    next_job: JobId,
+    next_transaction: u64,
+    jobs: Vec<Job>,
+    /// The units touched by the transactions that are not done yet
+    transactions: Vec<(u64, Vec<UnitId>)>,
+}
+
+#[derive(Default)]
+pub struct JobQueue {
+    table: Mutex<JobTable>,
+    changed: Condvar,
+}
+
+impl JobQueue {
+    /// Install the jobs of a planned transaction according to the mode
+    pub fn submit(
+        &self,
+        planned: Vec<(UnitId, JobKind)>,
+        mode: JobMode,
+    ) -> Result<Transaction, String> {
+        let mut table = self.table.lock().unwrap();
+
+        let conflicts: Vec<&Job> = table
+            .jobs
+            .iter()
+            .filter(|job| {
+                planned
+                    .iter()
+                    .any(|(unit, kind)| job.unit == *unit && job.kind != *kind)
+            })
+            .collect();
+        if mode == JobMode::Fail && !conflicts.is_empty() {
+            let descr: Vec<_> = conflicts
+                .iter()
+                .map(|job| format!("{} ({:?} {})", job.id, job.kind, job.unit.name))
+                .collect();
+            return Err(format!(
+                "The request conflicts with the pending jobs: {}",
+                descr.join(", ")
+            ));
+        }
+        let cancel: Vec<JobId> = conflicts
+            .iter()
+            .filter(|job| job.state == JobState::Waiting)
+            .map(|job| job.id)
+            .collect();
+        for id in cancel {
+            trace!("Cancel job {} because it conflicts with a new request", id);
+            Self::remove_cancelled(&mut table, id);
+        }
+
+        table.next_transaction += 1;
+        let mut transaction = Transaction {
+            id: table.next_transaction,
+            jobs: Vec::new(),
+            units: Vec::new(),
+        };
+        for (unit, kind) in planned {
+            // the jobs that are still there run before this one, only the last of them can be merged with
+            let last = table
+                .jobs
+                .iter_mut()
+                .filter(|job| job.unit == unit)
+                .max_by_key(|job| job.transaction);
+            match last {
+                Some(job) if job.kind == kind => job.merged.push(transaction.id),
+                _ => {
+                    table.next_job += 1;
+                    let job = Job {
+                        id: table.next_job,
+                        unit: unit.clone(),
+                        kind,
+                        state: JobState::Waiting,
+                        transaction: transaction.id,
+                        merged: Vec::new(),
+                    };
+                    transaction.jobs.push((job.id, unit.clone(), kind));
+                    table.jobs.push(job);
+                }
+            }
+            transaction.units.push(unit);
+        }
+        table
+            .transactions
+            .push((transaction.id, transaction.units.clone()));
+        self.changed.notify_all();
+        Ok(transaction)
+    }
+
+    /// Remove a job that did not start running. The first transaction that was merged into it gets it back as its own job (with the
+    /// other merged transactions still merged into it).
+    fn remove_cancelled(table: &mut JobTable, id: JobId) {
+        let pos = match table.jobs.iter().position(|job| job.id == id) {
+            Some(pos) => pos,
+            None => return,
+        };
+        let job = table.jobs.remove(pos);
+        let mut merged = job.merged.into_iter();
+        if let Some(transaction) = merged.next() {
+            table.next_job += 1;
+            trace!(
+                "Job {} was cancelled, install it again as job {} for a transaction that was merged into it",
+                id,
+                table.next_job
+            );
+            table.jobs.push(Job {
+                id: table.next_job,
+                unit: job.unit,
+                kind: job.kind,
+                state: JobState::Waiting,
+                transaction,
+                merged: merged.collect(),
+            });
+        }
+    }
+
+    /// The jobs that belong to the transaction right now. Jobs that were cancelled are not included anymore and jobs that were given
+    /// back to it, because the job it was merged into was cancelled, are.
+    pub fn transaction_jobs(&self, transaction: &Transaction) -> Vec<(JobId, UnitId, JobKind)> {
+        let table = self.table.lock().unwrap();
+        table
+            .jobs
+            .iter()
+            .filter(|job| job.transaction == transaction.id)
+            .map(|job| (job.id, job.unit.clone(), job.kind))
+            .collect()
+    }
+
+    /// The jobs of earlier transactions that touch the same units as this job's transaction
+    fn blocking_jobs(table: &JobTable, transaction: u64, units: &[UnitId]) -> Vec<JobId> {
+        table
+            .jobs
+            .iter()
+            .filter(|job| job.transaction < transaction && units.contains(&job.unit))
+            .map(|job| job.id)
+            .collect()
+    }
+
+    /// Block until all earlier transactions that touch the same units are done
+    pub fn wait_turn(&self, transaction: &Transaction) {
+        let mut table = self.table.lock().unwrap();
+        while !Self::blocking_jobs(&table, transaction.id, &transaction.units).is_empty() {
+            table = self.changed.wait(table).unwrap();
+        }
+    }
+
+    /// Mark the job as running. Returns false if it was cancelled in the meantime.
+    pub fn begin(&self, id: JobId) -> bool {
+        let mut table = self.table.lock().unwrap();
+        match table.jobs.iter_mut().find(|job| job.id == id) {
+            Some(job) => {
+                job.state = JobState::Running;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn finish(&self, id: JobId) {
+        let mut table = self.table.lock().unwrap();
+        table.jobs.retain(|job| job.id != id);
+        self.changed.notify_all();
+    }
+
+    /// Called when all jobs of the transaction ran, so later transactions on the same units can proceed
+    pub fn finish_transaction(&self, transaction: &Transaction) {
+        let mut table = self.table.lock().unwrap();
+        table.jobs.retain(|job| job.transaction != transaction.id);
+        table.transactions.retain(|(id, _)| *id != transaction.id);
+        self.changed.notify_all();
+    }
+
+    /// Only jobs that did not start running can be cancelled
+    pub fn cancel(&self, id: JobId) -> Result<(), String> {
+        let mut table = self.table.lock().unwrap();
+        match table.jobs.iter().find(|job| job.id == id) {
+            Some(job) if job.state == JobState::Running => {
+                Err(format!("Job {} is already running", id))
+            }
+            Some(_) => {
+                Self::remove_cancelled(&mut table, id);
+                self.changed.notify_all();
+                Ok(())
+            }
+            None => Err(format!("No job with id {}", id)),
+        }
+    }
+
+    /// All pending jobs with the ids of the jobs they are waiting for
+    pub fn list(&self) -> Vec<(Job, Vec<JobId>)> {
+        let table = self.table.lock().unwrap();
+        table
+            .jobs
+            .iter()
+            .map(|job| {
+                let units = table
+                    .transactions
+                    .iter()
+                    .find(|(id, _)| *id == job.transaction)
+                    .map(|(_, units)| units.as_slice())
+                    .unwrap_or(&[]);
+                let waiting_for = if job.state == JobState::Waiting {
+                    Self::blocking_jobs(&table, job.transaction, units)
+                } else {
+                    Vec::new()
+                };
+                (job.clone(), waiting_for)
+            })
+            .collect()
+    }
+}
+
+/// All units that have to be stopped if this unit is stopped
+fn collect_unit_stop_subgraph(ids: &mut Vec<UnitId>, unit_table: &crate::runtime_info::UnitTable) {
+    let mut idx = 0;
+    while idx < ids.len() {
+        if let Some(unit) = unit_table.get(&ids[idx]) {
+            for id in unit.common.dependencies.kill_before_this() {
+                if !ids.contains(&id) {
+                    ids.push(id);
+                }
+            }
+        }
+        idx += 1;
+    }
+}
+
+/// Compute the jobs needed for the request. With recursive the units needed by a started unit are started too and the units requiring a
+/// stopped unit are stopped too.
+///
+/// Isolating a unit additionally stops all active units that are not needed by it, except the ones with IgnoreOnIsolate=. This is only
+/// allowed for units with AllowIsolate=.
+pub fn plan_transaction(
+    unit: &UnitId,
+    kind: JobKind,
+    recursive: bool,
+    mode: JobMode,
+    run_info: &RuntimeInfo,
+) -> Result<Vec<(UnitId, JobKind)>, String> {
+    if mode == JobMode::Isolate {
+        if kind != JobKind::Start {
+            return Err("Only start requests can use the isolate mode".into());
+        }
+        let allowed = run_info
+            .unit_table
+            .get(unit)
+            .map(|unit| unit.common.unit.allow_isolate)
+            .unwrap_or(false);
+        if !allowed {
+            return Err(format!(
+                "Unit {} can not be isolated. It needs AllowIsolate=yes",
+                unit.name
+            ));
+        }
+    }
+
+    let mut ids = vec![unit.clone()];
+    match kind {
+        JobKind::Start if recursive || mode == JobMode::Isolate => {
+            collect_unit_start_subgraph(&mut ids, &run_info.unit_table)
+        }
+        JobKind::Stop if recursive => collect_unit_stop_subgraph(&mut ids, &run_info.unit_table),
+        _ => {}
+    }
+    let mut planned: Vec<_> = ids.iter().map(|id| (id.clone(), kind)).collect();
+
+    if kind == JobKind::Start && mode == JobMode::Isolate {
+        for (id, other) in run_info.unit_table.iter() {
+            let status = other.common.status.read().unwrap();
+            let active = status.is_started() || *status == UnitStatus::Starting;
+            if active && !ids.contains(id) && !other.common.unit.ignore_on_isolate {
+                planned.push((id.clone(), JobKind::Stop));
+            }
+        }
+    }
+    Ok(planned)
+}
+
+/// Run the jobs of the transaction. Stop jobs are run first in the same order as on shutdown (and units requiring a unit are stopped
+/// before it), then start jobs ordered by the after relations, then restart jobs.
+///
+/// Waiting for the earlier transactions happens without holding the lock of the RuntimeInfo, else a daemon-reload waiting for the write
+/// lock would block the transactions this one waits for. The read lock is taken once for the ordering and then for each job.
+pub fn run_transaction(
+    transaction: &Transaction,
+    run_info: &ArcMutRuntimeInfo,
+) -> Result<(), String> {
+    let queue = run_info.read().unwrap().jobs.clone();
+    queue.wait_turn(transaction);
+    // jobs given back after a cancellation are only known now
+    let transaction_jobs = queue.transaction_jobs(transaction);
+
+    let of_kind = |kind| {
+        transaction_jobs
+            .iter()
+            .filter(|(_, _, job_kind)| *job_kind == kind)
+            .map(|(_, unit, _)| unit.clone())
+            .collect::<Vec<_>>()
+    };
+    let units = {
+        let unit_table = &run_info.read().unwrap().unit_table;
+        let mut units = stop_order(of_kind(JobKind::Stop), unit_table);
+        units.extend(start_order(of_kind(JobKind::Start), unit_table));
+        units.extend(of_kind(JobKind::Restart));
+        units
+    };
+    // a transaction has at most one job per unit
+    let jobs = units.into_iter().map(|unit| {
+        transaction_jobs
+            .iter()
+            .find(|(_, job_unit, _)| *job_unit == unit)
+            .map(|(id, _, kind)| (*id, unit.clone(), *kind))
+            .unwrap()
+    });
+
+    let mut errors = Vec::new();
+    for (job_id, unit, kind) in jobs {
+        if !queue.begin(job_id) {
+            trace!("Job {} for {} was cancelled", job_id, unit.name);
+            continue;
+        }
+        trace!("Run job {}: {:?} {}", job_id, kind, unit.name);
+        let res = {
+            let run_info = &*run_info.read().unwrap();
+            match kind {
+                JobKind::Start => {
+                    activate_unit(unit.clone(), run_info, ActivationSource::Regular).map(|_| ())
+                }
+                JobKind::Stop => deactivate_unit(&unit, run_info),
+                JobKind::Restart => reactivate_unit(unit.clone(), run_info),
+            }
+        };
+        queue.finish(job_id);
+        if let Err(e) = res {
+            errors.push(format!("{}", e));
+        }
+    }
+    queue.finish_transaction(transaction);
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors.join("\n"))
+    }
+}
@@ -24,10 +24,15 @@
 pub mod config;
 pub mod control;
 pub mod dbus_wait;
+pub mod dynamic_users;
 pub mod fd_store;
+pub mod jobs;
+pub mod log_store;
 pub mod logging;
 pub mod notification_handler;
 pub mod platform;
+pub mod reactor;
+pub mod reexec;
 pub mod runtime_info;
 pub mod services;
 pub mod shutdown;
@@ -56,6 +61,12 @@ pub fn run_service_manager() {
         unreachable!();
     });
 
+    // after a daemon-reexec these are the filedescriptors kept for the running services, taken before anything else is opened
+    let inherited_fds = match &cli_args.deserialize {
+        Some(_) => reexec::inherited_fds(),
+        None => Vec::new(),
+    };
+
     if let Some(path) = &cli_args.conf {
         if !path.exists() {
             unrecoverable_error(format!("config path given that does not exist"));
@@ -67,7 +78,7 @@ pub fn run_service_manager() {
 
     let (log_conf, conf) = config::load_config(&cli_args.conf);
 
-    logging::setup_logging(&log_conf).unwrap();
+    let (log_store, syslog) = logging::setup_logging(&log_conf).unwrap();
     let conf = match conf {
         Ok(conf) => conf,
         Err(e) => {
@@ -96,7 +107,30 @@ pub fn run_service_manager() {
 
     crate::platform::become_subreaper(true);
 
-    let run_info = prepare_runtimeinfo(&conf, cli_args.dry_run);
+    let run_info = prepare_runtimeinfo(&conf, cli_args.dry_run, log_store, syslog);
+
+    // after a daemon-reexec the services are still running and only the state has to be restored
+    let restored = match &cli_args.deserialize {
+        Some(path) => {
+            let res = reexec::load_state(path)
+                .and_then(|state| reexec::restore_state(&state, &mut run_info.write().unwrap()));
+            match res {
+                Ok(()) => {
+                    reexec::close_unclaimed_fds(&inherited_fds, &run_info.read().unwrap());
+                    true
+                }
+                Err(e) => {
+                    error!(
+                        "Could not restore the state after a reexec, killing the old services and starting normally: {}",
+                        e
+                    );
+                    reexec::clean_up_failed_restore(&inherited_fds);
+                    false
+                }
+            }
+        }
+        None => false,
+    };
 
     let signals = match Signals::new(&[
         signal_hook::consts::SIGCHLD,
@@ -113,15 +147,21 @@ pub fn run_service_manager() {
     };
     // listen to signals
     let handle = start_signal_handler_thread(signals, run_info.clone());
+    if cli_args.deserialize.is_some() {
+        // children that exited (or were killed) while the new binary was starting up still need to be reaped
+        if let Err(e) = nix::sys::signal::raise(nix::sys::signal::Signal::SIGCHLD) {
+            error!(
+                "Could not check for exited children after the reexec: {}",
+                e
+            );
+        }
+    }
 
     // listen on user commands like listunits/kill/restart...
     control::open_all_sockets(run_info.clone(), &conf);
 
-    start_notification_handler_thread(run_info.clone());
-    start_stdout_handler_thread(run_info.clone());
-    start_stderr_handler_thread(run_info.clone());
-
-    socket_activation::start_socketactivation_thread(run_info.clone());
+    // listen on the notification sockets, stdout/stderr of services and the sockets for socket activation
+    start_reactor_thread(run_info.clone());
 
     trace!("Started all helper threads. Start activating units");
 
@@ -131,8 +171,12 @@ pub fn run_service_manager() {
         run_info.config.target_unit.as_str().try_into().unwrap()
     };
 
-    // parallel startup of all services
-    units::activate_needed_units(target_id, run_info.clone());
+    if restored {
+        trace!("Restored the state after a reexec, the units are already active");
+    } else {
+        // parallel startup of all services
+        units::activate_needed_units(target_id, run_info.clone());
+    }
 
     handle.join().unwrap();
 }
@@ -232,11 +276,20 @@ fn pid1_specific_setup() {
 #[cfg(not(target_os = "linux"))]
 fn pid1_specific_setup() {}
 
-fn prepare_runtimeinfo(conf: &config::Config, dry_run: bool) -> runtime_info::ArcMutRuntimeInfo {
+fn prepare_runtimeinfo(
+    conf: &config::Config,
+    dry_run: bool,
+    log_store: Option<log_store::LogStore>,
+    syslog: Option<logging::SyslogForwarder>,
+) -> runtime_info::ArcMutRuntimeInfo {
     // initial loading of the units and matching of the various before/after settings
     // also opening all fildescriptors in the socket files
-    let unit_table =
-        units::load_all_units(&conf.unit_dirs, &conf.target_unit).expect("loading unit files");
+    let unit_table = units::load_all_units(
+        &conf.unit_dirs,
+        &conf.target_unit,
+        conf.apply_install_sections(),
+    )
+    .expect("loading unit files");
     trace!("Finished loading units");
     if let Err(e) = units::sanity_check_dependencies(&unit_table) {
         match e {
@@ -266,33 +319,36 @@ fn prepare_runtimeinfo(conf: &config::Config, dry_run: bool) -> runtime_info::Ar
 
     let pid_table = Mutex::new(std::collections::HashMap::new());
 
+    let dynamic_users = match dynamic_users::DynamicUsers::load(conf) {
+        Ok(users) => users,
+        Err(e) => {
+            error!(
+                "Could not load the dynamic users, starting with no allocations: {}",
+                e
+            );
+            dynamic_users::DynamicUsers::empty(conf)
+        }
+    };
+
     let run_info = Arc::new(RwLock::new(runtime_info::RuntimeInfo {
         unit_table: unit_table,
         pid_table: pid_table,
         fd_store: std::sync::RwLock::new(crate::fd_store::FDStore::default()),
         config: conf.clone(),
-        stdout_eventfd: platform::make_event_fd().unwrap(),
-        stderr_eventfd: platform::make_event_fd().unwrap(),
-        notification_eventfd: platform::make_event_fd().unwrap(),
-        socket_activation_eventfd: platform::make_event_fd().unwrap(),
+        reactor: Arc::new(reactor::Reactor::new().unwrap()),
+        log_store,
+        syslog,
+        dynamic_users,
+        jobs: Arc::new(jobs::JobQueue::default()),
+        pending_reload: std::collections::HashMap::new(),
     }));
 
     run_info
 }
 
-fn start_notification_handler_thread(run_info: runtime_info::ArcMutRuntimeInfo) {
-    std::thread::spawn(move || {
-        notification_handler::handle_all_streams(run_info.clone());
-    });
-}
-fn start_stdout_handler_thread(run_info: runtime_info::ArcMutRuntimeInfo) {
-    std::thread::spawn(move || {
-        notification_handler::handle_all_std_out(run_info.clone());
-    });
-}
-fn start_stderr_handler_thread(run_info: runtime_info::ArcMutRuntimeInfo) {
+fn start_reactor_thread(run_info: runtime_info::ArcMutRuntimeInfo) {
     std::thread::spawn(move || {
-        notification_handler::handle_all_std_err(run_info.clone());
+        reactor::run_reactor(run_info);
     });
 }
 fn start_signal_handler_thread(
@@ -314,4 +370,7 @@ struct CliArgs {
     conf: Option<std::path::PathBuf>,
     #[clap(short, long, value_parser)]
     dry_run: bool,
+    /// Restore the state saved by daemon-reexec from this file instead of activating the units
+    #[clap(long, value_parser)]
+    deserialize: Option<std::path::PathBuf>,
 }
@@ -0,0 +1,452 @@
+//! The log store keeps the output of services (and optionally rustysd's own log) on disk so it can be read after the fact.
+//!
+//! Every line is stored as one json object per line with the unit name, the stream it came from, the PID, a timestamp and a syslog priority.
+//! The current file is `rustysd.log` in the logging dir. If it would grow larger than `max_file_size` it is rotated to `rustysd.log.1`,
+//! the old `rustysd.log.1` to `rustysd.log.2` and so on. Only `max_files` rotated files are kept, older ones are deleted.
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+/// A log file opened for reading, with its path for the error messages
+type OpenedFile = (PathBuf, Box<dyn Read>);
+
+/// Which stream a line was read from
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogStream {
+    Stdout,
+    Stderr,
+    /// Log messages of rustysd itself
+    Rustysd,
+}
+
+impl LogStream {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogStream::Stdout => "stdout",
+            LogStream::Stderr => "stderr",
+            LogStream::Rustysd => "rustysd",
+        }
+    }
+
+    fn from_str(stream: &str) -> Option<LogStream> {
+        match stream {
+            "stdout" => Some(LogStream::Stdout),
+            "stderr" => Some(LogStream::Stderr),
+            "rustysd" => Some(LogStream::Rustysd),
+            _ => None,
+        }
+    }
+}
+
+/// Syslog priorities
+pub const PRIO_ERR: u8 = 3;
+pub const PRIO_WARNING: u8 = 4;
+pub const PRIO_INFO: u8 = 6;
+pub const PRIO_DEBUG: u8 = 7;
+
+const PRIORITY_NAMES: [&str; 8] = [
+    "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+];
+
+/// Syslog facilities. The others are only needed when parsing names.
+pub const FACILITY_DAEMON: u8 = 3;
+
+const FACILITY_NAMES: [&str; 24] = [
+    "kern",
+    "user",
+    "mail",
+    "daemon",
+    "auth",
+    "syslog",
+    "lpr",
+    "news",
+    "uucp",
+    "cron",
+    "authpriv",
+    "ftp",
+    "ntp",
+    "security",
+    "console",
+    "solaris-cron",
+    "local0",
+    "local1",
+    "local2",
+    "local3",
+    "local4",
+    "local5",
+    "local6",
+    "local7",
+];
+
+/// Parse a facility name like "daemon" or "local3"
+pub fn parse_facility(facility: &str) -> Option<u8> {
+    FACILITY_NAMES
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(facility))
+        .map(|idx| idx as u8)
+}
+
+/// Split a kernel style priority prefix like "<3>" from the line. The number may also contain a facility (facility * 8 + priority).
+/// Returns the priority (and facility if one was encoded) and the rest of the line.
+pub fn split_priority_prefix(line: &[u8]) -> Option<(u8, Option<u8>, &[u8])> {
+    if line.first() != Some(&b'<') {
+        return None;
+    }
+    let end = line.iter().position(|c| *c == b'>')?;
+    let num = std::str::from_utf8(&line[1..end]).ok()?;
+    if num.is_empty() || num.len() > 3 || !num.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let num: u32 = num.parse().ok()?;
+    if num >= FACILITY_NAMES.len() as u32 * 8 {
+        return None;
+    }
+    let facility = if num > 7 {
+        Some((num >> 3) as u8)
+    } else {
+        None
+    };
+    Some(((num & 7) as u8, facility, &line[end + 1..]))
+}
+
+/// Map a syslog priority to the level used by the log crate
+pub fn priority_to_level(prio: u8) -> log::Level {
+    match prio {
+        0..=3 => log::Level::Error,
+        4 => log::Level::Warn,
+        5 | 6 => log::Level::Info,
+        _ => log::Level::Debug,
+    }
+}
+
+/// Parse a priority given either as a number (0-7) or as a name like "err" or "info"
+pub fn parse_priority(prio: &str) -> Option<u8> {
+    if let Ok(num) = prio.parse::<u8>() {
+        if num <= PRIO_DEBUG {
+            return Some(num);
+        }
+        return None;
+    }
+    PRIORITY_NAMES
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(prio))
+        .map(|idx| idx as u8)
+}
+
+pub fn priority_name(prio: u8) -> &'static str {
+    PRIORITY_NAMES
+        .get(prio as usize)
+        .copied()
+        .unwrap_or("debug")
+}
+
+/// Selects entries from the log
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    pub unit: Option<String>,
+    /// Microseconds since the unix epoch
+    pub since: Option<u64>,
+    /// Microseconds since the unix epoch
+    pub until: Option<u64>,
+    /// Only entries with this priority or a more important one (lower number) are selected
+    pub priority: Option<u8>,
+}
+
+impl LogFilter {
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        if let Some(unit) = &self.unit {
+            if *unit != entry.unit {
+                return false;
+            }
+        }
+        if let Some(since) = self.since {
+            if entry.timestamp < since {
+                return false;
+            }
+        }
+        if let Some(until) = self.until {
+            if entry.timestamp > until {
+                return false;
+            }
+        }
+        if let Some(priority) = self.priority {
+            if entry.priority > priority {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    /// Microseconds since the unix epoch
+    pub timestamp: u64,
+    pub unit: String,
+    /// SyslogIdentifier= of the unit. This is the unit name if none is set.
+    pub identifier: String,
+    pub facility: u8,
+    pub stream: LogStream,
+    pub pid: Option<i32>,
+    pub priority: u8,
+    pub message: String,
+}
+
+impl LogEntry {
+    pub fn new(
+        unit: &str,
+        stream: LogStream,
+        pid: Option<i32>,
+        priority: u8,
+        message: String,
+    ) -> LogEntry {
+        let timestamp = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|dur| dur.as_micros() as u64)
+            .unwrap_or(0);
+        LogEntry {
+            timestamp,
+            unit: unit.to_owned(),
+            identifier: unit.to_owned(),
+            facility: FACILITY_DAEMON,
+            stream,
+            pid,
+            priority,
+            message,
+        }
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let mut map = serde_json::Map::new();
+        map.insert("ts".into(), serde_json::Value::from(self.timestamp));
+        map.insert("unit".into(), serde_json::Value::from(self.unit.clone()));
+        map.insert(
+            "ident".into(),
+            serde_json::Value::from(self.identifier.clone()),
+        );
+        map.insert("facility".into(), serde_json::Value::from(self.facility));
+        map.insert(
+            "stream".into(),
+            serde_json::Value::from(self.stream.as_str()),
+        );
+        map.insert(
+            "pid".into(),
+            match self.pid {
+                Some(pid) => serde_json::Value::from(pid),
+                None => serde_json::Value::Null,
+            },
+        );
+        map.insert("prio".into(), serde_json::Value::from(self.priority));
+        map.insert("msg".into(), serde_json::Value::from(self.message.clone()));
+        serde_json::Value::Object(map)
+    }
+
+    fn from_json(value: &serde_json::Value) -> Option<LogEntry> {
+        let map = value.as_object()?;
+        let unit = map.get("unit")?.as_str()?.to_owned();
+        Some(LogEntry {
+            timestamp: map.get("ts")?.as_u64()?,
+            // older entries do not have an identifier and facility
+            identifier: map
+                .get("ident")
+                .and_then(|ident| ident.as_str())
+                .unwrap_or(&unit)
+                .to_owned(),
+            facility: map
+                .get("facility")
+                .and_then(|facility| facility.as_u64())
+                .map(|facility| facility as u8)
+                .unwrap_or(FACILITY_DAEMON),
+            unit,
+            stream: LogStream::from_str(map.get("stream")?.as_str()?)?,
+            pid: map
+                .get("pid")
+                .and_then(|pid| pid.as_i64())
+                .map(|pid| pid as i32),
+            priority: map.get("prio")?.as_u64()? as u8,
+            message: map.get("msg")?.as_str()?.to_owned(),
+        })
+    }
+}
+
+struct LogFiles {
+    dir: PathBuf,
+    max_file_size: u64,
+    max_files: usize,
+    current: File,
+    current_size: u64,
+    /// Every appended entry is sent to these
+    followers: Vec<SyncSender<LogEntry>>,
+}
+
+/// Handle to the on-disk log. Can be cloned and shared between threads.
+#[derive(Clone)]
+pub struct LogStore {
+    files: Arc<Mutex<LogFiles>>,
+}
+
+const CURRENT_FILE_NAME: &str = "rustysd.log";
+/// How many entries a follower may fall behind before it is disconnected
+const FOLLOW_BUFFER_ENTRIES: usize = 1000;
+
+fn rotated_path(dir: &Path, idx: usize) -> PathBuf {
+    dir.join(format!("{}.{}", CURRENT_FILE_NAME, idx))
+}
+
+fn open_current(dir: &Path) -> Result<(File, u64), String> {
+    let path = dir.join(CURRENT_FILE_NAME);
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .map_err(|e| format!("Error opening log file {:?}: {}", path, e))?;
+    let size = file
+        .metadata()
+        .map_err(|e| format!("Error reading metadata of log file {:?}: {}", path, e))?
+        .len();
+    Ok((file, size))
+}
+
+impl LogStore {
+    pub fn open(dir: &Path, max_file_size: u64, max_files: usize) -> Result<LogStore, String> {
+        std::fs::create_dir_all(dir)
+            .map_err(|e| format!("Error creating log dir {:?}: {}", dir, e))?;
+        let (current, current_size) = open_current(dir)?;
+        Ok(LogStore {
+            files: Arc::new(Mutex::new(LogFiles {
+                dir: dir.to_path_buf(),
+                max_file_size,
+                max_files,
+                current,
+                current_size,
+                followers: Vec::new(),
+            })),
+        })
+    }
+
+    /// Append an entry, rotating the files if necessary.
+    ///
+    /// Note that this must not use the logger since it is used by the logger itself
+    pub fn append(&self, entry: &LogEntry) -> Result<(), String> {
+        let mut line = entry.to_json().to_string();
+        line.push('\n');
+
+        let files = &mut *self.files.lock().unwrap();
+        if files.current_size > 0 && files.current_size + line.len() as u64 > files.max_file_size {
+            files.rotate()?;
+        }
+        files
+            .current
+            .write_all(line.as_bytes())
+            .map_err(|e| format!("Error writing to log file: {}", e))?;
+        files.current_size += line.len() as u64;
+
+        // followers that hung up or fell too far behind are removed
+        files
+            .followers
+            .retain(|follower| match follower.try_send(entry.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) | Err(TrySendError::Disconnected(_)) => false,
+            });
+        Ok(())
+    }
+
+    /// Get all entries that are appended from now on. If the receiver falls too far behind it is disconnected.
+    pub fn follow(&self) -> Receiver<LogEntry> {
+        let (tx, rx) = sync_channel(FOLLOW_BUFFER_ENTRIES);
+        self.files.lock().unwrap().followers.push(tx);
+        rx
+    }
+
+    /// Read the last `lines` entries that match the filter, oldest first. A `lines` of None returns all matching entries.
+    pub fn query(&self, filter: &LogFilter, lines: Option<usize>) -> Result<Vec<LogEntry>, String> {
+        let mut entries = Vec::new();
+        self.for_each_entry(|entry| {
+            if filter.matches(&entry) {
+                entries.push(entry);
+            }
+        })?;
+        if let Some(lines) = lines {
+            if entries.len() > lines {
+                entries.drain(..entries.len() - lines);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Read all entries that are still stored, oldest first. Lines that can not be parsed are skipped.
+    pub fn read_all(&self) -> Result<Vec<LogEntry>, String> {
+        let mut entries = Vec::new();
+        self.for_each_entry(|entry| entries.push(entry))?;
+        Ok(entries)
+    }
+
+    /// Open all files, oldest first. Only the opening is done with the lock held, so appending is not blocked while the files are read.
+    /// The open files are not affected by a rotation, the current file is only read up to the size it had when it was opened.
+    fn open_files(&self) -> Result<Vec<OpenedFile>, String> {
+        let files = &*self.files.lock().unwrap();
+        let mut opened: Vec<OpenedFile> = Vec::new();
+        for idx in (1..=files.max_files).rev() {
+            let path = rotated_path(&files.dir, idx);
+            if !path.exists() {
+                continue;
+            }
+            let file = File::open(&path).map_err(|e| format!("Error opening {:?}: {}", path, e))?;
+            opened.push((path, Box::new(file)));
+        }
+        let path = files.dir.join(CURRENT_FILE_NAME);
+        if path.exists() {
+            let file = File::open(&path).map_err(|e| format!("Error opening {:?}: {}", path, e))?;
+            opened.push((path, Box::new(file.take(files.current_size))));
+        }
+        Ok(opened)
+    }
+
+    fn for_each_entry(&self, mut f: impl FnMut(LogEntry)) -> Result<(), String> {
+        for (path, file) in self.open_files()? {
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(|e| format!("Error reading {:?}: {}", path, e))?;
+                if let Ok(value) = serde_json::from_str(&line) {
+                    if let Some(entry) = LogEntry::from_json(&value) {
+                        f(entry);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl LogFiles {
+    fn rotate(&mut self) -> Result<(), String> {
+        let current_path = self.dir.join(CURRENT_FILE_NAME);
+        if self.max_files == 0 {
+            std::fs::remove_file(&current_path)
+                .map_err(|e| format!("Error removing {:?}: {}", current_path, e))?;
+        } else {
+            let oldest = rotated_path(&self.dir, self.max_files);
+            if oldest.exists() {
+                std::fs::remove_file(&oldest)
+                    .map_err(|e| format!("Error removing {:?}: {}", oldest, e))?;
+            }
+            for idx in (1..self.max_files).rev() {
+                let from = rotated_path(&self.dir, idx);
+                if from.exists() {
+                    let to = rotated_path(&self.dir, idx + 1);
+                    std::fs::rename(&from, &to)
+                        .map_err(|e| format!("Error moving {:?} to {:?}: {}", from, to, e))?;
+                }
+            }
+            let to = rotated_path(&self.dir, 1);
+            std::fs::rename(&current_path, &to)
+                .map_err(|e| format!("Error moving {:?} to {:?}: {}", current_path, to, e))?;
+        }
+        let (current, current_size) = open_current(&self.dir)?;
+        self.current = current;
+        self.current_size = current_size;
+        Ok(())
+    }
+}
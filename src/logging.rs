@@ -1,25 +1,236 @@
-pub fn setup_logging(conf: &crate::config::LoggingConfig) -> Result<(), String> {
-    let mut logger = fern::Dispatch::new()
-        .format(|out, message, record| {
-            out.finish(format_args!(
-                "{}[{}][{}] {}",
-                chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
-                record.target(),
-                record.level(),
-                message
-            ))
+use crate::log_store::{LogEntry, LogStore, LogStream};
+
+use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// Output of services is logged with this prefix in front of the identifier as the target.
+/// It is stored by the services themselves with more information so the log store skips these.
+pub const SERVICE_OUTPUT_TARGET_PREFIX: &str = "unit:";
+
+/// Set if rustysd does not log to stdout. The output of services is then printed as it is, like before the output was logged.
+static PRINT_SERVICE_OUTPUT: AtomicBool = AtomicBool::new(false);
+
+/// Whether the services print their output to rustysd's stdout/stderr themselves because the logger does not
+pub fn print_service_output() -> bool {
+    PRINT_SERVICE_OUTPUT.load(Ordering::Relaxed)
+}
+
+fn level_to_priority(level: log::Level) -> u8 {
+    match level {
+        log::Level::Error => crate::log_store::PRIO_ERR,
+        log::Level::Warn => crate::log_store::PRIO_WARNING,
+        log::Level::Info => crate::log_store::PRIO_INFO,
+        log::Level::Debug | log::Level::Trace => crate::log_store::PRIO_DEBUG,
+    }
+}
+
+fn rustysd_entry(record: &log::Record) -> LogEntry {
+    LogEntry::new(
+        "rustysd",
+        LogStream::Rustysd,
+        Some(nix::unistd::getpid().as_raw()),
+        level_to_priority(record.level()),
+        format!("[{}] {}", record.target(), record.args()),
+    )
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyslogFormat {
+    /// The old BSD format: `<PRI>Mmm dd hh:mm:ss HOST TAG[PID]: MSG`
+    Rfc3164,
+    /// `<PRI>1 TIMESTAMP HOST APP-NAME PROCID MSGID STRUCTURED-DATA MSG`
+    Rfc5424,
+}
+
+/// Format an entry as a syslog message. The priority value contains the facility and the priority of the entry.
+pub fn format_syslog_message(entry: &LogEntry, format: SyslogFormat, hostname: &str) -> String {
+    use chrono::TimeZone;
+    let pri = u32::from(entry.facility) * 8 + u32::from(entry.priority);
+    let time = chrono::Local
+        .timestamp_opt(
+            (entry.timestamp / 1_000_000) as i64,
+            ((entry.timestamp % 1_000_000) * 1000) as u32,
+        )
+        .single()
+        .unwrap_or_else(chrono::Local::now);
+    match format {
+        SyslogFormat::Rfc3164 => {
+            let tag = match entry.pid {
+                Some(pid) => format!("{}[{}]", entry.identifier, pid),
+                None => entry.identifier.clone(),
+            };
+            format!(
+                "<{}>{} {} {}: {}",
+                pri,
+                time.format("%b %e %H:%M:%S"),
+                hostname,
+                tag,
+                entry.message
+            )
+        }
+        SyslogFormat::Rfc5424 => {
+            let procid = match entry.pid {
+                Some(pid) => format!("{}", pid),
+                None => "-".into(),
+            };
+            format!(
+                "<{}>1 {} {} {} {} - - {}",
+                pri,
+                time.format("%Y-%m-%dT%H:%M:%S%.6f%:z"),
+                hostname,
+                entry.identifier,
+                procid,
+                entry.message
+            )
+        }
+    }
+}
+
+struct SyslogSocket {
+    socket: UnixDatagram,
+    path: PathBuf,
+    format: SyslogFormat,
+    hostname: String,
+    kmsg_fallback: bool,
+    /// Opened when the first message could not be sent to the syslog socket
+    kmsg: Option<std::fs::File>,
+}
+
+/// Sends log entries as datagrams to a syslog socket (usually /dev/log).
+///
+/// During early boot no syslog daemon might be running yet. Messages that can not be sent are written to /dev/kmsg instead (if the fallback is enabled).
+///
+/// Note that this must not use the logger since it is used by the logger itself
+#[derive(Clone)]
+pub struct SyslogForwarder {
+    socket: Arc<Mutex<SyslogSocket>>,
+}
+
+impl SyslogForwarder {
+    pub fn new(
+        path: PathBuf,
+        format: SyslogFormat,
+        kmsg_fallback: bool,
+    ) -> Result<SyslogForwarder, String> {
+        let socket = UnixDatagram::unbound()
+            .map_err(|e| format!("Error creating socket for syslog: {}", e))?;
+        // a syslog daemon that does not read its socket must not block rustysd
+        socket
+            .set_nonblocking(true)
+            .map_err(|e| format!("Error making the syslog socket non blocking: {}", e))?;
+        let mut buf = [0u8; 256];
+        let hostname = nix::unistd::gethostname(&mut buf)
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| "localhost".into());
+        Ok(SyslogForwarder {
+            socket: Arc::new(Mutex::new(SyslogSocket {
+                socket,
+                path,
+                format,
+                hostname,
+                kmsg_fallback,
+                kmsg: None,
+            })),
         })
-        .level(log::LevelFilter::Trace);
+    }
 
-    if conf.log_to_stdout {
-        logger = logger.chain(std::io::stdout());
+    pub fn send(&self, entry: &LogEntry) {
+        let socket = &mut *self.socket.lock().unwrap();
+        let msg = format_syslog_message(entry, socket.format, &socket.hostname);
+        match socket.socket.send_to(msg.as_bytes(), &socket.path) {
+            Ok(_) => return,
+            // the syslog daemon is there but does not keep up, the message is dropped
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return,
+            Err(_) if !socket.kmsg_fallback => return,
+            Err(_) => {}
+        }
+
+        if socket.kmsg.is_none() {
+            socket.kmsg = std::fs::OpenOptions::new()
+                .write(true)
+                .open("/dev/kmsg")
+                .ok();
+        }
+        if let Some(kmsg) = &mut socket.kmsg {
+            // the kernel only understands the priority prefix. Every write is one record.
+            let pri = u32::from(entry.facility) * 8 + u32::from(entry.priority);
+            let tag = match entry.pid {
+                Some(pid) => format!("{}[{}]", entry.identifier, pid),
+                None => entry.identifier.clone(),
+            };
+            let _ = kmsg.write_all(format!("<{}>{}: {}\n", pri, tag, entry.message).as_bytes());
+        }
     }
+}
+
+/// Setup the logger. If logging to disk is enabled this returns the log store so the output of the services can be stored there too.
+/// If forwarding to syslog is enabled the forwarder is returned so the output of services can be forwarded too.
+pub fn setup_logging(
+    conf: &crate::config::LoggingConfig,
+) -> Result<(Option<LogStore>, Option<SyslogForwarder>), String> {
+    let mut logger = fern::Dispatch::new().level(log::LevelFilter::Trace);
 
-    if conf.log_to_disk {
-        unimplemented!("Logging to disk is currently not supported. Pipe the stdout logs to your preferred logging solution");
+    if conf.log_to_stdout {
+        logger = logger.chain(
+            fern::Dispatch::new()
+                .format(|out, message, record| {
+                    out.finish(format_args!(
+                        "{}[{}][{}] {}",
+                        chrono::Local::now().format("[%Y-%m-%d][%H:%M:%S]"),
+                        record.target(),
+                        record.level(),
+                        message
+                    ))
+                })
+                .chain(std::io::stdout()),
+        );
     }
+    PRINT_SERVICE_OUTPUT.store(!conf.log_to_stdout, Ordering::Relaxed);
+
+    let log_store = if conf.log_to_disk {
+        let log_store = LogStore::open(&conf.log_dir, conf.log_max_file_size, conf.log_max_files)?;
+        let store_clone = log_store.clone();
+        logger = logger.chain(fern::Output::call(move |record| {
+            if record.target().starts_with(SERVICE_OUTPUT_TARGET_PREFIX) {
+                return;
+            }
+            // can not use the logger here
+            if let Err(e) = store_clone.append(&rustysd_entry(record)) {
+                eprintln!("Error while writing log to disk: {}", e);
+            }
+        }));
+        Some(log_store)
+    } else {
+        None
+    };
+
+    let syslog = if conf.log_to_syslog {
+        let syslog = SyslogForwarder::new(
+            conf.syslog_socket.clone(),
+            conf.syslog_format,
+            conf.syslog_kmsg_fallback,
+        )?;
+        let syslog_clone = syslog.clone();
+        // the trace/debug logs of rustysd are too noisy for the system log
+        logger = logger.chain(fern::Dispatch::new().level(log::LevelFilter::Info).chain(
+            fern::Output::call(move |record| {
+                // the output of services is forwarded by the services themselves
+                if record.target().starts_with(SERVICE_OUTPUT_TARGET_PREFIX) {
+                    return;
+                }
+                syslog_clone.send(&rustysd_entry(record));
+            }),
+        ));
+        Some(syslog)
+    } else {
+        None
+    };
 
     logger
         .apply()
-        .map_err(|e| format!("Error while stting up logger: {}", e))
+        .map_err(|e| format!("Error while stting up logger: {}", e))?;
+    Ok((log_store, syslog))
 }
@@ -0,0 +1,183 @@
+//! Linux capabilities. The names are needed for parsing the unit files, applying them is linux only and happens in drop_privileges.
+
+/// The names of all capabilities, the index is the number of the capability
+pub const CAPABILITY_NAMES: [&str; 41] = [
+    "CAP_CHOWN",
+    "CAP_DAC_OVERRIDE",
+    "CAP_DAC_READ_SEARCH",
+    "CAP_FOWNER",
+    "CAP_FSETID",
+    "CAP_KILL",
+    "CAP_SETGID",
+    "CAP_SETUID",
+    "CAP_SETPCAP",
+    "CAP_LINUX_IMMUTABLE",
+    "CAP_NET_BIND_SERVICE",
+    "CAP_NET_BROADCAST",
+    "CAP_NET_ADMIN",
+    "CAP_NET_RAW",
+    "CAP_IPC_LOCK",
+    "CAP_IPC_OWNER",
+    "CAP_SYS_MODULE",
+    "CAP_SYS_RAWIO",
+    "CAP_SYS_CHROOT",
+    "CAP_SYS_PTRACE",
+    "CAP_SYS_PACCT",
+    "CAP_SYS_ADMIN",
+    "CAP_SYS_BOOT",
+    "CAP_SYS_NICE",
+    "CAP_SYS_RESOURCE",
+    "CAP_SYS_TIME",
+    "CAP_SYS_TTY_CONFIG",
+    "CAP_MKNOD",
+    "CAP_LEASE",
+    "CAP_AUDIT_WRITE",
+    "CAP_AUDIT_CONTROL",
+    "CAP_SETFCAP",
+    "CAP_MAC_OVERRIDE",
+    "CAP_MAC_ADMIN",
+    "CAP_SYSLOG",
+    "CAP_WAKE_ALARM",
+    "CAP_BLOCK_SUSPEND",
+    "CAP_AUDIT_READ",
+    "CAP_PERFMON",
+    "CAP_BPF",
+    "CAP_CHECKPOINT_RESTORE",
+];
+
+/// Bitmask with all capabilities that are known by name
+pub const ALL_CAPABILITIES: u64 = (1 << CAPABILITY_NAMES.len()) - 1;
+
+/// The number of the capability with this name. The name is matched case insensitive, like in systemd
+pub fn capability_from_name(name: &str) -> Option<u32> {
+    CAPABILITY_NAMES
+        .iter()
+        .position(|cap| cap.eq_ignore_ascii_case(name))
+        .map(|idx| idx as u32)
+}
+
+/// The highest capability the running kernel knows about
+#[cfg(target_os = "linux")]
+fn last_capability() -> u32 {
+    std::fs::read_to_string("/proc/sys/kernel/cap_last_cap")
+        .ok()
+        .and_then(|content| content.trim().parse().ok())
+        .unwrap_or(CAPABILITY_NAMES.len() as u32 - 1)
+}
+
+#[cfg(target_os = "linux")]
+fn prctl(option: libc::c_int, arg: libc::c_ulong, arg2: libc::c_ulong) -> Result<(), String> {
+    let res = unsafe { libc::prctl(option, arg, arg2, 0, 0) };
+    if res < 0 {
+        Err(format!("{}", std::io::Error::last_os_error()))
+    } else {
+        Ok(())
+    }
+}
+
+/// Drop all capabilities that are not in the set from the bounding set so they can never be gained again (e.g. through setuid binaries)
+#[cfg(target_os = "linux")]
+pub fn limit_bounding_set(set: u64) -> Result<(), String> {
+    for cap in 0..=last_capability().min(63) {
+        if set & (1 << cap) == 0 {
+            prctl(libc::PR_CAPBSET_DROP, cap as libc::c_ulong, 0).map_err(|e| {
+                format!(
+                    "Error while dropping capability {} from the bounding set: {}",
+                    cap, e
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Keep the permitted capabilities when switching from root to another user
+#[cfg(target_os = "linux")]
+pub fn set_keep_caps(keep: bool) -> Result<(), String> {
+    prctl(libc::PR_SET_KEEPCAPS, keep as libc::c_ulong, 0)
+        .map_err(|e| format!("Error while setting PR_SET_KEEPCAPS: {}", e))
+}
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+struct CapHeader {
+    version: u32,
+    pid: libc::c_int,
+}
+
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct CapData {
+    effective: u32,
+    permitted: u32,
+    inheritable: u32,
+}
+
+#[cfg(target_os = "linux")]
+const LINUX_CAPABILITY_VERSION_3: u32 = 0x2008_0522;
+
+/// Limit the capabilities of this process to the set and raise them as ambient capabilities, so they survive the exec
+/// of a binary without file capabilities. This needs to happen after the uid was changed.
+#[cfg(target_os = "linux")]
+pub fn raise_ambient_capabilities(set: u64) -> Result<(), String> {
+    let set = set & ALL_CAPABILITIES;
+    let mut header = CapHeader {
+        version: LINUX_CAPABILITY_VERSION_3,
+        pid: 0,
+    };
+    let data = [
+        CapData {
+            effective: set as u32,
+            permitted: set as u32,
+            inheritable: set as u32,
+        },
+        CapData {
+            effective: (set >> 32) as u32,
+            permitted: (set >> 32) as u32,
+            inheritable: (set >> 32) as u32,
+        },
+    ];
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_capset,
+            &mut header as *mut CapHeader,
+            data.as_ptr(),
+        )
+    };
+    if res < 0 {
+        return Err(format!(
+            "Error while setting the capabilities: {}",
+            std::io::Error::last_os_error()
+        ));
+    }
+
+    for cap in 0..=last_capability().min(63) {
+        if set & (1 << cap) != 0 {
+            prctl(
+                libc::PR_CAP_AMBIENT,
+                libc::PR_CAP_AMBIENT_RAISE as libc::c_ulong,
+                cap as libc::c_ulong,
+            )
+            .map_err(|e| {
+                format!(
+                    "Error while raising the ambient capability {}: {}",
+                    CAPABILITY_NAMES[cap as usize], e
+                )
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// After this neither setuid binaries nor file capabilities can give the process more privileges
+#[cfg(target_os = "linux")]
+pub fn set_no_new_privileges() -> Result<(), String> {
+    prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0)
+        .map_err(|e| format!("Error while setting PR_SET_NO_NEW_PRIVS: {}", e))
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn set_no_new_privileges() -> Result<(), String> {
+    Err("NoNewPrivileges= is only supported on linux".into())
+}
@@ -1,3 +1,5 @@
+use super::capabilities::*;
+use crate::units::CapabilitySettings;
 use nix::unistd::setresgid;
 use nix::unistd::setresuid;
 use nix::unistd::Gid;
@@ -7,11 +9,56 @@ use std::io::Read;
 /// This sequence should drop all privileges the root process might have had. I think this is how systemd does it too.
 /// They additionally have some checking if setgroups is possible
 ///
-/// I dont think this needs to explicitly drop any capabilities on linux. At least thats how I understood the man page
-pub fn drop_privileges(gid: Gid, supp_gids: &Vec<Gid>, uid: Uid) -> Result<(), String> {
+/// Changing the uid away from root clears all capabilities. The ambient capabilities are kept with PR_SET_KEEPCAPS
+/// and raised again after the uid was changed so they are passed on through the exec.
+pub fn drop_privileges(
+    gid: Gid,
+    supp_gids: &Vec<Gid>,
+    uid: Uid,
+    caps: &CapabilitySettings,
+    no_new_privileges: bool,
+) -> Result<(), String> {
+    apply_bounding_set(caps)?;
+    if caps.ambient != 0 {
+        set_keep_caps(true)?;
+    }
     setresgid(gid, gid, gid).map_err(|e| format!("Error while setting groupid: {}", e))?;
     maybe_set_groups(supp_gids)?;
     setresuid(uid, uid, uid).map_err(|e| format!("Error while setting userid: {}", e))?;
+    if caps.ambient != 0 {
+        set_keep_caps(false)?;
+        raise_ambient_capabilities(caps.ambient)?;
+    }
+    if no_new_privileges {
+        set_no_new_privileges()?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn apply_bounding_set(caps: &CapabilitySettings) -> Result<(), String> {
+    if let Some(set) = caps.bounding_set {
+        limit_bounding_set(set)?;
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn apply_bounding_set(caps: &CapabilitySettings) -> Result<(), String> {
+    if *caps != CapabilitySettings::default() {
+        Err("Capabilities are only supported on linux".into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_keep_caps(_keep: bool) -> Result<(), String> {
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn raise_ambient_capabilities(_set: u64) -> Result<(), String> {
     Ok(())
 }
 
@@ -0,0 +1,46 @@
+//! Thin wrapper around epoll. Filedescriptors are registered level-triggered with the fd itself as the event data.
+
+use nix::sys::epoll::{EpollCreateFlags, EpollEvent, EpollFlags, EpollOp};
+use std::os::unix::io::RawFd;
+
+pub struct Epoll(RawFd);
+
+impl Epoll {
+    pub fn new() -> Result<Epoll, String> {
+        let fd = nix::sys::epoll::epoll_create1(EpollCreateFlags::EPOLL_CLOEXEC)
+            .map_err(|e| format!("Error while creating epoll instance: {}", e))?;
+        Ok(Epoll(fd))
+    }
+
+    /// Start listening for readability on this fd. Adding an fd that is already registered is not an error.
+    pub fn add(&self, fd: RawFd) -> Result<(), String> {
+        let mut event = EpollEvent::new(EpollFlags::EPOLLIN, fd as u64);
+        match nix::sys::epoll::epoll_ctl(self.0, EpollOp::EpollCtlAdd, fd, &mut event) {
+            Ok(()) | Err(nix::Error::EEXIST) => Ok(()),
+            Err(e) => Err(format!("Error while adding fd {} to epoll: {}", fd, e)),
+        }
+    }
+
+    /// Stop listening on this fd. Removing an fd that is not registered (or already closed) is not an error.
+    pub fn remove(&self, fd: RawFd) {
+        let _ = nix::sys::epoll::epoll_ctl(self.0, EpollOp::EpollCtlDel, fd, None);
+    }
+
+    /// Wait for events and return the fds that are readable. A timeout of None blocks until an event happens.
+    pub fn wait(&self, timeout: Option<std::time::Duration>) -> Result<Vec<RawFd>, String> {
+        let timeout_ms = match timeout {
+            // round up so we do not wake up just before the timeout passed
+            Some(dur) => (dur.as_micros() as isize + 999) / 1000,
+            None => -1,
+        };
+        let mut events = [EpollEvent::empty(); 64];
+        match nix::sys::epoll::epoll_wait(self.0, &mut events, timeout_ms) {
+            Ok(count) => Ok(events[..count]
+                .iter()
+                .map(|ev| ev.data() as RawFd)
+                .collect()),
+            Err(nix::Error::EINTR) => Ok(Vec::new()),
+            Err(e) => Err(format!("Error while waiting on epoll: {}", e)),
+        }
+    }
+}
@@ -0,0 +1,481 @@
+//! Mount namespace based sandboxing of services. This runs in the forked child before exec (and before dropping privileges),
+//! so it must not use the logger.
+//!
+//! The child gets its own mount namespace in which the mounts are changed:
+//! 1. a new /dev for PrivateDevices=
+//! 1. tmpfs for PrivateTmp= and TemporaryFileSystem=
+//! 1. bind mounts for BindPaths= and BindReadOnlyPaths=
+//! 1. ReadWritePaths= are bound onto themselves so they stay writable when a parent directory is made read only
+//! 1. ProtectSystem=, ProtectHome=read-only, ReadOnlyPaths=, ProtectKernelTunables= and ProtectControlGroups= are remounted read only,
+//!    including all mounts below them
+//! 1. ProtectHome=yes, InaccessiblePaths=, ProtectKernelModules= and ProtectKernelLogs= are covered with an empty tmpfs (or /dev/null for files) that can not be accessed
+
+use crate::units::{BindPath, FilesystemSandbox, ProtectHome, ProtectSystem, SandboxPath};
+use nix::fcntl::OFlag;
+use nix::mount::MsFlags;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::io::RawFd;
+use std::path::{Path, PathBuf};
+
+const HOME_PATHS: [&str; 3] = ["/home", "/root", "/run/user"];
+
+/// Made read only by ProtectKernelTunables=
+const KERNEL_TUNABLE_PATHS: [&str; 9] = [
+    "/proc/sys",
+    "/proc/sysrq-trigger",
+    "/proc/latency_stats",
+    "/proc/acpi",
+    "/proc/timer_stats",
+    "/proc/fs",
+    "/proc/irq",
+    "/proc/bus",
+    "/sys",
+];
+/// Made inaccessible by ProtectKernelTunables=
+const KERNEL_MEMORY_PATHS: [&str; 2] = ["/proc/kallsyms", "/proc/kcore"];
+const KERNEL_MODULE_PATHS: [&str; 2] = ["/usr/lib/modules", "/lib/modules"];
+const KERNEL_LOG_PATHS: [&str; 2] = ["/proc/kmsg", "/dev/kmsg"];
+
+/// The device nodes that are available with PrivateDevices=
+const PRIVATE_DEVICE_NODES: [&str; 6] = ["null", "zero", "full", "random", "urandom", "tty"];
+
+fn protected_system_paths(protect_system: ProtectSystem) -> Vec<&'static str> {
+    match protect_system {
+        ProtectSystem::No => vec![],
+        ProtectSystem::Yes => vec!["/usr", "/boot", "/efi"],
+        ProtectSystem::Full => vec!["/usr", "/boot", "/efi", "/etc"],
+        ProtectSystem::Strict => vec!["/"],
+    }
+}
+
+fn mount(
+    src: Option<&Path>,
+    target: &Path,
+    fstype: Option<&str>,
+    flags: MsFlags,
+    data: Option<&str>,
+) -> Result<(), String> {
+    nix::mount::mount(src, target, fstype, flags, data)
+        .map_err(|e| format!("Error while mounting {:?} on {:?}: {}", src, target, e))
+}
+
+/// Undo the escaping of spaces etc. in /proc/self/mountinfo (e.g. "\040" for a space)
+fn unescape_mount_path(path: &str) -> PathBuf {
+    let bytes = path.as_bytes();
+    let mut unescaped = Vec::new();
+    let mut idx = 0;
+    while idx < bytes.len() {
+        if bytes[idx] == b'\\' && idx + 3 < bytes.len() {
+            if let Ok(c) = u8::from_str_radix(&path[idx + 1..idx + 4], 8) {
+                unescaped.push(c);
+                idx += 4;
+                continue;
+            }
+        }
+        unescaped.push(bytes[idx]);
+        idx += 1;
+    }
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(unescaped))
+}
+
+/// All current mount points of this mount namespace
+fn mount_points() -> Result<Vec<PathBuf>, String> {
+    let mountinfo = std::fs::read_to_string("/proc/self/mountinfo")
+        .map_err(|e| format!("Error while reading /proc/self/mountinfo: {}", e))?;
+    let mut points = Vec::new();
+    for line in mountinfo.lines() {
+        if let Some(point) = line.split(' ').nth(4) {
+            let point = unescape_mount_path(point);
+            if !points.contains(&point) {
+                points.push(point);
+            }
+        }
+    }
+    Ok(points)
+}
+
+/// A remount with MS_BIND only changes the flags of this one mount. The flags that are not changed need to be passed again.
+fn remount(path: &Path, flags: MsFlags) -> Result<(), String> {
+    let mut flags = flags | MsFlags::MS_REMOUNT | MsFlags::MS_BIND;
+    if let Ok(stat) = nix::sys::statvfs::statvfs(path) {
+        use nix::sys::statvfs::FsFlags;
+        let stat_flags = stat.flags();
+        if stat_flags.contains(FsFlags::ST_NOSUID) {
+            flags |= MsFlags::MS_NOSUID;
+        }
+        if stat_flags.contains(FsFlags::ST_NODEV) {
+            flags |= MsFlags::MS_NODEV;
+        }
+        if stat_flags.contains(FsFlags::ST_NOEXEC) {
+            flags |= MsFlags::MS_NOEXEC;
+        }
+    }
+    mount(None, path, None, flags, None)
+}
+
+fn bind_onto_itself(path: &Path) -> Result<(), String> {
+    mount(
+        Some(path),
+        path,
+        None,
+        MsFlags::MS_BIND | MsFlags::MS_REC,
+        None,
+    )
+}
+
+/// Mount a tmpfs with options like "ro,mode=0755". Flags like ro/nosuid are passed as flags, the rest as data.
+fn mount_tmpfs(path: &Path, options: &str, flags: MsFlags) -> Result<(), String> {
+    let mut flags = flags;
+    let mut data = Vec::new();
+    for option in options.split(',').filter(|option| !option.is_empty()) {
+        match option {
+            "ro" => flags |= MsFlags::MS_RDONLY,
+            "rw" => flags &= !MsFlags::MS_RDONLY,
+            "nosuid" => flags |= MsFlags::MS_NOSUID,
+            "nodev" => flags |= MsFlags::MS_NODEV,
+            "noexec" => flags |= MsFlags::MS_NOEXEC,
+            "suid" => flags &= !MsFlags::MS_NOSUID,
+            "dev" => flags &= !MsFlags::MS_NODEV,
+            "exec" => flags &= !MsFlags::MS_NOEXEC,
+            _ => data.push(option),
+        }
+    }
+    let data = data.join(",");
+    mount(
+        Some(Path::new("tmpfs")),
+        path,
+        Some("tmpfs"),
+        flags,
+        Some(data.as_str()),
+    )
+}
+
+fn check_exists(path: &SandboxPath) -> Result<bool, String> {
+    if path.path.exists() {
+        Ok(true)
+    } else if path.ignore_missing {
+        Ok(false)
+    } else {
+        Err(format!("Path does not exist: {:?}", path.path))
+    }
+}
+
+/// Open the source of a bind mount before any mounts are changed, so sources below e.g. /tmp are still found after PrivateTmp= hid them.
+/// The fd is closed on exec. None if the source does not exist and may be ignored.
+fn open_bind_source(bind: &BindPath) -> Result<Option<RawFd>, String> {
+    if !bind.src.exists() {
+        if bind.ignore_missing {
+            return Ok(None);
+        }
+        return Err(format!("Path does not exist: {:?}", bind.src));
+    }
+    nix::fcntl::open(
+        &bind.src,
+        OFlag::O_PATH | OFlag::O_CLOEXEC,
+        nix::sys::stat::Mode::empty(),
+    )
+    .map(Some)
+    .map_err(|e| format!("Error while opening {:?}: {}", bind.src, e))
+}
+
+fn bind_path(bind: &BindPath, src_fd: Option<RawFd>, read_only: bool) -> Result<(), String> {
+    let src_fd = if let Some(fd) = src_fd {
+        fd
+    } else {
+        return Ok(());
+    };
+    if !bind.dst.exists() {
+        return Err(format!("Bind mount target does not exist: {:?}", bind.dst));
+    }
+    let mut flags = MsFlags::MS_BIND;
+    if bind.recursive {
+        flags |= MsFlags::MS_REC;
+    }
+    let src = PathBuf::from(format!("/proc/self/fd/{}", src_fd));
+    nix::mount::mount(Some(&src), &bind.dst, None::<&str>, flags, None::<&str>).map_err(|e| {
+        format!(
+            "Error while mounting {:?} on {:?}: {}",
+            bind.src, bind.dst, e
+        )
+    })?;
+    if read_only {
+        remount(&bind.dst, MsFlags::MS_RDONLY)?;
+    }
+    Ok(())
+}
+
+/// Make this path and all mounts below it read only. Mounts below paths in `exempt` that are below this path are left as they are.
+fn make_read_only(path: &Path, exempt: &[PathBuf]) -> Result<(), String> {
+    // only mount points can be remounted
+    if !mount_points()?.iter().any(|point| point == path) {
+        bind_onto_itself(path)?;
+    }
+    for point in mount_points()? {
+        if !point.starts_with(path) {
+            continue;
+        }
+        let is_exempt = exempt
+            .iter()
+            .any(|ex| point.starts_with(ex) && ex.starts_with(path) && ex != path);
+        if is_exempt {
+            continue;
+        }
+        remount(&point, MsFlags::MS_RDONLY)?;
+    }
+    Ok(())
+}
+
+fn make_inaccessible(path: &Path) -> Result<(), String> {
+    if path.is_dir() {
+        mount_tmpfs(
+            path,
+            "mode=000",
+            MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+        )
+    } else {
+        // opening a device node on a nodev mount is denied
+        mount(
+            Some(Path::new("/dev/null")),
+            path,
+            None,
+            MsFlags::MS_BIND,
+            None,
+        )?;
+        remount(
+            path,
+            MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV | MsFlags::MS_NOEXEC,
+        )
+    }
+}
+
+/// Build a new /dev that only contains the pseudo devices, /dev/pts and /dev/shm. It is built in a temporary directory
+/// because the device nodes are bind mounted from the old /dev and then moved onto /dev.
+/// Create a new dir with a random suffix and mode 0700 (mkdtemp). Nobody else can have created or replaced it before, even in a world
+/// writable dir like /tmp.
+fn make_temp_dir(prefix: &str) -> Result<PathBuf, String> {
+    let mut template = format!("{}XXXXXX", prefix).into_bytes();
+    template.push(0);
+    let res = unsafe { libc::mkdtemp(template.as_mut_ptr() as *mut libc::c_char) };
+    if res.is_null() {
+        return Err(format!(
+            "Error while creating a temporary dir {}XXXXXX: {}",
+            prefix,
+            nix::errno::Errno::last()
+        ));
+    }
+    template.pop();
+    Ok(PathBuf::from(std::ffi::OsString::from_vec(template)))
+}
+
+fn setup_private_devices() -> Result<(), String> {
+    let tmp_dev = make_temp_dir("/tmp/rustysd-private-dev-")?;
+    let res = build_private_devices(&tmp_dev);
+    let _ = std::fs::remove_dir(&tmp_dev);
+    res
+}
+
+fn build_private_devices(tmp_dev: &Path) -> Result<(), String> {
+    mount_tmpfs(
+        tmp_dev,
+        "mode=0755",
+        MsFlags::MS_NOSUID | MsFlags::MS_NOEXEC | MsFlags::MS_STRICTATIME,
+    )?;
+    for node in &PRIVATE_DEVICE_NODES {
+        let src = Path::new("/dev").join(node);
+        if !src.exists() {
+            continue;
+        }
+        let dst = tmp_dev.join(node);
+        std::fs::File::create(&dst)
+            .map_err(|e| format!("Error while creating {:?}: {}", dst, e))?;
+        mount(Some(&src), &dst, None, MsFlags::MS_BIND, None)?;
+    }
+    for dir in &["pts", "shm"] {
+        let src = Path::new("/dev").join(dir);
+        if !src.exists() {
+            continue;
+        }
+        let dst = tmp_dev.join(dir);
+        std::fs::create_dir(&dst).map_err(|e| format!("Error while creating {:?}: {}", dst, e))?;
+        mount(
+            Some(&src),
+            &dst,
+            None,
+            MsFlags::MS_BIND | MsFlags::MS_REC,
+            None,
+        )?;
+    }
+    let links = [
+        ("ptmx", "pts/ptmx"),
+        ("fd", "/proc/self/fd"),
+        ("stdin", "/proc/self/fd/0"),
+        ("stdout", "/proc/self/fd/1"),
+        ("stderr", "/proc/self/fd/2"),
+    ];
+    for (name, target) in &links {
+        let link = tmp_dev.join(name);
+        std::os::unix::fs::symlink(target, &link)
+            .map_err(|e| format!("Error while creating {:?}: {}", link, e))?;
+    }
+    mount(
+        Some(tmp_dev),
+        Path::new("/dev"),
+        None,
+        MsFlags::MS_MOVE,
+        None,
+    )
+}
+
+/// The paths of the protection settings that exist on this system
+fn existing_paths(paths: &[&str]) -> Vec<PathBuf> {
+    paths
+        .iter()
+        .map(PathBuf::from)
+        .filter(|path| path.exists())
+        .collect()
+}
+
+pub fn setup_filesystem_sandbox(sandbox: &FilesystemSandbox) -> Result<(), String> {
+    if !sandbox.is_enabled() {
+        return Ok(());
+    }
+
+    nix::sched::unshare(nix::sched::CloneFlags::CLONE_NEWNS)
+        .map_err(|e| format!("Error while creating mount namespace: {}", e))?;
+    // mounts of the host still show up in the service but changes made here must not propagate back to the host
+    mount(
+        None,
+        Path::new("/"),
+        None,
+        MsFlags::MS_REC | MsFlags::MS_SLAVE,
+        None,
+    )?;
+
+    let bind_sources = sandbox
+        .bind_paths
+        .iter()
+        .map(open_bind_source)
+        .collect::<Result<Vec<_>, _>>()?;
+    let bind_read_only_sources = sandbox
+        .bind_read_only_paths
+        .iter()
+        .map(open_bind_source)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // these stay as they are even if they are below a read only path
+    let mut exempt: Vec<PathBuf> = Vec::new();
+
+    if sandbox.private_devices {
+        setup_private_devices()?;
+    }
+
+    if sandbox.private_tmp {
+        for path in &["/tmp", "/var/tmp"] {
+            let path = Path::new(path);
+            if path.exists() {
+                mount_tmpfs(path, "mode=1777", MsFlags::MS_NOSUID | MsFlags::MS_NODEV)?;
+                exempt.push(path.to_path_buf());
+            }
+        }
+    }
+    for tmpfs in &sandbox.temporary_file_systems {
+        mount_tmpfs(
+            &tmpfs.path,
+            &tmpfs.options,
+            MsFlags::MS_NODEV | MsFlags::MS_STRICTATIME,
+        )?;
+        exempt.push(tmpfs.path.clone());
+    }
+    for (bind, src_fd) in sandbox.bind_paths.iter().zip(bind_sources) {
+        bind_path(bind, src_fd, false)?;
+        exempt.push(bind.dst.clone());
+    }
+    for (bind, src_fd) in sandbox
+        .bind_read_only_paths
+        .iter()
+        .zip(bind_read_only_sources)
+    {
+        bind_path(bind, src_fd, true)?;
+        exempt.push(bind.dst.clone());
+    }
+    for path in &sandbox.read_write_paths {
+        if check_exists(path)? {
+            bind_onto_itself(&path.path)?;
+            exempt.push(path.path.clone());
+        }
+    }
+
+    let mut read_only: Vec<PathBuf> = protected_system_paths(sandbox.protect_system)
+        .iter()
+        .map(PathBuf::from)
+        .filter(|path| path.exists())
+        .collect();
+    if sandbox.protect_system == ProtectSystem::Strict {
+        for path in &["/dev", "/proc", "/sys"] {
+            exempt.push(PathBuf::from(path));
+        }
+    }
+    if sandbox.protect_home == ProtectHome::ReadOnly {
+        read_only.extend(
+            HOME_PATHS
+                .iter()
+                .map(PathBuf::from)
+                .filter(|path| path.exists()),
+        );
+    }
+    for path in &sandbox.read_only_paths {
+        if check_exists(path)? {
+            read_only.push(path.path.clone());
+        }
+    }
+    if sandbox.protect_kernel_tunables {
+        read_only.extend(existing_paths(&KERNEL_TUNABLE_PATHS));
+    }
+    if sandbox.protect_control_groups {
+        read_only.extend(existing_paths(&["/sys/fs/cgroup"]));
+    }
+    // parents first so the paths below them are handled after them
+    read_only.sort_by_key(|path| path.components().count());
+    for path in &read_only {
+        make_read_only(path, &exempt)?;
+    }
+
+    match sandbox.protect_home {
+        ProtectHome::Yes => {
+            for path in HOME_PATHS.iter().map(Path::new).filter(|p| p.exists()) {
+                make_inaccessible(path)?;
+            }
+        }
+        ProtectHome::Tmpfs => {
+            for path in HOME_PATHS.iter().map(Path::new).filter(|p| p.exists()) {
+                mount_tmpfs(
+                    path,
+                    "mode=0755",
+                    MsFlags::MS_RDONLY | MsFlags::MS_NOSUID | MsFlags::MS_NODEV,
+                )?;
+            }
+        }
+        ProtectHome::No | ProtectHome::ReadOnly => {}
+    }
+    let mut inaccessible = Vec::new();
+    if sandbox.protect_kernel_tunables {
+        inaccessible.extend(existing_paths(&KERNEL_MEMORY_PATHS));
+    }
+    if sandbox.protect_kernel_modules {
+        inaccessible.extend(existing_paths(&KERNEL_MODULE_PATHS));
+    }
+    if sandbox.protect_kernel_logs {
+        inaccessible.extend(existing_paths(&KERNEL_LOG_PATHS));
+    }
+    for path in &sandbox.inaccessible_paths {
+        if check_exists(path)? {
+            inaccessible.push(path.path.clone());
+        }
+    }
+    for path in &inaccessible {
+        make_inaccessible(path)?;
+    }
+
+    Ok(())
+}
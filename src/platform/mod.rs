@@ -7,12 +7,26 @@
 //! subreaper should contain an implementation that sets a process as the subreaper for the current process tree
 //! (not sure what should happen if the platform doesnt provide this feature)
 //!
-//! eventfd should contain an implementation that creates an eventfd (or a similarly working) tuple of filedescriptors
-//! The pipe() implementation should work (in some variation) on many platforms
+//! epoll should contain an implementation that waits for readability on a dynamic set of filedescriptors.
+//! On linux this uses epoll, the other platforms use poll with the same interface (kqueue would be the better fit there)
+//!
+//! capabilities knows the names of the linux capabilities and applies the capability settings when privileges are dropped
+//!
+//! namespaces sets up the network, UTS and user namespaces of services. This is linux only too.
+//!
+//! seccomp builds and installs the system call filters of services. This is only implemented for linux on x86_64 and aarch64
+//! because it needs a table of the system call numbers for each architecture.
+//!
+//! resource_limits applies the Limit*= settings with setrlimit()
+//!
+//! process_settings applies the scheduling settings, umask, personality and root/working directory of service processes
+//!
+//! fs_sandbox sets up the mount namespace of services for the filesystem sandboxing settings. This is linux only, on other
+//! platforms starting a service that uses these settings fails.
 //!
 //! ## Redox support
 //! To implement all this stuff in redox we probably need these crates:
-//! 1. relibc (for the select, which is not yet in the syscalls crate?)
+//! 1. relibc (for waiting on filedescriptors, the syscalls crate has no epoll equivalent)
 //! 2. syscall (for most of all other nix:: functions)
 //!
 //! We could also wait for [this pull request](https://github.com/nix-rust/nix/pull/1098) to the nix crate to get redox support in there which would
@@ -21,17 +35,102 @@
 //! We'd also need to make some more functionality optional like subprocess reaping (which only matters if we are not PID1)
 //!
 
+mod capabilities;
 mod drop_privileges;
-mod eventfd;
+#[cfg(target_os = "linux")]
+mod epoll;
+#[cfg(target_os = "linux")]
+mod fs_sandbox;
+#[cfg(target_os = "linux")]
+mod namespaces;
+#[cfg(not(target_os = "linux"))]
+mod poll;
+mod process_settings;
+mod resource_limits;
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+mod seccomp;
 mod subreaper;
 mod unix_common;
 
+pub use capabilities::*;
 pub use drop_privileges::*;
-pub use eventfd::*;
+#[cfg(target_os = "linux")]
+pub use epoll::*;
+#[cfg(target_os = "linux")]
+pub use fs_sandbox::*;
+#[cfg(target_os = "linux")]
+pub use namespaces::*;
+#[cfg(not(target_os = "linux"))]
+pub use poll::Poll as Epoll;
+pub use process_settings::*;
+pub use resource_limits::*;
+#[cfg(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+))]
+pub use seccomp::*;
 pub use subreaper::*;
 pub mod grnam;
 pub mod pwnam;
 
+#[cfg(not(target_os = "linux"))]
+pub fn setup_filesystem_sandbox(sandbox: &crate::units::FilesystemSandbox) -> Result<(), String> {
+    if sandbox.is_enabled() {
+        Err("Filesystem sandboxing is only supported on linux".into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn setup_syscall_filter(filter: &crate::units::SyscallFilter) -> Result<(), String> {
+    if filter.is_enabled() {
+        Err("System call filtering is not supported on this platform".into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(all(
+    target_os = "linux",
+    any(target_arch = "x86_64", target_arch = "aarch64")
+)))]
+pub fn setup_protection_filter(
+    sandbox: &crate::units::FilesystemSandbox,
+    namespaces: &crate::units::NamespaceSandbox,
+) -> Result<(), String> {
+    let needs_filter = sandbox.protect_kernel_modules
+        || sandbox.protect_kernel_logs
+        || sandbox.private_devices
+        || namespaces.protect_hostname
+        || namespaces.restrict_namespaces.is_some();
+    if needs_filter {
+        Err("System call filtering is not supported on this platform".into())
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn setup_namespaces(
+    namespaces: &crate::units::NamespaceSandbox,
+    _uid: nix::unistd::Uid,
+    _gid: nix::unistd::Gid,
+    _supp_gids: &[nix::unistd::Gid],
+) -> Result<(), String> {
+    if *namespaces != crate::units::NamespaceSandbox::default() {
+        Err("Namespaces are only supported on linux".into())
+    } else {
+        Ok(())
+    }
+}
+
 //#[cfg(feature = "cgroups")]
 pub mod cgroups;
 
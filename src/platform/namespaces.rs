@@ -0,0 +1,136 @@
+//! Namespaces other than the mount namespace for PrivateNetwork=, ProtectHostname= and PrivateUsers=. Like the filesystem sandbox
+//! this runs in the forked child, so it must not use the logger.
+
+use crate::units::NamespaceSandbox;
+use nix::sched::CloneFlags;
+use nix::unistd::{Gid, Uid};
+
+const IFNAMSIZ: usize = 16;
+
+/// struct ifreq with the ifr_flags member of the union
+#[repr(C)]
+struct IfReqFlags {
+    name: [u8; IFNAMSIZ],
+    flags: libc::c_short,
+    _pad: [u8; 22],
+}
+
+/// A new network namespace only has a loopback device which is down
+fn loopback_up() -> Result<(), String> {
+    let sock = nix::sys::socket::socket(
+        nix::sys::socket::AddressFamily::Inet,
+        nix::sys::socket::SockType::Datagram,
+        nix::sys::socket::SockFlag::SOCK_CLOEXEC,
+        None,
+    )
+    .map_err(|e| format!("Error while opening socket: {}", e))?;
+
+    let mut req = IfReqFlags {
+        name: [0; IFNAMSIZ],
+        flags: 0,
+        _pad: [0; 22],
+    };
+    req.name[..2].copy_from_slice(b"lo");
+    let res = unsafe { libc::ioctl(sock, libc::SIOCGIFFLAGS as _, &mut req as *mut IfReqFlags) };
+    let res = if res < 0 {
+        res
+    } else {
+        req.flags |= libc::IFF_UP as libc::c_short;
+        unsafe { libc::ioctl(sock, libc::SIOCSIFFLAGS as _, &mut req as *mut IfReqFlags) }
+    };
+    let err = std::io::Error::last_os_error();
+    let _ = nix::unistd::close(sock);
+    if res < 0 {
+        Err(format!(
+            "Error while setting the loopback device up: {}",
+            err
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Map root and the ids of the service to themselves. All other ids show up as the overflow id (nobody).
+fn id_map(ids: &[u32]) -> String {
+    let mut mapped: Vec<u32> = vec![0];
+    for id in ids {
+        if !mapped.contains(id) {
+            mapped.push(*id);
+        }
+    }
+    mapped
+        .iter()
+        .map(|id| format!("{} {} 1\n", id, id))
+        .collect()
+}
+
+/// A process in a new user namespace has no capabilities in the parent namespace, so it can only map its own uid.
+/// The maps are written by a helper process that stays in the parent namespace instead.
+fn setup_user_namespace(uid: Uid, gid: Gid, supp_gids: &[Gid]) -> Result<(), String> {
+    let uid_map = id_map(&[uid.as_raw()]);
+    let mut gids = vec![gid.as_raw()];
+    gids.extend(supp_gids.iter().map(|gid| gid.as_raw()));
+    let gid_map = id_map(&gids);
+
+    let (read_fd, write_fd) = nix::unistd::pipe2(nix::fcntl::OFlag::O_CLOEXEC)
+        .map_err(|e| format!("Error while creating pipe: {}", e))?;
+    let pid = nix::unistd::getpid();
+
+    match unsafe { nix::unistd::fork() } {
+        Ok(nix::unistd::ForkResult::Child) => {
+            let _ = nix::unistd::close(write_fd);
+            let mut buf = [0u8; 1];
+            // zero bytes means the parent closed the pipe without creating the namespace
+            let ok = nix::unistd::read(read_fd, &mut buf) == Ok(1)
+                && std::fs::write(format!("/proc/{}/uid_map", pid), &uid_map).is_ok()
+                && std::fs::write(format!("/proc/{}/gid_map", pid), &gid_map).is_ok();
+            std::process::exit(if ok { 0 } else { 1 });
+        }
+        Ok(nix::unistd::ForkResult::Parent { child }) => {
+            let _ = nix::unistd::close(read_fd);
+            let res = nix::sched::unshare(CloneFlags::CLONE_NEWUSER)
+                .map_err(|e| format!("Error while creating user namespace: {}", e));
+            if res.is_ok() {
+                let _ = nix::unistd::write(write_fd, &[1]);
+            }
+            let _ = nix::unistd::close(write_fd);
+            let status = nix::sys::wait::waitpid(child, None);
+            res?;
+            match status {
+                Ok(nix::sys::wait::WaitStatus::Exited(_, 0)) => Ok(()),
+                _ => Err(format!(
+                    "Could not write the uid/gid map of the user namespace of {}",
+                    pid
+                )),
+            }
+        }
+        Err(e) => {
+            let _ = nix::unistd::close(read_fd);
+            let _ = nix::unistd::close(write_fd);
+            Err(format!("Error while forking the uid map helper: {}", e))
+        }
+    }
+}
+
+/// The user namespace is created last, so the other namespaces and the mounts are still owned by the
+/// initial user namespace and can not be changed from within the service.
+pub fn setup_namespaces(
+    namespaces: &NamespaceSandbox,
+    uid: Uid,
+    gid: Gid,
+    supp_gids: &[Gid],
+) -> Result<(), String> {
+    if namespaces.private_network {
+        nix::sched::unshare(CloneFlags::CLONE_NEWNET)
+            .map_err(|e| format!("Error while creating network namespace: {}", e))?;
+        loopback_up()?;
+    }
+    if namespaces.protect_hostname {
+        nix::sched::unshare(CloneFlags::CLONE_NEWUTS)
+            .map_err(|e| format!("Error while creating UTS namespace: {}", e))?;
+    }
+    if namespaces.private_users {
+        setup_user_namespace(uid, gid, supp_gids)?;
+    }
+    Ok(())
+}
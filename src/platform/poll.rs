@@ -0,0 +1,103 @@
+//! Fallback for the platforms without epoll, with the same interface as the epoll wrapper. poll() gets the whole set of filedescriptors on
+//! every call, so adding or removing one wakes up a waiting call through a pipe, which then picks up the new set.
+
+use nix::poll::{PollFd, PollFlags};
+use std::os::unix::io::RawFd;
+use std::sync::Mutex;
+
+pub struct Poll {
+    fds: Mutex<Vec<RawFd>>,
+    wakeup_read: RawFd,
+    wakeup_write: RawFd,
+}
+
+impl Poll {
+    pub fn new() -> Result<Poll, String> {
+        let (wakeup_read, wakeup_write) = nix::unistd::pipe()
+            .map_err(|e| format!("Error while creating the wakeup pipe: {}", e))?;
+        for fd in &[wakeup_read, wakeup_write] {
+            nix::fcntl::fcntl(
+                *fd,
+                nix::fcntl::FcntlArg::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC),
+            )
+            .and_then(|_| {
+                nix::fcntl::fcntl(
+                    *fd,
+                    nix::fcntl::FcntlArg::F_SETFL(nix::fcntl::OFlag::O_NONBLOCK),
+                )
+            })
+            .map_err(|e| format!("Error while setting up the wakeup pipe: {}", e))?;
+        }
+        Ok(Poll {
+            fds: Mutex::new(Vec::new()),
+            wakeup_read,
+            wakeup_write,
+        })
+    }
+
+    /// Interrupt a waiting call so it uses the changed set of fds. If the pipe is full there is a wakeup pending anyways.
+    fn wake(&self) {
+        let _ = nix::unistd::write(self.wakeup_write, &[0]);
+    }
+
+    /// Start listening for readability on this fd. Adding an fd that is already registered is not an error.
+    pub fn add(&self, fd: RawFd) -> Result<(), String> {
+        let mut fds = self.fds.lock().unwrap();
+        if !fds.contains(&fd) {
+            fds.push(fd);
+            self.wake();
+        }
+        Ok(())
+    }
+
+    /// Stop listening on this fd. Removing an fd that is not registered (or already closed) is not an error.
+    pub fn remove(&self, fd: RawFd) {
+        let mut fds = self.fds.lock().unwrap();
+        if fds.contains(&fd) {
+            fds.retain(|registered| *registered != fd);
+            self.wake();
+        }
+    }
+
+    /// Wait for events and return the fds that are readable. A timeout of None blocks until an event happens.
+    pub fn wait(&self, timeout: Option<std::time::Duration>) -> Result<Vec<RawFd>, String> {
+        let timeout_ms = match timeout {
+            // round up so we do not wake up just before the timeout passed
+            Some(dur) => {
+                dur.as_micros().div_ceil(1000).min(libc::c_int::MAX as u128) as libc::c_int
+            }
+            None => -1,
+        };
+        let mut fds = self.fds.lock().unwrap().clone();
+        fds.push(self.wakeup_read);
+        let mut poll_fds: Vec<PollFd> = fds
+            .iter()
+            .map(|fd| PollFd::new(*fd, PollFlags::POLLIN))
+            .collect();
+        match nix::poll::poll(&mut poll_fds, timeout_ms) {
+            Ok(_) => {}
+            Err(nix::Error::EINTR) => return Ok(Vec::new()),
+            Err(e) => return Err(format!("Error while waiting on poll: {}", e)),
+        }
+
+        let mut ready = Vec::new();
+        for (fd, poll_fd) in fds.iter().zip(poll_fds.iter()) {
+            let revents = poll_fd.revents().unwrap_or_else(PollFlags::empty);
+            // like epoll, hangups and errors are reported as events too. Fds that were closed without being removed are skipped
+            if !revents.intersects(PollFlags::POLLIN | PollFlags::POLLHUP | PollFlags::POLLERR) {
+                continue;
+            }
+            if *fd == self.wakeup_read {
+                let mut buf = [0u8; 64];
+                while let Ok(count) = nix::unistd::read(self.wakeup_read, &mut buf) {
+                    if count == 0 {
+                        break;
+                    }
+                }
+            } else {
+                ready.push(*fd);
+            }
+        }
+        Ok(ready)
+    }
+}
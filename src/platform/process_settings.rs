@@ -0,0 +1,309 @@
+//! Scheduling, umask, personality, root and working directory of the processes of a service. This runs in the forked child
+//! (or before the exec of helper commands), so it must not use the logger.
+
+use crate::units::{
+    CpuSchedulingPolicy, IoSchedulingClass, Personality, ProcessSettings, SandboxPath,
+};
+use std::ffi::{CStr, CString};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+fn with_context(what: &str) -> impl Fn(nix::errno::Errno) -> String + '_ {
+    move |e| format!("Error while {}: {}", what, e)
+}
+
+fn set_nice(nice: i32) -> nix::Result<()> {
+    let res = unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) };
+    nix::errno::Errno::result(res).map(drop)
+}
+
+/// The policy and its parameter for sched_setscheduler()
+fn cpu_scheduling(
+    policy: Option<CpuSchedulingPolicy>,
+    priority: Option<i32>,
+) -> (libc::c_int, libc::sched_param) {
+    let policy = match policy {
+        Some(CpuSchedulingPolicy::Other) => libc::SCHED_OTHER,
+        Some(CpuSchedulingPolicy::Batch) => libc::SCHED_BATCH,
+        Some(CpuSchedulingPolicy::Idle) => libc::SCHED_IDLE,
+        Some(CpuSchedulingPolicy::Fifo) => libc::SCHED_FIFO,
+        Some(CpuSchedulingPolicy::RoundRobin) => libc::SCHED_RR,
+        None => unsafe { libc::sched_getscheduler(0) },
+    };
+    // the priority only has a meaning for the realtime policies, the others need 0
+    let sched_priority = if policy == libc::SCHED_FIFO || policy == libc::SCHED_RR {
+        priority.unwrap_or(1)
+    } else {
+        0
+    };
+    (policy, libc::sched_param { sched_priority })
+}
+
+fn set_cpu_scheduling(policy: libc::c_int, param: &libc::sched_param) -> nix::Result<()> {
+    let res = unsafe { libc::sched_setscheduler(0, policy, param) };
+    nix::errno::Errno::result(res).map(drop)
+}
+
+#[cfg(target_os = "linux")]
+type CpuSet = nix::sched::CpuSet;
+#[cfg(not(target_os = "linux"))]
+type CpuSet = ();
+
+#[cfg(target_os = "linux")]
+fn cpu_set(cpus: &[usize]) -> Result<CpuSet, String> {
+    let mut set = nix::sched::CpuSet::new();
+    for cpu in cpus {
+        set.set(*cpu)
+            .map_err(|e| format!("Invalid cpu {} in CPUAffinity=: {}", cpu, e))?;
+    }
+    Ok(set)
+}
+
+#[cfg(target_os = "linux")]
+fn set_cpu_affinity(set: &CpuSet) -> nix::Result<()> {
+    nix::sched::sched_setaffinity(nix::unistd::Pid::from_raw(0), set)
+}
+
+/// The value for ioprio_set()
+#[cfg(target_os = "linux")]
+fn io_priority(
+    class: Option<IoSchedulingClass>,
+    priority: Option<u8>,
+) -> Result<libc::c_int, String> {
+    const IOPRIO_CLASS_SHIFT: libc::c_int = 13;
+    // like systemd the class defaults to best-effort if only the priority is set
+    let class = match class.unwrap_or(IoSchedulingClass::BestEffort) {
+        IoSchedulingClass::None => 0,
+        IoSchedulingClass::Realtime => 1,
+        IoSchedulingClass::BestEffort => 2,
+        IoSchedulingClass::Idle => 3,
+    };
+    // the idle class has no priorities
+    let priority = if class == 3 {
+        0
+    } else {
+        libc::c_int::from(priority.unwrap_or(4))
+    };
+    Ok((class << IOPRIO_CLASS_SHIFT) | priority)
+}
+
+#[cfg(target_os = "linux")]
+fn set_io_priority(io_priority: libc::c_int) -> nix::Result<()> {
+    const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+    let res = unsafe { libc::syscall(libc::SYS_ioprio_set, IOPRIO_WHO_PROCESS, 0, io_priority) };
+    nix::errno::Errno::result(res).map(drop)
+}
+
+/// Only the native personality and the 32 bit one of the same architecture family can be set
+#[cfg(target_os = "linux")]
+fn persona(personality: Personality) -> Result<libc::c_ulong, String> {
+    const PER_LINUX: libc::c_ulong = 0x0000;
+    const PER_LINUX32: libc::c_ulong = 0x0008;
+    match personality {
+        #[cfg(target_arch = "x86_64")]
+        Personality::X86_64 => Ok(PER_LINUX),
+        #[cfg(target_arch = "x86_64")]
+        Personality::X86 => Ok(PER_LINUX32),
+        #[cfg(target_arch = "aarch64")]
+        Personality::Arm64 => Ok(PER_LINUX),
+        #[cfg(target_arch = "aarch64")]
+        Personality::Arm => Ok(PER_LINUX32),
+        other => Err(format!(
+            "Personality {:?} is not supported on this architecture",
+            other
+        )),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn set_personality(persona: libc::c_ulong) -> nix::Result<()> {
+    let res = unsafe { libc::personality(persona) };
+    nix::errno::Errno::result(res).map(drop)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn cpu_set(_cpus: &[usize]) -> Result<CpuSet, String> {
+    Err("CPUAffinity= is only supported on linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_cpu_affinity(_set: &CpuSet) -> nix::Result<()> {
+    Err(nix::errno::Errno::ENOSYS)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn io_priority(
+    _class: Option<IoSchedulingClass>,
+    _priority: Option<u8>,
+) -> Result<libc::c_int, String> {
+    Err("IOSchedulingClass= is only supported on linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_io_priority(_io_priority: libc::c_int) -> nix::Result<()> {
+    Err(nix::errno::Errno::ENOSYS)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn persona(_personality: Personality) -> Result<libc::c_ulong, String> {
+    Err("Personality= is only supported on linux".into())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn set_personality(_persona: libc::c_ulong) -> nix::Result<()> {
+    Err(nix::errno::Errno::ENOSYS)
+}
+
+fn path_cstring(path: &Path) -> Result<CString, String> {
+    CString::new(path.as_os_str().as_bytes()).map_err(|e| format!("Invalid path {:?}: {}", path, e))
+}
+
+fn change_root(root: &CStr) -> nix::Result<()> {
+    nix::unistd::chroot(root)?;
+    nix::unistd::chdir("/")
+}
+
+/// The process settings converted to the arguments of the system calls. Applying them does not allocate, so helper commands
+/// can apply them in the pre_exec hook of std::process::Command, which runs between fork and exec in a copy of the multithreaded rustysd.
+pub struct PreparedProcessSettings {
+    nice: Option<i32>,
+    cpu_scheduling: Option<(libc::c_int, libc::sched_param)>,
+    cpu_affinity: Option<CpuSet>,
+    io_priority: Option<libc::c_int>,
+    persona: Option<libc::c_ulong>,
+    umask: Option<nix::sys::stat::Mode>,
+    root_directory: Option<CString>,
+    working_directory: Option<(CString, bool)>,
+}
+
+impl PreparedProcessSettings {
+    pub fn new(
+        settings: &ProcessSettings,
+        working_directory: Option<&SandboxPath>,
+    ) -> Result<PreparedProcessSettings, String> {
+        Ok(PreparedProcessSettings {
+            nice: settings.nice,
+            cpu_scheduling: if settings.cpu_scheduling_policy.is_some()
+                || settings.cpu_scheduling_priority.is_some()
+            {
+                Some(cpu_scheduling(
+                    settings.cpu_scheduling_policy,
+                    settings.cpu_scheduling_priority,
+                ))
+            } else {
+                None
+            },
+            cpu_affinity: if settings.cpu_affinity.is_empty() {
+                None
+            } else {
+                Some(cpu_set(&settings.cpu_affinity)?)
+            },
+            io_priority: if settings.io_scheduling_class.is_some()
+                || settings.io_scheduling_priority.is_some()
+            {
+                Some(io_priority(
+                    settings.io_scheduling_class,
+                    settings.io_scheduling_priority,
+                )?)
+            } else {
+                None
+            },
+            persona: settings.personality.map(persona).transpose()?,
+            umask: settings
+                .umask
+                .map(|umask| nix::sys::stat::Mode::from_bits_truncate(umask as _)),
+            root_directory: settings
+                .root_directory
+                .as_deref()
+                .map(path_cstring)
+                .transpose()?,
+            working_directory: working_directory
+                .map(|dir| path_cstring(&dir.path).map(|path| (path, dir.ignore_missing)))
+                .transpose()?,
+        })
+    }
+
+    /// Apply all settings including the root and working directory. Only the errno is returned, a message would need an allocation.
+    pub fn apply(&self) -> nix::Result<()> {
+        if let Some(nice) = self.nice {
+            set_nice(nice)?;
+        }
+        if let Some((policy, param)) = &self.cpu_scheduling {
+            set_cpu_scheduling(*policy, param)?;
+        }
+        if let Some(set) = &self.cpu_affinity {
+            set_cpu_affinity(set)?;
+        }
+        if let Some(io_priority) = self.io_priority {
+            set_io_priority(io_priority)?;
+        }
+        if let Some(persona) = self.persona {
+            set_personality(persona)?;
+        }
+        if let Some(umask) = self.umask {
+            nix::sys::stat::umask(umask);
+        }
+        if let Some(root) = &self.root_directory {
+            change_root(root)?;
+        }
+        match &self.working_directory {
+            Some((dir, ignore_missing)) => match nix::unistd::chdir(dir.as_c_str()) {
+                Err(_) if *ignore_missing => Ok(()),
+                res => res,
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+/// Apply the settings to the current process, except for the root directory. This needs to happen before the privileges are dropped
+/// (lowering the nice level and realtime scheduling need root) and before the user namespace is created, which has none of these privileges.
+pub fn apply_process_settings(settings: &ProcessSettings) -> Result<(), String> {
+    let prepared = PreparedProcessSettings::new(settings, None)?;
+    if let Some(nice) = prepared.nice {
+        set_nice(nice).map_err(with_context("setting the nice level"))?;
+    }
+    if let Some((policy, param)) = &prepared.cpu_scheduling {
+        set_cpu_scheduling(*policy, param)
+            .map_err(with_context("setting the cpu scheduling policy"))?;
+    }
+    if let Some(set) = &prepared.cpu_affinity {
+        set_cpu_affinity(set).map_err(with_context("setting the cpu affinity"))?;
+    }
+    if let Some(io_priority) = prepared.io_priority {
+        set_io_priority(io_priority).map_err(with_context("setting the io scheduling class"))?;
+    }
+    if let Some(persona) = prepared.persona {
+        set_personality(persona).map_err(with_context("setting the personality"))?;
+    }
+    if let Some(umask) = prepared.umask {
+        nix::sys::stat::umask(umask);
+    }
+    Ok(())
+}
+
+/// chroot() into RootDirectory=. This happens after the namespaces are set up, so the uid map of PrivateUsers= is still
+/// written through the /proc of the host. The user namespace gives the process CAP_SYS_CHROOT until the privileges are dropped.
+pub fn change_root_directory(settings: &ProcessSettings) -> Result<(), String> {
+    if let Some(root) = &settings.root_directory {
+        change_root(&path_cstring(root)?).map_err(|e| {
+            format!(
+                "Error while changing the root directory to {:?}: {}",
+                root, e
+            )
+        })?;
+    }
+    Ok(())
+}
+
+/// This happens after the privileges were dropped, so the user of the service needs to be allowed to enter the directory
+pub fn change_working_directory(dir: &SandboxPath) -> Result<(), String> {
+    match nix::unistd::chdir(&dir.path) {
+        Ok(()) => Ok(()),
+        Err(_) if dir.ignore_missing => Ok(()),
+        Err(e) => Err(format!(
+            "Error while changing the working directory to {:?}: {}",
+            dir.path, e
+        )),
+    }
+}
@@ -0,0 +1,95 @@
+//! Apply the Limit*= settings with setrlimit(). This runs in the forked child (or before the exec of helper commands), so it must not use the logger.
+
+use crate::units::{LimitResource, ResourceLimit, ResourceLimits};
+
+#[cfg(target_os = "linux")]
+type ResourceType = libc::__rlimit_resource_t;
+#[cfg(not(target_os = "linux"))]
+type ResourceType = libc::c_int;
+
+fn resource_number(resource: LimitResource) -> Result<ResourceType, String> {
+    let number = match resource {
+        LimitResource::Cpu => libc::RLIMIT_CPU,
+        LimitResource::FileSize => libc::RLIMIT_FSIZE,
+        LimitResource::Data => libc::RLIMIT_DATA,
+        LimitResource::Stack => libc::RLIMIT_STACK,
+        LimitResource::Core => libc::RLIMIT_CORE,
+        LimitResource::Rss => libc::RLIMIT_RSS,
+        LimitResource::NoFile => libc::RLIMIT_NOFILE,
+        LimitResource::AddressSpace => libc::RLIMIT_AS,
+        LimitResource::NProc => libc::RLIMIT_NPROC,
+        LimitResource::MemLock => libc::RLIMIT_MEMLOCK,
+        #[cfg(target_os = "linux")]
+        LimitResource::Locks => libc::RLIMIT_LOCKS,
+        #[cfg(target_os = "linux")]
+        LimitResource::SigPending => libc::RLIMIT_SIGPENDING,
+        #[cfg(target_os = "linux")]
+        LimitResource::MsgQueue => libc::RLIMIT_MSGQUEUE,
+        #[cfg(target_os = "linux")]
+        LimitResource::Nice => libc::RLIMIT_NICE,
+        #[cfg(target_os = "linux")]
+        LimitResource::RtPrio => libc::RLIMIT_RTPRIO,
+        #[cfg(target_os = "linux")]
+        LimitResource::RtTime => libc::RLIMIT_RTTIME,
+        #[cfg(not(target_os = "linux"))]
+        other => {
+            return Err(format!(
+                "The resource limit {:?} is only supported on linux",
+                other
+            ))
+        }
+    };
+    Ok(number)
+}
+
+fn rlimit(limit: &ResourceLimit) -> libc::rlimit {
+    libc::rlimit {
+        rlim_cur: limit
+            .soft
+            .map(|val| val as libc::rlim_t)
+            .unwrap_or(libc::RLIM_INFINITY),
+        rlim_max: limit
+            .hard
+            .map(|val| val as libc::rlim_t)
+            .unwrap_or(libc::RLIM_INFINITY),
+    }
+}
+
+/// This needs to happen before the privileges are dropped, only root can raise the hard limits
+pub fn apply_resource_limits(limits: &ResourceLimits) -> Result<(), String> {
+    for (resource, limit) in &limits.limits {
+        let res = unsafe { libc::setrlimit(resource_number(*resource)?, &rlimit(limit)) };
+        if res < 0 {
+            return Err(format!(
+                "Error while setting the resource limit {:?}: {}",
+                resource,
+                std::io::Error::last_os_error()
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The limits converted to the arguments of setrlimit(), so they can be applied in the pre_exec hook of helper commands without allocating
+pub struct PreparedResourceLimits {
+    limits: Vec<(ResourceType, libc::rlimit)>,
+}
+
+impl PreparedResourceLimits {
+    pub fn new(limits: &ResourceLimits) -> Result<PreparedResourceLimits, String> {
+        let limits = limits
+            .limits
+            .iter()
+            .map(|(resource, limit)| Ok((resource_number(*resource)?, rlimit(limit))))
+            .collect::<Result<_, String>>()?;
+        Ok(PreparedResourceLimits { limits })
+    }
+
+    pub fn apply(&self) -> nix::Result<()> {
+        for (resource, rlim) in &self.limits {
+            let res = unsafe { libc::setrlimit(*resource, rlim) };
+            nix::errno::Errno::result(res)?;
+        }
+        Ok(())
+    }
+}
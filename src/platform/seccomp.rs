@@ -0,0 +1,1202 @@
+//! Seccomp based system call filtering for SystemCallFilter= and SystemCallArchitectures=. The filter is compiled into a
+//! classic BPF program in the forked child and installed right before the exec, so it does not affect rustysd itself.
+//!
+//! The program is simple:
+//! 1. Check the architecture. System calls of other architectures (e.g. 32 bit calls on a 64 bit system) get the deny action,
+//!    their numbers would not match the table used here
+//! 1. Compare the system call number with every filtered number and return the action for it
+//! 1. Return the default action (deny for allow lists, allow for deny lists)
+//!
+//! The protection settings like ProtectKernelModules= and RestrictNamespaces= get their own filter that is built the same way.
+
+use crate::units::{
+    parse_errno, FilesystemSandbox, NamespaceSandbox, SyscallFilter, ALL_NAMESPACE_FLAGS,
+};
+use std::collections::BTreeMap;
+
+/// Turns a list of libc::SYS_* constants into pairs of (name, number)
+macro_rules! syscall_table {
+    ($($name:ident),* $(,)?) => {
+        &[$((stringify!($name), libc::$name)),*]
+    };
+}
+
+#[cfg(target_arch = "x86_64")]
+const AUDIT_ARCH_NATIVE: u32 = 0xC000_003E;
+#[cfg(target_arch = "aarch64")]
+const AUDIT_ARCH_NATIVE: u32 = 0xC000_00B7;
+
+/// offsetof(struct seccomp_data, nr)
+const NR_OFFSET: u32 = 0;
+/// offsetof(struct seccomp_data, arch)
+const ARCH_OFFSET: u32 = 4;
+
+/// offsetof(struct seccomp_data, args[idx]). Only the lower 32 bits are loaded, which is enough for flag arguments
+fn arg_offset(idx: u32) -> u32 {
+    let offset = 16 + 8 * idx;
+    if cfg!(target_endian = "big") {
+        offset + 4
+    } else {
+        offset
+    }
+}
+
+/// System calls of the x32 ABI have the same architecture as x86_64 but this bit set in the number
+#[cfg(target_arch = "x86_64")]
+const X32_SYSCALL_BIT: u32 = 0x4000_0000;
+
+/// The groups like in systemd. Names that do not exist on this architecture are skipped, so the groups can contain them all.
+const SYSCALL_GROUPS: &[(&str, &[&str])] = &[
+    (
+        "@aio",
+        &[
+            "io_cancel",
+            "io_destroy",
+            "io_getevents",
+            "io_pgetevents",
+            "io_setup",
+            "io_submit",
+            "io_uring_enter",
+            "io_uring_register",
+            "io_uring_setup",
+        ],
+    ),
+    (
+        "@basic-io",
+        &[
+            "_llseek",
+            "close",
+            "close_range",
+            "dup",
+            "dup2",
+            "dup3",
+            "lseek",
+            "pread64",
+            "preadv",
+            "preadv2",
+            "pwrite64",
+            "pwritev",
+            "pwritev2",
+            "read",
+            "readv",
+            "write",
+            "writev",
+        ],
+    ),
+    (
+        "@chown",
+        &[
+            "chown", "chown32", "fchown", "fchown32", "fchownat", "lchown", "lchown32",
+        ],
+    ),
+    (
+        "@clock",
+        &[
+            "adjtimex",
+            "clock_adjtime",
+            "clock_settime",
+            "settimeofday",
+            "stime",
+        ],
+    ),
+    (
+        "@cpu-emulation",
+        &[
+            "modify_ldt",
+            "subpage_prot",
+            "switch_endian",
+            "vm86",
+            "vm86old",
+        ],
+    ),
+    (
+        "@debug",
+        &[
+            "lookup_dcookie",
+            "perf_event_open",
+            "pidfd_getfd",
+            "ptrace",
+            "rtas",
+            "s390_runtime_instr",
+            "sys_debug_setcontext",
+        ],
+    ),
+    (
+        "@default",
+        &[
+            "arch_prctl",
+            "brk",
+            "cacheflush",
+            "clock_getres",
+            "clock_gettime",
+            "clock_nanosleep",
+            "execve",
+            "exit",
+            "exit_group",
+            "futex",
+            "get_robust_list",
+            "get_thread_area",
+            "getegid",
+            "geteuid",
+            "getgid",
+            "getgroups",
+            "getpgid",
+            "getpgrp",
+            "getpid",
+            "getppid",
+            "getrandom",
+            "getresgid",
+            "getresuid",
+            "getrlimit",
+            "getsid",
+            "gettid",
+            "gettimeofday",
+            "getuid",
+            "membarrier",
+            "mmap",
+            "mprotect",
+            "munmap",
+            "nanosleep",
+            "pause",
+            "prlimit64",
+            "restart_syscall",
+            "rseq",
+            "rt_sigreturn",
+            "sched_getaffinity",
+            "sched_yield",
+            "set_robust_list",
+            "set_thread_area",
+            "set_tid_address",
+            "set_tls",
+            "sigreturn",
+            "time",
+            "ugetrlimit",
+        ],
+    ),
+    (
+        "@file-system",
+        &[
+            "access",
+            "chdir",
+            "chmod",
+            "close",
+            "creat",
+            "faccessat",
+            "faccessat2",
+            "fallocate",
+            "fchdir",
+            "fchmod",
+            "fchmodat",
+            "fcntl",
+            "fgetxattr",
+            "flistxattr",
+            "fremovexattr",
+            "fsetxattr",
+            "fstat",
+            "fstatfs",
+            "ftruncate",
+            "futimesat",
+            "getcwd",
+            "getdents",
+            "getdents64",
+            "getxattr",
+            "inotify_add_watch",
+            "inotify_init",
+            "inotify_init1",
+            "inotify_rm_watch",
+            "lgetxattr",
+            "link",
+            "linkat",
+            "listxattr",
+            "llistxattr",
+            "lremovexattr",
+            "lsetxattr",
+            "lstat",
+            "mkdir",
+            "mkdirat",
+            "mknod",
+            "mknodat",
+            "mmap",
+            "munmap",
+            "newfstatat",
+            "open",
+            "openat",
+            "openat2",
+            "readlink",
+            "readlinkat",
+            "removexattr",
+            "rename",
+            "renameat",
+            "renameat2",
+            "rmdir",
+            "setxattr",
+            "stat",
+            "statfs",
+            "statx",
+            "symlink",
+            "symlinkat",
+            "truncate",
+            "unlink",
+            "unlinkat",
+            "utime",
+            "utimensat",
+            "utimes",
+        ],
+    ),
+    (
+        "@io-event",
+        &[
+            "_newselect",
+            "epoll_create",
+            "epoll_create1",
+            "epoll_ctl",
+            "epoll_ctl_old",
+            "epoll_pwait",
+            "epoll_pwait2",
+            "epoll_wait",
+            "epoll_wait_old",
+            "eventfd",
+            "eventfd2",
+            "poll",
+            "ppoll",
+            "pselect6",
+            "select",
+        ],
+    ),
+    (
+        "@ipc",
+        &[
+            "ipc",
+            "memfd_create",
+            "mq_getsetattr",
+            "mq_notify",
+            "mq_open",
+            "mq_timedreceive",
+            "mq_timedsend",
+            "mq_unlink",
+            "msgctl",
+            "msgget",
+            "msgrcv",
+            "msgsnd",
+            "pipe",
+            "pipe2",
+            "process_madvise",
+            "process_vm_readv",
+            "process_vm_writev",
+            "semctl",
+            "semget",
+            "semop",
+            "semtimedop",
+            "shmat",
+            "shmctl",
+            "shmdt",
+            "shmget",
+        ],
+    ),
+    ("@keyring", &["add_key", "keyctl", "request_key"]),
+    (
+        "@memlock",
+        &["mlock", "mlock2", "mlockall", "munlock", "munlockall"],
+    ),
+    ("@module", &["delete_module", "finit_module", "init_module"]),
+    (
+        "@mount",
+        &[
+            "chroot",
+            "fsconfig",
+            "fsmount",
+            "fsopen",
+            "fspick",
+            "mount",
+            "mount_setattr",
+            "move_mount",
+            "open_tree",
+            "pivot_root",
+            "umount",
+            "umount2",
+        ],
+    ),
+    (
+        "@network-io",
+        &[
+            "accept",
+            "accept4",
+            "bind",
+            "connect",
+            "getpeername",
+            "getsockname",
+            "getsockopt",
+            "listen",
+            "recv",
+            "recvfrom",
+            "recvmmsg",
+            "recvmsg",
+            "send",
+            "sendmmsg",
+            "sendmsg",
+            "sendto",
+            "setsockopt",
+            "shutdown",
+            "socket",
+            "socketcall",
+            "socketpair",
+        ],
+    ),
+    (
+        "@obsolete",
+        &[
+            "_sysctl",
+            "afs_syscall",
+            "bdflush",
+            "break",
+            "create_module",
+            "ftime",
+            "get_kernel_syms",
+            "getpmsg",
+            "gtty",
+            "idle",
+            "lock",
+            "mpx",
+            "prof",
+            "profil",
+            "putpmsg",
+            "query_module",
+            "security",
+            "sgetmask",
+            "ssetmask",
+            "stty",
+            "sysfs",
+            "tuxcall",
+            "ulimit",
+            "uselib",
+            "ustat",
+            "vserver",
+        ],
+    ),
+    (
+        "@privileged",
+        &[
+            "@chown",
+            "@clock",
+            "@module",
+            "@raw-io",
+            "@reboot",
+            "@swap",
+            "_sysctl",
+            "acct",
+            "bpf",
+            "capset",
+            "chroot",
+            "fanotify_init",
+            "fanotify_mark",
+            "nfsservctl",
+            "open_by_handle_at",
+            "pivot_root",
+            "quotactl",
+            "setdomainname",
+            "setfsuid",
+            "setgroups",
+            "sethostname",
+            "setresuid",
+            "setreuid",
+            "setuid",
+            "vhangup",
+        ],
+    ),
+    (
+        "@process",
+        &[
+            "capget",
+            "clone",
+            "clone3",
+            "execveat",
+            "fork",
+            "getrusage",
+            "kill",
+            "pidfd_open",
+            "pidfd_send_signal",
+            "prctl",
+            "rt_sigqueueinfo",
+            "rt_tgsigqueueinfo",
+            "setns",
+            "swapcontext",
+            "tgkill",
+            "times",
+            "tkill",
+            "unshare",
+            "vfork",
+            "wait4",
+            "waitid",
+            "waitpid",
+        ],
+    ),
+    (
+        "@raw-io",
+        &[
+            "ioperm",
+            "iopl",
+            "pciconfig_iobase",
+            "pciconfig_read",
+            "pciconfig_write",
+            "s390_pci_mmio_read",
+            "s390_pci_mmio_write",
+        ],
+    ),
+    ("@reboot", &["kexec_file_load", "kexec_load", "reboot"]),
+    (
+        "@resources",
+        &[
+            "ioprio_set",
+            "mbind",
+            "migrate_pages",
+            "move_pages",
+            "nice",
+            "sched_setaffinity",
+            "sched_setattr",
+            "sched_setparam",
+            "sched_setscheduler",
+            "set_mempolicy",
+            "setpriority",
+            "setrlimit",
+        ],
+    ),
+    (
+        "@setuid",
+        &[
+            "setgid",
+            "setgroups",
+            "setregid",
+            "setresgid",
+            "setresuid",
+            "setreuid",
+            "setuid",
+        ],
+    ),
+    (
+        "@signal",
+        &[
+            "rt_sigaction",
+            "rt_sigpending",
+            "rt_sigprocmask",
+            "rt_sigsuspend",
+            "rt_sigtimedwait",
+            "sigaction",
+            "sigaltstack",
+            "signal",
+            "signalfd",
+            "signalfd4",
+            "sigpending",
+            "sigprocmask",
+            "sigsuspend",
+        ],
+    ),
+    ("@swap", &["swapoff", "swapon"]),
+    (
+        "@sync",
+        &[
+            "fdatasync",
+            "fsync",
+            "msync",
+            "sync",
+            "sync_file_range",
+            "sync_file_range2",
+            "syncfs",
+        ],
+    ),
+    (
+        "@system-service",
+        &[
+            "@aio",
+            "@basic-io",
+            "@chown",
+            "@default",
+            "@file-system",
+            "@io-event",
+            "@ipc",
+            "@keyring",
+            "@memlock",
+            "@network-io",
+            "@process",
+            "@resources",
+            "@setuid",
+            "@signal",
+            "@sync",
+            "@timer",
+            "capget",
+            "capset",
+            "copy_file_range",
+            "fadvise64",
+            "flock",
+            "get_mempolicy",
+            "getcpu",
+            "getpriority",
+            "ioctl",
+            "ioprio_get",
+            "kcmp",
+            "madvise",
+            "mremap",
+            "name_to_handle_at",
+            "personality",
+            "readahead",
+            "remap_file_pages",
+            "sched_get_priority_max",
+            "sched_get_priority_min",
+            "sched_getattr",
+            "sched_getparam",
+            "sched_getscheduler",
+            "sched_rr_get_interval",
+            "sendfile",
+            "setfsgid",
+            "setfsuid",
+            "setpgid",
+            "setsid",
+            "splice",
+            "sysinfo",
+            "tee",
+            "umask",
+            "uname",
+            "userfaultfd",
+            "vmsplice",
+        ],
+    ),
+    (
+        "@timer",
+        &[
+            "alarm",
+            "getitimer",
+            "setitimer",
+            "timer_create",
+            "timer_delete",
+            "timer_getoverrun",
+            "timer_gettime",
+            "timer_settime",
+            "timerfd_create",
+            "timerfd_gettime",
+            "timerfd_settime",
+            "times",
+        ],
+    ),
+];
+
+fn syscall_number(name: &str) -> Option<u32> {
+    COMMON_SYSCALLS
+        .iter()
+        .chain(ARCH_SYSCALLS.iter())
+        .find(|(sys_name, _)| sys_name.trim_start_matches("SYS_") == name)
+        .map(|(_, nr)| *nr as u32)
+}
+
+/// Add the numbers of a system call or group to the set. Unknown names are only an error if they were given directly in the unit,
+/// in groups they are just not available on this architecture.
+fn resolve_name(name: &str, in_group: bool, numbers: &mut Vec<u32>) -> Result<(), String> {
+    if name.starts_with('@') {
+        let group = SYSCALL_GROUPS
+            .iter()
+            .find(|(group_name, _)| *group_name == name)
+            .ok_or_else(|| format!("Unknown system call group: {}", name))?;
+        for member in group.1 {
+            resolve_name(member, true, numbers)?;
+        }
+    } else {
+        match syscall_number(name) {
+            Some(nr) => numbers.push(nr),
+            None if in_group => {}
+            None => return Err(format!("Unknown system call: {}", name)),
+        }
+    }
+    Ok(())
+}
+
+/// Evaluate the SystemCallFilter= assignments. The first one decides if this is an allow or a deny list, later assignments
+/// of the other kind remove entries again. Returns whether this is an allow list and the filtered numbers with an optional errno for each.
+fn resolve_rules(filter: &SyscallFilter) -> Result<(bool, BTreeMap<u32, Option<i32>>), String> {
+    let mut allow_list = None;
+    let mut entries = BTreeMap::new();
+    for (deny, names) in &filter.rules {
+        let is_allow_list = *allow_list.get_or_insert_with(|| {
+            if !*deny {
+                // like in systemd these are always allowed so the service can at least start and exit
+                let mut numbers = Vec::new();
+                let _ = resolve_name("@default", true, &mut numbers);
+                for nr in numbers {
+                    entries.insert(nr, None);
+                }
+            }
+            !*deny
+        });
+        for name in names {
+            let (name, errno) = match name.find(':') {
+                Some(pos) => {
+                    let errno = parse_errno(&name[pos + 1..])
+                        .ok_or_else(|| format!("Unknown error number: {}", &name[pos + 1..]))?;
+                    (&name[..pos], Some(errno))
+                }
+                None => (name.as_str(), None),
+            };
+            let mut numbers = Vec::new();
+            resolve_name(name, false, &mut numbers)?;
+            for nr in numbers {
+                if is_allow_list != *deny {
+                    entries.insert(nr, errno);
+                } else {
+                    entries.remove(&nr);
+                }
+            }
+        }
+    }
+    Ok((allow_list.unwrap_or(false), entries))
+}
+
+fn bpf_stmt(code: u32, k: u32) -> libc::sock_filter {
+    libc::sock_filter {
+        code: code as u16,
+        jt: 0,
+        jf: 0,
+        k,
+    }
+}
+
+fn bpf_jump(code: u32, k: u32, jt: u8, jf: u8) -> libc::sock_filter {
+    libc::sock_filter {
+        code: (libc::BPF_JMP | code | libc::BPF_K) as u16,
+        jt,
+        jf,
+        k,
+    }
+}
+
+fn bpf_ret(action: u32) -> libc::sock_filter {
+    bpf_stmt(libc::BPF_RET | libc::BPF_K, action)
+}
+
+fn errno_action(errno: i32) -> u32 {
+    libc::SECCOMP_RET_ERRNO | (errno as u32 & libc::SECCOMP_RET_DATA)
+}
+
+/// Check the architecture and load the system call number. Calls of other architectures get the arch_action.
+fn program_start(arch_action: u32) -> Vec<libc::sock_filter> {
+    let mut program = vec![
+        bpf_stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, ARCH_OFFSET),
+        bpf_jump(libc::BPF_JEQ, AUDIT_ARCH_NATIVE, 1, 0),
+        bpf_ret(arch_action),
+        bpf_stmt(libc::BPF_LD | libc::BPF_W | libc::BPF_ABS, NR_OFFSET),
+    ];
+    #[cfg(target_arch = "x86_64")]
+    {
+        program.push(bpf_jump(libc::BPF_JGE, X32_SYSCALL_BIT, 0, 1));
+        program.push(bpf_ret(arch_action));
+    }
+    program
+}
+
+fn build_program(filter: &SyscallFilter) -> Result<Vec<libc::sock_filter>, String> {
+    let (allow_list, entries) = resolve_rules(filter)?;
+    let deny_action = match filter.error_number {
+        Some(errno) => errno_action(errno),
+        None => libc::SECCOMP_RET_KILL_PROCESS,
+    };
+    let default_action = if allow_list {
+        deny_action
+    } else {
+        libc::SECCOMP_RET_ALLOW
+    };
+    // with only SystemCallArchitectures=native set there is nothing to deny, so other architectures are killed
+    let arch_action = if filter.rules.is_empty() {
+        libc::SECCOMP_RET_KILL_PROCESS
+    } else {
+        deny_action
+    };
+
+    let mut program = program_start(arch_action);
+    for (nr, errno) in entries {
+        let action = if allow_list {
+            libc::SECCOMP_RET_ALLOW
+        } else {
+            errno.map(errno_action).unwrap_or(deny_action)
+        };
+        program.push(bpf_jump(libc::BPF_JEQ, nr, 0, 1));
+        program.push(bpf_ret(action));
+    }
+    program.push(bpf_ret(default_action));
+    Ok(program)
+}
+
+/// Without CAP_SYS_ADMIN installing a filter needs PR_SET_NO_NEW_PRIVS so it is set for services that do not run as root, like systemd does.
+fn install_program(mut program: Vec<libc::sock_filter>) -> Result<(), String> {
+    if program.len() > u16::MAX as usize {
+        return Err("The system call filter is too long".into());
+    }
+    if !nix::unistd::getuid().is_root() {
+        super::set_no_new_privileges()?;
+    }
+    let prog = libc::sock_fprog {
+        len: program.len() as u16,
+        filter: program.as_mut_ptr(),
+    };
+    let res = unsafe {
+        libc::prctl(
+            libc::PR_SET_SECCOMP,
+            libc::SECCOMP_MODE_FILTER,
+            &prog as *const libc::sock_fprog,
+        )
+    };
+    if res < 0 {
+        Err(format!(
+            "Error while installing the seccomp filter: {}",
+            std::io::Error::last_os_error()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// Build the filter from SystemCallFilter= and install it for this process
+pub fn setup_syscall_filter(filter: &SyscallFilter) -> Result<(), String> {
+    if !filter.is_enabled() {
+        return Ok(());
+    }
+    install_program(build_program(filter)?)
+}
+
+/// Deny creating namespaces with the flags in `forbidden`. The flags are the first argument of unshare and clone and the
+/// second argument of setns, where 0 means any namespace. clone3 passes the flags in a struct that a seccomp filter can not
+/// look into, so it fails with ENOSYS which makes the libc fall back to clone.
+fn restrict_namespaces(program: &mut Vec<libc::sock_filter>, forbidden: u32) {
+    let deny = errno_action(libc::EPERM);
+    for (name, arg) in &[("unshare", 0), ("clone", 0)] {
+        if let Some(nr) = syscall_number(name) {
+            program.push(bpf_jump(libc::BPF_JEQ, nr, 0, 4));
+            program.push(bpf_stmt(
+                libc::BPF_LD | libc::BPF_W | libc::BPF_ABS,
+                arg_offset(*arg),
+            ));
+            program.push(bpf_jump(libc::BPF_JSET, forbidden, 0, 1));
+            program.push(bpf_ret(deny));
+            program.push(bpf_ret(libc::SECCOMP_RET_ALLOW));
+        }
+    }
+    if let Some(nr) = syscall_number("setns") {
+        program.push(bpf_jump(libc::BPF_JEQ, nr, 0, 5));
+        program.push(bpf_stmt(
+            libc::BPF_LD | libc::BPF_W | libc::BPF_ABS,
+            arg_offset(1),
+        ));
+        program.push(bpf_jump(libc::BPF_JEQ, 0, 1, 0));
+        program.push(bpf_jump(libc::BPF_JSET, forbidden, 0, 1));
+        program.push(bpf_ret(deny));
+        program.push(bpf_ret(libc::SECCOMP_RET_ALLOW));
+    }
+    if let Some(nr) = syscall_number("clone3") {
+        program.push(bpf_jump(libc::BPF_JEQ, nr, 0, 1));
+        program.push(bpf_ret(errno_action(libc::ENOSYS)));
+    }
+}
+
+/// The system call part of the protection settings: ProtectKernelModules=, ProtectKernelLogs=, PrivateDevices=, ProtectHostname= and
+/// RestrictNamespaces=. This is a separate filter that is installed in addition to the one from SystemCallFilter=.
+pub fn setup_protection_filter(
+    sandbox: &FilesystemSandbox,
+    namespaces: &NamespaceSandbox,
+) -> Result<(), String> {
+    let mut denied = Vec::new();
+    if sandbox.protect_kernel_modules {
+        denied.push("@module");
+    }
+    if sandbox.protect_kernel_logs {
+        denied.push("syslog");
+    }
+    if sandbox.private_devices {
+        denied.push("@raw-io");
+    }
+    if namespaces.protect_hostname {
+        denied.push("sethostname");
+        denied.push("setdomainname");
+    }
+    let forbidden_namespaces = namespaces
+        .restrict_namespaces
+        .map(|allowed| (ALL_NAMESPACE_FLAGS & !allowed) as u32)
+        .unwrap_or(0);
+    if denied.is_empty() && forbidden_namespaces == 0 {
+        return Ok(());
+    }
+
+    let deny = errno_action(libc::EPERM);
+    let mut program = program_start(deny);
+    let mut numbers = Vec::new();
+    for name in denied {
+        resolve_name(name, true, &mut numbers)?;
+    }
+    for nr in numbers {
+        program.push(bpf_jump(libc::BPF_JEQ, nr, 0, 1));
+        program.push(bpf_ret(deny));
+    }
+    if forbidden_namespaces != 0 {
+        restrict_namespaces(&mut program, forbidden_namespaces);
+    }
+    program.push(bpf_ret(libc::SECCOMP_RET_ALLOW));
+    install_program(program)
+}
+
+/// System calls that exist on all supported architectures
+const COMMON_SYSCALLS: &[(&str, libc::c_long)] = syscall_table![
+    SYS_accept,
+    SYS_accept4,
+    SYS_acct,
+    SYS_add_key,
+    SYS_adjtimex,
+    SYS_bind,
+    SYS_bpf,
+    SYS_brk,
+    SYS_capget,
+    SYS_capset,
+    SYS_chdir,
+    SYS_chroot,
+    SYS_clock_adjtime,
+    SYS_clock_getres,
+    SYS_clock_gettime,
+    SYS_clock_nanosleep,
+    SYS_clock_settime,
+    SYS_clone,
+    SYS_clone3,
+    SYS_close,
+    SYS_close_range,
+    SYS_connect,
+    SYS_copy_file_range,
+    SYS_delete_module,
+    SYS_dup,
+    SYS_dup3,
+    SYS_epoll_create1,
+    SYS_epoll_ctl,
+    SYS_epoll_pwait,
+    SYS_epoll_pwait2,
+    SYS_eventfd2,
+    SYS_execve,
+    SYS_execveat,
+    SYS_exit,
+    SYS_exit_group,
+    SYS_faccessat,
+    SYS_faccessat2,
+    SYS_fallocate,
+    SYS_fanotify_init,
+    SYS_fanotify_mark,
+    SYS_fchdir,
+    SYS_fchmod,
+    SYS_fchmodat,
+    SYS_fchown,
+    SYS_fchownat,
+    SYS_fcntl,
+    SYS_fdatasync,
+    SYS_fgetxattr,
+    SYS_finit_module,
+    SYS_flistxattr,
+    SYS_flock,
+    SYS_fremovexattr,
+    SYS_fsconfig,
+    SYS_fsetxattr,
+    SYS_fsmount,
+    SYS_fsopen,
+    SYS_fspick,
+    SYS_fstat,
+    SYS_fstatfs,
+    SYS_fsync,
+    SYS_ftruncate,
+    SYS_futex,
+    SYS_get_mempolicy,
+    SYS_get_robust_list,
+    SYS_getcpu,
+    SYS_getcwd,
+    SYS_getdents64,
+    SYS_getegid,
+    SYS_geteuid,
+    SYS_getgid,
+    SYS_getgroups,
+    SYS_getitimer,
+    SYS_getpeername,
+    SYS_getpgid,
+    SYS_getpid,
+    SYS_getppid,
+    SYS_getpriority,
+    SYS_getrandom,
+    SYS_getresgid,
+    SYS_getresuid,
+    SYS_getrusage,
+    SYS_getsid,
+    SYS_getsockname,
+    SYS_getsockopt,
+    SYS_gettid,
+    SYS_gettimeofday,
+    SYS_getuid,
+    SYS_getxattr,
+    SYS_init_module,
+    SYS_inotify_add_watch,
+    SYS_inotify_init1,
+    SYS_inotify_rm_watch,
+    SYS_io_cancel,
+    SYS_io_destroy,
+    SYS_io_getevents,
+    SYS_io_setup,
+    SYS_io_submit,
+    SYS_io_uring_enter,
+    SYS_io_uring_register,
+    SYS_io_uring_setup,
+    SYS_ioctl,
+    SYS_ioprio_get,
+    SYS_ioprio_set,
+    SYS_kcmp,
+    SYS_kexec_load,
+    SYS_keyctl,
+    SYS_kill,
+    SYS_lgetxattr,
+    SYS_linkat,
+    SYS_listen,
+    SYS_listxattr,
+    SYS_llistxattr,
+    SYS_lookup_dcookie,
+    SYS_lremovexattr,
+    SYS_lseek,
+    SYS_lsetxattr,
+    SYS_madvise,
+    SYS_mbind,
+    SYS_membarrier,
+    SYS_memfd_create,
+    SYS_migrate_pages,
+    SYS_mincore,
+    SYS_mkdirat,
+    SYS_mknodat,
+    SYS_mlock,
+    SYS_mlock2,
+    SYS_mlockall,
+    SYS_mmap,
+    SYS_mount,
+    SYS_mount_setattr,
+    SYS_move_mount,
+    SYS_move_pages,
+    SYS_mprotect,
+    SYS_mq_getsetattr,
+    SYS_mq_notify,
+    SYS_mq_open,
+    SYS_mq_timedreceive,
+    SYS_mq_timedsend,
+    SYS_mq_unlink,
+    SYS_mremap,
+    SYS_msgctl,
+    SYS_msgget,
+    SYS_msgrcv,
+    SYS_msgsnd,
+    SYS_msync,
+    SYS_munlock,
+    SYS_munlockall,
+    SYS_munmap,
+    SYS_name_to_handle_at,
+    SYS_nanosleep,
+    SYS_newfstatat,
+    SYS_nfsservctl,
+    SYS_open_by_handle_at,
+    SYS_open_tree,
+    SYS_openat,
+    SYS_openat2,
+    SYS_perf_event_open,
+    SYS_personality,
+    SYS_pidfd_getfd,
+    SYS_pidfd_open,
+    SYS_pidfd_send_signal,
+    SYS_pipe2,
+    SYS_pivot_root,
+    SYS_pkey_alloc,
+    SYS_pkey_free,
+    SYS_pkey_mprotect,
+    SYS_ppoll,
+    SYS_prctl,
+    SYS_pread64,
+    SYS_preadv,
+    SYS_preadv2,
+    SYS_prlimit64,
+    SYS_process_madvise,
+    SYS_process_vm_readv,
+    SYS_process_vm_writev,
+    SYS_pselect6,
+    SYS_ptrace,
+    SYS_pwrite64,
+    SYS_pwritev,
+    SYS_pwritev2,
+    SYS_quotactl,
+    SYS_read,
+    SYS_readahead,
+    SYS_readlinkat,
+    SYS_readv,
+    SYS_reboot,
+    SYS_recvfrom,
+    SYS_recvmmsg,
+    SYS_recvmsg,
+    SYS_remap_file_pages,
+    SYS_removexattr,
+    SYS_renameat2,
+    SYS_request_key,
+    SYS_restart_syscall,
+    SYS_rt_sigaction,
+    SYS_rt_sigpending,
+    SYS_rt_sigprocmask,
+    SYS_rt_sigqueueinfo,
+    SYS_rt_sigreturn,
+    SYS_rt_sigsuspend,
+    SYS_rt_sigtimedwait,
+    SYS_rt_tgsigqueueinfo,
+    SYS_sched_get_priority_max,
+    SYS_sched_get_priority_min,
+    SYS_sched_getaffinity,
+    SYS_sched_getattr,
+    SYS_sched_getparam,
+    SYS_sched_getscheduler,
+    SYS_sched_rr_get_interval,
+    SYS_sched_setaffinity,
+    SYS_sched_setattr,
+    SYS_sched_setparam,
+    SYS_sched_setscheduler,
+    SYS_sched_yield,
+    SYS_seccomp,
+    SYS_semctl,
+    SYS_semget,
+    SYS_semop,
+    SYS_semtimedop,
+    SYS_sendmmsg,
+    SYS_sendmsg,
+    SYS_sendto,
+    SYS_set_mempolicy,
+    SYS_set_robust_list,
+    SYS_set_tid_address,
+    SYS_setdomainname,
+    SYS_setfsgid,
+    SYS_setfsuid,
+    SYS_setgid,
+    SYS_setgroups,
+    SYS_sethostname,
+    SYS_setitimer,
+    SYS_setns,
+    SYS_setpgid,
+    SYS_setpriority,
+    SYS_setregid,
+    SYS_setresgid,
+    SYS_setresuid,
+    SYS_setreuid,
+    SYS_setsid,
+    SYS_setsockopt,
+    SYS_settimeofday,
+    SYS_setuid,
+    SYS_setxattr,
+    SYS_shmat,
+    SYS_shmctl,
+    SYS_shmdt,
+    SYS_shmget,
+    SYS_shutdown,
+    SYS_sigaltstack,
+    SYS_signalfd4,
+    SYS_socket,
+    SYS_socketpair,
+    SYS_splice,
+    SYS_statfs,
+    SYS_statx,
+    SYS_swapoff,
+    SYS_swapon,
+    SYS_symlinkat,
+    SYS_sync,
+    SYS_syncfs,
+    SYS_sysinfo,
+    SYS_syslog,
+    SYS_tee,
+    SYS_tgkill,
+    SYS_timer_create,
+    SYS_timer_delete,
+    SYS_timer_getoverrun,
+    SYS_timer_gettime,
+    SYS_timer_settime,
+    SYS_timerfd_create,
+    SYS_timerfd_gettime,
+    SYS_timerfd_settime,
+    SYS_times,
+    SYS_tkill,
+    SYS_truncate,
+    SYS_umask,
+    SYS_umount2,
+    SYS_uname,
+    SYS_unlinkat,
+    SYS_unshare,
+    SYS_userfaultfd,
+    SYS_utimensat,
+    SYS_vhangup,
+    SYS_vmsplice,
+    SYS_wait4,
+    SYS_waitid,
+    SYS_write,
+    SYS_writev,
+];
+
+/// The older system calls that only exist on x86_64
+#[cfg(target_arch = "x86_64")]
+const ARCH_SYSCALLS: &[(&str, libc::c_long)] = syscall_table![
+    SYS__sysctl,
+    SYS_access,
+    SYS_afs_syscall,
+    SYS_alarm,
+    SYS_arch_prctl,
+    SYS_chmod,
+    SYS_chown,
+    SYS_creat,
+    SYS_create_module,
+    SYS_dup2,
+    SYS_epoll_create,
+    SYS_epoll_ctl_old,
+    SYS_epoll_wait,
+    SYS_epoll_wait_old,
+    SYS_eventfd,
+    SYS_fadvise64,
+    SYS_fork,
+    SYS_futimesat,
+    SYS_get_kernel_syms,
+    SYS_get_thread_area,
+    SYS_getdents,
+    SYS_getpgrp,
+    SYS_getpmsg,
+    SYS_getrlimit,
+    SYS_inotify_init,
+    SYS_ioperm,
+    SYS_iopl,
+    SYS_kexec_file_load,
+    SYS_lchown,
+    SYS_link,
+    SYS_lstat,
+    SYS_mkdir,
+    SYS_mknod,
+    SYS_modify_ldt,
+    SYS_open,
+    SYS_pause,
+    SYS_pipe,
+    SYS_poll,
+    SYS_putpmsg,
+    SYS_query_module,
+    SYS_readlink,
+    SYS_rename,
+    SYS_renameat,
+    SYS_rmdir,
+    SYS_security,
+    SYS_select,
+    SYS_sendfile,
+    SYS_set_thread_area,
+    SYS_setrlimit,
+    SYS_signalfd,
+    SYS_stat,
+    SYS_symlink,
+    SYS_sync_file_range,
+    SYS_sysfs,
+    SYS_time,
+    SYS_tuxcall,
+    SYS_unlink,
+    SYS_uselib,
+    SYS_ustat,
+    SYS_utime,
+    SYS_utimes,
+    SYS_vfork,
+    SYS_vserver,
+];
+
+#[cfg(target_arch = "aarch64")]
+const ARCH_SYSCALLS: &[(&str, libc::c_long)] = &[];
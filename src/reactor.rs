@@ -0,0 +1,198 @@
+//! The reactor waits for events on all filedescriptors rustysd listens on and dispatches them to the respective handlers.
+//! These are the notification sockets and stdout/stderr pipes of services and the sockets of socket units.
+//!
+//! Filedescriptors are registered when they are opened (or when a socket should be listened on again) and deregistered
+//! when they are closed or passed to a service. So there is no need to collect them from the unit table on every wakeup.
+
+use log::error;
+use log::trace;
+
+use crate::fd_store::FDStore;
+use crate::platform::Epoll;
+use crate::runtime_info::*;
+use crate::services::Service;
+use crate::services::StdIo;
+use crate::units::*;
+
+use std::collections::HashMap;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// What a registered filedescriptor belongs to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EventSource {
+    Notifications(UnitId),
+    Stdout(UnitId),
+    Stderr(UnitId),
+    Socket(UnitId),
+}
+
+impl EventSource {
+    pub fn id(&self) -> &UnitId {
+        match self {
+            EventSource::Notifications(id) => id,
+            EventSource::Stdout(id) => id,
+            EventSource::Stderr(id) => id,
+            EventSource::Socket(id) => id,
+        }
+    }
+}
+
+pub struct Reactor {
+    epoll: Epoll,
+    sources: Mutex<HashMap<RawFd, EventSource>>,
+    /// Sockets that hit their PollLimit and should be listened on again after the instant has passed
+    paused_sockets: Mutex<Vec<(Instant, UnitId)>>,
+}
+
+impl Reactor {
+    pub fn new() -> Result<Reactor, String> {
+        Ok(Reactor {
+            epoll: Epoll::new()?,
+            sources: Mutex::new(HashMap::new()),
+            paused_sockets: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn register(&self, fd: RawFd, source: EventSource) -> Result<(), String> {
+        let mut sources = self.sources.lock().unwrap();
+        self.epoll.add(fd)?;
+        trace!("Register fd {} for {:?}", fd, source);
+        sources.insert(fd, source);
+        Ok(())
+    }
+
+    /// Remove all filedescriptors that belong to this unit. This needs to happen before they are closed.
+    pub fn deregister_unit(&self, id: &UnitId) {
+        let mut sources = self.sources.lock().unwrap();
+        let epoll = &self.epoll;
+        sources.retain(|fd, source| {
+            if source.id() == id {
+                epoll.remove(*fd);
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    /// Register the notification socket and the stdout/stderr pipes of this service
+    pub fn register_service(&self, id: &UnitId, srvc: &Service) -> Result<(), String> {
+        if let Some(socket) = &srvc.notifications {
+            self.register(socket.as_raw_fd(), EventSource::Notifications(id.clone()))?;
+        }
+        if let Some(StdIo::Piped(r, _w)) = &srvc.stdout {
+            self.register(*r, EventSource::Stdout(id.clone()))?;
+        }
+        if let Some(StdIo::Piped(r, _w)) = &srvc.stderr {
+            self.register(*r, EventSource::Stderr(id.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// Register all filedescriptors this socket unit currently has in the fd_store
+    pub fn register_socket(&self, id: &UnitId, fd_store: &FDStore) -> Result<(), String> {
+        if let Some(fds) = fd_store.get_global(&id.name) {
+            for (_, _, fd) in fds {
+                self.register(fd.as_raw_fd(), EventSource::Socket(id.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stop listening on this socket until the instant has passed
+    pub fn pause_socket(&self, id: &UnitId, until: Instant) {
+        self.deregister_unit(id);
+        self.paused_sockets
+            .lock()
+            .unwrap()
+            .push((until, id.clone()));
+    }
+
+    /// Returns the sockets whose pause has passed
+    fn take_resumable_sockets(&self) -> Vec<UnitId> {
+        let now = Instant::now();
+        let mut paused = self.paused_sockets.lock().unwrap();
+        let mut resumable = Vec::new();
+        paused.retain(|(until, id)| {
+            if *until <= now {
+                resumable.push(id.clone());
+                false
+            } else {
+                true
+            }
+        });
+        resumable
+    }
+
+    /// Wait for the next events. Returns early if a paused socket should be resumed.
+    fn wait(&self) -> Result<Vec<EventSource>, String> {
+        let timeout = self
+            .paused_sockets
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(until, _)| *until)
+            .min()
+            .map(|until| until.saturating_duration_since(Instant::now()));
+
+        let fds = self.epoll.wait(timeout)?;
+
+        // fds that were deregistered in the meantime are ignored
+        let sources = self.sources.lock().unwrap();
+        let mut events = Vec::new();
+        for fd in fds {
+            if let Some(source) = sources.get(&fd) {
+                if !events.contains(source) {
+                    events.push(source.clone());
+                }
+            }
+        }
+        Ok(events)
+    }
+}
+
+pub fn run_reactor(run_info: ArcMutRuntimeInfo) {
+    let reactor = run_info.read().unwrap().reactor.clone();
+    loop {
+        let events = match reactor.wait() {
+            Ok(events) => events,
+            Err(e) => {
+                error!("{}", e);
+                continue;
+            }
+        };
+
+        let run_info_locked = &*run_info.read().unwrap();
+        for id in reactor.take_resumable_sockets() {
+            crate::socket_activation::resume_socket(&id, run_info_locked);
+        }
+
+        let mut readable_sockets = Vec::new();
+        for event in events {
+            match event {
+                EventSource::Notifications(id) => {
+                    crate::notification_handler::handle_notifications(&id, run_info_locked)
+                }
+                EventSource::Stdout(id) => {
+                    crate::notification_handler::handle_std_out(&id, run_info_locked)
+                }
+                EventSource::Stderr(id) => {
+                    crate::notification_handler::handle_std_err(&id, run_info_locked)
+                }
+                EventSource::Socket(id) => readable_sockets.push(id),
+            }
+        }
+
+        let activated_sockets =
+            crate::socket_activation::take_readable_sockets(readable_sockets, run_info_locked);
+        if !activated_sockets.is_empty() {
+            // starting the services might take a while and needs the reactor to keep handling their output
+            let run_info = run_info.clone();
+            std::thread::spawn(move || {
+                crate::socket_activation::activate_services(activated_sockets, run_info);
+            });
+        }
+    }
+}
@@ -0,0 +1,622 @@
+//! daemon-reexec: save the state rustysd needs to keep the running services, exec the (possibly updated) rustysd binary and restore the
+//! state in the new process.
+//!
+//! The state is written as json into a file in the notifications dir and the path is passed to the new process with --deserialize.
+//! The filedescriptors (sockets in the fd store, notification sockets and stdout/stderr pipes of services) stay open over the exec, only
+//! their numbers are saved. The units themselves are loaded from the unit files again, so a changed unit file is used from then on.
+//!
+//! Not kept are the errors of stopped units, the rate limit counters of sockets and the changes pending from a daemon-reload.
+//! Units in the middle of starting or stopping are settled into the status they actually have and running helper processes are
+//! killed, because the threads waiting for them are gone. If the state can not be restored all leftover processes are killed and
+//! the kept filedescriptors closed, so the units start from scratch.
+
+use log::{error, info, trace, warn};
+use serde_json::Value;
+
+use crate::fd_store::FDStore;
+use crate::runtime_info::*;
+use crate::services::{Service, StdIo};
+use crate::signal_handler::ChildTermination;
+use crate::units::*;
+
+use std::convert::TryFrom;
+use std::convert::TryInto;
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
+
+fn status_to_json(status: &UnitStatus) -> Value {
+    let name = match status {
+        UnitStatus::NeverStarted => "NeverStarted",
+        UnitStatus::Starting => "Starting",
+        UnitStatus::Stopping => "Stopping",
+        UnitStatus::Restarting => "Restarting",
+        UnitStatus::Started(StatusStarted::Running) => "Running",
+        UnitStatus::Started(StatusStarted::WaitingForSocket) => "WaitingForSocket",
+        UnitStatus::Started(StatusStarted::Exited) => "Exited",
+        UnitStatus::Stopped(StatusStopped::StoppedFinal, _) => "StoppedFinal",
+        UnitStatus::Stopped(StatusStopped::StoppedUnexpected, _) => "StoppedUnexpected",
+        UnitStatus::Stopped(StatusStopped::Finished, _) => "Finished",
+    };
+    Value::String(name.to_owned())
+}
+
+fn status_from_json(value: &Value) -> Result<UnitStatus, String> {
+    let status = match value.as_str().unwrap_or_default() {
+        "NeverStarted" => UnitStatus::NeverStarted,
+        "Starting" => UnitStatus::Starting,
+        "Stopping" => UnitStatus::Stopping,
+        "Restarting" => UnitStatus::Restarting,
+        "Running" => UnitStatus::Started(StatusStarted::Running),
+        "WaitingForSocket" => UnitStatus::Started(StatusStarted::WaitingForSocket),
+        "Exited" => UnitStatus::Started(StatusStarted::Exited),
+        "StoppedFinal" => UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![]),
+        "StoppedUnexpected" => UnitStatus::Stopped(StatusStopped::StoppedUnexpected, vec![]),
+        "Finished" => UnitStatus::Stopped(StatusStopped::Finished, vec![]),
+        _ => return Err(format!("Unknown unit status: {}", value)),
+    };
+    Ok(status)
+}
+
+fn termination_to_json(termination: &ChildTermination) -> Value {
+    let mut map = serde_json::Map::new();
+    match termination {
+        ChildTermination::Exit(code) => map.insert("exit".into(), Value::from(*code)),
+        ChildTermination::Signal(signal) => {
+            map.insert("signal".into(), Value::from(*signal as i32))
+        }
+    };
+    Value::Object(map)
+}
+
+fn termination_from_json(value: &Value) -> Result<ChildTermination, String> {
+    if let Some(code) = value.get("exit").and_then(Value::as_i64) {
+        Ok(ChildTermination::Exit(code as i32))
+    } else if let Some(signal) = value.get("signal").and_then(Value::as_i64) {
+        nix::sys::signal::Signal::try_from(signal as i32)
+            .map(ChildTermination::Signal)
+            .map_err(|e| format!("Invalid signal {}: {}", signal, e))
+    } else {
+        Err(format!("Invalid child termination: {}", value))
+    }
+}
+
+fn pid_entry_to_json(pid: nix::unistd::Pid, entry: &PidEntry) -> Value {
+    let mut map = serde_json::Map::new();
+    map.insert("pid".into(), Value::from(pid.as_raw()));
+    match entry {
+        // the service type is taken from the unit again when restoring
+        PidEntry::Service(id, _) => {
+            map.insert("service".into(), Value::String(id.name.clone()));
+        }
+        PidEntry::ServiceExited(termination) => {
+            map.insert("service_exited".into(), termination_to_json(termination));
+        }
+        PidEntry::Helper(id, name) => {
+            map.insert("helper".into(), Value::String(id.name.clone()));
+            map.insert("name".into(), Value::String(name.clone()));
+        }
+        PidEntry::HelperExited(termination) => {
+            map.insert("helper_exited".into(), termination_to_json(termination));
+        }
+    }
+    Value::Object(map)
+}
+
+fn pid_entry_from_json(
+    value: &Value,
+    unit_table: &UnitTable,
+) -> Result<Option<(nix::unistd::Pid, PidEntry)>, String> {
+    let pid = value
+        .get("pid")
+        .and_then(Value::as_i64)
+        .ok_or_else(|| format!("Missing pid in: {}", value))?;
+    let pid = nix::unistd::Pid::from_raw(pid as i32);
+    let str_field = |name: &str| value.get(name).and_then(Value::as_str);
+
+    let entry = if let Some(name) = str_field("service") {
+        let id: UnitId = name.try_into()?;
+        match unit_table.get(&id).map(|unit| &unit.specific) {
+            Some(Specific::Service(srvc)) => PidEntry::Service(id, srvc.conf.srcv_type),
+            _ => {
+                warn!("Unit {} of process {} is not loaded anymore", name, pid);
+                return Ok(None);
+            }
+        }
+    } else if let Some(name) = str_field("helper") {
+        PidEntry::Helper(
+            name.try_into()?,
+            str_field("name").unwrap_or_default().to_owned(),
+        )
+    } else if let Some(termination) = value.get("service_exited") {
+        PidEntry::ServiceExited(termination_from_json(termination)?)
+    } else if let Some(termination) = value.get("helper_exited") {
+        PidEntry::HelperExited(termination_from_json(termination)?)
+    } else {
+        return Err(format!("Invalid pid entry: {}", value));
+    };
+    Ok(Some((pid, entry)))
+}
+
+fn stdio_to_json(stdio: &Option<StdIo>) -> Value {
+    match stdio {
+        Some(StdIo::Piped(r, w)) => Value::Array(vec![Value::from(*r), Value::from(*w)]),
+        // files were only needed to start the service
+        _ => Value::Null,
+    }
+}
+
+fn stdio_from_json(value: &Value) -> Option<StdIo> {
+    let fds = value.as_array()?;
+    let r = fds.first()?.as_i64()? as RawFd;
+    let w = fds.get(1)?.as_i64()? as RawFd;
+    Some(StdIo::Piped(r, w))
+}
+
+/// The output of services does not need to be valid utf8, so it is saved as an array of bytes
+fn bytes_to_json(bytes: &[u8]) -> Value {
+    Value::Array(bytes.iter().map(|byte| Value::from(*byte)).collect())
+}
+
+/// Older versions saved the bytes as a string, these are still accepted
+fn bytes_from_json(value: &Value) -> Vec<u8> {
+    match value {
+        Value::String(s) => s.as_bytes().to_vec(),
+        _ => value
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|byte| byte.as_u64().map(|byte| byte as u8))
+            .collect(),
+    }
+}
+
+fn service_to_json(srvc: &Service) -> Value {
+    let mut map = serde_json::Map::new();
+    let pid_to_json = |pid: Option<nix::unistd::Pid>| match pid {
+        Some(pid) => Value::from(pid.as_raw()),
+        None => Value::Null,
+    };
+    map.insert("pid".into(), pid_to_json(srvc.pid));
+    map.insert("process_group".into(), pid_to_json(srvc.process_group));
+    map.insert("signaled_ready".into(), Value::Bool(srvc.signaled_ready));
+    map.insert(
+        "status_msgs".into(),
+        Value::Array(
+            srvc.status_msgs
+                .iter()
+                .map(|msg| Value::String(msg.clone()))
+                .collect(),
+        ),
+    );
+    map.insert(
+        "notifications".into(),
+        match &srvc.notifications {
+            Some(socket) => Value::from(socket.as_raw_fd()),
+            None => Value::Null,
+        },
+    );
+    map.insert(
+        "notifications_path".into(),
+        match &srvc.notifications_path {
+            Some(path) => Value::String(path.to_string_lossy().into_owned()),
+            None => Value::Null,
+        },
+    );
+    map.insert("stdout".into(), stdio_to_json(&srvc.stdout));
+    map.insert("stderr".into(), stdio_to_json(&srvc.stderr));
+    map.insert(
+        "notifications_buffer".into(),
+        Value::String(srvc.notifications_buffer.clone()),
+    );
+    map.insert("stdout_buffer".into(), bytes_to_json(&srvc.stdout_buffer));
+    map.insert("stderr_buffer".into(), bytes_to_json(&srvc.stderr_buffer));
+    Value::Object(map)
+}
+
+fn restore_service(srvc: &mut Service, value: &Value) {
+    let pid_from_json = |name: &str| {
+        value
+            .get(name)
+            .and_then(Value::as_i64)
+            .map(|pid| nix::unistd::Pid::from_raw(pid as i32))
+    };
+    let str_field = |name: &str| value.get(name).and_then(Value::as_str);
+
+    srvc.pid = pid_from_json("pid");
+    srvc.process_group = pid_from_json("process_group");
+    srvc.signaled_ready = value
+        .get("signaled_ready")
+        .and_then(Value::as_bool)
+        .unwrap_or(false);
+    srvc.status_msgs = value
+        .get("status_msgs")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|msg| msg.as_str().map(str::to_owned))
+        .collect();
+    srvc.notifications = value
+        .get("notifications")
+        .and_then(Value::as_i64)
+        .map(|fd| unsafe { std::os::unix::net::UnixDatagram::from_raw_fd(fd as RawFd) });
+    srvc.notifications_path = str_field("notifications_path").map(std::path::PathBuf::from);
+    srvc.stdout = value.get("stdout").and_then(stdio_from_json);
+    srvc.stderr = value.get("stderr").and_then(stdio_from_json);
+    srvc.notifications_buffer = str_field("notifications_buffer")
+        .unwrap_or_default()
+        .to_owned();
+    srvc.stdout_buffer = value
+        .get("stdout_buffer")
+        .map(bytes_from_json)
+        .unwrap_or_default();
+    srvc.stderr_buffer = value
+        .get("stderr_buffer")
+        .map(bytes_from_json)
+        .unwrap_or_default();
+}
+
+/// Save the statuses of the units, the state of the services and sockets, the pid table and the fd store
+pub fn serialize_state(run_info: &RuntimeInfo) -> Value {
+    let mut units = Vec::new();
+    for unit in run_info.unit_table.values() {
+        let mut map = serde_json::Map::new();
+        map.insert("name".into(), Value::String(unit.id.name.clone()));
+        map.insert(
+            "status".into(),
+            status_to_json(&unit.common.status.read().unwrap()),
+        );
+        let common = match &unit.specific {
+            Specific::Service(specific) => {
+                let state = specific.state.read().unwrap();
+                map.insert("service".into(), service_to_json(&state.srvc));
+                (state.common.up_since, state.common.restart_count)
+            }
+            Specific::Socket(specific) => {
+                let state = specific.state.read().unwrap();
+                let mut sock = serde_json::Map::new();
+                sock.insert("activated".into(), Value::Bool(state.sock.activated));
+                map.insert("socket".into(), Value::Object(sock));
+                (state.common.up_since, state.common.restart_count)
+            }
+            Specific::Target(specific) => {
+                let state = specific.state.read().unwrap();
+                (state.common.up_since, state.common.restart_count)
+            }
+        };
+        let (up_since, restart_count) = common;
+        // instants can not be saved, the time that passed is saved instead
+        if let Some(up_since) = up_since {
+            map.insert(
+                "up_for_secs".into(),
+                Value::from(up_since.elapsed().as_secs_f64()),
+            );
+        }
+        map.insert("restart_count".into(), Value::from(restart_count));
+        units.push(Value::Object(map));
+    }
+
+    let pids = run_info
+        .pid_table
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|(pid, entry)| pid_entry_to_json(*pid, entry))
+        .collect();
+
+    let mut map = serde_json::Map::new();
+    map.insert("units".into(), Value::Array(units));
+    map.insert("pids".into(), Value::Array(pids));
+    map.insert(
+        "fd_store".into(),
+        run_info.fd_store.read().unwrap().to_json(),
+    );
+    Value::Object(map)
+}
+
+/// A status that was changing when the state was saved. The thread doing the change did not survive the exec, so the unit
+/// is put into the status it actually has now.
+fn settle_status(status: UnitStatus, running: bool) -> UnitStatus {
+    match status {
+        UnitStatus::Starting | UnitStatus::Restarting | UnitStatus::Stopping if running => {
+            UnitStatus::Started(StatusStarted::Running)
+        }
+        UnitStatus::Starting | UnitStatus::Restarting => {
+            UnitStatus::Stopped(StatusStopped::StoppedUnexpected, vec![])
+        }
+        UnitStatus::Stopping => UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![]),
+        status => status,
+    }
+}
+
+/// Restore the state saved with serialize_state into the freshly loaded units and register the filedescriptors with the reactor again.
+/// The whole state is parsed before anything is changed, so if this returns an error the runtime info is left as it was.
+pub fn restore_state(state: &Value, run_info: &mut RuntimeInfo) -> Result<(), String> {
+    let fd_store = FDStore::from_json(state.get("fd_store").unwrap_or(&Value::Null))?;
+
+    let mut pid_table = PidTable::new();
+    let mut helpers = Vec::new();
+    for entry in state
+        .get("pids")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        match pid_entry_from_json(entry, &run_info.unit_table)? {
+            // nothing waits for the helpers anymore
+            Some((pid, PidEntry::Helper(id, name))) => helpers.push((pid, id, name)),
+            Some((_, PidEntry::HelperExited(_))) => {}
+            Some((pid, entry)) => {
+                pid_table.insert(pid, entry);
+            }
+            None => {}
+        }
+    }
+
+    let mut units = Vec::new();
+    for saved in state
+        .get("units")
+        .and_then(Value::as_array)
+        .into_iter()
+        .flatten()
+    {
+        let name = saved
+            .get("name")
+            .and_then(Value::as_str)
+            .ok_or_else(|| format!("Missing unit name in: {}", saved))?;
+        let id: UnitId = name.try_into()?;
+        if !run_info.unit_table.contains_key(&id) {
+            warn!("Unit {} is not loaded anymore, its state is dropped", name);
+            continue;
+        }
+        let status = status_from_json(saved.get("status").unwrap_or(&Value::Null))?;
+        units.push((id, status, saved));
+    }
+
+    for (pid, id, name) in helpers {
+        warn!(
+            "Helper {} of unit {} was interrupted by the reexec, killing it",
+            name, id.name
+        );
+        if let Err(e) = nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL) {
+            warn!("Could not kill helper {} ({}): {}", name, pid, e);
+        }
+    }
+    *run_info.fd_store.write().unwrap() = fd_store;
+    *run_info.pid_table.lock().unwrap() = pid_table;
+
+    for (id, status, saved) in units {
+        let unit = &run_info.unit_table[&id];
+        let up_since = saved
+            .get("up_for_secs")
+            .and_then(Value::as_f64)
+            .and_then(|secs| {
+                std::time::Instant::now().checked_sub(std::time::Duration::from_secs_f64(secs))
+            });
+        let restart_count = saved
+            .get("restart_count")
+            .and_then(Value::as_u64)
+            .unwrap_or(0);
+
+        let restore_common = |common: &mut CommonState| {
+            common.up_since = up_since;
+            common.restart_count = restart_count;
+        };
+        let status = match &unit.specific {
+            Specific::Service(specific) => {
+                let mut state = specific.state.write().unwrap();
+                restore_common(&mut state.common);
+                if let Some(srvc) = saved.get("service") {
+                    restore_service(&mut state.srvc, srvc);
+                }
+                if let Err(e) = run_info.reactor.register_service(&id, &state.srvc) {
+                    error!("Could not listen to service {} again: {}", id.name, e);
+                }
+                settle_status(status, state.srvc.pid.is_some())
+            }
+            Specific::Socket(specific) => {
+                let mut state = specific.state.write().unwrap();
+                restore_common(&mut state.common);
+                state.sock.activated = saved
+                    .get("socket")
+                    .and_then(|sock| sock.get("activated"))
+                    .and_then(Value::as_bool)
+                    .unwrap_or(false);
+                let has_fds = run_info
+                    .fd_store
+                    .read()
+                    .unwrap()
+                    .get_global(&id.name)
+                    .is_some();
+                let status = settle_status(status, has_fds);
+                if status.is_started() {
+                    crate::socket_activation::arm_socket(&id, &state.sock, run_info);
+                }
+                status
+            }
+            Specific::Target(specific) => {
+                restore_common(&mut specific.state.write().unwrap().common);
+                let running = status != UnitStatus::Stopping;
+                settle_status(status, running)
+            }
+        };
+        trace!("Restored state of unit {}: {:?}", id.name, status);
+        *unit.common.status.write().unwrap() = status;
+    }
+    Ok(())
+}
+
+/// The filedescriptors that have to survive the exec
+fn fds_to_keep(run_info: &RuntimeInfo) -> Vec<RawFd> {
+    let mut fds = run_info.fd_store.read().unwrap().all_fds();
+    for unit in run_info.unit_table.values() {
+        if let Specific::Service(specific) = &unit.specific {
+            let state = specific.state.read().unwrap();
+            if let Some(socket) = &state.srvc.notifications {
+                fds.push(socket.as_raw_fd());
+            }
+            for stdio in [&state.srvc.stdout, &state.srvc.stderr].iter() {
+                if let Some(StdIo::Piped(r, w)) = stdio {
+                    fds.push(*r);
+                    fds.push(*w);
+                }
+            }
+        }
+    }
+    fds
+}
+
+fn set_cloexec(fds: &[RawFd]) {
+    for fd in fds {
+        let _ = nix::fcntl::fcntl(
+            *fd,
+            nix::fcntl::FcntlArg::F_SETFD(nix::fcntl::FdFlag::FD_CLOEXEC),
+        );
+    }
+}
+
+/// The filedescriptors (besides stdin/stdout/stderr) that are open before rustysd opened anything itself. After a reexec these
+/// are the ones that were kept over the exec.
+pub fn inherited_fds() -> Vec<RawFd> {
+    let fds: Vec<RawFd> = match std::fs::read_dir("/proc/self/fd") {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+            .filter(|fd| *fd > 2)
+            .collect(),
+        Err(e) => {
+            warn!("Could not list the inherited filedescriptors: {}", e);
+            return Vec::new();
+        }
+    };
+    // one of them belonged to the directory listing and is closed again
+    fds.into_iter()
+        .filter(|fd| nix::fcntl::fcntl(*fd, nix::fcntl::FcntlArg::F_GETFD).is_ok())
+        .collect()
+}
+
+/// The processes rustysd is the parent of, together with their process groups
+fn children() -> Vec<(nix::unistd::Pid, nix::unistd::Pid)> {
+    let own_pid = nix::unistd::getpid().as_raw();
+    let entries = match std::fs::read_dir("/proc") {
+        Ok(entries) => entries,
+        Err(e) => {
+            warn!("Could not list the running processes: {}", e);
+            return Vec::new();
+        }
+    };
+    entries
+        .filter_map(|entry| {
+            let pid: i32 = entry.ok()?.file_name().to_str()?.parse().ok()?;
+            let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+            // the fields after the command name are: state, ppid, pgrp, ...
+            let mut fields = stat.get(stat.rfind(')')? + 1..)?.split_whitespace().skip(1);
+            let ppid: i32 = fields.next()?.parse().ok()?;
+            let pgrp: i32 = fields.next()?.parse().ok()?;
+            if ppid == own_pid {
+                Some((
+                    nix::unistd::Pid::from_raw(pid),
+                    nix::unistd::Pid::from_raw(pgrp),
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Restoring the state failed, so rustysd does not know which of its children belong to which unit. Kill them and close the
+/// filedescriptors that were kept for them, so the units can be started from scratch.
+pub fn clean_up_failed_restore(inherited_fds: &[RawFd]) {
+    let own_group = nix::unistd::getpgrp();
+    for (pid, group) in children() {
+        warn!("Killing process {} left over from before the reexec", pid);
+        // services run in their own process group, which also catches the processes they forked
+        let res = if group != own_group {
+            nix::sys::signal::killpg(group, nix::sys::signal::Signal::SIGKILL)
+        } else {
+            nix::sys::signal::kill(pid, nix::sys::signal::Signal::SIGKILL)
+        };
+        if let Err(e) = res {
+            warn!("Could not kill process {}: {}", pid, e);
+        }
+    }
+    for fd in inherited_fds {
+        let _ = nix::unistd::close(*fd);
+    }
+}
+
+/// After the state was restored, close the inherited filedescriptors that no unit took over, e.g. the ones of units that are not loaded
+/// anymore.
+pub fn close_unclaimed_fds(inherited_fds: &[RawFd], run_info: &RuntimeInfo) {
+    let claimed = fds_to_keep(run_info);
+    for fd in inherited_fds {
+        if !claimed.contains(fd) {
+            trace!("Close fd {} that no unit took over after the reexec", fd);
+            let _ = nix::unistd::close(*fd);
+        }
+    }
+}
+
+/// Read the state written by reexec. The file is removed afterwards so it is not used again by accident.
+pub fn load_state(path: &std::path::Path) -> Result<Value, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read state file {:?}: {}", path, e))?;
+    if let Err(e) = std::fs::remove_file(path) {
+        warn!("Could not remove state file {:?}: {}", path, e);
+    }
+    serde_json::from_str(&content).map_err(|e| format!("Could not parse state file: {}", e))
+}
+
+/// Save the state, exec the rustysd binary with --deserialize and the same arguments it was started with.
+///
+/// This only returns if something went wrong. The runtime info must not be changed while this runs,
+/// so it is best called with the write lock held.
+pub fn reexec(run_info: &RuntimeInfo) -> Result<(), String> {
+    let state_path = run_info
+        .config
+        .notification_sockets_dir
+        .join("reexec_state.json");
+    let state = serialize_state(run_info);
+    std::fs::write(&state_path, state.to_string())
+        .map_err(|e| format!("Could not write state file {:?}: {}", state_path, e))?;
+
+    let exe =
+        std::env::current_exe().map_err(|e| format!("Could not find the rustysd binary: {}", e))?;
+    // if the binary was replaced on disk the link points to the old, deleted file
+    let exe = exe
+        .to_string_lossy()
+        .trim_end_matches(" (deleted)")
+        .to_owned();
+
+    let mut args = vec![exe.clone()];
+    let mut old_args = std::env::args().skip(1);
+    while let Some(arg) = old_args.next() {
+        if arg == "--deserialize" {
+            old_args.next();
+        } else if !arg.starts_with("--deserialize=") {
+            args.push(arg);
+        }
+    }
+    args.push("--deserialize".into());
+    args.push(state_path.to_string_lossy().into_owned());
+    let args = args
+        .into_iter()
+        .map(std::ffi::CString::new)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Invalid argument: {}", e))?;
+    let exe = std::ffi::CString::new(exe).map_err(|e| format!("Invalid binary path: {}", e))?;
+
+    let fds = fds_to_keep(run_info);
+    for fd in &fds {
+        if let Err(e) = unsafe { crate::platform::unset_cloexec(*fd) } {
+            set_cloexec(&fds);
+            let _ = std::fs::remove_file(&state_path);
+            return Err(e);
+        }
+    }
+
+    info!("Re-executing {:?} with args: {:?}", exe, args);
+    let err = nix::unistd::execv(&exe, &args).unwrap_err();
+
+    set_cloexec(&fds);
+    let _ = std::fs::remove_file(&state_path);
+    Err(format!("Could not exec {:?}: {}", exe, err))
+}
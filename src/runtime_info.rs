@@ -3,9 +3,16 @@
 //!
 //! The idea here is to make as much as possible concurrently readable while still being able to get exclusive access to e.g. remove units.
 //! Note that units themselves contain RWLocks so they can be worked on concurrently as long as no write() lock is placed on the RuntimeInfo.
+//!
+//! The locks inside the RuntimeInfo are always taken in this order: the state of a unit, then the pid table, then the fd store. E.g. a socket
+//! unit closes its sockets while holding its state and takes the fd store for that. Nothing takes the state of a unit while holding the fd store.
 
+use crate::dynamic_users::DynamicUsers;
 use crate::fd_store::FDStore;
-use crate::platform::EventFd;
+use crate::jobs::JobQueue;
+use crate::log_store::LogStore;
+use crate::logging::SyslogForwarder;
+use crate::reactor::Reactor;
 use crate::units::*;
 
 use nix::unistd::Pid;
@@ -19,21 +26,20 @@ pub type MutFDStore = RwLock<FDStore>;
 pub struct RuntimeInfo {
     pub unit_table: UnitTable,
     pub pid_table: Mutex<PidTable>,
+    /// Locked last, see the lock order above
     pub fd_store: MutFDStore,
     pub config: crate::config::Config,
-    pub stdout_eventfd: EventFd,
-    pub stderr_eventfd: EventFd,
-    pub notification_eventfd: EventFd,
-    pub socket_activation_eventfd: EventFd,
-}
-
-impl RuntimeInfo {
-    pub fn notify_eventfds(&self) {
-        crate::platform::notify_event_fd(self.stdout_eventfd);
-        crate::platform::notify_event_fd(self.stderr_eventfd);
-        crate::platform::notify_event_fd(self.notification_eventfd);
-        crate::platform::notify_event_fd(self.socket_activation_eventfd);
-    }
+    pub reactor: Arc<Reactor>,
+    /// Only set if logging to disk is enabled
+    pub log_store: Option<LogStore>,
+    /// Only set if forwarding to syslog is enabled
+    pub syslog: Option<SyslogForwarder>,
+    /// Uids allocated for services with DynamicUser=yes
+    pub dynamic_users: DynamicUsers,
+    /// Pending start/stop requests from the control interface. Shared so requests can wait for their turn without holding the lock of the RuntimeInfo
+    pub jobs: Arc<JobQueue>,
+    /// Changes found by daemon-reload for units that were running. Applied when the units are stopped
+    pub pending_reload: HashMap<UnitId, PendingReload>,
 }
 
 pub type ArcMutRuntimeInfo = Arc<RwLock<RuntimeInfo>>;
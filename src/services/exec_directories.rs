@@ -0,0 +1,65 @@
+//! The directories of RuntimeDirectory=, StateDirectory= etc. are created by rustysd before the service is started
+//! and the runtime directories are removed again when it stops.
+
+use crate::units::{ExecConfig, ExecDirectoryKind, RuntimeDirectoryPreserve};
+use log::{error, trace};
+use std::os::unix::fs::PermissionsExt;
+
+/// Create the directories and give the innermost ones the configured mode. Except for the configuration directories they are owned by the user and group of the service.
+pub fn create_directories(conf: &ExecConfig) -> Result<(), String> {
+    for dir in &conf.directories.directories {
+        for path in dir.paths() {
+            std::fs::create_dir_all(&path)
+                .map_err(|e| format!("Error while creating directory {:?}: {}", path, e))?;
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(dir.mode))
+                .map_err(|e| format!("Error while setting the mode of {:?}: {}", path, e))?;
+            if dir.kind != ExecDirectoryKind::Configuration {
+                nix::unistd::chown(&path, Some(conf.user), Some(conf.group))
+                    .map_err(|e| format!("Error while changing the owner of {:?}: {}", path, e))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The value of RUNTIME_DIRECTORY etc. for all kinds of directories that are configured
+pub fn directory_env_vars(conf: &ExecConfig) -> Vec<(&'static str, String)> {
+    conf.directories
+        .directories
+        .iter()
+        .map(|dir| {
+            let paths: Vec<String> = dir
+                .paths()
+                .iter()
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect();
+            (dir.kind.env_var(), paths.join(":"))
+        })
+        .collect()
+}
+
+/// Remove the runtime directories of a service that stopped. `restart` is true if the service is about to be started again.
+pub fn remove_runtime_directories(conf: &ExecConfig, name: &str, restart: bool) {
+    match conf.directories.runtime_preserve {
+        RuntimeDirectoryPreserve::Yes => return,
+        RuntimeDirectoryPreserve::Restart if restart => return,
+        _ => {}
+    }
+    for dir in &conf.directories.directories {
+        if dir.kind != ExecDirectoryKind::Runtime {
+            continue;
+        }
+        for path in dir.paths() {
+            if !path.exists() {
+                continue;
+            }
+            trace!("Remove runtime directory {:?} of {}", path, name);
+            if let Err(e) = std::fs::remove_dir_all(&path) {
+                error!(
+                    "Error while removing runtime directory {:?} of {}: {}",
+                    path, name, e
+                );
+            }
+        }
+    }
+}
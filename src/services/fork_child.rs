@@ -1,6 +1,7 @@
 use crate::fd_store::FDStore;
 use crate::platform::setenv;
 use crate::services::Service;
+use crate::units::Commandline;
 use crate::units::ServiceConfig;
 use std::os::unix::io::RawFd;
 
@@ -66,6 +67,12 @@ fn setup_env_vars(socket_names: Vec<String>, notify_socket_env_var: &str, conf:
         setenv("NOTIFY_SOCKET", notify_socket_env_var);
     }
 
+    for (key, val) in super::directory_env_vars(&conf.exec_config) {
+        unsafe {
+            setenv(key, &val);
+        }
+    }
+
     if let Some(env) = &conf.exec_config.environment {
         for (key, val) in &env.vars {
             unsafe {
@@ -84,7 +91,15 @@ fn setup_env_vars(socket_names: Vec<String>, notify_socket_env_var: &str, conf:
     //);
 }
 
-fn dup_stdio(new_stdout: RawFd, new_stderr: RawFd) {
+fn dup_stdio(new_stdin: RawFd, new_stdout: RawFd, new_stderr: RawFd) {
+    // dup new stdin to fd 0. This is /dev/null if nothing else was configured
+    let actual_new_fd = nix::unistd::dup2(new_stdin, 0).unwrap();
+    if actual_new_fd != 0 {
+        panic!(
+            "Could not dup the fd to stdin. Got duped to: {}",
+            actual_new_fd
+        );
+    }
     // dup new stdout to fd 1. The other end of the pipe will be read from the service daemon
     let actual_new_fd = nix::unistd::dup2(new_stdout, 1).unwrap();
     if actual_new_fd != 1 {
@@ -140,10 +155,10 @@ fn dup_fds(name: &str, sockets: Vec<RawFd>) -> Result<(), String> {
     Ok(())
 }
 
-fn prepare_exec_args(conf: &ServiceConfig) -> (std::ffi::CString, Vec<std::ffi::CString>) {
-    let cmd = std::ffi::CString::new(conf.exec.cmd.as_str()).unwrap();
+fn prepare_exec_args(cmdline: &Commandline) -> (std::ffi::CString, Vec<std::ffi::CString>) {
+    let cmd = std::ffi::CString::new(cmdline.cmd.as_str()).unwrap();
 
-    let exec_name = std::path::PathBuf::from(&conf.exec.cmd);
+    let exec_name = std::path::PathBuf::from(&cmdline.cmd);
     let exec_name = exec_name.file_name().unwrap();
     let exec_name: Vec<u8> = exec_name.to_str().unwrap().bytes().collect();
     let exec_name = std::ffi::CString::new(exec_name).unwrap();
@@ -151,7 +166,7 @@ fn prepare_exec_args(conf: &ServiceConfig) -> (std::ffi::CString, Vec<std::ffi::
     let mut args = Vec::new();
     args.push(exec_name);
 
-    for word in &conf.exec.args {
+    for word in &cmdline.args {
         args.push(std::ffi::CString::new(word.as_str()).unwrap());
     }
 
@@ -163,33 +178,34 @@ fn move_into_new_process_group() {
     nix::unistd::setpgid(nix::unistd::getpid(), nix::unistd::Pid::from_raw(0)).unwrap();
 }
 
+/// Report the error to the parent through the error pipe and exit. The parent uses the message as the reason why starting the service failed.
+fn fail_child(error_fd: RawFd, name: &str, msg: &str) -> ! {
+    eprintln!("[FORK_CHILD {}] {}", name, msg);
+    let _ = nix::unistd::write(error_fd, msg.as_bytes());
+    std::process::exit(1);
+}
+
+/// Move the error pipe above the fds that will be used for the sockets so it does not get overwritten by dup_fds
+fn move_error_fd(error_fd: RawFd, num_socket_fds: usize) -> Result<RawFd, String> {
+    let min_fd = 3 + num_socket_fds as RawFd;
+    if error_fd >= min_fd {
+        return Ok(error_fd);
+    }
+    let new_fd = nix::fcntl::fcntl(error_fd, nix::fcntl::FcntlArg::F_DUPFD_CLOEXEC(min_fd))
+        .map_err(|e| format!("Error while moving error pipe: {}", e))?;
+    let _ = nix::unistd::close(error_fd);
+    Ok(new_fd)
+}
+
 pub fn after_fork_child(
     srvc: &mut Service,
     conf: &ServiceConfig,
+    cmdline: &Commandline,
     name: &str,
     fd_store: &FDStore,
     notify_socket_env_var: &str,
-    new_stdout: RawFd,
-    new_stderr: RawFd,
+    error_fd: RawFd,
 ) {
-    if let Err(e) = super::fork_os_specific::post_fork_os_specific(srvc) {
-        eprintln!("[FORK_CHILD {}] postfork error: {}", name, e);
-        std::process::exit(1);
-    }
-
-    // DO NOT USE THE LOGGER HERE. It aquires a global lock which might be held at the time of forking
-    // But since this is the only thread that is in the child process the lock will never be released!
-    move_into_new_process_group();
-
-    // no more logging after this point!
-    // The filedescriptor used by the logger might have been duped to another
-    // one and logging into that one would be.... bad
-    // Hopefully the close() means that no old logs will get written to that filedescriptor
-
-    close_all_unneeded_fds(srvc, fd_store);
-
-    dup_stdio(new_stdout, new_stderr);
-
     let mut fds = Vec::new();
     let mut names = Vec::new();
 
@@ -212,29 +228,100 @@ pub fn after_fork_child(
         names.extend(sock_names);
     }
 
+    let error_fd = match move_error_fd(error_fd, fds.len()) {
+        Ok(fd) => fd,
+        Err(e) => fail_child(error_fd, name, &e),
+    };
+
+    if let Err(e) = super::fork_os_specific::post_fork_os_specific(srvc) {
+        fail_child(error_fd, name, &format!("postfork error: {}", e));
+    }
+
+    // DO NOT USE THE LOGGER HERE. It aquires a global lock which might be held at the time of forking
+    // But since this is the only thread that is in the child process the lock will never be released!
+    move_into_new_process_group();
+
+    // no more logging after this point!
+    // The filedescriptor used by the logger might have been duped to another
+    // one and logging into that one would be.... bad
+    // Hopefully the close() means that no old logs will get written to that filedescriptor
+
+    close_all_unneeded_fds(srvc, fd_store);
+
+    match (&srvc.stdin, &srvc.stdout, &srvc.stderr) {
+        (Some(stdin), Some(stdout), Some(stderr)) => {
+            dup_stdio(stdin.read_fd(), stdout.write_fd(), stderr.write_fd())
+        }
+        _ => unreachable!(),
+    }
+
     if let Err(e) = dup_fds(name, fds) {
-        eprintln!("[FORK_CHILD {}] error while duping fds: {}", name, e);
-        std::process::exit(1);
+        fail_child(error_fd, name, &format!("error while duping fds: {}", e));
     }
 
     setup_env_vars(names, notify_socket_env_var, conf);
-    let (cmd, args) = prepare_exec_args(conf);
+    let (cmd, args) = prepare_exec_args(cmdline);
+
+    // needs to happen before dropping privileges, mounting is only allowed for root
+    if let Err(e) = crate::platform::setup_filesystem_sandbox(&conf.exec_config.sandbox) {
+        fail_child(
+            error_fd,
+            name,
+            &format!("could not setup the filesystem sandbox: {}", e),
+        );
+    }
+
+    if let Err(e) = crate::platform::apply_resource_limits(&conf.exec_config.limits) {
+        fail_child(error_fd, name, &e);
+    }
+    if let Err(e) = crate::platform::apply_process_settings(&conf.exec_config.process) {
+        fail_child(error_fd, name, &e);
+    }
+
+    if let Err(e) = crate::platform::setup_namespaces(
+        &conf.exec_config.namespaces,
+        conf.exec_config.user,
+        conf.exec_config.group,
+        &conf.exec_config.supplementary_groups,
+    ) {
+        fail_child(
+            error_fd,
+            name,
+            &format!("could not setup the namespaces: {}", e),
+        );
+    }
+    // after the namespaces, the helper that writes the uid map of PrivateUsers= needs the /proc of the host
+    if let Err(e) = crate::platform::change_root_directory(&conf.exec_config.process) {
+        fail_child(error_fd, name, &e);
+    }
 
     if nix::unistd::getuid().is_root() {
         match crate::platform::drop_privileges(
             conf.exec_config.group,
             &conf.exec_config.supplementary_groups,
             conf.exec_config.user,
+            &conf.exec_config.capabilities,
+            conf.exec_config.no_new_privileges,
         ) {
             Ok(()) => { /* Happy */ }
             Err(e) => {
-                eprintln!(
-                    "[FORK_CHILD {}] could not drop privileges because: {}",
-                    name, e
+                fail_child(
+                    error_fd,
+                    name,
+                    &format!("could not drop privileges because: {}", e),
                 );
-                std::process::exit(1);
             }
         }
+    } else if conf.exec_config.no_new_privileges {
+        if let Err(e) = crate::platform::set_no_new_privileges() {
+            fail_child(error_fd, name, &e);
+        }
+    }
+
+    if let Some(dir) = &conf.exec_config.working_directory {
+        if let Err(e) = crate::platform::change_working_directory(dir) {
+            fail_child(error_fd, name, &e);
+        }
     }
 
     eprintln!("EXECV: {:?} {:?}", &cmd, &args);
@@ -242,6 +329,25 @@ pub fn after_fork_child(
         .iter()
         .map(|cstring| cstring.as_c_str())
         .collect::<Vec<_>>();
+    // the filters are the last thing before the exec so rustysd's own code in the child is not affected by them.
+    // The protection filter comes first, the filter from SystemCallFilter= might not allow installing another one
+    if let Err(e) = crate::platform::setup_protection_filter(
+        &conf.exec_config.sandbox,
+        &conf.exec_config.namespaces,
+    ) {
+        fail_child(
+            error_fd,
+            name,
+            &format!("could not setup the system call filter: {}", e),
+        );
+    }
+    if let Err(e) = crate::platform::setup_syscall_filter(&conf.exec_config.syscall_filter) {
+        fail_child(
+            error_fd,
+            name,
+            &format!("could not setup the system call filter: {}", e),
+        );
+    }
     match nix::unistd::execv(&cmd, &cstr_args) {
         Ok(_) => {
             eprintln!(
@@ -250,8 +356,7 @@ pub fn after_fork_child(
             );
         }
         Err(e) => {
-            eprintln!("[FORK_CHILD {}] execv errored: {:?}", name, e);
-            std::process::exit(1);
+            fail_child(error_fd, name, &format!("execv errored: {:?}", e));
         }
     }
 }
@@ -11,6 +11,7 @@ use crate::units::*;
 pub fn wait_for_service(
     srvc: &mut Service,
     conf: &ServiceConfig,
+    cmdline: &Commandline,
     name: &str,
     run_info: &RuntimeInfo,
 ) -> Result<(), RunCmdError> {
@@ -62,7 +63,7 @@ pub fn wait_for_service(
                     if duration_elapsed > duration_timeout {
                         trace!("[FORK_PARENT] Service {} notification timed out", name);
                         return Err(RunCmdError::Timeout(
-                            conf.exec.to_string(),
+                            cmdline.to_string(),
                             format!("{:?}", duration_timeout),
                         ));
                     } else {
@@ -110,7 +111,7 @@ pub fn wait_for_service(
                     if start_time.elapsed() >= time_out {
                         error!("oneshot service {} reached timeout", name);
                         return Err(RunCmdError::Timeout(
-                            conf.exec.to_string(),
+                            cmdline.to_string(),
                             format!("{:?}", duration_timeout),
                         ));
                     }
@@ -127,14 +128,11 @@ pub fn wait_for_service(
                                     trace!("End wait for {}", name);
                                     let entry_owned = pid_table_locked.remove(&pid).unwrap();
                                     if let PidEntry::ServiceExited(code) = entry_owned {
-                                        if !code.success() {
-                                            if !conf
-                                                .exec
-                                                .prefixes
-                                                .contains(&CommandlinePrefix::Minus)
+                                        if !conf.exit_status.is_success(&code) {
+                                            if !cmdline.prefixes.contains(&CommandlinePrefix::Minus)
                                             {
                                                 return Err(RunCmdError::BadExitCode(
-                                                    conf.exec.to_string(),
+                                                    cmdline.to_string(),
                                                     code,
                                                 ));
                                             }
@@ -187,14 +185,14 @@ pub fn wait_for_service(
                         crate::dbus_wait::WaitResult::Timedout => {
                             warn!("[FORK_PARENT] Did not find dbus name on bus: {}", dbus_name);
                             return Err(RunCmdError::Timeout(
-                                conf.exec.to_string(),
+                                cmdline.to_string(),
                                 format!("{:?}", duration_timeout),
                             ));
                         }
                     },
                     Err(e) => {
                         return Err(RunCmdError::WaitError(
-                            conf.exec.to_string(),
+                            cmdline.to_string(),
                             format!("Error while waiting for dbus name: {}", e),
                         ));
                     }
@@ -14,3 +14,42 @@ pub fn kill(srvc: &mut Service, sig: nix::sys::signal::Signal) -> Result<(), Str
     let _ = sig;
     Ok(())
 }
+
+/// Send the signal to all processes in the cgroup of the service without freezing it first. Used for the graceful
+/// KillSignal= which the processes need to be able to handle.
+pub fn signal_all(srvc: &Service, sig: nix::sys::signal::Signal) -> Result<(), String> {
+    #[cfg(feature = "cgroups")]
+    {
+        cgroups::kill_cgroup(&srvc.platform_specific.cgroup_path, sig)
+            .map_err(|e| format!("{}", e))?;
+    }
+    let _ = srvc;
+    let _ = sig;
+    Ok(())
+}
+
+/// Whether there are processes left in the cgroup of the service. Without cgroups this can not be known, so it returns false.
+pub fn has_remaining_processes(srvc: &Service) -> bool {
+    #[cfg(feature = "cgroups")]
+    {
+        if let Ok(pids) = cgroups::get_all_procs(&srvc.platform_specific.cgroup_path) {
+            return !pids.is_empty();
+        }
+    }
+    let _ = srvc;
+    false
+}
+
+/// Remove the cgroup of the service if no processes are left in it. With KillMode=process or SendSIGKILL=no processes can
+/// survive the stop, then the cgroup stays and the next start of the service uses it again.
+pub fn remove_cgroup(srvc: &Service) -> Result<(), String> {
+    #[cfg(feature = "cgroups")]
+    {
+        let path = &srvc.platform_specific.cgroup_path;
+        if path.exists() && !has_remaining_processes(srvc) {
+            std::fs::remove_dir(path).map_err(|e| format!("{}", e))?;
+        }
+    }
+    let _ = srvc;
+    Ok(())
+}
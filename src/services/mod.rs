@@ -3,6 +3,7 @@
 //! 2. processgroupid setting
 //! 3. duping of filedescriptors
 //! 4. signaling processgroup on kill
+mod exec_directories;
 mod fork_child;
 mod fork_os_specific;
 mod fork_parent;
@@ -11,5 +12,7 @@ mod prepare_service;
 mod service_exit_handler;
 mod services;
 mod start_service;
+mod stop_service;
+pub use exec_directories::*;
 pub use service_exit_handler::*;
 pub use services::*;
@@ -1,42 +1,224 @@
 use super::StdIo;
+use crate::fd_store::FDStore;
 use crate::services::Service;
 use crate::units::ServiceConfig;
+use crate::units::StdInOption;
 use crate::units::StdIoOption;
-use std::os::unix::io::AsRawFd;
+use std::io::{Seek, SeekFrom, Write};
+use std::os::unix::io::{AsRawFd, FromRawFd, RawFd};
 use std::os::unix::net::UnixDatagram;
+use std::path::Path;
 
-fn open_stdio(setting: &Option<StdIoOption>) -> Result<StdIo, String> {
+const DEFAULT_TTY_PATH: &str = "/dev/console";
+
+fn open_file(path: &Path, options: &std::fs::OpenOptions) -> Result<std::fs::File, String> {
+    options
+        .open(path)
+        .map_err(|e| format!("Error opening file: {:?}: {}", path, e))
+}
+
+fn open_null() -> Result<StdIo, String> {
+    let file = open_file(
+        Path::new("/dev/null"),
+        std::fs::OpenOptions::new().read(true).write(true),
+    )?;
+    Ok(StdIo::Null(file))
+}
+
+fn open_tty(conf: &ServiceConfig) -> Result<StdIo, String> {
+    let path = conf
+        .exec_config
+        .tty_path
+        .clone()
+        .unwrap_or_else(|| DEFAULT_TTY_PATH.into());
+    let file = open_file(&path, std::fs::OpenOptions::new().read(true).write(true))?;
+    Ok(StdIo::File(file))
+}
+
+/// Duplicate the fd into a new file that is closed on exec like all other fds rustysd holds
+fn dup_to_file(fd: RawFd) -> Result<std::fs::File, String> {
+    let duped = nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_DUPFD_CLOEXEC(0))
+        .map_err(|e| format!("Error while duping fd {}: {}", fd, e))?;
+    Ok(unsafe { std::fs::File::from_raw_fd(duped) })
+}
+
+/// Find the fd with this name in the sockets that belong to this service
+fn find_socket_fd(
+    fd_name: &str,
+    conf: &ServiceConfig,
+    fd_store: &FDStore,
+) -> Result<StdIo, String> {
+    for socket in &conf.sockets {
+        if let Some(fds) = fd_store.get_global(&socket.name) {
+            for (_, name, fd) in fds {
+                if name == fd_name {
+                    return Ok(StdIo::File(dup_to_file(fd.as_raw_fd())?));
+                }
+            }
+        }
+    }
+    Err(format!(
+        "None of the sockets of this service has a filedescriptor named: {}",
+        fd_name
+    ))
+}
+
+fn open_stdio(
+    setting: &Option<StdIoOption>,
+    conf: &ServiceConfig,
+    fd_store: &FDStore,
+) -> Result<StdIo, String> {
     match setting {
         Some(StdIoOption::File(path)) => {
-            let file = std::fs::OpenOptions::new()
-                .create(true)
-                .write(true)
-                .read(true)
-                .open(path)
-                .map_err(|e| format!("Error opening file: {:?}: {}", path, e))?;
+            let file = open_file(
+                path,
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .write(true)
+                    .read(true),
+            )?;
             Ok(StdIo::File(file))
         }
         Some(StdIoOption::AppendFile(path)) => {
-            let file = std::fs::OpenOptions::new()
-                .create(true)
-                .append(true)
-                .read(true)
-                .open(path)
-                .map_err(|e| format!("Error opening file: {:?}: {}", path, e))?;
+            let file = open_file(
+                path,
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .read(true),
+            )?;
             Ok(StdIo::File(file))
         }
-        None => {
+        Some(StdIoOption::TruncateFile(path)) => {
+            let file = open_file(
+                path,
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .truncate(true)
+                    .write(true)
+                    .read(true),
+            )?;
+            Ok(StdIo::File(file))
+        }
+        Some(StdIoOption::Null) => open_null(),
+        Some(StdIoOption::Tty) => open_tty(conf),
+        Some(StdIoOption::Kmsg) => {
+            // every write to /dev/kmsg becomes one record in the kernel log
+            let file = open_file(
+                Path::new("/dev/kmsg"),
+                std::fs::OpenOptions::new().write(true),
+            )?;
+            Ok(StdIo::File(file))
+        }
+        Some(StdIoOption::Fd(fd_name)) => find_socket_fd(fd_name, conf, fd_store),
+        Some(StdIoOption::Socket) => {
+            Err("Socket as stdio is only possible with Accept=yes which is not supported".into())
+        }
+        Some(StdIoOption::Inherit) => {
+            // stdout inherits the target of stdin. Stdin files are opened read only so they are opened again for writing.
+            match &conf.exec_config.stdin_option {
+                Some(StdInOption::Null) => open_null(),
+                Some(StdInOption::Tty) => open_tty(conf),
+                Some(StdInOption::File(path)) => {
+                    open_stdio(&Some(StdIoOption::File(path.clone())), conf, fd_store)
+                }
+                Some(StdInOption::Socket) => Err(
+                    "Socket as stdio is only possible with Accept=yes which is not supported"
+                        .into(),
+                ),
+                Some(StdInOption::Data(_)) | None => open_stdio(&None, conf, fd_store),
+            }
+        }
+        Some(StdIoOption::Journal) | None => {
             let (r, w) = nix::unistd::pipe().unwrap();
             Ok(super::StdIo::Piped(r, w))
         }
     }
 }
 
+/// Whether open_stdio creates a pipe for this setting
+fn wants_pipe(setting: &Option<StdIoOption>, conf: &ServiceConfig) -> bool {
+    match setting {
+        Some(StdIoOption::Journal) | None => true,
+        Some(StdIoOption::Inherit) => matches!(
+            conf.exec_config.stdin_option,
+            Some(StdInOption::Data(_)) | None
+        ),
+        _ => false,
+    }
+}
+
+fn is_pipe(stdio: &Option<StdIo>) -> bool {
+    matches!(stdio, Some(StdIo::Piped(_, _)))
+}
+
+/// Files are closed when they are dropped, the fds of pipes have to be closed explicitly
+fn close_pipe(stdio: Option<StdIo>) {
+    if let Some(StdIo::Piped(r, w)) = stdio {
+        let _ = nix::unistd::close(r);
+        let _ = nix::unistd::close(w);
+    }
+}
+
+/// Stderr can inherit stdout. In this case the service gets the same target on both fds.
+fn open_stderr(conf: &ServiceConfig, stdout: &StdIo, fd_store: &FDStore) -> Result<StdIo, String> {
+    if let Some(StdIoOption::Inherit) = &conf.exec_config.stderr_path {
+        match stdout {
+            StdIo::Piped(_r, w) => Ok(StdIo::File(dup_to_file(*w)?)),
+            StdIo::File(f) => Ok(StdIo::File(dup_to_file(f.as_raw_fd())?)),
+            StdIo::Null(f) => Ok(StdIo::Null(dup_to_file(f.as_raw_fd())?)),
+        }
+    } else {
+        open_stdio(&conf.exec_config.stderr_path, conf, fd_store)
+    }
+}
+
+/// Write the data into a file that is already unlinked, so the service gets it as stdin
+fn open_stdin_data(data: &str, path: &Path) -> Result<StdIo, String> {
+    let mut file = open_file(
+        path,
+        std::fs::OpenOptions::new()
+            .create(true)
+            .truncate(true)
+            .write(true)
+            .read(true),
+    )?;
+    std::fs::remove_file(path).map_err(|e| format!("Error removing file {:?}: {}", path, e))?;
+    file.write_all(data.as_bytes())
+        .map_err(|e| format!("Error writing stdin data to {:?}: {}", path, e))?;
+    file.seek(SeekFrom::Start(0))
+        .map_err(|e| format!("Error seeking in {:?}: {}", path, e))?;
+    Ok(StdIo::File(file))
+}
+
+fn open_stdin(
+    conf: &ServiceConfig,
+    name: &str,
+    notification_socket_path: &Path,
+) -> Result<StdIo, String> {
+    match &conf.exec_config.stdin_option {
+        Some(StdInOption::Null) | None => open_null(),
+        Some(StdInOption::Tty) => open_tty(conf),
+        Some(StdInOption::File(path)) => {
+            let file = open_file(path, std::fs::OpenOptions::new().read(true))?;
+            Ok(StdIo::File(file))
+        }
+        Some(StdInOption::Socket) => {
+            Err("Socket as stdio is only possible with Accept=yes which is not supported".into())
+        }
+        Some(StdInOption::Data(data)) => open_stdin_data(
+            data,
+            &notification_socket_path.join(format!("{}.stdin", name)),
+        ),
+    }
+}
+
 pub fn prepare_service(
     srvc: &mut Service,
     conf: &ServiceConfig,
     name: &str,
     notification_socket_path: &std::path::PathBuf,
+    fd_store: &FDStore,
 ) -> Result<(), String> {
     // setup socket for notifications from the service
     if !notification_socket_path.exists() {
@@ -68,11 +250,21 @@ pub fn prepare_service(
         srvc.notifications = Some(stream);
     }
 
-    if srvc.stdout.is_none() {
-        srvc.stdout = Some(open_stdio(&conf.exec_config.stdout_path)?);
+    // stdin is opened for every start so files (and data) are read from the beginning again
+    srvc.stdin = Some(open_stdin(conf, name, notification_socket_path)?);
+    // pipes are kept over restarts so output that is still in them is not lost. Everything else is opened again on
+    // every start, so truncate: files are truncated again and file:/append: files are reopened
+    let stdout_pipe = wants_pipe(&conf.exec_config.stdout_path, conf);
+    if !(stdout_pipe && is_pipe(&srvc.stdout)) {
+        close_pipe(srvc.stdout.take());
+        srvc.stdout = Some(open_stdio(&conf.exec_config.stdout_path, conf, fd_store)?);
     }
-    if srvc.stderr.is_none() {
-        srvc.stderr = Some(open_stdio(&conf.exec_config.stderr_path)?);
+    let stderr_pipe = !matches!(conf.exec_config.stderr_path, Some(StdIoOption::Inherit))
+        && wants_pipe(&conf.exec_config.stderr_path, conf);
+    if !(stderr_pipe && is_pipe(&srvc.stderr)) {
+        close_pipe(srvc.stderr.take());
+        let stderr = open_stderr(conf, srvc.stdout.as_ref().unwrap(), fd_store)?;
+        srvc.stderr = Some(stderr);
     }
 
     srvc.notifications_path = Some(notify_socket_env_var);
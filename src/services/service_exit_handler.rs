@@ -10,17 +10,36 @@ pub fn service_exit_handler_new_thread(
     run_info: ArcMutRuntimeInfo,
 ) {
     std::thread::spawn(move || {
-        if let Err(e) = service_exit_handler(pid, code, &*run_info.read().unwrap()) {
-            error!("{}", e);
+        let res = service_exit_handler(pid, code, &run_info.read().unwrap());
+        // the unit might be stopped now, so the changes from the last daemon-reload can be applied
+        if !run_info.read().unwrap().pending_reload.is_empty() {
+            crate::units::apply_pending_reloads(&mut run_info.write().unwrap());
+        }
+        match res {
+            Ok(Some(srvc_id)) => {
+                let run_info = &*run_info.read().unwrap();
+                if run_info.unit_table.contains_key(&srvc_id) {
+                    trace!("Start service {} again with its new config", srvc_id.name);
+                    if let Err(e) =
+                        crate::units::activate_unit(srvc_id, run_info, ActivationSource::Regular)
+                    {
+                        error!("{}", e);
+                    }
+                }
+            }
+            Ok(None) => {}
+            Err(e) => error!("{}", e),
         }
     });
 }
 
+/// Returns the id of the service if it should be restarted but was only stopped, because a daemon-reload changed it while it was running.
+/// The new config is swapped in before it is started again.
 pub fn service_exit_handler(
     pid: nix::unistd::Pid,
     code: ChildTermination,
     run_info: &RuntimeInfo,
-) -> Result<(), String> {
+) -> Result<Option<UnitId>, String> {
     trace!("Exit handler with pid: {}", pid);
 
     // Handle exiting of helper processes and oneshot processes
@@ -40,17 +59,17 @@ pub fn service_exit_handler(
                     );
                     // this will be collected by the thread that waits for the helper process to exit
                     pid_table_locked.insert(pid, PidEntry::HelperExited(code));
-                    return Ok(());
+                    return Ok(None);
                 }
                 PidEntry::HelperExited(_) => {
                     // TODO is this sensibel? How do we handle this?
                     error!("Pid exited that was already saved as exited");
-                    return Ok(());
+                    return Ok(None);
                 }
                 PidEntry::ServiceExited(_) => {
                     // TODO is this sensibel? How do we handle this?
                     error!("Pid exited that was already saved as exited");
-                    return Ok(());
+                    return Ok(None);
                 }
             },
             None => {
@@ -58,7 +77,7 @@ pub fn service_exit_handler(
                     "All processes spawned by rustysd have a pid entry. This did not: {}. Probably a rerooted orphan that got killed.",
                     pid
                 );
-                return Ok(());
+                return Ok(None);
             }
         }
     }
@@ -102,8 +121,10 @@ pub fn service_exit_handler(
         if let Specific::Service(srvc) = &unit.specific {
             if srvc.conf.srcv_type == ServiceType::OneShot {
                 let mut_state = &mut *srvc.state.write().unwrap();
-                mut_state.srvc.kill_all_remaining_processes(&unit.id.name);
-                return Ok(());
+                mut_state
+                    .srvc
+                    .kill_all_remaining_processes(&srvc.conf, &unit.id.name);
+                return Ok(None);
             }
         }
     }
@@ -120,11 +141,9 @@ pub fn service_exit_handler(
                 code
             );
 
-            if srvc.conf.restart == ServiceRestart::Always {
-                true
-            } else {
-                false
-            }
+            srvc.conf
+                .exit_status
+                .should_restart(&srvc.conf.restart, &code)
         } else {
             false
         }
@@ -135,11 +154,31 @@ pub fn service_exit_handler(
         let status_locked = &*unit.common.status.read().unwrap();
         if !(status_locked.is_started() || *status_locked == UnitStatus::Starting) {
             trace!("Exit handler ignores exit of service {}. Its status is not 'Started'/'Starting', it is: {:?}", name, *status_locked);
-            return Ok(());
+            return Ok(None);
+        }
+    }
+
+    // a restart might have started a new main process before the exit of the old one was handled here
+    if let Specific::Service(srvc) = &unit.specific {
+        if srvc.state.read().unwrap().srvc.pid != Some(pid) {
+            trace!(
+                "Exit handler ignores exit of pid {}, it is not the main process of service {} anymore",
+                pid,
+                name
+            );
+            return Ok(None);
         }
     }
 
     if restart_unit {
+        if run_info.pending_reload.contains_key(&srvc_id) {
+            trace!(
+                "Stop service {} after it died, it is started again after its new config is applied",
+                name
+            );
+            crate::units::deactivate_unit(&srvc_id, run_info).map_err(|e| format!("{}", e))?;
+            return Ok(Some(srvc_id));
+        }
         trace!("Restart service {} after it died", name);
         crate::units::reactivate_unit(srvc_id, run_info).map_err(|e| format!("{}", e))?;
     } else {
@@ -163,8 +202,9 @@ pub fn service_exit_handler(
             };
             if !retry {
                 res.map_err(|e| format!("{}", e))?;
+                break;
             }
         }
     }
-    Ok(())
+    Ok(None)
 }
@@ -2,6 +2,7 @@ use log::error;
 use log::trace;
 
 use super::start_service::*;
+use crate::log_store::{LogEntry, LogStream};
 use crate::runtime_info::*;
 use crate::units::*;
 
@@ -15,7 +16,9 @@ use std::process::{Command, Stdio};
 /// made a new enum here
 #[derive(Debug)]
 pub enum StdIo {
+    /// Files, terminals, kmsg and filedescriptors from sockets. Everything that is just passed to the service as is.
     File(std::fs::File),
+    /// Read end and write end of a pipe. The read end is read by rustysd and the lines are logged.
     Piped(RawFd, RawFd),
 
     /// just like the regular file but will always point to /dev/null
@@ -51,6 +54,7 @@ pub struct Service {
     pub notifications: Option<UnixDatagram>,
     pub notifications_path: Option<std::path::PathBuf>,
 
+    pub stdin: Option<StdIo>,
     pub stdout: Option<StdIo>,
     pub stderr: Option<StdIo>,
     pub notifications_buffer: String,
@@ -149,6 +153,49 @@ impl std::fmt::Display for ServiceErrorReason {
     }
 }
 
+/// Allocate the uid for a service with DynamicUser=yes and make the user and group names resolvable inside of the service
+fn apply_dynamic_user(
+    exec_config: &mut ExecConfig,
+    dynamic_user: &DynamicUser,
+    run_info: &RuntimeInfo,
+) -> Result<(), String> {
+    let uid = run_info.dynamic_users.get_or_allocate(&dynamic_user.user)?;
+    let (passwd, group) =
+        run_info
+            .dynamic_users
+            .write_user_files(&dynamic_user.user, &dynamic_user.group, uid)?;
+
+    exec_config.user = nix::unistd::Uid::from_raw(uid);
+    exec_config.group = nix::unistd::Gid::from_raw(uid);
+    for (src, dst) in &[(passwd, "/etc/passwd"), (group, "/etc/group")] {
+        exec_config.sandbox.bind_read_only_paths.push(BindPath {
+            src: src.clone(),
+            dst: dst.into(),
+            recursive: false,
+            ignore_missing: false,
+        });
+    }
+    Ok(())
+}
+
+/// The config of the service with the settings that can only be resolved when it is started: the dynamic user and the default resource limits.
+/// None if the config can be used as it is.
+fn resolve_start_config(
+    conf: &ServiceConfig,
+    run_info: &RuntimeInfo,
+) -> Result<Option<ServiceConfig>, String> {
+    let default_limits = &run_info.config.default_limits;
+    if conf.exec_config.dynamic_user.is_none() && default_limits.limits.is_empty() {
+        return Ok(None);
+    }
+    let mut conf = conf.clone();
+    if let Some(dynamic_user) = conf.exec_config.dynamic_user.clone() {
+        apply_dynamic_user(&mut conf.exec_config, &dynamic_user, run_info)?;
+    }
+    conf.exec_config.limits = default_limits.merged_with(&conf.exec_config.limits);
+    Ok(Some(conf))
+}
+
 impl Service {
     pub fn start(
         &mut self,
@@ -172,13 +219,26 @@ impl Service {
         if source.is_socket_activation() || conf.sockets.is_empty() {
             trace!("Start service {}", name);
 
+            let start_conf = resolve_start_config(conf, run_info)
+                .map_err(ServiceErrorReason::PreparingFailed)?;
+            let conf = start_conf.as_ref().unwrap_or(conf);
+            super::create_directories(&conf.exec_config)
+                .map_err(ServiceErrorReason::PreparingFailed)?;
+
             super::prepare_service::prepare_service(
                 self,
                 conf,
                 name,
                 &run_info.config.notification_sockets_dir,
+                &run_info.fd_store.read().unwrap(),
             )
             .map_err(|e| ServiceErrorReason::PreparingFailed(e))?;
+            // fds of the last run that were closed or replaced must not stay registered
+            run_info.reactor.deregister_unit(&id);
+            run_info
+                .reactor
+                .register_service(&id, self)
+                .map_err(ServiceErrorReason::PreparingFailed)?;
             self.run_prestart(conf, id.clone(), name, run_info.clone())
                 .map_err(|prestart_err| {
                     match self.run_poststop(conf, id.clone(), name, run_info.clone()) {
@@ -189,31 +249,47 @@ impl Service {
                         ),
                     }
                 })?;
-            {
-                let mut pid_table_locked = run_info.pid_table.lock().unwrap();
-                // This mainly just forks the process. The waiting (if necessary) is done below
-                // Doing it under the lock of the pid_table prevents races between processes exiting very
-                // fast and inserting the new pid into the pid table
-                start_service(
-                    self,
-                    conf,
-                    name.clone(),
-                    &*run_info.fd_store.read().unwrap(),
-                )
-                .map_err(|e| ServiceErrorReason::StartFailed(e))?;
-                if let Some(new_pid) = self.pid {
-                    pid_table_locked.insert(new_pid, PidEntry::Service(id.clone(), conf.srcv_type));
+            // only oneshot services can have multiple commands, they are run one after the other
+            for (idx, cmdline) in conf.exec.iter().enumerate() {
+                let error_pipe = {
+                    let mut pid_table_locked = run_info.pid_table.lock().unwrap();
+                    // This mainly just forks the process. The waiting (if necessary) is done below
+                    // Doing it under the lock of the pid_table prevents races between processes exiting very
+                    // fast and inserting the new pid into the pid table
+                    let error_pipe = start_service(
+                        self,
+                        conf,
+                        cmdline,
+                        name.clone(),
+                        &*run_info.fd_store.read().unwrap(),
+                    )?;
+                    if let Some(new_pid) = self.pid {
+                        pid_table_locked
+                            .insert(new_pid, PidEntry::Service(id.clone(), conf.srcv_type));
+                    }
+                    error_pipe
+                };
+                if let Err(child_error) = error_pipe.wait_for_exec() {
+                    // the child exited already. Its exit is ignored if the exit handler did not pick it up yet.
+                    if let Some(pid) = self.pid.take() {
+                        run_info.pid_table.lock().unwrap().remove(&pid);
+                    }
+                    self.process_group = None;
+                    return Err(ServiceErrorReason::PreparingFailed(child_error));
                 }
-            }
 
-            super::fork_parent::wait_for_service(self, conf, name, run_info).map_err(
-                |start_err| match self.run_poststop(conf, id.clone(), name, run_info.clone()) {
-                    Ok(_) => ServiceErrorReason::StartFailed(start_err),
-                    Err(poststop_err) => {
-                        ServiceErrorReason::StartAndPoststopFailed(start_err, poststop_err)
-                    }
-                },
-            )?;
+                super::fork_parent::wait_for_service(self, conf, cmdline, name, run_info).map_err(
+                    |start_err| match self.run_poststop(conf, id.clone(), name, run_info.clone()) {
+                        Ok(_) => ServiceErrorReason::StartFailed(start_err),
+                        Err(poststop_err) => {
+                            ServiceErrorReason::StartAndPoststopFailed(start_err, poststop_err)
+                        }
+                    },
+                )?;
+                if idx + 1 < conf.exec.len() {
+                    self.kill_remaining_processes_of_command(conf, name);
+                }
+            }
             self.run_poststart(conf, id.clone(), name, run_info.clone())
                 .map_err(|poststart_err| {
                     match self.run_poststop(conf, id.clone(), name, run_info.clone()) {
@@ -224,6 +300,13 @@ impl Service {
                         ),
                     }
                 })?;
+            if conf.srcv_type == ServiceType::OneShot && !conf.remain_after_exit {
+                // the service is done already, clean up like it was stopped
+                self.kill_all_remaining_processes(conf, name);
+                self.run_poststop(conf, id.clone(), name, run_info)
+                    .map_err(ServiceErrorReason::PoststopFailed)?;
+                super::remove_runtime_directories(&conf.exec_config, name, false);
+            }
             Ok(StartResult::Started)
         } else {
             trace!(
@@ -234,18 +317,58 @@ impl Service {
         }
     }
 
-    pub fn kill_all_remaining_processes(&mut self, name: &str) {
+    /// Each command of a oneshot service gets its own process group, but only the one of the last command is kept. So what is left of a
+    /// finished command is killed before the next one starts, following the same kill settings as when the service is stopped.
+    fn kill_remaining_processes_of_command(&mut self, conf: &ServiceConfig, name: &str) {
+        let proc_group = match self.process_group.take() {
+            Some(proc_group) => proc_group,
+            None => return,
+        };
+        if !conf.kill.send_sigkill
+            || conf.kill.mode == KillMode::Process
+            || conf.kill.mode == KillMode::None
+        {
+            return;
+        }
+        match nix::sys::signal::kill(proc_group, conf.kill.final_signal) {
+            Ok(_) | Err(nix::Error::ESRCH) => {}
+            Err(e) => error!(
+                "Error killing the remaining processes of a command of service {}: {}",
+                name, e
+            ),
+        }
+    }
+
+    /// Kill everything that is left after stopping. With KillMode=process or none, or SendSIGKILL=no, the remaining processes are left alone.
+    pub fn kill_all_remaining_processes(&mut self, conf: &ServiceConfig, name: &str) {
+        if !conf.kill.send_sigkill
+            || conf.kill.mode == KillMode::Process
+            || conf.kill.mode == KillMode::None
+        {
+            trace!(
+                "Not killing remaining processes of {} because of its kill settings",
+                name
+            );
+            if let Err(e) = super::kill_os_specific::remove_cgroup(self) {
+                error!("Error removing the cgroup of service {}: {}", name, e);
+            }
+            return;
+        }
+        let sig = conf.kill.final_signal;
         trace!("Kill all process for {}", name);
         if let Some(proc_group) = self.process_group {
             // TODO handle these errors
-            match nix::sys::signal::kill(proc_group, nix::sys::signal::Signal::SIGKILL) {
+            match nix::sys::signal::kill(proc_group, sig) {
                 Ok(_) => trace!("Success killing process group for service {}", name,),
+                Err(nix::Error::ESRCH) => {
+                    trace!("No processes left in process group for service {}", name,)
+                }
                 Err(e) => error!("Error killing process group for service {}: {}", name, e,),
             }
         } else {
             trace!("Tried to kill service that didn't have a process-group. This might have resulted in orphan processes.");
         }
-        match super::kill_os_specific::kill(self, nix::sys::signal::Signal::SIGKILL) {
+        match super::kill_os_specific::kill(self, sig) {
             Ok(_) => trace!("Success killing process os specificly for service {}", name,),
             Err(e) => error!(
                 "Error killing process os specificly for service {}: {}",
@@ -260,17 +383,25 @@ impl Service {
         id: UnitId,
         name: &str,
         run_info: &RuntimeInfo,
+        restart: bool,
     ) -> Result<(), RunCmdError> {
-        self.run_stop_cmd(conf, id, name, run_info.clone())
+        let res = self.run_stop_cmd(conf, id, name, run_info.clone());
+        if conf.srcv_type != ServiceType::OneShot {
+            let timeout = self.get_stop_timeout(conf);
+            super::stop_service::stop_processes(self, conf, name, restart, timeout);
+        }
+        res
     }
+    /// Stop the service. If it is going to be started again right after, RestartKillSignal= is used instead of KillSignal=.
     pub fn kill(
         &mut self,
         conf: &ServiceConfig,
         id: UnitId,
         name: &str,
         run_info: &RuntimeInfo,
+        restart: bool,
     ) -> Result<(), ServiceErrorReason> {
-        self.stop(conf, id.clone(), name, run_info)
+        self.stop(conf, id.clone(), name, run_info, restart)
             .map_err(|stop_err| {
                 trace!(
                     "Stop process failed with: {:?} for service: {}. Running poststop commands",
@@ -339,6 +470,7 @@ impl Service {
         name: &str,
         timeout: Option<std::time::Duration>,
         run_info: &RuntimeInfo,
+        exit_status: &ExitStatusConfig,
     ) -> Result<(), RunCmdError> {
         let mut cmd = Command::new(&cmdline.cmd);
         for part in &cmdline.args {
@@ -365,6 +497,36 @@ impl Service {
         cmd.stdout(stdout);
         cmd.stderr(stderr);
         cmd.stdin(Stdio::null());
+        {
+            // helper commands get the same resource limits, scheduling, root and working directory as the main process.
+            // The sandbox settings are not applied to them, see doc/Sandboxing.md
+            let unit = run_info.unit_table.get(&id).unwrap();
+            if let Specific::Service(srvc) = &unit.specific {
+                let exec_config = &srvc.conf.exec_config;
+                // the pre_exec hook runs between fork and exec and must not allocate, everything is prepared here
+                let limits = crate::platform::PreparedResourceLimits::new(
+                    &run_info
+                        .config
+                        .default_limits
+                        .merged_with(&exec_config.limits),
+                )
+                .map_err(|e| RunCmdError::SpawnError(cmdline.to_string(), e))?;
+                let process = crate::platform::PreparedProcessSettings::new(
+                    &exec_config.process,
+                    exec_config.working_directory.as_ref(),
+                )
+                .map_err(|e| RunCmdError::SpawnError(cmdline.to_string(), e))?;
+                use std::os::unix::process::CommandExt;
+                unsafe {
+                    cmd.pre_exec(move || {
+                        limits
+                            .apply()
+                            .and_then(|()| process.apply())
+                            .map_err(|e| std::io::Error::from_raw_os_error(e as i32))
+                    });
+                }
+            }
+        }
         trace!("Run {:?} for service: {}", cmdline, name);
         let spawn_result = {
             let mut pid_table_locked = run_info.pid_table.lock().unwrap();
@@ -390,7 +552,7 @@ impl Service {
                         ));
                     }
                     WaitResult::InTime(Ok(exitstatus)) => {
-                        if exitstatus.success() {
+                        if exit_status.is_success(&exitstatus) {
                             trace!("success running {:?} for service: {}", cmdline, name);
                             Ok(())
                         } else {
@@ -425,18 +587,24 @@ impl Service {
                 {
                     let unit = run_info.unit_table.get(&id).unwrap();
                     let status = &*unit.common.status.read().unwrap();
+                    let conf = if let Specific::Service(srvc) = &unit.specific {
+                        &srvc.conf.exec_config
+                    } else {
+                        unreachable!("Helper commands are only run for services");
+                    };
+                    let pid = Some(nix::unistd::Pid::from_raw(child.id() as i32));
                     use std::io::Read;
                     if let Some(stream) = &mut child.stderr {
                         let mut buf = Vec::new();
                         let _bytes = stream.read_to_end(&mut buf).unwrap();
                         self.stderr_buffer.extend(buf);
-                        self.log_stderr_lines(name, status).unwrap();
+                        self.log_stderr_lines(name, status, pid, conf, run_info);
                     }
                     if let Some(stream) = &mut child.stdout {
                         let mut buf = Vec::new();
                         let _bytes = stream.read_to_end(&mut buf).unwrap();
                         self.stdout_buffer.extend(buf);
-                        self.log_stdout_lines(name, status).unwrap();
+                        self.log_stdout_lines(name, status, pid, conf, run_info);
                     }
                 }
 
@@ -461,9 +629,17 @@ impl Service {
         name: &str,
         timeout: Option<std::time::Duration>,
         run_info: &RuntimeInfo,
+        exit_status: &ExitStatusConfig,
     ) -> Result<(), RunCmdError> {
         for cmd in cmds {
-            self.run_cmd(cmd, id.clone(), name, timeout, run_info.clone())?;
+            self.run_cmd(
+                cmd,
+                id.clone(),
+                name,
+                timeout,
+                run_info.clone(),
+                exit_status,
+            )?;
         }
         Ok(())
     }
@@ -480,7 +656,14 @@ impl Service {
         }
         let timeout = self.get_stop_timeout(conf);
         let cmds = conf.stop.clone();
-        self.run_all_cmds(&cmds, id, name, timeout, run_info.clone())
+        self.run_all_cmds(
+            &cmds,
+            id,
+            name,
+            timeout,
+            run_info.clone(),
+            &conf.exit_status,
+        )
     }
     fn run_prestart(
         &mut self,
@@ -494,7 +677,14 @@ impl Service {
         }
         let timeout = self.get_start_timeout(conf);
         let cmds = conf.startpre.clone();
-        self.run_all_cmds(&cmds, id, name, timeout, run_info.clone())
+        self.run_all_cmds(
+            &cmds,
+            id,
+            name,
+            timeout,
+            run_info.clone(),
+            &conf.exit_status,
+        )
     }
     fn run_poststart(
         &mut self,
@@ -508,7 +698,14 @@ impl Service {
         }
         let timeout = self.get_start_timeout(conf);
         let cmds = conf.startpost.clone();
-        self.run_all_cmds(&cmds, id, name, timeout, run_info.clone())
+        self.run_all_cmds(
+            &cmds,
+            id,
+            name,
+            timeout,
+            run_info.clone(),
+            &conf.exit_status,
+        )
     }
     fn run_poststop(
         &mut self,
@@ -520,70 +717,132 @@ impl Service {
         trace!("Run poststop for {}", name);
         let timeout = self.get_stop_timeout(conf);
         let cmds = conf.stoppost.clone();
-        let res = self.run_all_cmds(&cmds, id, name, timeout, run_info.clone());
+        let res = self.run_all_cmds(
+            &cmds,
+            id,
+            name,
+            timeout,
+            run_info.clone(),
+            &conf.exit_status,
+        );
 
         if conf.srcv_type != ServiceType::OneShot {
             // already happened when the oneshot process exited in the exit handler
-            self.kill_all_remaining_processes(name);
+            self.kill_all_remaining_processes(conf, name);
         }
         self.pid = None;
         self.process_group = None;
         res
     }
 
-    pub fn log_stdout_lines(&mut self, name: &str, status: &UnitStatus) -> std::io::Result<()> {
-        let mut prefix = String::new();
-        prefix.push('[');
-        prefix.push_str(name);
-        prefix.push(']');
-        prefix.push_str(&format!("[{:?}]", *status));
-        prefix.push(' ');
-        let mut outbuf: Vec<u8> = Vec::new();
-        while self.stdout_buffer.contains(&b'\n') {
-            let split_pos = self.stdout_buffer.iter().position(|r| *r == b'\n').unwrap();
-            let (line, lines) = self.stdout_buffer.split_at(split_pos + 1);
-
-            // drop \n at the end of the line
-            let line = &line[0..line.len() - 1].to_vec();
-            self.stdout_buffer = lines.to_vec();
-            if line.is_empty() {
-                continue;
-            }
-            outbuf.clear();
-            outbuf.extend(prefix.as_bytes());
-            outbuf.extend(line);
-            outbuf.push(b'\n');
-            std::io::stdout().write_all(&outbuf)?;
+    pub fn log_stdout_lines(
+        &mut self,
+        name: &str,
+        status: &UnitStatus,
+        pid: Option<nix::unistd::Pid>,
+        conf: &ExecConfig,
+        run_info: &RuntimeInfo,
+    ) {
+        for line in take_lines(&mut self.stdout_buffer) {
+            log_line(name, status, LogStream::Stdout, pid, conf, run_info, &line);
         }
-        Ok(())
     }
-    pub fn log_stderr_lines(&mut self, name: &str, status: &UnitStatus) -> std::io::Result<()> {
-        let mut prefix = String::new();
-        prefix.push('[');
-        prefix.push_str(&name);
-        prefix.push(']');
-        prefix.push_str(&format!("[{:?}]", *status));
-        prefix.push_str("[STDERR]");
-        prefix.push(' ');
-
-        let mut outbuf: Vec<u8> = Vec::new();
-        while self.stderr_buffer.contains(&b'\n') {
-            let split_pos = self.stderr_buffer.iter().position(|r| *r == b'\n').unwrap();
-            let (line, lines) = self.stderr_buffer.split_at(split_pos + 1);
-
-            // drop \n at the end of the line
-            let line = &line[0..line.len() - 1].to_vec();
-            self.stderr_buffer = lines.to_vec();
-            if line.is_empty() {
-                continue;
-            }
-            outbuf.clear();
-            outbuf.extend(prefix.as_bytes());
-            outbuf.extend(line);
-            outbuf.push(b'\n');
-            std::io::stderr().write_all(&outbuf).unwrap();
+    pub fn log_stderr_lines(
+        &mut self,
+        name: &str,
+        status: &UnitStatus,
+        pid: Option<nix::unistd::Pid>,
+        conf: &ExecConfig,
+        run_info: &RuntimeInfo,
+    ) {
+        for line in take_lines(&mut self.stderr_buffer) {
+            log_line(name, status, LogStream::Stderr, pid, conf, run_info, &line);
         }
-        Ok(())
+    }
+}
+
+/// Remove all complete lines from the buffer. Empty lines are dropped.
+fn take_lines(buffer: &mut Vec<u8>) -> Vec<Vec<u8>> {
+    let mut lines = Vec::new();
+    while let Some(split_pos) = buffer.iter().position(|r| *r == b'\n') {
+        let rest = buffer.split_off(split_pos + 1);
+        // drop \n at the end of the line
+        buffer.pop();
+        let line = std::mem::replace(buffer, rest);
+        if !line.is_empty() {
+            lines.push(line);
+        }
+    }
+    lines
+}
+
+/// Log a line of output of a service with the priority from its prefix (or the SyslogLevel=), put it into the log store (if logging to disk is enabled)
+/// and forward it to syslog (if that is enabled)
+fn log_line(
+    name: &str,
+    status: &UnitStatus,
+    stream: LogStream,
+    pid: Option<nix::unistd::Pid>,
+    conf: &ExecConfig,
+    run_info: &RuntimeInfo,
+    line: &[u8],
+) {
+    let (priority, facility, line) = match crate::log_store::split_priority_prefix(line) {
+        Some((priority, facility, rest)) if conf.syslog_level_prefix => (priority, facility, rest),
+        _ => (conf.syslog_level, None, line),
+    };
+    if let Some(max) = conf.log_level_max {
+        if priority > max {
+            return;
+        }
+    }
+    let identifier = conf.syslog_identifier.as_deref().unwrap_or(name);
+    let message = String::from_utf8_lossy(line).into_owned();
+
+    if crate::logging::print_service_output() {
+        let line = format!("[{}][{:?}] {}\n", name, status, message);
+        let res = if let LogStream::Stderr = stream {
+            std::io::stderr().write_all(line.as_bytes())
+        } else {
+            std::io::stdout().write_all(line.as_bytes())
+        };
+        if let Err(e) = res {
+            error!("Could not print output of {}: {}", name, e);
+        }
+    }
+
+    let target = format!(
+        "{}{}",
+        crate::logging::SERVICE_OUTPUT_TARGET_PREFIX,
+        identifier
+    );
+    let stream_marker = if let LogStream::Stderr = stream {
+        "[STDERR]"
+    } else {
+        ""
+    };
+    log::log!(
+        target: &target,
+        crate::log_store::priority_to_level(priority),
+        "[{:?}]{} {}",
+        status,
+        stream_marker,
+        message
+    );
+
+    if run_info.log_store.is_none() && run_info.syslog.is_none() {
+        return;
+    }
+    let mut entry = LogEntry::new(name, stream, pid.map(|pid| pid.as_raw()), priority, message);
+    entry.identifier = identifier.to_owned();
+    entry.facility = facility.unwrap_or(conf.syslog_facility);
+    if let Some(log_store) = &run_info.log_store {
+        if let Err(e) = log_store.append(&entry) {
+            error!("Could not store output of {}: {}", name, e);
+        }
+    }
+    if let Some(syslog) = &run_info.syslog {
+        syslog.send(&entry);
     }
 }
 
@@ -4,35 +4,40 @@ use super::fork_child;
 use crate::fd_store::FDStore;
 use crate::services::RunCmdError;
 use crate::services::Service;
+use crate::services::ServiceErrorReason;
+use crate::units::Commandline;
 use crate::units::ServiceConfig;
+use std::io::Read;
+use std::os::unix::io::FromRawFd;
 
 fn start_service_with_filedescriptors(
     srvc: &mut Service,
     conf: &ServiceConfig,
+    cmdline: &Commandline,
     name: &str,
     fd_store: &FDStore,
-) -> Result<(), RunCmdError> {
+) -> Result<ExecErrorPipe, ServiceErrorReason> {
     // check if executable even exists
-    let cmd = std::path::PathBuf::from(&conf.exec.cmd);
+    let cmd = std::path::PathBuf::from(&cmdline.cmd);
     if !cmd.exists() {
         error!(
             "The service {} specified an executable that does not exist: {:?}",
-            name, &conf.exec.cmd
+            name, &cmdline.cmd
         );
-        return Err(RunCmdError::SpawnError(
-            conf.exec.cmd.clone(),
+        return Err(ServiceErrorReason::StartFailed(RunCmdError::SpawnError(
+            cmdline.cmd.clone(),
             format!("Executable does not exist"),
-        ));
+        )));
     }
     if !cmd.is_file() {
         error!(
             "The service {} specified an executable that is not a file: {:?}",
             name, &cmd
         );
-        return Err(RunCmdError::SpawnError(
-            conf.exec.cmd.clone(),
+        return Err(ServiceErrorReason::StartFailed(RunCmdError::SpawnError(
+            cmdline.cmd.clone(),
             format!("Executable does not exist (is a directory)"),
-        ));
+        )));
     }
 
     // 1. fork
@@ -46,14 +51,27 @@ fn start_service_with_filedescriptors(
         if let Some(p) = &srvc.notifications_path {
             p.to_str().unwrap().to_owned()
         } else {
-            return Err(RunCmdError::Generic(format!(
-                "Tried to start service: {} without a notifications path",
-                name,
+            return Err(ServiceErrorReason::StartFailed(RunCmdError::Generic(
+                format!(
+                    "Tried to start service: {} without a notifications path",
+                    name,
+                ),
             )));
         }
     };
 
-    super::fork_os_specific::pre_fork_os_specific(srvc).map_err(|e| RunCmdError::Generic(e))?;
+    super::fork_os_specific::pre_fork_os_specific(srvc)
+        .map_err(|e| ServiceErrorReason::StartFailed(RunCmdError::Generic(e)))?;
+
+    // The child reports errors while setting itself up through this pipe. On a successful exec the write end is closed
+    // because of the O_CLOEXEC, so the parent reads nothing.
+    let (error_read, error_write) =
+        nix::unistd::pipe2(nix::fcntl::OFlag::O_CLOEXEC).map_err(|e| {
+            ServiceErrorReason::StartFailed(RunCmdError::Generic(format!(
+                "Could not create error pipe: {}",
+                e
+            )))
+        })?;
 
     // make sure we have the lock that the child will need
     match unsafe { nix::unistd::fork() } {
@@ -62,41 +80,49 @@ fn start_service_with_filedescriptors(
             srvc.process_group = Some(nix::unistd::Pid::from_raw(-child.as_raw()));
         }
         Ok(nix::unistd::ForkResult::Child) => {
-            let stdout = {
-                if let Some(stdio) = &srvc.stdout {
-                    stdio.write_fd()
-                } else {
-                    unreachable!();
-                }
-            };
-            let stderr = {
-                if let Some(stdio) = &srvc.stderr {
-                    stdio.write_fd()
-                } else {
-                    unreachable!();
-                }
-            };
+            let _ = nix::unistd::close(error_read);
             fork_child::after_fork_child(
                 srvc,
                 conf,
+                cmdline,
                 &name,
                 fd_store,
                 &notifications_path,
-                stdout,
-                stderr,
+                error_write,
             );
         }
         Err(e) => error!("Fork for service: {} failed with: {}", name, e),
     }
-    Ok(())
+    let _ = nix::unistd::close(error_write);
+
+    Ok(ExecErrorPipe(unsafe {
+        std::fs::File::from_raw_fd(error_read)
+    }))
+}
+
+/// The read end of the pipe the child reports errors through while setting itself up. Setting up might take a while (e.g. for the
+/// mount namespace), so this should be read after the pid_table is unlocked again.
+pub struct ExecErrorPipe(std::fs::File);
+
+impl ExecErrorPipe {
+    /// Blocks until the child either did the exec or reported an error. After an error the child has exited already.
+    pub fn wait_for_exec(mut self) -> Result<(), String> {
+        let mut child_error = String::new();
+        let _ = self.0.read_to_string(&mut child_error);
+        if child_error.is_empty() {
+            Ok(())
+        } else {
+            Err(child_error)
+        }
+    }
 }
 
 pub fn start_service(
     srvc: &mut Service,
     conf: &ServiceConfig,
+    cmdline: &Commandline,
     name: &str,
     fd_store: &FDStore,
-) -> Result<(), super::RunCmdError> {
-    start_service_with_filedescriptors(srvc, conf, name, fd_store)?;
-    Ok(())
+) -> Result<ExecErrorPipe, ServiceErrorReason> {
+    start_service_with_filedescriptors(srvc, conf, cmdline, name, fd_store)
 }
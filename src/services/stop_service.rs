@@ -0,0 +1,139 @@
+//! Stopping the processes of a service according to its KillMode=. The KillSignal= (or RestartKillSignal=) is sent first,
+//! if processes remain after the stop timeout they get the FinalKillSignal=, if SendSIGKILL= is enabled.
+
+use log::trace;
+use log::warn;
+
+use super::kill_os_specific;
+use crate::services::Service;
+use crate::units::{KillMode, ServiceConfig};
+use nix::sys::signal::Signal;
+use nix::unistd::Pid;
+
+fn send(pid: Pid, sig: Signal, name: &str) {
+    match nix::sys::signal::kill(pid, sig) {
+        Ok(()) => {}
+        // the process(es) exited already
+        Err(nix::Error::ESRCH) => {}
+        Err(e) => warn!(
+            "Error sending {} to {} of service {}: {}",
+            sig, pid, name, e
+        ),
+    }
+}
+
+fn is_alive(pid: Pid) -> bool {
+    !matches!(nix::sys::signal::kill(pid, None), Err(nix::Error::ESRCH))
+}
+
+fn signal_main(srvc: &Service, sig: Signal, name: &str) {
+    if let Some(pid) = srvc.pid {
+        send(pid, sig, name);
+    }
+}
+
+fn signal_all(srvc: &Service, sig: Signal, name: &str) {
+    if let Some(proc_group) = srvc.process_group {
+        send(proc_group, sig, name);
+    }
+    if let Err(e) = kill_os_specific::signal_all(srvc, sig) {
+        warn!(
+            "Error sending {} to the cgroup of service {}: {}",
+            sig, name, e
+        );
+    }
+}
+
+fn main_alive(srvc: &Service) -> bool {
+    srvc.pid.map(is_alive).unwrap_or(false)
+}
+
+fn any_alive(srvc: &Service) -> bool {
+    srvc.process_group.map(is_alive).unwrap_or(false)
+        || main_alive(srvc)
+        || kill_os_specific::has_remaining_processes(srvc)
+}
+
+/// Poll until the processes are gone. Returns false if the timeout was hit first.
+fn wait_until_gone(srvc: &Service, only_main: bool, timeout: Option<std::time::Duration>) -> bool {
+    let start = std::time::Instant::now();
+    loop {
+        let alive = if only_main {
+            main_alive(srvc)
+        } else {
+            any_alive(srvc)
+        };
+        if !alive {
+            return true;
+        }
+        if let Some(timeout) = timeout {
+            if start.elapsed() >= timeout {
+                return false;
+            }
+        }
+        std::thread::sleep(std::time::Duration::from_millis(10));
+    }
+}
+
+/// Signal the processes of the service and wait for them to exit. This runs after ExecStop= and before ExecStopPost=.
+pub fn stop_processes(
+    srvc: &Service,
+    conf: &ServiceConfig,
+    name: &str,
+    restart: bool,
+    timeout: Option<std::time::Duration>,
+) {
+    let kill = &conf.kill;
+    if kill.mode == KillMode::None {
+        trace!("KillMode=none, not signaling the processes of {}", name);
+        return;
+    }
+    let sig = if restart {
+        kill.restart_signal.unwrap_or(kill.signal)
+    } else {
+        kill.signal
+    };
+    let only_main = kill.mode != KillMode::ControlGroup;
+
+    trace!("Send {} to service {} with {:?}", sig, name, kill.mode);
+    let signal = if only_main { signal_main } else { signal_all };
+    signal(srvc, sig, name);
+    if kill.send_sighup {
+        signal(srvc, Signal::SIGHUP, name);
+    }
+    // stopped processes would not act on the signal otherwise
+    if sig != Signal::SIGKILL && sig != Signal::SIGCONT {
+        signal(srvc, Signal::SIGCONT, name);
+    }
+
+    let exited = wait_until_gone(srvc, only_main, timeout);
+    if !kill.send_sigkill {
+        if !exited {
+            warn!(
+                "Processes of service {} did not exit after {} and SendSIGKILL= is disabled",
+                name, sig
+            );
+        }
+        return;
+    }
+    match kill.mode {
+        KillMode::Process => {
+            if !exited {
+                trace!("Send {} to main process of {}", kill.final_signal, name);
+                signal_main(srvc, kill.final_signal, name);
+            }
+        }
+        // with mixed the remaining processes get the final signal right after the main process is gone
+        KillMode::Mixed | KillMode::ControlGroup => {
+            if !exited || any_alive(srvc) {
+                trace!(
+                    "Send {} to remaining processes of {}",
+                    kill.final_signal,
+                    name
+                );
+                signal_all(srvc, kill.final_signal, name);
+            }
+        }
+        KillMode::None => unreachable!(),
+    }
+}
@@ -6,43 +6,6 @@ use log::warn;
 use crate::runtime_info::*;
 use crate::units::*;
 
-fn get_next_service_to_shutdown(unit_table: &UnitTable) -> Option<UnitId> {
-    for (_, unit) in unit_table.iter() {
-        let status = &unit.common.status;
-        {
-            let status_locked = status.read().unwrap();
-            if !(*status_locked).is_started() {
-                continue;
-            }
-        }
-
-        let kill_before = unit
-            .common
-            .dependencies
-            .before
-            .iter()
-            .cloned()
-            .filter(|next_id| {
-                let unit = unit_table.get(next_id).unwrap();
-                let status = &unit.common.status;
-                let status_locked = status.read().unwrap();
-                status_locked.is_started()
-            })
-            .collect::<Vec<_>>();
-        if kill_before.is_empty() {
-            trace!("Chose unit: {}", unit.id.name);
-            return Some(unit.id.clone());
-        } else {
-            trace!(
-                "Dont kill service {} yet. These Units depend on it: {:?}",
-                unit.id.name,
-                kill_before
-            );
-        }
-    }
-    None
-}
-
 fn shutdown_unit(shutdown_id: &UnitId, run_info: &RuntimeInfo) {
     let unit = run_info.unit_table.get(shutdown_id).unwrap();
     {
@@ -53,16 +16,25 @@ fn shutdown_unit(shutdown_id: &UnitId, run_info: &RuntimeInfo) {
     match &unit.specific {
         Specific::Service(specific) => {
             let mut_state = &mut *specific.state.write().unwrap();
-            let kill_res =
-                mut_state
-                    .srvc
-                    .kill(&specific.conf, unit.id.clone(), &unit.id.name, run_info);
+            let kill_res = mut_state.srvc.kill(
+                &specific.conf,
+                unit.id.clone(),
+                &unit.id.name,
+                run_info,
+                false,
+            );
             match kill_res {
                 Ok(()) => {
                     trace!("Killed service unit: {}", unit.id.name);
                 }
                 Err(e) => error!("{}", e),
             }
+            crate::services::remove_runtime_directories(
+                &specific.conf.exec_config,
+                &unit.id.name,
+                false,
+            );
+            run_info.reactor.deregister_unit(&unit.id);
             if let Some(datagram) = &mut_state.srvc.notifications {
                 match datagram.shutdown(std::net::Shutdown::Both) {
                     Ok(()) => {
@@ -146,14 +118,14 @@ pub fn shutdown_sequence(run_info: ArcMutRuntimeInfo) {
         let run_info_locked = &*run_info_lock;
 
         trace!("Kill all units");
-        loop {
-            let id = {
-                if let Some(id) = get_next_service_to_shutdown(&run_info_locked.unit_table) {
-                    id
-                } else {
-                    break;
-                }
-            };
+        let started = run_info_locked
+            .unit_table
+            .values()
+            .filter(|unit| unit.common.status.read().unwrap().is_started())
+            .map(|unit| unit.id.clone())
+            .collect();
+        for id in stop_order(started, &run_info_locked.unit_table) {
+            trace!("Chose unit: {}", id.name);
             shutdown_unit(&id, run_info_locked);
         }
         trace!("Killed all units");
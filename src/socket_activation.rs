@@ -1,143 +1,207 @@
 //! Wait for sockets to activate their respective services
 use log::error;
 use log::trace;
+use log::warn;
 
 use crate::runtime_info::*;
 use crate::units::*;
 
-pub fn start_socketactivation_thread(run_info: ArcMutRuntimeInfo) {
-    std::thread::spawn(move || loop {
-        let wait_result = wait_for_socket(run_info.clone());
-        match wait_result {
-            Ok(ids) => {
-                let run_info = run_info.read().unwrap();
-                let unit_table = &run_info.unit_table;
-                for socket_id in ids {
-                    {
-                        // search the service this socket belongs to.
-                        // Note that this differs from systemd behaviour where one socket may belong to multiple services
-                        let mut srvc_unit = None;
-                        for unit in unit_table.values() {
-                            if let crate::units::Specific::Service(specific) = &unit.specific {
-                                if specific.has_socket(&socket_id.name) {
-                                    srvc_unit = Some(unit);
-                                    trace!("Start service {} by socket activation", unit.id.name);
-                                    break;
-                                }
-                            }
-                        }
+/// Activate the services belonging to these sockets. The sockets must have been taken out of the reactor with take_readable_sockets
+pub fn activate_services(ids: Vec<UnitId>, run_info: ArcMutRuntimeInfo) {
+    let run_info = run_info.read().unwrap();
+    let unit_table = &run_info.unit_table;
+    for socket_id in ids {
+        {
+            // search the service this socket belongs to.
+            // Note that this differs from systemd behaviour where one socket may belong to multiple services
+            let mut srvc_unit = None;
+            for unit in unit_table.values() {
+                if let crate::units::Specific::Service(specific) = &unit.specific {
+                    if specific.has_socket(&socket_id.name) {
+                        srvc_unit = Some(unit);
+                        trace!("Start service {} by socket activation", unit.id.name);
+                        break;
+                    }
+                }
+            }
 
-                        // mark socket as activated, removing it from the set of
-                        // fds rustysd is actively listening on
-                        let sock_unit = unit_table.get(&socket_id).unwrap();
-                        if let Specific::Socket(specific) = &sock_unit.specific {
-                            let mut_state = &mut *specific.state.write().unwrap();
-                            mut_state.sock.activated = true;
-                        }
-                        if srvc_unit.is_none() {
-                            error!(
-                                "Socket unit {:?} activated, but the service could not be found",
-                                socket_id
+            let sock_unit = unit_table.get(&socket_id).unwrap();
+            if srvc_unit.is_none() {
+                error!(
+                    "Socket unit {:?} activated, but the service could not be found",
+                    socket_id
+                );
+            }
+            if let Some(srvc_unit) = srvc_unit {
+                let srvc_status = {
+                    let status_locked = &*srvc_unit.common.status.read().unwrap();
+                    status_locked.clone()
+                };
+
+                if srvc_status != UnitStatus::Started(StatusStarted::WaitingForSocket) {
+                    // This should not happen too often because the sockets of a service
+                    // should only be listened on if the service is currently waiting on socket activation.
+                    // The socket stays marked as activated and will be listened on again once the
+                    // service goes back into WaitingForSocket (e.g. because it got restarted)
+                    trace!(
+                        "Ignore socket activation. Service has status: {:?}",
+                        srvc_status
+                    );
+                } else if !count_trigger(sock_unit, &run_info) {
+                    // the socket has been closed, nothing to activate anymore
+                } else {
+                    // the service unit gets activated
+                    match crate::units::activate_unit(
+                        srvc_unit.id.clone(),
+                        &*run_info,
+                        ActivationSource::SocketActivation,
+                    ) {
+                        Ok(_) => {
+                            trace!(
+                                "New status after socket activation: {:?}",
+                                *unit_table
+                                    .get(&srvc_unit.id)
+                                    .unwrap()
+                                    .common
+                                    .status
+                                    .read()
+                                    .unwrap()
                             );
                         }
-                        if let Some(srvc_unit) = srvc_unit {
-                            let srvc_status = {
-                                let status_locked = &*srvc_unit.common.status.read().unwrap();
-                                status_locked.clone()
-                            };
-
-                            if srvc_status != UnitStatus::Started(StatusStarted::WaitingForSocket) {
-                                // This should not happen too often because the sockets of a service
-                                // should only be listened on if the service is currently waiting on socket activation
-                                trace!(
-                                    "Ignore socket activation. Service has status: {:?}",
-                                    srvc_status
-                                );
-                            } else {
-                                // the service unit gets activated
-                                match crate::units::activate_unit(
-                                    srvc_unit.id.clone(),
-                                    &*run_info,
-                                    ActivationSource::SocketActivation,
-                                ) {
-                                    Ok(_) => {
-                                        trace!(
-                                            "New status after socket activation: {:?}",
-                                            *unit_table
-                                                .get(&srvc_unit.id)
-                                                .unwrap()
-                                                .common
-                                                .status
-                                                .read()
-                                                .unwrap()
-                                        );
-                                    }
-                                    Err(e) => {
-                                        format!(
-                                                "Error while starting service from socket activation: {}",
-                                                e
-                                            );
-                                    }
-                                }
-                            }
+                        Err(e) => {
+                            error!("Error while starting service from socket activation: {}", e);
                         }
                     }
                 }
             }
-            Err(e) => {
-                error!("Error in socket activation loop: {}", e);
-                break;
-            }
         }
-    });
+    }
 }
 
-pub fn wait_for_socket(run_info: ArcMutRuntimeInfo) -> Result<Vec<UnitId>, String> {
-    let eventfd = { run_info.read().unwrap().socket_activation_eventfd };
-    let (mut fdset, fd_to_sock_id) = {
-        let run_info_locked = &*run_info.read().unwrap();
+/// Count a trigger of the socket. If the TriggerLimit was hit the socket gets closed and put into a failed state.
+///
+/// Returns whether the service may be started
+fn count_trigger(sock_unit: &Unit, run_info: &RuntimeInfo) -> bool {
+    if let Specific::Socket(specific) = &sock_unit.specific {
+        let mut_state = &mut *specific.state.write().unwrap();
+        if mut_state.sock.triggers.count(&specific.conf.trigger_limit) {
+            return true;
+        }
+        error!(
+            "Socket {} hit its trigger limit ({:?}). Closing the socket.",
+            sock_unit.id.name, specific.conf.trigger_limit
+        );
+        run_info.reactor.deregister_unit(&sock_unit.id);
+        // the fd store is locked after the state of the unit, like everywhere else
+        if let Err(e) = mut_state.sock.close_all(
+            &specific.conf,
+            sock_unit.id.name.clone(),
+            &mut run_info.fd_store.write().unwrap(),
+        ) {
+            error!(
+                "Error while closing sockets of {}: {}",
+                sock_unit.id.name, e
+            );
+        }
+        let mut status = sock_unit.common.status.write().unwrap();
+        *status = UnitStatus::Stopped(
+            StatusStopped::StoppedUnexpected,
+            vec![UnitOperationErrorReason::SocketTriggerLimitHit],
+        );
+        false
+    } else {
+        true
+    }
+}
 
-        let fd_to_sock_id = run_info_locked.fd_store.read().unwrap().global_fds_to_ids();
-        let mut fdset = nix::sys::select::FdSet::new();
-        {
-            let unit_table_locked = &run_info_locked.unit_table;
-            for (fd, id) in &fd_to_sock_id {
-                let unit = unit_table_locked.get(id).unwrap();
-                if let Specific::Socket(specific) = &unit.specific {
-                    let mut_state = &*specific.state.read().unwrap();
-                    if !mut_state.sock.activated {
-                        fdset.insert(*fd);
-                    }
-                }
-            }
-            fdset.insert(eventfd.read_end());
+/// Count a wakeup caused by the socket. If the PollLimit was hit the socket is ignored
+/// for the length of the PollLimitIntervalSec.
+///
+/// Returns whether the wakeup should be handled
+fn count_poll(sock_unit: &Unit, run_info: &RuntimeInfo) -> bool {
+    if let Specific::Socket(specific) = &sock_unit.specific {
+        let mut_state = &mut *specific.state.write().unwrap();
+        if mut_state.sock.polls.count(&specific.conf.poll_limit) {
+            return true;
         }
-        (fdset, fd_to_sock_id)
-    };
+        let pause = match specific.conf.poll_limit.interval {
+            Timeout::Duration(dur) => dur,
+            // effectively until the socket gets restarted
+            Timeout::Infinity => std::time::Duration::from_secs(60 * 60 * 24 * 365),
+        };
+        warn!(
+            "Socket {} hit its poll limit ({:?}). Pausing for {:?}",
+            sock_unit.id.name, specific.conf.poll_limit, pause
+        );
+        let until = std::time::Instant::now() + pause;
+        mut_state.sock.polling_paused_until = Some(until);
+        run_info.reactor.pause_socket(&sock_unit.id, until);
+        false
+    } else {
+        true
+    }
+}
 
-    let result = nix::sys::select::select(None, Some(&mut fdset), None, None, None);
-    match result {
-        Ok(_) => {
-            let mut activated_ids = Vec::new();
-            if fdset.contains(eventfd.read_end()) {
-                trace!("Interrupted socketactivation select because the eventfd fired");
-                crate::platform::reset_event_fd(eventfd);
-                trace!("Reset eventfd value");
-            } else {
-                for (fd, id) in &fd_to_sock_id {
-                    if fdset.contains(*fd) {
-                        activated_ids.push(id.clone());
-                    }
-                }
+/// Called by the reactor with the sockets that have become readable. Returns the sockets that should activate their services.
+///
+/// These are marked as activated and are not listened on anymore until the service is waiting for socket activation again
+pub fn take_readable_sockets(ids: Vec<UnitId>, run_info: &RuntimeInfo) -> Vec<UnitId> {
+    let mut activated_ids = Vec::new();
+    for id in ids {
+        if let Some(sock_unit) = run_info.unit_table.get(&id) {
+            if !count_poll(sock_unit, run_info) {
+                continue;
             }
-            Ok(activated_ids)
+            if let Specific::Socket(specific) = &sock_unit.specific {
+                let mut_state = &mut *specific.state.write().unwrap();
+                mut_state.sock.activated = true;
+            }
+            run_info.reactor.deregister_unit(&id);
+            activated_ids.push(id);
+        }
+    }
+    activated_ids
+}
+
+/// Register the fds of the socket with the reactor unless the socket is activated or paused because it hit its PollLimit
+pub fn arm_socket(id: &UnitId, sock: &crate::sockets::Socket, run_info: &RuntimeInfo) {
+    if sock.activated {
+        return;
+    }
+    if let Some(until) = sock.polling_paused_until {
+        if until > std::time::Instant::now() {
+            // the reactor resumes the socket after the pause
+            return;
         }
-        Err(e) => {
-            if let nix::Error::EINTR = e {
-                Ok(Vec::new())
-            } else {
-                Err(format!("Error while selecting: {}", e))
+    }
+    if let Err(e) = run_info
+        .reactor
+        .register_socket(id, &run_info.fd_store.read().unwrap())
+    {
+        error!("Could not listen on socket {}: {}", id.name, e);
+    }
+}
+
+/// Listen on the sockets of this unit again, after the service they were passed to went back to waiting for socket activation
+pub fn rearm_socket(sock_unit: &Unit, run_info: &RuntimeInfo) {
+    if let Specific::Socket(specific) = &sock_unit.specific {
+        let mut_state = &mut *specific.state.write().unwrap();
+        mut_state.sock.activated = false;
+        arm_socket(&sock_unit.id, &mut_state.sock, run_info);
+    }
+}
+
+/// Called by the reactor when the pause of a socket that hit its PollLimit has passed
+pub fn resume_socket(id: &UnitId, run_info: &RuntimeInfo) {
+    if let Some(sock_unit) = run_info.unit_table.get(id) {
+        if let Specific::Socket(specific) = &sock_unit.specific {
+            let status = sock_unit.common.status.read().unwrap().clone();
+            if status != UnitStatus::Started(StatusStarted::Running) {
+                return;
             }
+            let mut_state = &*specific.state.read().unwrap();
+            trace!("Resume listening on socket {}", id.name);
+            arm_socket(id, &mut_state.sock, run_info);
         }
     }
 }
@@ -27,6 +27,49 @@ pub fn close_raw_fd(fd: RawFd) {
     }
 }
 
+/// Counts events (like triggers of a socket) and checks them against a RateLimit
+#[derive(Clone, Debug, Default)]
+pub struct RateLimitCounter {
+    pub total: u64,
+    pub in_interval: u64,
+    pub interval_start: Option<std::time::Instant>,
+}
+
+impl RateLimitCounter {
+    /// Record a new event. Returns false if this event exceeded the limit.
+    pub fn count(&mut self, limit: &RateLimit) -> bool {
+        self.total += 1;
+        let interval = match limit.interval {
+            Timeout::Duration(dur) => Some(dur),
+            Timeout::Infinity => None,
+        };
+        if limit.burst == 0 || interval == Some(std::time::Duration::ZERO) {
+            // limit is disabled
+            return true;
+        }
+
+        let now = std::time::Instant::now();
+        let new_interval = match (self.interval_start, interval) {
+            (None, _) => true,
+            (Some(start), Some(interval)) => now.duration_since(start) >= interval,
+            // an infinite interval never starts over
+            (Some(_), None) => false,
+        };
+        if new_interval {
+            self.interval_start = Some(now);
+            self.in_interval = 0;
+        }
+        self.in_interval += 1;
+        self.in_interval <= limit.burst
+    }
+
+    /// Start a fresh interval on the next event. The total count is kept.
+    pub fn reset_interval(&mut self) {
+        self.in_interval = 0;
+        self.interval_start = None;
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum SocketKind {
     Stream(String),
@@ -63,6 +106,13 @@ impl SpecializedSocketConfig {
 }
 
 impl Socket {
+    /// A (re-)opened socket gets a fresh chance before hitting its rate limits
+    pub fn reset_limits(&mut self) {
+        self.triggers.reset_interval();
+        self.polls.reset_interval();
+        self.polling_paused_until = None;
+    }
+
     pub fn build_name_list(&self, conf: SocketConfig) -> String {
         let mut name_list = String::with_capacity(
             conf.filedesc_name.as_bytes().len() * conf.sockets.len() + conf.sockets.len(),
@@ -114,10 +164,8 @@ impl Socket {
         fd_store: &mut FDStore,
     ) -> Result<(), String> {
         if let Some(fds) = fd_store.remove_global(&name) {
-            for idx in 0..fds.len() {
-                conf.sockets[idx]
-                    .specialized
-                    .close(fds[idx].2.as_raw_fd())?;
+            for (sock_conf, (_, _, fd)) in conf.sockets.iter().zip(fds.iter()) {
+                sock_conf.specialized.close(fd.as_raw_fd())?;
             }
         }
         Ok(())
@@ -42,4 +42,10 @@ impl UdpSocketConfig {
 #[derive(Clone, Debug)]
 pub struct Socket {
     pub activated: bool,
+    /// How often this socket started its service
+    pub triggers: super::RateLimitCounter,
+    /// How often the fds of this socket woke up the socket activation
+    pub polls: super::RateLimitCounter,
+    /// Set when the PollLimit was hit. The fds are not listened on until then
+    pub polling_paused_until: Option<std::time::Instant>,
 }
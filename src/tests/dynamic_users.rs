@@ -0,0 +1,29 @@
+use crate::dynamic_users::DynamicUsers;
+
+#[test]
+fn test_dynamic_user_allocation() {
+    let dir = super::test_dir("dynamic_users");
+
+    let users = DynamicUsers::new(&dir, 61184, 61190).unwrap();
+    let uid1 = users.get_or_allocate("first").unwrap();
+    let uid2 = users.get_or_allocate("second").unwrap();
+    assert_ne!(uid1, uid2);
+    assert!((61184..=61190).contains(&uid1));
+    assert_eq!(users.get_or_allocate("first").unwrap(), uid1);
+
+    // the allocations survive a restart
+    let users = DynamicUsers::new(&dir, 61184, 61190).unwrap();
+    assert_eq!(users.get_or_allocate("second").unwrap(), uid2);
+
+    let (passwd, group) = users.write_user_files("first", "first", uid1).unwrap();
+    let passwd = std::fs::read_to_string(passwd).unwrap();
+    assert!(passwd.ends_with(&format!(
+        "first:x:{}:{}:Dynamic User:/:/usr/sbin/nologin\n",
+        uid1, uid1
+    )));
+    let group = std::fs::read_to_string(group).unwrap();
+    assert!(group.ends_with(&format!("first:x:{}:\n", uid1)));
+
+    assert!(DynamicUsers::new(&dir, 10, 5).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,162 @@
+use crate::runtime_info::UnitTable;
+use crate::units::*;
+use std::convert::TryInto;
+
+fn test_conf(name: &str) -> crate::config::Config {
+    let dir = super::test_dir(name);
+    let unit_dir = dir.join("units");
+    let install_dir = dir.join("install");
+    let preset_dir = dir.join("presets");
+    for dir in &[&unit_dir, &install_dir, &preset_dir] {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    crate::config::Config {
+        install_dir: Some(install_dir.clone()),
+        preset_dirs: vec![preset_dir],
+        ..super::test_config(&dir, vec![install_dir, unit_dir])
+    }
+}
+
+fn wants(units: &UnitTable, name: &str) -> Vec<String> {
+    let id: UnitId = name.try_into().unwrap();
+    units[&id]
+        .common
+        .dependencies
+        .wants
+        .iter()
+        .map(|id| id.name.clone())
+        .collect()
+}
+
+#[test]
+fn test_enable_disable() {
+    let conf = test_conf("install");
+    let unit_dir = &conf.unit_dirs[1];
+    let write = |name: &str, content: &str| std::fs::write(unit_dir.join(name), content).unwrap();
+    write("default.target", "[Unit]\nDescription = target\n");
+    write(
+        "a.service",
+        "[Service]\nExecStart = /bin/true\n[Install]\nWantedBy = default.target\nAlias = b.service\nAlso = c.service\n",
+    );
+    write(
+        "c.service",
+        "[Service]\nExecStart = /bin/true\n[Install]\nRequiredBy = default.target\n",
+    );
+    write("static.service", "[Service]\nExecStart = /bin/true\n");
+
+    // without the symlinks the [Install] sections are ignored
+    let units = load_all_unit_files(&conf.unit_dirs, false).unwrap();
+    assert!(wants(&units, "default.target").is_empty());
+    assert_eq!(
+        unit_enable_state(&conf, "a.service"),
+        Ok(EnableState::Disabled)
+    );
+    assert_eq!(
+        unit_enable_state(&conf, "static.service"),
+        Ok(EnableState::Static)
+    );
+
+    let mut changes = InstallChanges::default();
+    enable_unit(&conf, "a.service", &mut changes).unwrap();
+    assert_eq!(changes.created.len(), 3);
+    assert_eq!(
+        unit_enable_state(&conf, "a.service"),
+        Ok(EnableState::Enabled)
+    );
+    assert_eq!(
+        unit_enable_state(&conf, "c.service"),
+        Ok(EnableState::Enabled)
+    );
+
+    // b.service only exists after enable created the alias
+    write(
+        "user.target",
+        "[Unit]\nDescription = target\nWants = b.service\n",
+    );
+    let units = load_all_unit_files(&conf.unit_dirs, false).unwrap();
+    assert_eq!(
+        wants(&units, "default.target"),
+        vec!["a.service".to_owned()]
+    );
+    let target_id: UnitId = "default.target".try_into().unwrap();
+    let c_id: UnitId = "c.service".try_into().unwrap();
+    assert_eq!(units[&target_id].common.dependencies.requires, vec![c_id]);
+    // the alias is not loaded as its own unit but references to it point to the real unit
+    assert!(!units.contains_key(&"b.service".try_into().unwrap()));
+    assert_eq!(wants(&units, "user.target"), vec!["a.service".to_owned()]);
+
+    // enabling again changes nothing
+    let mut changes = InstallChanges::default();
+    enable_unit(&conf, "a.service", &mut changes).unwrap();
+    assert!(changes.created.is_empty());
+
+    let mut changes = InstallChanges::default();
+    disable_unit(&conf, "a.service", &mut changes).unwrap();
+    assert_eq!(changes.removed.len(), 3);
+    assert_eq!(
+        unit_enable_state(&conf, "a.service"),
+        Ok(EnableState::Disabled)
+    );
+    assert_eq!(
+        unit_enable_state(&conf, "c.service"),
+        Ok(EnableState::Disabled)
+    );
+
+    std::fs::remove_dir_all(conf.unit_dirs[1].parent().unwrap()).unwrap();
+}
+
+#[test]
+fn test_mask_and_preset() {
+    let conf = test_conf("mask");
+    let unit_dir = &conf.unit_dirs[1];
+    std::fs::write(
+        unit_dir.join("default.target"),
+        "[Unit]\nDescription = target\n",
+    )
+    .unwrap();
+    for name in &["foo.service", "bar.service"] {
+        std::fs::write(
+            unit_dir.join(name),
+            "[Service]\nExecStart = /bin/true\n[Install]\nWantedBy = default.target\n",
+        )
+        .unwrap();
+    }
+
+    let mut changes = InstallChanges::default();
+    mask_unit(&conf, "foo.service", &mut changes).unwrap();
+    assert_eq!(
+        unit_enable_state(&conf, "foo.service"),
+        Ok(EnableState::Masked)
+    );
+    assert!(enable_unit(&conf, "foo.service", &mut changes).is_err());
+    // the masked unit is still shown but refuses to start
+    let units = load_all_unit_files(&conf.unit_dirs, false).unwrap();
+    let foo_id: UnitId = "foo.service".try_into().unwrap();
+    assert!(units[&foo_id].common.unit.masked);
+
+    unmask_unit(&conf, "foo.service", &mut changes).unwrap();
+    assert_eq!(
+        unit_enable_state(&conf, "foo.service"),
+        Ok(EnableState::Disabled)
+    );
+
+    std::fs::write(
+        conf.preset_dirs[0].join("50-test.preset"),
+        "# comment\nenable f?o.*\ndisable *\n",
+    )
+    .unwrap();
+    assert_eq!(preset_enables(&conf, "foo.service"), Ok(true));
+    assert_eq!(preset_enables(&conf, "bar.service"), Ok(false));
+    preset_unit(&conf, "foo.service", &mut changes).unwrap();
+    preset_unit(&conf, "bar.service", &mut changes).unwrap();
+    assert_eq!(
+        unit_enable_state(&conf, "foo.service"),
+        Ok(EnableState::Enabled)
+    );
+    assert_eq!(
+        unit_enable_state(&conf, "bar.service"),
+        Ok(EnableState::Disabled)
+    );
+
+    std::fs::remove_dir_all(conf.unit_dirs[1].parent().unwrap()).unwrap();
+}
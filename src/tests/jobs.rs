@@ -0,0 +1,264 @@
+use crate::jobs::*;
+use crate::units::*;
+
+fn service_id(name: &str) -> UnitId {
+    UnitId {
+        kind: UnitIdKind::Service,
+        name: name.to_owned(),
+    }
+}
+
+fn target_id(name: &str) -> UnitId {
+    UnitId {
+        kind: UnitIdKind::Target,
+        name: name.to_owned(),
+    }
+}
+
+#[test]
+fn test_job_merging() {
+    let queue = JobQueue::default();
+    let a = service_id("a.service");
+    let b = service_id("b.service");
+
+    let first = queue
+        .submit(
+            vec![(a.clone(), JobKind::Start), (b.clone(), JobKind::Start)],
+            JobMode::Replace,
+        )
+        .unwrap();
+    assert_eq!(first.jobs.len(), 2);
+
+    // starting b again must not add a second start job for b
+    let second = queue
+        .submit(vec![(b.clone(), JobKind::Start)], JobMode::Fail)
+        .unwrap();
+    assert!(second.jobs.is_empty());
+    assert_eq!(queue.list().len(), 2);
+
+    // the second request waits for the job it was merged into
+    let (_, waiting_for) = queue
+        .list()
+        .into_iter()
+        .find(|(job, _)| job.unit == b)
+        .unwrap();
+    assert!(waiting_for.is_empty());
+
+    queue.finish_transaction(&first);
+    queue.finish_transaction(&second);
+    assert!(queue.list().is_empty());
+}
+
+#[test]
+fn test_job_conflicts() {
+    let queue = JobQueue::default();
+    let a = service_id("a.service");
+    let b = service_id("b.service");
+
+    let start = queue
+        .submit(
+            vec![(a.clone(), JobKind::Start), (b.clone(), JobKind::Start)],
+            JobMode::Replace,
+        )
+        .unwrap();
+    let start_a = start.jobs[0].0;
+    let start_b = start.jobs[1].0;
+
+    // fail rejects the whole request and leaves the queue alone
+    assert!(queue
+        .submit(vec![(a.clone(), JobKind::Stop)], JobMode::Fail)
+        .is_err());
+    assert_eq!(queue.list().len(), 2);
+
+    // replace cancels the waiting start job but not the running one
+    assert!(queue.begin(start_a));
+    let stop = queue
+        .submit(
+            vec![(a.clone(), JobKind::Stop), (b.clone(), JobKind::Stop)],
+            JobMode::Replace,
+        )
+        .unwrap();
+    assert!(!queue.begin(start_b));
+
+    let jobs = queue.list();
+    assert_eq!(jobs.len(), 3);
+    for (job, waiting_for) in &jobs {
+        if job.kind == JobKind::Stop {
+            assert_eq!(job.state, JobState::Waiting);
+            assert_eq!(waiting_for, &vec![start_a]);
+        } else {
+            assert_eq!(job.id, start_a);
+            assert_eq!(job.state, JobState::Running);
+        }
+    }
+
+    queue.finish(start_a);
+    queue.finish_transaction(&start);
+    for (_, waiting_for) in queue.list() {
+        assert!(waiting_for.is_empty());
+    }
+    queue.finish_transaction(&stop);
+}
+
+#[test]
+fn test_job_cancel() {
+    let queue = JobQueue::default();
+    let a = service_id("a.service");
+    let b = service_id("b.service");
+
+    let transaction = queue
+        .submit(
+            vec![(a.clone(), JobKind::Start), (b.clone(), JobKind::Start)],
+            JobMode::Replace,
+        )
+        .unwrap();
+    let start_a = transaction.jobs[0].0;
+    let start_b = transaction.jobs[1].0;
+
+    assert!(queue.begin(start_a));
+    assert!(queue.cancel(start_a).is_err());
+    assert!(queue.cancel(start_b).is_ok());
+    assert!(queue.cancel(start_b).is_err());
+    assert!(!queue.begin(start_b));
+
+    assert_eq!(queue.list().len(), 1);
+    queue.finish_transaction(&transaction);
+    assert!(queue.list().is_empty());
+}
+
+#[test]
+fn test_job_cancel_gives_merged_jobs_back() {
+    let queue = JobQueue::default();
+    let a = service_id("a.service");
+
+    let first = queue
+        .submit(vec![(a.clone(), JobKind::Start)], JobMode::Replace)
+        .unwrap();
+    let start_a = first.jobs[0].0;
+    let second = queue
+        .submit(vec![(a.clone(), JobKind::Start)], JobMode::Replace)
+        .unwrap();
+    assert!(second.jobs.is_empty());
+
+    // the second request gets the start job back instead of silently skipping a
+    let stop = queue
+        .submit(vec![(a.clone(), JobKind::Stop)], JobMode::Replace)
+        .unwrap();
+    assert!(!queue.begin(start_a));
+    assert!(queue.transaction_jobs(&first).is_empty());
+    let given_back = queue.transaction_jobs(&second);
+    assert_eq!(given_back.len(), 1);
+    let (start_again, unit, kind) = &given_back[0];
+    assert_eq!((unit, *kind), (&a, JobKind::Start));
+
+    // and the stop runs after it
+    let (_, waiting_for) = queue
+        .list()
+        .into_iter()
+        .find(|(job, _)| job.kind == JobKind::Stop)
+        .unwrap();
+    assert_eq!(waiting_for, vec![*start_again]);
+
+    queue.finish_transaction(&first);
+    queue.finish_transaction(&second);
+    queue.finish_transaction(&stop);
+    assert!(queue.list().is_empty());
+}
+
+fn target(name: &str, unit_section: &str) -> Unit {
+    use std::convert::TryInto;
+    let unit_str = format!("[Unit]\nDescription = {}\n{}\n", name, unit_section);
+    let parsed_file = crate::units::parse_file(&unit_str).unwrap();
+    let parsed = crate::units::parse_target(
+        parsed_file,
+        &std::path::PathBuf::from(format!("/path/to/{}", name)),
+    )
+    .unwrap();
+    parsed.try_into().unwrap()
+}
+
+#[test]
+fn test_stop_order() {
+    let mut unit_table = std::collections::HashMap::new();
+    for unit in vec![
+        target("a.target", ""),
+        target("b.target", "After = a.target"),
+        target("c.target", "Requires = b.target"),
+        target("d.target", "After = e.target"),
+        target("e.target", "After = d.target"),
+    ] {
+        unit_table.insert(unit.id.clone(), unit);
+    }
+    crate::units::fill_dependencies(&mut unit_table).unwrap();
+    unit_table
+        .values_mut()
+        .for_each(|unit| unit.dedup_dependencies());
+
+    let ids = ["a.target", "b.target", "c.target", "d.target", "e.target"]
+        .iter()
+        .map(|name| target_id(name))
+        .collect();
+    // the cycle between d.target and e.target is broken up by keeping their order
+    assert_eq!(
+        stop_order(ids, &unit_table),
+        ["c.target", "b.target", "a.target", "d.target", "e.target"]
+            .iter()
+            .map(|name| target_id(name))
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_isolate_planning() {
+    let mut unit_table = std::collections::HashMap::new();
+    for unit in vec![
+        target("rescue.target", "AllowIsolate = yes\nWants = a.target"),
+        target("a.target", ""),
+        target("b.target", "IgnoreOnIsolate = true\nBefore = c.target"),
+        target("c.target", ""),
+    ] {
+        unit_table.insert(unit.id.clone(), unit);
+    }
+    crate::units::fill_dependencies(&mut unit_table).unwrap();
+    unit_table
+        .values_mut()
+        .for_each(|unit| unit.dedup_dependencies());
+
+    for name in &["a.target", "b.target", "c.target"] {
+        let unit = unit_table.get(&target_id(name)).unwrap();
+        *unit.common.status.write().unwrap() = UnitStatus::Started(StatusStarted::Running);
+    }
+
+    let dir = super::test_dir("isolate");
+    let run_info = super::test_run_info(&dir, vec![], unit_table);
+
+    let mut planned = plan_transaction(
+        &target_id("rescue.target"),
+        JobKind::Start,
+        false,
+        JobMode::Isolate,
+        &run_info,
+    )
+    .unwrap();
+    planned.sort_by(|(a, _), (b, _)| a.cmp(b));
+    assert_eq!(
+        planned,
+        vec![
+            (target_id("a.target"), JobKind::Start),
+            (target_id("c.target"), JobKind::Stop),
+            (target_id("rescue.target"), JobKind::Start),
+        ]
+    );
+
+    // c.target does not have AllowIsolate=
+    assert!(plan_transaction(
+        &target_id("c.target"),
+        JobKind::Start,
+        false,
+        JobMode::Isolate,
+        &run_info,
+    )
+    .is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
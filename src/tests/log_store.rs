@@ -0,0 +1,149 @@
+use crate::log_store::{LogEntry, LogFilter, LogStore, LogStream};
+
+#[test]
+fn test_log_store_roundtrip() {
+    let dir = super::test_dir("log_roundtrip");
+    let store = LogStore::open(&dir, 1024 * 1024, 2).unwrap();
+
+    let entry1 = LogEntry::new(
+        "test.service",
+        LogStream::Stdout,
+        Some(42),
+        6,
+        "Hello".into(),
+    );
+    let entry2 = LogEntry::new(
+        "test.service",
+        LogStream::Stderr,
+        None,
+        3,
+        "World \"quoted\"".into(),
+    );
+    store.append(&entry1).unwrap();
+    store.append(&entry2).unwrap();
+
+    // entries survive reopening the store
+    let store = LogStore::open(&dir, 1024 * 1024, 2).unwrap();
+    assert_eq!(store.read_all().unwrap(), vec![entry1, entry2]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_log_store_rotation() {
+    let dir = super::test_dir("log_rotation");
+    // small enough that every entry needs its own file
+    let store = LogStore::open(&dir, 10, 2).unwrap();
+
+    let entries: Vec<_> = (0..5)
+        .map(|idx| {
+            LogEntry::new(
+                "test.service",
+                LogStream::Stdout,
+                None,
+                6,
+                format!("{}", idx),
+            )
+        })
+        .collect();
+    for entry in &entries {
+        store.append(entry).unwrap();
+    }
+
+    // only the current file and two rotated ones are kept
+    assert!(dir.join("rustysd.log.2").exists());
+    assert!(!dir.join("rustysd.log.3").exists());
+    assert_eq!(store.read_all().unwrap(), entries[2..].to_vec());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_log_store_query() {
+    let dir = super::test_dir("log_query");
+    let store = LogStore::open(&dir, 1024 * 1024, 2).unwrap();
+
+    let mut entries = Vec::new();
+    for idx in 0..6u64 {
+        let unit = if idx % 2 == 0 {
+            "a.service"
+        } else {
+            "b.service"
+        };
+        let prio = if idx < 3 { 3 } else { 6 };
+        let mut entry = LogEntry::new(unit, LogStream::Stdout, None, prio, format!("{}", idx));
+        entry.timestamp = idx * 1_000_000;
+        store.append(&entry).unwrap();
+        entries.push(entry);
+    }
+
+    let filter = LogFilter {
+        unit: Some("a.service".into()),
+        ..Default::default()
+    };
+    assert_eq!(
+        store.query(&filter, None).unwrap(),
+        vec![entries[0].clone(), entries[2].clone(), entries[4].clone()]
+    );
+    assert_eq!(
+        store.query(&filter, Some(1)).unwrap(),
+        vec![entries[4].clone()]
+    );
+
+    let filter = LogFilter {
+        since: Some(1_000_000),
+        until: Some(4_000_000),
+        priority: crate::log_store::parse_priority("err"),
+        ..Default::default()
+    };
+    assert_eq!(
+        store.query(&filter, None).unwrap(),
+        vec![entries[1].clone(), entries[2].clone()]
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_priority_prefix() {
+    use crate::log_store::split_priority_prefix;
+
+    assert_eq!(
+        split_priority_prefix(b"<3>Error happened"),
+        Some((3, None, &b"Error happened"[..]))
+    );
+    // local0.warning
+    assert_eq!(
+        split_priority_prefix(b"<132>Careful"),
+        Some((4, Some(16), &b"Careful"[..]))
+    );
+    assert_eq!(split_priority_prefix(b"No prefix"), None);
+    assert_eq!(split_priority_prefix(b"<x>Not a number"), None);
+    assert_eq!(split_priority_prefix(b"<3 Unterminated"), None);
+    assert_eq!(split_priority_prefix(b"<192>Facility too big"), None);
+
+    assert_eq!(crate::log_store::parse_facility("local3"), Some(19));
+    assert_eq!(crate::log_store::parse_facility("nonsense"), None);
+}
+
+#[test]
+fn test_slow_follower_is_disconnected() {
+    let dir = super::test_dir("log_follow");
+    let store = LogStore::open(&dir, 1024 * 1024, 2).unwrap();
+    let follower = store.follow();
+
+    for idx in 0..1001 {
+        let entry = LogEntry::new(
+            "test.service",
+            LogStream::Stdout,
+            None,
+            6,
+            format!("{}", idx),
+        );
+        store.append(&entry).unwrap();
+    }
+    // the buffered entries can still be read, then the follower notices it was dropped
+    assert_eq!(follower.iter().count(), 1000);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,27 @@
+use crate::log_store::{LogEntry, LogStream};
+use crate::logging::{format_syslog_message, SyslogFormat};
+
+#[test]
+fn test_syslog_formats() {
+    let mut entry = LogEntry::new(
+        "test.service",
+        LogStream::Stdout,
+        Some(42),
+        6,
+        "Hello".into(),
+    );
+    entry.identifier = "mydaemon".into();
+
+    // daemon.info
+    let msg = format_syslog_message(&entry, SyslogFormat::Rfc3164, "host");
+    assert!(msg.starts_with("<30>"));
+    assert!(msg.ends_with(" host mydaemon[42]: Hello"));
+
+    entry.pid = None;
+    entry.facility = 16;
+    entry.priority = 3;
+    // local0.err
+    let msg = format_syslog_message(&entry, SyslogFormat::Rfc5424, "host");
+    assert!(msg.starts_with("<131>1 "));
+    assert!(msg.ends_with(" host mydaemon - - - Hello"));
+}
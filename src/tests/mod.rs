@@ -1,5 +1,62 @@
 #![cfg(test)]
 
+mod dynamic_users;
+mod install;
+mod jobs;
+mod log_store;
+mod logging;
 mod ordering;
 mod parsing;
+mod reexec;
+mod reload;
 mod state_transition;
+mod unit_dirs;
+
+/// An empty dir for one test, named after the test and the pid so parallel test runs do not share it
+pub fn test_dir(name: &str) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("rustysd_{}_{}", name, std::process::id()));
+    if dir.exists() {
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// The config for tests. The notification sockets and dynamic users go into dir
+pub fn test_config(
+    dir: &std::path::Path,
+    unit_dirs: Vec<std::path::PathBuf>,
+) -> crate::config::Config {
+    crate::config::Config {
+        notification_sockets_dir: dir.join("notifications"),
+        target_unit: "default.target".into(),
+        unit_dirs,
+        install_dir: None,
+        preset_dirs: vec![],
+        dynamic_users_dir: dir.join("dynamic_users"),
+        dynamic_user_min_uid: 61184,
+        dynamic_user_max_uid: 65519,
+        default_limits: crate::units::ResourceLimits::default(),
+    }
+}
+
+/// A RuntimeInfo with the units and the config from test_config
+pub fn test_run_info(
+    dir: &std::path::Path,
+    unit_dirs: Vec<std::path::PathBuf>,
+    unit_table: crate::runtime_info::UnitTable,
+) -> crate::runtime_info::RuntimeInfo {
+    let config = test_config(dir, unit_dirs);
+    crate::runtime_info::RuntimeInfo {
+        fd_store: std::sync::RwLock::new(crate::fd_store::FDStore::default()),
+        pid_table: std::sync::Mutex::new(crate::runtime_info::PidTable::default()),
+        unit_table,
+        reactor: std::sync::Arc::new(crate::reactor::Reactor::new().unwrap()),
+        log_store: None,
+        syslog: None,
+        dynamic_users: crate::dynamic_users::DynamicUsers::load(&config).unwrap(),
+        jobs: std::sync::Arc::new(crate::jobs::JobQueue::default()),
+        pending_reload: std::collections::HashMap::new(),
+        config,
+    }
+}
@@ -84,11 +84,11 @@ fn test_service_parsing() {
 
     assert_eq!(
         service.srvc.exec,
-        crate::units::Commandline {
+        vec![crate::units::Commandline {
             cmd: "/path/to/startbin".into(),
             args: vec!["arg1".into(), "arg2".into(), "arg3".into()],
             prefixes: vec![],
-        }
+        }]
     );
     assert_eq!(
         service.srvc.startpre,
@@ -296,3 +296,907 @@ fn test_socket_parsing() {
         panic!("Not enough sockets parsed");
     }
 }
+
+#[test]
+fn test_socket_rate_limit_parsing() {
+    let test_socket_str = r#"
+    [Socket]
+    ListenStream = /path/to/socket
+    TriggerLimitIntervalSec = 10s
+    TriggerLimitBurst = 5
+    PollLimitIntervalSec = infinity
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_socket_str).unwrap();
+    let socket_unit = crate::units::parse_socket(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.socket"),
+    )
+    .unwrap();
+
+    assert_eq!(
+        socket_unit.sock.trigger_limit,
+        crate::units::RateLimit {
+            interval: crate::units::Timeout::Duration(std::time::Duration::from_secs(10)),
+            burst: 5,
+        }
+    );
+    assert_eq!(
+        socket_unit.sock.poll_limit,
+        crate::units::RateLimit {
+            interval: crate::units::Timeout::Infinity,
+            burst: 150,
+        }
+    );
+
+    let mut counter = crate::sockets::RateLimitCounter::default();
+    for _ in 0..5 {
+        assert!(counter.count(&socket_unit.sock.trigger_limit));
+    }
+    assert!(!counter.count(&socket_unit.sock.trigger_limit));
+    counter.reset_interval();
+    assert!(counter.count(&socket_unit.sock.trigger_limit));
+    assert_eq!(counter.total, 7);
+}
+
+#[test]
+fn test_stdio_parsing() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    StandardOutput = truncate:/path/to/out
+    StandardError = fd:stderr_fd
+    StandardInputText = line1
+    StandardInputText = line2
+    TTYPath = /dev/tty5
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let exec = &service.srvc.exec_section;
+    assert_eq!(
+        exec.stdout_path,
+        Some(crate::units::StdIoOption::TruncateFile(
+            "/path/to/out".into()
+        ))
+    );
+    assert_eq!(
+        exec.stderr_path,
+        Some(crate::units::StdIoOption::Fd("stderr_fd".into()))
+    );
+    // StandardInputText= implies StandardInput=data
+    assert_eq!(
+        exec.stdin_option,
+        Some(crate::units::StdInOption::Data("line1\nline2\n".into()))
+    );
+    assert_eq!(exec.tty_path, Some("/dev/tty5".into()));
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    StandardOutput = journal+console
+    StandardError = inherit
+    StandardInput = file:/path/to/in
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let exec = &service.srvc.exec_section;
+    assert_eq!(exec.stdout_path, Some(crate::units::StdIoOption::Journal));
+    assert_eq!(exec.stderr_path, Some(crate::units::StdIoOption::Inherit));
+    assert_eq!(
+        exec.stdin_option,
+        Some(crate::units::StdInOption::File("/path/to/in".into()))
+    );
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    StandardOutput = nonsense
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    assert!(crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_syslog_settings_parsing() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    SyslogIdentifier = mydaemon
+    SyslogFacility = local2
+    SyslogLevel = warning
+    SyslogLevelPrefix = no
+    LogLevelMax = 5
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let exec = &service.srvc.exec_section;
+    assert_eq!(exec.syslog_identifier, Some("mydaemon".into()));
+    assert_eq!(exec.syslog_facility, 18);
+    assert_eq!(exec.syslog_level, 4);
+    assert!(!exec.syslog_level_prefix);
+    assert_eq!(exec.log_level_max, Some(5));
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    SyslogLevel = loud
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    assert!(crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_sandbox_parsing() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    PrivateTmp = yes
+    ProtectSystem = strict
+    ProtectHome = read-only
+    ReadWritePaths = /var/lib/foo -/var/cache/foo
+    ReadOnlyPaths = /etc/foo
+    ReadOnlyPaths =
+    ReadOnlyPaths = /etc/bar
+    BindPaths = /src:/dst:norbind /other
+    TemporaryFileSystem = /var:ro
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let sandbox = &service.srvc.exec_section.sandbox;
+    assert!(sandbox.private_tmp);
+    assert_eq!(sandbox.protect_system, crate::units::ProtectSystem::Strict);
+    assert_eq!(sandbox.protect_home, crate::units::ProtectHome::ReadOnly);
+    assert_eq!(
+        sandbox.read_write_paths,
+        vec![
+            crate::units::SandboxPath {
+                path: "/var/lib/foo".into(),
+                ignore_missing: false,
+            },
+            crate::units::SandboxPath {
+                path: "/var/cache/foo".into(),
+                ignore_missing: true,
+            },
+        ]
+    );
+    assert_eq!(sandbox.read_only_paths.len(), 1);
+    assert_eq!(
+        sandbox.read_only_paths[0].path,
+        std::path::PathBuf::from("/etc/bar")
+    );
+    assert_eq!(
+        sandbox.bind_paths,
+        vec![
+            crate::units::BindPath {
+                src: "/src".into(),
+                dst: "/dst".into(),
+                recursive: false,
+                ignore_missing: false,
+            },
+            crate::units::BindPath {
+                src: "/other".into(),
+                dst: "/other".into(),
+                recursive: true,
+                ignore_missing: false,
+            },
+        ]
+    );
+    assert_eq!(sandbox.temporary_file_systems.len(), 1);
+    assert_eq!(sandbox.temporary_file_systems[0].options, "ro");
+    assert!(sandbox.is_enabled());
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    ReadOnlyPaths = relative/path
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    assert!(crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_capability_parsing() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    NoNewPrivileges = yes
+    CapabilityBoundingSet = CAP_NET_BIND_SERVICE
+    CapabilityBoundingSet = cap_net_raw
+    AmbientCapabilities = CAP_SYS_ADMIN
+    AmbientCapabilities =
+    AmbientCapabilities = CAP_NET_BIND_SERVICE
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let exec = &service.srvc.exec_section;
+    assert!(exec.no_new_privileges);
+    assert_eq!(exec.capabilities.bounding_set, Some((1 << 10) | (1 << 13)));
+    assert_eq!(exec.capabilities.ambient, 1 << 10);
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    CapabilityBoundingSet = ~CAP_SYS_ADMIN CAP_SYS_PTRACE
+    AmbientCapabilities = ~CAP_SYS_ADMIN
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let exec = &service.srvc.exec_section;
+    assert!(!exec.no_new_privileges);
+    assert_eq!(
+        exec.capabilities.bounding_set,
+        Some(!((1 << 21) | (1 << 19)))
+    );
+    assert_eq!(
+        exec.capabilities.ambient,
+        crate::platform::ALL_CAPABILITIES & !(1 << 21)
+    );
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    CapabilityBoundingSet = CAP_DOES_NOT_EXIST
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    assert!(crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_syscall_filter_parsing() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    SystemCallFilter = @system-service
+    SystemCallFilter = ~@mount reboot:EACCES
+    SystemCallErrorNumber = EPERM
+    SystemCallArchitectures = native
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let filter = &service.srvc.exec_section.syscall_filter;
+    assert_eq!(
+        filter.rules,
+        vec![
+            (false, vec!["@system-service".to_owned()]),
+            (true, vec!["@mount".to_owned(), "reboot:EACCES".to_owned()]),
+        ]
+    );
+    assert_eq!(filter.error_number, Some(libc::EPERM));
+    assert!(filter.native_arch_only);
+    assert!(filter.is_enabled());
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    SystemCallFilter = read
+    SystemCallFilter =
+    SystemCallErrorNumber = 13
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+    let filter = &service.srvc.exec_section.syscall_filter;
+    assert!(filter.rules.is_empty());
+    assert_eq!(filter.error_number, Some(13));
+    assert!(!filter.is_enabled());
+
+    for setting in &[
+        "SystemCallErrorNumber = ENOTANERRNO",
+        "SystemCallFilter = read:ENOTANERRNO",
+        "SystemCallArchitectures = x86",
+    ] {
+        let test_service_str = format!("[Service]\nExecStart = /path/to/startbin\n{}\n", setting);
+        let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+        assert!(crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/unitfile.service"),
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn test_namespace_parsing() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    PrivateNetwork = yes
+    PrivateDevices = yes
+    PrivateUsers = true
+    ProtectKernelTunables = yes
+    ProtectKernelModules = yes
+    ProtectKernelLogs = yes
+    ProtectControlGroups = yes
+    ProtectHostname = yes
+    RestrictNamespaces = net uts
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let exec = &service.srvc.exec_section;
+    assert!(exec.sandbox.private_devices);
+    assert!(exec.sandbox.protect_kernel_tunables);
+    assert!(exec.sandbox.protect_kernel_modules);
+    assert!(exec.sandbox.protect_kernel_logs);
+    assert!(exec.sandbox.protect_control_groups);
+    assert!(exec.sandbox.is_enabled());
+    assert_eq!(
+        exec.namespaces,
+        crate::units::NamespaceSandbox {
+            private_network: true,
+            private_users: true,
+            protect_hostname: true,
+            restrict_namespaces: Some(libc::CLONE_NEWNET as u64 | libc::CLONE_NEWUTS as u64),
+        }
+    );
+    // CAP_MKNOD, CAP_SYS_RAWIO, CAP_SYS_MODULE and CAP_SYSLOG are dropped by the protection settings
+    assert_eq!(
+        exec.capabilities.bounding_set,
+        Some(!((1 << 27) | (1 << 17) | (1 << 16) | (1 << 34)))
+    );
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    RestrictNamespaces = ~user
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+    let exec = &service.srvc.exec_section;
+    assert_eq!(
+        exec.namespaces.restrict_namespaces,
+        Some(crate::units::ALL_NAMESPACE_FLAGS & !(libc::CLONE_NEWUSER as u64))
+    );
+    assert_eq!(exec.capabilities.bounding_set, None);
+    assert!(!exec.sandbox.is_enabled());
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    RestrictNamespaces = yes
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+    assert_eq!(
+        service.srvc.exec_section.namespaces.restrict_namespaces,
+        Some(0)
+    );
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    RestrictNamespaces = net foo
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    assert!(crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_dynamic_user_parsing() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    DynamicUser = yes
+    ProtectHome = tmpfs
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let exec = &service.srvc.exec_section;
+    assert!(exec.dynamic_user);
+    assert_eq!(exec.user, Some("unitfile".to_owned()));
+    assert!(exec.no_new_privileges);
+    assert!(exec.sandbox.private_tmp);
+    assert_eq!(
+        exec.sandbox.protect_system,
+        crate::units::ProtectSystem::Strict
+    );
+    // explicitly set protections are not overwritten
+    assert_eq!(exec.sandbox.protect_home, crate::units::ProtectHome::Tmpfs);
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    DynamicUser = yes
+    User = someone
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+    let exec_config: crate::units::ExecConfig =
+        std::convert::TryFrom::try_from(service.srvc.exec_section).unwrap();
+    assert_eq!(
+        exec_config.dynamic_user,
+        Some(crate::units::DynamicUser {
+            user: "someone".to_owned(),
+            group: "someone".to_owned(),
+        })
+    );
+}
+
+#[test]
+fn test_directories_parsing() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    RuntimeDirectory = foo bar/baz
+    RuntimeDirectoryMode = 0700
+    RuntimeDirectoryPreserve = restart
+    StateDirectory = foo
+    ConfigurationDirectory = foo
+    ProtectSystem = strict
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let directories = &service.srvc.exec_section.directories;
+    assert_eq!(
+        directories.runtime_preserve,
+        crate::units::RuntimeDirectoryPreserve::Restart
+    );
+    assert_eq!(directories.directories.len(), 3);
+    let runtime = &directories.directories[0];
+    assert_eq!(runtime.kind, crate::units::ExecDirectoryKind::Runtime);
+    assert_eq!(runtime.mode, 0o700);
+    assert_eq!(
+        runtime.paths(),
+        vec![
+            std::path::PathBuf::from("/run/foo"),
+            std::path::PathBuf::from("/run/bar/baz")
+        ]
+    );
+    assert_eq!(directories.directories[1].mode, 0o755);
+
+    // all but the configuration directory stay writable
+    let read_write: Vec<_> = service
+        .srvc
+        .exec_section
+        .sandbox
+        .read_write_paths
+        .iter()
+        .map(|path| path.path.clone())
+        .collect();
+    assert_eq!(
+        read_write,
+        vec![
+            std::path::PathBuf::from("/run/foo"),
+            std::path::PathBuf::from("/run/bar/baz"),
+            std::path::PathBuf::from("/var/lib/foo"),
+        ]
+    );
+
+    for invalid in &[
+        "RuntimeDirectory = /absolute",
+        "StateDirectory = ../up",
+        "CacheDirectoryMode = 999",
+    ] {
+        let test_service_str = format!("[Service]\nExecStart = /path/to/startbin\n{}\n", invalid);
+        let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+        assert!(crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/unitfile.service"),
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn test_process_settings_parsing() {
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    WorkingDirectory = -/var/lib/something
+    RootDirectory = /srv/root
+    UMask = 0027
+    Nice = -5
+    CPUSchedulingPolicy = rr
+    CPUSchedulingPriority = 20
+    CPUAffinity = 0-2 4,6
+    IOSchedulingClass = best-effort
+    IOSchedulingPriority = 2
+    Personality = x86
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let exec = &service.srvc.exec_section;
+    assert_eq!(
+        exec.working_directory,
+        Some(crate::units::SandboxPath {
+            path: "/var/lib/something".into(),
+            ignore_missing: true,
+        })
+    );
+    assert_eq!(
+        exec.process,
+        crate::units::ProcessSettings {
+            root_directory: Some("/srv/root".into()),
+            umask: Some(0o027),
+            nice: Some(-5),
+            cpu_scheduling_policy: Some(crate::units::CpuSchedulingPolicy::RoundRobin),
+            cpu_scheduling_priority: Some(20),
+            cpu_affinity: vec![0, 1, 2, 4, 6],
+            io_scheduling_class: Some(crate::units::IoSchedulingClass::BestEffort),
+            io_scheduling_priority: Some(2),
+            personality: Some(crate::units::Personality::X86),
+        }
+    );
+
+    // ~ is resolved to the home directory of the user when the config is loaded
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    WorkingDirectory = ~
+    User = 0
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+    let exec_config: crate::units::ExecConfig =
+        std::convert::TryFrom::try_from(service.srvc.exec_section).unwrap();
+    assert_eq!(
+        exec_config.working_directory.unwrap().path,
+        nix::unistd::User::from_uid(nix::unistd::Uid::from_raw(0))
+            .unwrap()
+            .unwrap()
+            .dir
+    );
+
+    for invalid in &[
+        "WorkingDirectory = relative",
+        "Nice = 20",
+        "CPUSchedulingPolicy = fast",
+        "CPUAffinity = 3-1",
+        "IOSchedulingPriority = 8",
+        "UMask = 0999",
+    ] {
+        let test_service_str = format!("[Service]\nExecStart = /path/to/startbin\n{}\n", invalid);
+        let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+        assert!(crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/unitfile.service"),
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn test_limit_parsing() {
+    use crate::units::{LimitResource, ResourceLimit};
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    LimitNOFILE = 1024:524288
+    LimitCORE = 0
+    LimitAS = 4G:infinity
+    LimitCPU = 1min 30s
+    LimitRTTIME = 500ms
+    LimitNICE = -5
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let limits = &service.srvc.exec_section.limits;
+    let limit = |soft, hard| Some(ResourceLimit { soft, hard });
+    assert_eq!(
+        limits.get(LimitResource::NoFile),
+        limit(Some(1024), Some(524288))
+    );
+    assert_eq!(limits.get(LimitResource::Core), limit(Some(0), Some(0)));
+    assert_eq!(
+        limits.get(LimitResource::AddressSpace),
+        limit(Some(4 * 1024 * 1024 * 1024), None)
+    );
+    assert_eq!(limits.get(LimitResource::Cpu), limit(Some(90), Some(90)));
+    assert_eq!(
+        limits.get(LimitResource::RtTime),
+        limit(Some(500_000), Some(500_000))
+    );
+    assert_eq!(limits.get(LimitResource::Nice), limit(Some(25), Some(25)));
+    assert_eq!(limits.get(LimitResource::NProc), None);
+
+    // the settings of the unit override the defaults
+    let mut defaults = crate::units::ResourceLimits::default();
+    defaults.set(
+        LimitResource::NoFile,
+        ResourceLimit {
+            soft: Some(1),
+            hard: Some(1),
+        },
+    );
+    defaults.set(
+        LimitResource::NProc,
+        ResourceLimit {
+            soft: Some(2),
+            hard: Some(2),
+        },
+    );
+    let merged = defaults.merged_with(limits);
+    assert_eq!(
+        merged.get(LimitResource::NoFile),
+        limit(Some(1024), Some(524288))
+    );
+    assert_eq!(merged.get(LimitResource::NProc), limit(Some(2), Some(2)));
+
+    for invalid in &[
+        "LimitNOFILE = 10:5",
+        "LimitNOFILE = infinity:5",
+        "LimitCORE = 5X",
+        "LimitNICE = +20",
+    ] {
+        let test_service_str = format!("[Service]\nExecStart = /path/to/startbin\n{}\n", invalid);
+        let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+        assert!(crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/unitfile.service"),
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn test_kill_parsing() {
+    use crate::units::{KillConfig, KillMode};
+    use nix::sys::signal::Signal;
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    KillMode = mixed
+    KillSignal = SIGINT
+    RestartKillSignal = HUP
+    FinalKillSignal = 3
+    SendSIGHUP = yes
+    SendSIGKILL = no
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    assert_eq!(
+        service.srvc.kill,
+        KillConfig {
+            mode: KillMode::Mixed,
+            signal: Signal::SIGINT,
+            restart_signal: Some(Signal::SIGHUP),
+            final_signal: Signal::SIGQUIT,
+            send_sighup: true,
+            send_sigkill: false,
+        }
+    );
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+    assert_eq!(service.srvc.kill, KillConfig::default());
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    KillSignal = SIGNOPE
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    assert!(crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .is_err());
+}
+
+#[test]
+fn test_exit_status_parsing() {
+    use crate::signal_handler::ChildTermination;
+    use crate::units::ServiceRestart;
+    use nix::sys::signal::Signal;
+
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/startbin
+    SuccessExitStatus = 143 SIGUSR1
+    SuccessExitStatus = 1
+    RestartPreventExitStatus = 2 TERM
+    RestartForceExitStatus = 3
+    Restart = always
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    let exit_status = &service.srvc.exit_status;
+    assert_eq!(exit_status.success.exit_codes, vec![143, 1]);
+    assert_eq!(exit_status.success.signals, vec![Signal::SIGUSR1]);
+    assert!(exit_status.is_success(&ChildTermination::Exit(0)));
+    assert!(exit_status.is_success(&ChildTermination::Exit(143)));
+    assert!(exit_status.is_success(&ChildTermination::Signal(Signal::SIGUSR1)));
+    assert!(!exit_status.is_success(&ChildTermination::Exit(2)));
+
+    assert!(!exit_status.should_restart(&ServiceRestart::Always, &ChildTermination::Exit(2)));
+    assert!(!exit_status.should_restart(
+        &ServiceRestart::Always,
+        &ChildTermination::Signal(Signal::SIGTERM)
+    ));
+    assert!(exit_status.should_restart(&ServiceRestart::Always, &ChildTermination::Exit(1)));
+    assert!(exit_status.should_restart(&ServiceRestart::No, &ChildTermination::Exit(3)));
+    assert!(!exit_status.should_restart(&ServiceRestart::No, &ChildTermination::Exit(1)));
+
+    for bad in ["256", "-1", "SIGNOPE"] {
+        let test_service_str = format!(
+            "[Service]\nExecStart = /path/to/startbin\nSuccessExitStatus = {}\n",
+            bad
+        );
+        let parsed_file = crate::units::parse_file(&test_service_str).unwrap();
+        assert!(crate::units::parse_service(
+            parsed_file,
+            &std::path::PathBuf::from("/path/to/unitfile.service"),
+        )
+        .is_err());
+    }
+}
+
+#[test]
+fn test_oneshot_parsing() {
+    let test_service_str = r#"
+    [Service]
+    Type = oneshot
+    ExecStart = /path/to/first arg1
+    ExecStart = -/path/to/second
+    RemainAfterExit = yes
+    "#;
+
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    let service = crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .unwrap();
+
+    assert!(service.srvc.remain_after_exit);
+    assert_eq!(
+        service.srvc.exec,
+        vec![
+            crate::units::Commandline {
+                cmd: "/path/to/first".into(),
+                args: vec!["arg1".into()],
+                prefixes: vec![],
+            },
+            crate::units::Commandline {
+                cmd: "/path/to/second".into(),
+                args: vec![],
+                prefixes: vec![crate::units::CommandlinePrefix::Minus],
+            }
+        ]
+    );
+
+    // other types can only have one ExecStart=
+    let test_service_str = r#"
+    [Service]
+    ExecStart = /path/to/first
+    ExecStart = /path/to/second
+    "#;
+    let parsed_file = crate::units::parse_file(test_service_str).unwrap();
+    assert!(crate::units::parse_service(
+        parsed_file,
+        &std::path::PathBuf::from("/path/to/unitfile.service"),
+    )
+    .is_err());
+}
@@ -0,0 +1,235 @@
+use crate::runtime_info::*;
+use crate::services::StdIo;
+use crate::signal_handler::ChildTermination;
+use crate::units::*;
+use std::convert::TryInto;
+
+fn load_run_info(dir: &std::path::Path) -> RuntimeInfo {
+    let unit_table =
+        crate::units::load_all_units(&[dir.to_owned()], "default.target", true).unwrap();
+    super::test_run_info(dir, vec![dir.to_owned()], unit_table)
+}
+
+#[test]
+fn test_reexec_state_roundtrip() {
+    let dir = super::test_dir("reexec");
+    std::fs::write(dir.join("default.target"), "[Unit]\nDescription = target\n").unwrap();
+    std::fs::write(
+        dir.join("running.service"),
+        "[Service]\nExecStart = /bin/true\n[Install]\nWantedBy = default.target\n",
+    )
+    .unwrap();
+
+    let srvc_id: UnitId = "running.service".try_into().unwrap();
+    let (pipe_r, pipe_w) = nix::unistd::pipe().unwrap();
+    let pid = nix::unistd::Pid::from_raw(4242);
+    let exited_pid = nix::unistd::Pid::from_raw(4343);
+
+    let old = load_run_info(&dir);
+    {
+        let unit = old.unit_table.get(&srvc_id).unwrap();
+        *unit.common.status.write().unwrap() = UnitStatus::Started(StatusStarted::Running);
+        if let Specific::Service(specific) = &unit.specific {
+            let mut state = specific.state.write().unwrap();
+            state.srvc.pid = Some(pid);
+            state.srvc.signaled_ready = true;
+            state.srvc.status_msgs.push("up".into());
+            state.srvc.stdout = Some(StdIo::Piped(pipe_r, pipe_w));
+            // a partial line of output that is not valid utf8
+            state.srvc.stdout_buffer = vec![b'a', 0xff, 0xfe];
+            state.common.restart_count = 2;
+        }
+    }
+    {
+        let mut pid_table = old.pid_table.lock().unwrap();
+        pid_table.insert(pid, PidEntry::Service(srvc_id.clone(), ServiceType::Simple));
+        pid_table.insert(
+            exited_pid,
+            PidEntry::ServiceExited(ChildTermination::Signal(nix::sys::signal::Signal::SIGKILL)),
+        );
+    }
+    old.fd_store.write().unwrap().insert_service_stored(
+        "running.service".into(),
+        "stored".into(),
+        vec![Box::new(pipe_r)],
+    );
+
+    // the state goes through a file like it does over the exec
+    let state_path = dir.join("state.json");
+    std::fs::write(
+        &state_path,
+        crate::reexec::serialize_state(&old).to_string(),
+    )
+    .unwrap();
+    let state = crate::reexec::load_state(&state_path).unwrap();
+    assert!(!state_path.exists());
+
+    let mut new = load_run_info(&dir);
+    crate::reexec::restore_state(&state, &mut new).unwrap();
+
+    let unit = new.unit_table.get(&srvc_id).unwrap();
+    assert_eq!(
+        *unit.common.status.read().unwrap(),
+        UnitStatus::Started(StatusStarted::Running)
+    );
+    if let Specific::Service(specific) = &unit.specific {
+        let state = specific.state.read().unwrap();
+        assert_eq!(state.srvc.pid, Some(pid));
+        assert!(state.srvc.signaled_ready);
+        assert_eq!(state.srvc.status_msgs, vec!["up".to_owned()]);
+        assert_eq!(state.srvc.stdout.as_ref().unwrap().read_fd(), pipe_r);
+        assert_eq!(state.srvc.stdout.as_ref().unwrap().write_fd(), pipe_w);
+        assert!(state.srvc.stderr.is_none());
+        assert_eq!(state.srvc.stdout_buffer, vec![b'a', 0xff, 0xfe]);
+        assert_eq!(state.common.restart_count, 2);
+    } else {
+        unreachable!();
+    }
+    let target_id: UnitId = "default.target".try_into().unwrap();
+    assert_eq!(
+        *new.unit_table
+            .get(&target_id)
+            .unwrap()
+            .common
+            .status
+            .read()
+            .unwrap(),
+        UnitStatus::NeverStarted
+    );
+
+    let pid_table = new.pid_table.lock().unwrap();
+    assert!(matches!(
+        pid_table.get(&pid),
+        Some(PidEntry::Service(id, ServiceType::Simple)) if *id == srvc_id
+    ));
+    assert!(matches!(
+        pid_table.get(&exited_pid),
+        Some(PidEntry::ServiceExited(ChildTermination::Signal(
+            nix::sys::signal::Signal::SIGKILL
+        )))
+    ));
+
+    let stored = new
+        .fd_store
+        .read()
+        .unwrap()
+        .get_service_stored(&"running.service".to_owned(), &"stored".to_owned())
+        .map(|fds| fds.iter().map(|fd| **fd).collect::<Vec<_>>());
+    assert_eq!(stored, Some(vec![pipe_r]));
+
+    // fds that were kept over the exec but that no unit took over are closed
+    let (unclaimed_r, unclaimed_w) = nix::unistd::pipe().unwrap();
+    crate::reexec::close_unclaimed_fds(&[pipe_r, pipe_w, unclaimed_r, unclaimed_w], &new);
+    let is_open = |fd| nix::fcntl::fcntl(fd, nix::fcntl::FcntlArg::F_GETFD).is_ok();
+    assert!(is_open(pipe_r) && is_open(pipe_w));
+    assert!(!is_open(unclaimed_r) && !is_open(unclaimed_w));
+
+    nix::unistd::close(pipe_r).unwrap();
+    nix::unistd::close(pipe_w).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_reexec_settles_changing_states() {
+    let dir = super::test_dir("reexec_settle");
+    std::fs::write(dir.join("default.target"), "[Unit]\nDescription = target\n").unwrap();
+    for name in &["starting", "stopping", "pre"] {
+        std::fs::write(
+            dir.join(format!("{}.service", name)),
+            "[Service]\nExecStart = /bin/true\n[Install]\nWantedBy = default.target\n",
+        )
+        .unwrap();
+    }
+    let starting_id: UnitId = "starting.service".try_into().unwrap();
+    let stopping_id: UnitId = "stopping.service".try_into().unwrap();
+    let pre_id: UnitId = "pre.service".try_into().unwrap();
+    let target_id: UnitId = "default.target".try_into().unwrap();
+
+    // the helper of pre.service that was running its ExecStartPre while the state was saved
+    let mut helper = std::process::Command::new("/bin/sleep")
+        .arg("100")
+        .spawn()
+        .unwrap();
+    let helper_pid = nix::unistd::Pid::from_raw(helper.id() as i32);
+    let pid = nix::unistd::Pid::from_raw(4242);
+
+    let old = load_run_info(&dir);
+    let set_status = |id: &UnitId, status: UnitStatus| {
+        *old.unit_table[id].common.status.write().unwrap() = status;
+    };
+    set_status(&starting_id, UnitStatus::Starting);
+    set_status(&stopping_id, UnitStatus::Stopping);
+    set_status(&pre_id, UnitStatus::Starting);
+    set_status(&target_id, UnitStatus::Restarting);
+    if let Specific::Service(specific) = &old.unit_table[&starting_id].specific {
+        specific.state.write().unwrap().srvc.pid = Some(pid);
+    }
+    {
+        let mut pid_table = old.pid_table.lock().unwrap();
+        pid_table.insert(
+            pid,
+            PidEntry::Service(starting_id.clone(), ServiceType::Simple),
+        );
+        pid_table.insert(
+            helper_pid,
+            PidEntry::Helper(pre_id.clone(), "pre.service".into()),
+        );
+    }
+    let state = crate::reexec::serialize_state(&old);
+
+    let mut new = load_run_info(&dir);
+    crate::reexec::restore_state(&state, &mut new).unwrap();
+
+    let status = |id: &UnitId| new.unit_table[id].common.status.read().unwrap().clone();
+    assert_eq!(
+        status(&starting_id),
+        UnitStatus::Started(StatusStarted::Running)
+    );
+    assert_eq!(
+        status(&stopping_id),
+        UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![])
+    );
+    assert_eq!(
+        status(&pre_id),
+        UnitStatus::Stopped(StatusStopped::StoppedUnexpected, vec![])
+    );
+    assert_eq!(
+        status(&target_id),
+        UnitStatus::Started(StatusStarted::Running)
+    );
+
+    // nothing waits for the helper anymore, so it is killed and forgotten
+    assert!(new.pid_table.lock().unwrap().get(&helper_pid).is_none());
+    use std::os::unix::process::ExitStatusExt;
+    assert_eq!(
+        helper.wait().unwrap().signal(),
+        Some(nix::sys::signal::Signal::SIGKILL as i32)
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_reexec_invalid_state_changes_nothing() {
+    let dir = super::test_dir("reexec_invalid");
+    std::fs::write(dir.join("default.target"), "[Unit]\nDescription = target\n").unwrap();
+    let target_id: UnitId = "default.target".try_into().unwrap();
+
+    let state = serde_json::json!({
+        "pids": [{"pid": 4242, "service_exited": {"exit": 0}}],
+        "units": [{"name": "default.target", "status": "Bogus"}],
+    });
+    let mut run_info = load_run_info(&dir);
+    assert!(crate::reexec::restore_state(&state, &mut run_info).is_err());
+    assert!(run_info.pid_table.lock().unwrap().is_empty());
+    assert_eq!(
+        *run_info.unit_table[&target_id]
+            .common
+            .status
+            .read()
+            .unwrap(),
+        UnitStatus::NeverStarted
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,151 @@
+use crate::runtime_info::*;
+use crate::units::*;
+use std::convert::TryInto;
+
+fn service_conf<'a>(run_info: &'a RuntimeInfo, name: &str) -> &'a ServiceConfig {
+    let id = UnitId {
+        kind: UnitIdKind::Service,
+        name: name.to_owned(),
+    };
+    match &run_info.unit_table.get(&id).unwrap().specific {
+        Specific::Service(srvc) => &srvc.conf,
+        _ => unreachable!(),
+    }
+}
+
+#[test]
+fn test_reload_all_units() {
+    let dir = super::test_dir("reload");
+    let write = |name: &str, content: &str| std::fs::write(dir.join(name), content).unwrap();
+    write("default.target", "[Unit]\nDescription = target\n");
+    for name in &["running", "stopped", "removed"] {
+        write(
+            &format!("{}.service", name),
+            "[Service]\nExecStart = /bin/true\n[Install]\nWantedBy = default.target\n",
+        );
+    }
+
+    let unit_table = crate::units::load_all_units(&[dir.clone()], "default.target", true).unwrap();
+    let mut run_info = super::test_run_info(&dir, vec![dir.clone()], unit_table);
+    for unit in run_info.unit_table.values() {
+        *unit.common.status.write().unwrap() =
+            UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![]);
+    }
+    let running_id: UnitId = "running.service".try_into().unwrap();
+    *run_info
+        .unit_table
+        .get(&running_id)
+        .unwrap()
+        .common
+        .status
+        .write()
+        .unwrap() = UnitStatus::Started(StatusStarted::Running);
+
+    // nothing changed
+    let result = reload_all_units(&mut run_info).unwrap();
+    assert!(result.added.is_empty());
+    assert!(result.changed.is_empty());
+    assert!(result.removed.is_empty());
+    assert!(result.needs_restart.is_empty());
+
+    let changed = "[Service]\nExecStart = /bin/false\n[Install]\nWantedBy = default.target\n";
+    write("running.service", changed);
+    write("stopped.service", changed);
+    write("added.service", changed);
+    std::fs::remove_file(dir.join("removed.service")).unwrap();
+
+    let mut result = reload_all_units(&mut run_info).unwrap();
+    result.changed.sort();
+    assert_eq!(result.added, vec!["added.service".to_owned()]);
+    // the target lost the wants to removed.service and gained the one to added.service
+    assert_eq!(
+        result.changed,
+        vec!["default.target".to_owned(), "stopped.service".to_owned()]
+    );
+    assert_eq!(result.removed, vec!["removed.service".to_owned()]);
+    assert_eq!(result.needs_restart, vec!["running.service".to_owned()]);
+
+    assert_eq!(
+        service_conf(&run_info, "stopped.service").exec[0].cmd,
+        "/bin/false"
+    );
+    assert_eq!(
+        service_conf(&run_info, "running.service").exec[0].cmd,
+        "/bin/true"
+    );
+
+    // the running unit keeps its config until it is stopped
+    apply_pending_reloads(&mut run_info);
+    assert!(run_info.pending_reload.contains_key(&running_id));
+    *run_info
+        .unit_table
+        .get(&running_id)
+        .unwrap()
+        .common
+        .status
+        .write()
+        .unwrap() = UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![]);
+    apply_pending_reloads(&mut run_info);
+    assert!(run_info.pending_reload.is_empty());
+    assert_eq!(
+        service_conf(&run_info, "running.service").exec[0].cmd,
+        "/bin/false"
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_reload_repairs_reverse_dependencies() {
+    let dir = super::test_dir("reload_reverse");
+    let write = |name: &str, content: &str| std::fs::write(dir.join(name), content).unwrap();
+    write("default.target", "[Unit]\nDescription = target\n");
+    write(
+        "changed.service",
+        "[Unit]\nWants = old.service\nAfter = old.service\n[Service]\nExecStart = /bin/true\n[Install]\nWantedBy = default.target\n",
+    );
+    for name in &["old", "new"] {
+        write(
+            &format!("{}.service", name),
+            "[Service]\nExecStart = /bin/true\n[Install]\nWantedBy = default.target\n",
+        );
+    }
+
+    let unit_table = crate::units::load_all_units(&[dir.clone()], "default.target", true).unwrap();
+    let mut run_info = super::test_run_info(&dir, vec![dir.clone()], unit_table);
+    for unit in run_info.unit_table.values() {
+        *unit.common.status.write().unwrap() =
+            UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![]);
+    }
+    // only the reverse edges of these change, they do not need a restart for that
+    let id = |name: &str| -> UnitId { name.try_into().unwrap() };
+    for name in &["old.service", "new.service"] {
+        *run_info.unit_table[&id(name)]
+            .common
+            .status
+            .write()
+            .unwrap() = UnitStatus::Started(StatusStarted::Running);
+    }
+
+    write(
+        "changed.service",
+        "[Unit]\nWants = new.service\nBefore = new.service\n[Service]\nExecStart = /bin/true\n[Install]\nWantedBy = default.target\n",
+    );
+    let result = reload_all_units(&mut run_info).unwrap();
+    assert_eq!(result.changed, vec!["changed.service".to_owned()]);
+    assert!(result.needs_restart.is_empty());
+
+    let deps = |name: &str| run_info.unit_table[&id(name)].common.dependencies.clone();
+    assert_eq!(deps("old.service").wanted_by, vec![id("default.target")]);
+    assert!(deps("old.service").before.is_empty());
+    assert!(deps("new.service")
+        .wanted_by
+        .contains(&id("changed.service")));
+    assert_eq!(deps("new.service").after, vec![id("changed.service")]);
+    assert_eq!(
+        deps("default.target").wants,
+        vec![id("changed.service"), id("new.service"), id("old.service")]
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
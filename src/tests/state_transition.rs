@@ -4,20 +4,12 @@ use std::convert::TryInto;
 
 #[test]
 fn test_service_state_transitions() {
-    let run_info = std::sync::Arc::new(std::sync::RwLock::new(RuntimeInfo {
-        config: crate::config::Config {
-            notification_sockets_dir: "./notifications".into(),
-            target_unit: "".into(),
-            unit_dirs: vec![],
-        },
-        fd_store: std::sync::RwLock::new(crate::fd_store::FDStore::default()),
-        pid_table: std::sync::Mutex::new(PidTable::default()),
-        unit_table: UnitTable::default(),
-        stdout_eventfd: crate::platform::make_event_fd().unwrap(),
-        stderr_eventfd: crate::platform::make_event_fd().unwrap(),
-        notification_eventfd: crate::platform::make_event_fd().unwrap(),
-        socket_activation_eventfd: crate::platform::make_event_fd().unwrap(),
-    }));
+    let dir = super::test_dir("state_transition");
+    let run_info = std::sync::Arc::new(std::sync::RwLock::new(super::test_run_info(
+        &dir,
+        vec![],
+        UnitTable::default(),
+    )));
 
     let signals = signal_hook::iterator::Signals::new(&[signal_hook::consts::SIGCHLD]).unwrap();
 
@@ -31,6 +23,8 @@ fn test_service_state_transitions() {
     // This needs to be used by all tests that need the signal handling, because else the signal handlers interfere.
     successful(run_info.clone());
     failing_startexec(run_info.clone());
+
+    std::fs::remove_dir_all(&dir).unwrap();
 }
 
 fn successful(run_info: ArcMutRuntimeInfo) {
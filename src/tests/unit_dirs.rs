@@ -0,0 +1,144 @@
+use crate::units::*;
+use std::convert::TryInto;
+
+fn id(name: &str) -> UnitId {
+    name.try_into().unwrap()
+}
+
+#[test]
+fn test_distro_unit_tree() {
+    let dir = super::test_dir("distro");
+    // like /etc/systemd/system and /usr/lib/systemd/system. The vendor dir with foo.service is not a unit dir
+    let etc = dir.join("etc");
+    let lib = dir.join("lib");
+    let vendor = dir.join("vendor");
+    for dir in &[&etc, &lib, &vendor] {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    let service = "[Service]\nExecStart = /bin/true\n";
+    std::fs::write(
+        lib.join("default.target"),
+        "[Unit]\nDescription = default\nWants = multi-user.target\n",
+    )
+    .unwrap();
+    std::fs::write(
+        lib.join("multi-user.target"),
+        "[Unit]\nDescription = multi user\n",
+    )
+    .unwrap();
+    std::fs::write(lib.join("bar.service"), service).unwrap();
+    std::fs::write(vendor.join("foo.service"), service).unwrap();
+    // files that are not supported units are ignored
+    std::fs::write(lib.join("tmp.mount"), "").unwrap();
+    std::fs::write(lib.join("README"), "").unwrap();
+    std::fs::create_dir_all(lib.join("bar.service.d")).unwrap();
+    std::fs::write(lib.join("bar.service.d").join("override.conf"), "").unwrap();
+
+    let wants = etc.join("multi-user.target.wants");
+    let requires = lib.join("default.target.requires");
+    std::fs::create_dir_all(&wants).unwrap();
+    std::fs::create_dir_all(&requires).unwrap();
+    std::os::unix::fs::symlink(vendor.join("foo.service"), wants.join("foo.service")).unwrap();
+    std::os::unix::fs::symlink("../bar.service", requires.join("bar.service")).unwrap();
+    // links to unsupported or missing units are ignored
+    std::os::unix::fs::symlink(lib.join("tmp.mount"), wants.join("tmp.mount")).unwrap();
+    std::os::unix::fs::symlink(lib.join("gone.service"), wants.join("gone.service")).unwrap();
+
+    let units = load_all_units(&[etc, lib], "default.target", false).unwrap();
+    let deps = |name: &str| &units[&id(name)].common.dependencies;
+
+    assert_eq!(units.len(), 4);
+    assert_eq!(deps("multi-user.target").wants, vec![id("foo.service")]);
+    assert_eq!(deps("foo.service").wanted_by, vec![id("multi-user.target")]);
+    assert_eq!(deps("default.target").requires, vec![id("bar.service")]);
+    assert_eq!(deps("bar.service").required_by, vec![id("default.target")]);
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_unit_dir_precedence() {
+    let dir = super::test_dir("precedence");
+    let etc = dir.join("etc");
+    let lib = dir.join("lib");
+    for dir in &[&etc, &lib] {
+        std::fs::create_dir_all(dir).unwrap();
+    }
+    std::fs::write(
+        lib.join("default.target"),
+        "[Unit]\nDescription = default\nWants = a.service,b.target,c.service,d.service\n",
+    )
+    .unwrap();
+    std::fs::write(
+        lib.join("a.service"),
+        "[Unit]\nDescription = lib\n[Service]\nExecStart = /bin/true\n",
+    )
+    .unwrap();
+    std::fs::write(
+        etc.join("a.service"),
+        "[Unit]\nDescription = etc\n[Service]\nExecStart = /bin/true\n",
+    )
+    .unwrap();
+    std::fs::write(
+        lib.join("b.target"),
+        "[Unit]\nDescription = masked\nWants = a.service\n",
+    )
+    .unwrap();
+    std::fs::write(etc.join("d.service"), "[Service]\nExecStart = /bin/true\n").unwrap();
+    // b is masked, c is masked without any unit file and the mask of d is overridden by the file in etc
+    std::os::unix::fs::symlink("/dev/null", etc.join("b.target")).unwrap();
+    std::os::unix::fs::symlink("/dev/null", etc.join("c.service")).unwrap();
+    std::os::unix::fs::symlink("/dev/null", lib.join("d.service")).unwrap();
+
+    let unit_table = load_all_units(&[etc.clone(), lib.clone()], "default.target", true).unwrap();
+    let unit = |name: &str| &unit_table[&id(name)].common.unit;
+
+    assert_eq!(unit_table.len(), 4);
+    assert_eq!(unit("a.service").description, "etc");
+    assert_eq!(
+        unit("a.service").shadowed_files,
+        vec![lib.join("a.service")]
+    );
+    assert!(unit("b.target").masked);
+    assert_eq!(unit("b.target").shadowed_files, vec![lib.join("b.target")]);
+    assert!(!unit("d.service").masked);
+    assert_eq!(
+        unit("d.service").shadowed_files,
+        vec![lib.join("d.service")]
+    );
+    // the masked unit does not pull in its own dependencies
+    assert_eq!(
+        unit_table[&id("b.target")].common.dependencies.wants,
+        Vec::<UnitId>::new()
+    );
+    assert_eq!(
+        unit_table[&id("default.target")].common.dependencies.wants,
+        vec![id("a.service"), id("b.target"), id("d.service")]
+    );
+
+    let run_info = super::test_run_info(&dir, vec![etc, lib], unit_table);
+    let masked = &run_info.unit_table[&id("b.target")];
+    let err = masked
+        .activate(&run_info, ActivationSource::Regular)
+        .unwrap_err();
+    assert_eq!(err.reason, UnitOperationErrorReason::Masked);
+    assert_eq!(
+        *masked.common.status.read().unwrap(),
+        UnitStatus::Stopped(
+            StatusStopped::StoppedFinal,
+            vec![UnitOperationErrorReason::Masked]
+        )
+    );
+    // a unit masked while it was running is left alone, it does not need to be started
+    *masked.common.status.write().unwrap() = UnitStatus::Started(StatusStarted::Running);
+    assert_eq!(
+        masked.activate(&run_info, ActivationSource::Regular),
+        Ok(UnitStatus::Started(StatusStarted::Running))
+    );
+    assert_eq!(
+        *masked.common.status.read().unwrap(),
+        UnitStatus::Started(StatusStarted::Running)
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
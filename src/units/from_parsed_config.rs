@@ -64,6 +64,9 @@ pub fn unit_from_parsed_service(conf: ParsedServiceConfig) -> Result<Unit, Strin
                 starttimeout: conf.srvc.starttimeout,
                 stoptimeout: conf.srvc.stoptimeout,
                 generaltimeout: conf.srvc.generaltimeout,
+                remain_after_exit: conf.srvc.remain_after_exit,
+                kill: conf.srvc.kill,
+                exit_status: conf.srvc.exit_status,
                 platform_specific,
             },
             state: RwLock::new(ServiceState {
@@ -75,6 +78,7 @@ pub fn unit_from_parsed_service(conf: ParsedServiceConfig) -> Result<Unit, Strin
                     signaled_ready: false,
                     notifications: None,
                     notifications_path: None,
+                    stdin: None,
                     stdout: None,
                     stderr: None,
                     notifications_buffer: String::new(),
@@ -107,10 +111,17 @@ pub fn unit_from_parsed_socket(conf: ParsedSocketConfig) -> Result<Unit, String>
                 filedesc_name: conf.sock.filedesc_name.unwrap_or("unknown".to_owned()),
                 services: services,
                 sockets: conf.sock.sockets.into_iter().map(Into::into).collect(),
+                trigger_limit: conf.sock.trigger_limit,
+                poll_limit: conf.sock.poll_limit,
             },
             state: RwLock::new(SocketState {
                 common: CommonState::default(),
-                sock: Socket { activated: false },
+                sock: Socket {
+                    activated: false,
+                    triggers: RateLimitCounter::default(),
+                    polls: RateLimitCounter::default(),
+                    polling_paused_until: None,
+                },
             }),
         }),
     })
@@ -142,7 +153,24 @@ impl From<ParsedSingleSocketConfig> for SingleSocketConfig {
 impl std::convert::TryFrom<ParsedExecSection> for ExecConfig {
     type Error = String;
     fn try_from(parsed: ParsedExecSection) -> Result<ExecConfig, String> {
-        let uid = if let Some(user) = &parsed.user {
+        let dynamic_user = if parsed.dynamic_user {
+            let user = parsed
+                .user
+                .clone()
+                .ok_or_else(|| "DynamicUser=yes needs a user name".to_owned())?;
+            let group = parsed.group.clone().unwrap_or_else(|| user.clone());
+            Some(DynamicUser { user, group })
+        } else {
+            None
+        };
+        // the ids of dynamic users are allocated when the service is started
+        let (user, group) = if dynamic_user.is_some() {
+            (None, None)
+        } else {
+            (parsed.user.clone(), parsed.group.clone())
+        };
+
+        let uid = if let Some(user) = &user {
             if let Ok(uid) = user.parse::<u32>() {
                 Some(nix::unistd::Uid::from_raw(uid))
             } else {
@@ -159,7 +187,7 @@ impl std::convert::TryFrom<ParsedExecSection> for ExecConfig {
         };
         let uid = uid.unwrap_or(nix::unistd::getuid());
 
-        let gid = if let Some(group) = &parsed.group {
+        let gid = if let Some(group) = &group {
             if let Ok(gid) = group.parse::<u32>() {
                 Some(nix::unistd::Gid::from_raw(gid))
             } else {
@@ -191,12 +219,48 @@ impl std::convert::TryFrom<ParsedExecSection> for ExecConfig {
             };
             supp_gids.push(gid);
         }
+        let working_directory = match parsed.working_directory {
+            Some(dir) if dir.path == std::path::Path::new("~") => {
+                // dynamic users do not have a home directory
+                let home = if dynamic_user.is_some() {
+                    std::path::PathBuf::from("/")
+                } else {
+                    match nix::unistd::User::from_uid(uid) {
+                        Ok(Some(user)) => user.dir,
+                        _ => return Err(format!("Couldnt get home directory for uid: {}", uid)),
+                    }
+                };
+                Some(SandboxPath {
+                    path: home,
+                    ignore_missing: dir.ignore_missing,
+                })
+            }
+            other => other,
+        };
+
         Ok(ExecConfig {
             user: uid,
             group: gid,
             supplementary_groups: supp_gids,
             stderr_path: parsed.stderr_path,
             stdout_path: parsed.stdout_path,
+            stdin_option: parsed.stdin_option,
+            tty_path: parsed.tty_path,
+            syslog_identifier: parsed.syslog_identifier,
+            syslog_facility: parsed.syslog_facility,
+            syslog_level: parsed.syslog_level,
+            syslog_level_prefix: parsed.syslog_level_prefix,
+            log_level_max: parsed.log_level_max,
+            sandbox: parsed.sandbox,
+            no_new_privileges: parsed.no_new_privileges,
+            dynamic_user,
+            directories: parsed.directories,
+            working_directory,
+            process: parsed.process,
+            limits: parsed.limits,
+            namespaces: parsed.namespaces,
+            capabilities: parsed.capabilities,
+            syscall_filter: parsed.syscall_filter,
             environment: parsed.environment,
         })
     }
@@ -244,6 +308,10 @@ fn make_common_from_parsed(
         unit: UnitConfig {
             description: unit.description,
             refs_by_name,
+            allow_isolate: unit.allow_isolate,
+            ignore_on_isolate: unit.ignore_on_isolate,
+            masked: false,
+            shadowed_files: Vec::new(),
         },
         dependencies: Dependencies {
             wants,
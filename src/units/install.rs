@@ -0,0 +1,389 @@
+//! Enable/disable/mask units by managing the symlinks in the install dir from the config, like systemctl does in /etc/systemd/system.
+//!
+//! 1. enable creates <install_dir>/<X>.wants/<unit> for each WantedBy=X, <install_dir>/<X>.requires/<unit> for each RequiredBy=X and
+//!    <install_dir>/<alias> for each Alias=. All point to the unit file. The units in Also= are enabled too
+//! 1. disable removes these symlinks again
+//! 1. mask creates <install_dir>/<unit> pointing to /dev/null, unmask removes it
+//! 1. preset enables or disables the unit according to the *.preset files in the preset dirs
+//!
+//! The changes take effect with the next daemon-reload.
+
+use log::trace;
+
+use crate::config::Config;
+use crate::units::*;
+
+use std::convert::TryInto;
+use std::path::{Path, PathBuf};
+
+/// What is_enabled reports for a unit
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EnableState {
+    /// There are symlinks for the unit in a .wants/.requires dir or for one of its aliases
+    Enabled,
+    /// The unit has an [Install] section but no symlinks
+    Disabled,
+    /// The unit has no [Install] section, it can only be pulled in by other units
+    Static,
+    /// The unit is symlinked to /dev/null
+    Masked,
+}
+
+impl EnableState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            EnableState::Enabled => "enabled",
+            EnableState::Disabled => "disabled",
+            EnableState::Static => "static",
+            EnableState::Masked => "masked",
+        }
+    }
+}
+
+/// The symlinks that were created and removed
+#[derive(Default, Debug)]
+pub struct InstallChanges {
+    pub created: Vec<PathBuf>,
+    pub removed: Vec<PathBuf>,
+}
+
+fn install_dir(conf: &Config) -> Result<&PathBuf, String> {
+    conf.install_dir
+        .as_ref()
+        .ok_or_else(|| "No install_dir is set in the config".to_owned())
+}
+
+fn is_link_dir(path: &Path) -> bool {
+    let name = path.to_string_lossy();
+    path.is_dir() && (name.ends_with(".wants") || name.ends_with(".requires"))
+}
+
+fn is_masked_link(path: &Path) -> bool {
+    std::fs::read_link(path)
+        .map(|target| target == Path::new("/dev/null"))
+        .unwrap_or(false)
+}
+
+fn find_in_dir(dir: &Path, name: &str) -> Option<PathBuf> {
+    let path = dir.join(name);
+    if path.symlink_metadata().is_ok() {
+        return Some(path);
+    }
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.is_dir() && !is_link_dir(path))
+        .collect();
+    entries.sort();
+    entries.iter().find_map(|subdir| find_in_dir(subdir, name))
+}
+
+/// Find the unit file in the unit dirs. Returns the path of the file itself (symlinks resolved), so the name can differ if
+/// an alias was given.
+fn find_unit_file(conf: &Config, name: &str) -> Result<PathBuf, String> {
+    let _: UnitId = name.try_into()?;
+    for dir in &conf.unit_dirs {
+        if let Some(path) = find_in_dir(dir, name) {
+            if is_masked_link(&path) {
+                return Err(format!("Unit {} is masked", name));
+            }
+            return std::fs::canonicalize(&path)
+                .map_err(|e| format!("Could not resolve {:?}: {}", path, e));
+        }
+    }
+    Err(format!("Cannot find unit file for unit: {}", name))
+}
+
+fn read_install_section(path: &Path) -> Result<ParsedInstallSection, String> {
+    let content = std::fs::read_to_string(path)
+        .map_err(|e| format!("Could not read unit file {:?}: {}", path, e))?;
+    let mut parsed =
+        parse_file(&content).map_err(|e| format!("{}", ParsingError::new(e, path.to_owned())))?;
+    match parsed.remove("[Install]") {
+        Some(section) => parse_install_section(section)
+            .map_err(|e| format!("{}", ParsingError::new(e, path.to_owned()))),
+        None => Ok(ParsedInstallSection::default()),
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default()
+}
+
+fn create_link(link: PathBuf, target: &Path, changes: &mut InstallChanges) -> Result<(), String> {
+    if let Ok(meta) = link.symlink_metadata() {
+        if !meta.file_type().is_symlink() {
+            return Err(format!("{:?} exists and is not a symlink", link));
+        }
+        if std::fs::read_link(&link).ok().as_deref() == Some(target) {
+            return Ok(());
+        }
+        std::fs::remove_file(&link).map_err(|e| format!("Could not remove {:?}: {}", link, e))?;
+        changes.removed.push(link.clone());
+    }
+    if let Some(parent) = link.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|e| format!("Could not create dir {:?}: {}", parent, e))?;
+    }
+    trace!("Create symlink {:?} -> {:?}", link, target);
+    std::os::unix::fs::symlink(target, &link)
+        .map_err(|e| format!("Could not create symlink {:?}: {}", link, e))?;
+    changes.created.push(link);
+    Ok(())
+}
+
+fn remove_link(link: PathBuf, changes: &mut InstallChanges) -> Result<(), String> {
+    trace!("Remove symlink {:?}", link);
+    std::fs::remove_file(&link).map_err(|e| format!("Could not remove {:?}: {}", link, e))?;
+    changes.removed.push(link);
+    Ok(())
+}
+
+/// The .wants/.requires dirs in the install dir
+fn link_dirs(install_dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(install_dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| is_link_dir(path))
+                .collect()
+        })
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
+
+/// Create the symlinks for the [Install] section of the unit and the units in its Also=
+pub fn enable_unit(conf: &Config, name: &str, changes: &mut InstallChanges) -> Result<(), String> {
+    enable_unit_rec(conf, name, changes, &mut Vec::new())
+}
+
+fn enable_unit_rec(
+    conf: &Config,
+    name: &str,
+    changes: &mut InstallChanges,
+    done: &mut Vec<String>,
+) -> Result<(), String> {
+    if done.iter().any(|done| done == name) {
+        return Ok(());
+    }
+    done.push(name.to_owned());
+    let install_dir = install_dir(conf)?;
+    let path = find_unit_file(conf, name)?;
+    let unit_name = file_name(&path);
+    let install = read_install_section(&path)?;
+
+    for by in &install.wanted_by {
+        let _: UnitId = by.as_str().try_into()?;
+        let link = install_dir.join(format!("{}.wants", by)).join(&unit_name);
+        create_link(link, &path, changes)?;
+    }
+    for by in &install.required_by {
+        let _: UnitId = by.as_str().try_into()?;
+        let link = install_dir
+            .join(format!("{}.requires", by))
+            .join(&unit_name);
+        create_link(link, &path, changes)?;
+    }
+    for alias in &install.alias {
+        let _: UnitId = alias.as_str().try_into()?;
+        create_link(install_dir.join(alias), &path, changes)?;
+    }
+    for also in &install.also {
+        enable_unit_rec(conf, also, changes, done)?;
+    }
+    Ok(())
+}
+
+/// Remove all symlinks to the unit from the install dir and disable the units in its Also=
+pub fn disable_unit(conf: &Config, name: &str, changes: &mut InstallChanges) -> Result<(), String> {
+    disable_unit_rec(conf, name, changes, &mut Vec::new())
+}
+
+fn disable_unit_rec(
+    conf: &Config,
+    name: &str,
+    changes: &mut InstallChanges,
+    done: &mut Vec<String>,
+) -> Result<(), String> {
+    if done.iter().any(|done| done == name) {
+        return Ok(());
+    }
+    done.push(name.to_owned());
+    let install_dir = install_dir(conf)?;
+    // a masked unit can still be disabled, its links are found by name
+    let path = find_unit_file(conf, name).ok();
+    let unit_name = path
+        .as_deref()
+        .map(file_name)
+        .unwrap_or_else(|| name.to_owned());
+    let install = match &path {
+        Some(path) => read_install_section(path)?,
+        None => ParsedInstallSection::default(),
+    };
+
+    let points_to_unit = |link: &Path| match (&path, std::fs::canonicalize(link)) {
+        (Some(path), Ok(target)) => target == *path,
+        _ => false,
+    };
+    for dir in link_dirs(install_dir) {
+        let link = dir.join(&unit_name);
+        if link.symlink_metadata().is_ok() {
+            remove_link(link, changes)?;
+        }
+        let is_empty = std::fs::read_dir(&dir)
+            .map(|mut entries| entries.next().is_none())
+            .unwrap_or(false);
+        if is_empty {
+            let _ = std::fs::remove_dir(&dir);
+        }
+    }
+    for alias in &install.alias {
+        let link = install_dir.join(alias);
+        if points_to_unit(&link) {
+            remove_link(link, changes)?;
+        }
+    }
+    for also in &install.also {
+        disable_unit_rec(conf, also, changes, done)?;
+    }
+    Ok(())
+}
+
+/// Symlink the unit to /dev/null in the install dir so it can not be loaded
+pub fn mask_unit(conf: &Config, name: &str, changes: &mut InstallChanges) -> Result<(), String> {
+    let _: UnitId = name.try_into()?;
+    let link = install_dir(conf)?.join(name);
+    if is_masked_link(&link) {
+        return Ok(());
+    }
+    if link.symlink_metadata().is_ok() {
+        return Err(format!(
+            "{:?} exists already, remove it to mask the unit",
+            link
+        ));
+    }
+    create_link(link, Path::new("/dev/null"), changes)
+}
+
+/// Remove the /dev/null symlink of the unit from the install dir
+pub fn unmask_unit(conf: &Config, name: &str, changes: &mut InstallChanges) -> Result<(), String> {
+    let link = install_dir(conf)?.join(name);
+    if is_masked_link(&link) {
+        remove_link(link, changes)?;
+    }
+    Ok(())
+}
+
+fn has_links(conf: &Config, unit_name: &str, install: &ParsedInstallSection) -> bool {
+    for dir in &conf.unit_dirs {
+        if link_dirs(dir)
+            .iter()
+            .any(|link_dir| link_dir.join(unit_name).symlink_metadata().is_ok())
+        {
+            return true;
+        }
+    }
+    match &conf.install_dir {
+        Some(install_dir) => install
+            .alias
+            .iter()
+            .any(|alias| install_dir.join(alias).symlink_metadata().is_ok()),
+        None => false,
+    }
+}
+
+/// Whether the unit is enabled. Without an install dir the [Install] sections are applied directly, so every unit that has one is enabled.
+pub fn unit_enable_state(conf: &Config, name: &str) -> Result<EnableState, String> {
+    for dir in &conf.unit_dirs {
+        if let Some(path) = find_in_dir(dir, name) {
+            if is_masked_link(&path) {
+                return Ok(EnableState::Masked);
+            }
+            break;
+        }
+    }
+    let path = find_unit_file(conf, name)?;
+    let install = read_install_section(&path)?;
+    let has_install_section = !install.wanted_by.is_empty()
+        || !install.required_by.is_empty()
+        || !install.alias.is_empty()
+        || !install.also.is_empty();
+
+    let state = if conf.apply_install_sections() {
+        if install.wanted_by.is_empty() && install.required_by.is_empty() {
+            EnableState::Static
+        } else {
+            EnableState::Enabled
+        }
+    } else if has_links(conf, &file_name(&path), &install) {
+        EnableState::Enabled
+    } else if has_install_section {
+        EnableState::Disabled
+    } else {
+        EnableState::Static
+    };
+    Ok(state)
+}
+
+/// Match a unit name against a pattern from a preset file. Supports * and ?
+fn pattern_matches(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some('*') => (0..=name.len()).any(|skip| pattern_matches(&pattern[1..], &name[skip..])),
+        Some('?') => !name.is_empty() && pattern_matches(&pattern[1..], &name[1..]),
+        Some(c) => name.first() == Some(c) && pattern_matches(&pattern[1..], &name[1..]),
+    }
+}
+
+/// Find the preset for the unit. The *.preset files of all preset dirs are read sorted by their file name (a file in an earlier dir
+/// replaces one with the same name in a later dir) and the first line that matches decides. Units without a matching line are enabled.
+pub fn preset_enables(conf: &Config, name: &str) -> Result<bool, String> {
+    let mut files: Vec<(String, PathBuf)> = Vec::new();
+    for dir in &conf.preset_dirs {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| format!("Could not read preset dir {:?}: {}", dir, e))?;
+        for entry in entries.filter_map(|entry| entry.ok()) {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            if file_name.ends_with(".preset") && !files.iter().any(|(name, _)| *name == file_name) {
+                files.push((file_name, entry.path()));
+            }
+        }
+    }
+    files.sort();
+
+    let name: Vec<char> = name.chars().collect();
+    for (_, path) in files {
+        let content = std::fs::read_to_string(&path)
+            .map_err(|e| format!("Could not read preset file {:?}: {}", path, e))?;
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            let (enable, pattern) = if let Some(pattern) = line.strip_prefix("enable ") {
+                (true, pattern)
+            } else if let Some(pattern) = line.strip_prefix("disable ") {
+                (false, pattern)
+            } else {
+                return Err(format!("Invalid line in preset file {:?}: {}", path, line));
+            };
+            let pattern: Vec<char> = pattern.trim().chars().collect();
+            if pattern_matches(&pattern, &name) {
+                return Ok(enable);
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// Enable or disable the unit according to the preset files
+pub fn preset_unit(conf: &Config, name: &str, changes: &mut InstallChanges) -> Result<(), String> {
+    let unit_name = file_name(&find_unit_file(conf, name)?);
+    if preset_enables(conf, &unit_name)? {
+        enable_unit(conf, name, changes)
+    } else {
+        disable_unit(conf, name, changes)
+    }
+}
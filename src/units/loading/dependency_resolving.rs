@@ -23,8 +23,23 @@ pub fn prune_units(
     } else {
         return Err(format!("Target unit {} not found", target_unit_name));
     };
-    // This vec will record the unit ids that will be kept
-    let mut ids_to_keep = vec![startunit_id.clone()];
+    // masked units are kept so starting them fails with a clear error instead of not finding them
+    let mut ids_to_keep = vec![startunit_id];
+    ids_to_keep.extend(
+        unit_table
+            .values()
+            .filter(|unit| unit.common.unit.masked)
+            .map(|unit| unit.id.clone()),
+    );
+    prune_units_not_needed_by(ids_to_keep, unit_table);
+    Ok(())
+}
+
+/// Takes a set of units and prunes those that are not needed to start any of the units in ids_to_keep
+pub fn prune_units_not_needed_by(
+    mut ids_to_keep: Vec<UnitId>,
+    unit_table: &mut HashMap<UnitId, Unit>,
+) {
     crate::units::collect_unit_start_subgraph(&mut ids_to_keep, unit_table);
 
     // walk the tree along the wants/requires/before/... relations and record which ids are needed
@@ -122,7 +137,6 @@ pub fn prune_units(
 
         unit.dedup_dependencies();
     }
-    Ok(())
 }
 
 /// make edges between units visible on bot sides: required <-> required_by  after <-> before
@@ -267,6 +281,10 @@ fn apply_sockets_to_services(unit_table: &mut UnitTable) -> Result<(), String> {
         }
     }
 
+    // always match in the same order so the sockets of a service are listed in the same order every time the units are loaded
+    service_ids.sort();
+    socket_ids.sort();
+
     for sock_unit in &socket_ids {
         let mut sock_unit = unit_table.remove(sock_unit).unwrap();
         let mut counter = 0;
@@ -1,6 +1,7 @@
 mod dependency_resolving;
 pub use dependency_resolving::*;
 use log::trace;
+use log::warn;
 
 use crate::runtime_info::*;
 use crate::units::*;
@@ -47,15 +48,70 @@ impl std::convert::From<ParsingError> for LoadingError {
 pub fn load_all_units(
     paths: &[PathBuf],
     target_unit: &str,
+    apply_install_sections: bool,
+) -> Result<HashMap<UnitId, Unit>, LoadingError> {
+    let mut unit_table = load_all_unit_files(paths, apply_install_sections)?;
+
+    prune_units(target_unit, &mut unit_table).unwrap();
+    trace!("Finished pruning units");
+
+    prune_sockets(&mut unit_table);
+
+    Ok(unit_table)
+}
+
+/// A symlink in a <unit>.wants/ or <unit>.requires/ dir
+struct InstallLink {
+    unit: UnitId,
+    /// The unit whose .wants/.requires dir this link is in
+    by: UnitId,
+    required: bool,
+    /// Where the link points to. The unit is loaded from there if it is not in any unit dir (e.g. distro trees where the units
+    /// are in /usr/lib/systemd/system but only /etc/systemd/system is a unit dir)
+    target: Option<PathBuf>,
+}
+
+/// What was found in the unit dirs besides the unit files
+#[derive(Default)]
+struct InstallLinks {
+    links: Vec<InstallLink>,
+    /// Units that are symlinked to /dev/null
+    masked: Vec<UnitId>,
+    /// Symlinks to unit files with another name (created from Alias=)
+    aliases: HashMap<UnitId, UnitId>,
+}
+
+/// Which file is used for each unit name. Like with systemd the unit dirs are searched in order and the first file
+/// with a name wins, so earlier dirs override later ones (e.g. /etc/systemd/system over /usr/lib/systemd/system)
+#[derive(Default)]
+struct UnitFiles {
+    used: HashMap<UnitId, PathBuf>,
+    /// Files in later dirs that are overridden by the used one
+    shadowed: HashMap<UnitId, Vec<PathBuf>>,
+}
+
+/// Parse all unit files in these paths and resolve their dependencies. Nothing is pruned.
+///
+/// If the same unit name is found in multiple dirs the file in the earlier dir is used, see UnitFiles.
+///
+/// If apply_install_sections is false WantedBy=/RequiredBy= are ignored and only the symlinks in the .wants/.requires dirs
+/// (created by enable) are used.
+pub fn load_all_unit_files(
+    paths: &[PathBuf],
+    apply_install_sections: bool,
 ) -> Result<HashMap<UnitId, Unit>, LoadingError> {
     let mut service_unit_table = HashMap::new();
     let mut socket_unit_table = HashMap::new();
     let mut target_unit_table = HashMap::new();
+    let mut install_links = InstallLinks::default();
+    let mut unit_files = UnitFiles::default();
     for path in paths {
         parse_all_units(
             &mut service_unit_table,
             &mut socket_unit_table,
             &mut target_unit_table,
+            &mut install_links,
+            &mut unit_files,
             path,
         )?;
     }
@@ -65,19 +121,172 @@ pub fn load_all_units(
     unit_table.extend(socket_unit_table);
     unit_table.extend(target_unit_table);
 
+    load_link_targets(&mut unit_table, &install_links)?;
+    let missing_masked = load_masked_units(&mut unit_table, &install_links, &unit_files)?;
+    for (id, shadowed) in unit_files.shadowed {
+        if let Some(unit) = unit_table.get_mut(&id) {
+            unit.common.unit.shadowed_files = shadowed;
+        }
+    }
+    if !apply_install_sections {
+        for unit in unit_table.values_mut() {
+            drop_install_section(unit);
+        }
+    }
+    apply_install_links(&mut unit_table, install_links);
+    if !missing_masked.is_empty() {
+        forget_missing_units(&mut unit_table, &missing_masked);
+    }
+
     trace!("Units found: {}", unit_table.len());
 
     fill_dependencies(&mut unit_table).map_err(|e| LoadingError::Dependency(e.into()))?;
 
-    prune_units(target_unit, &mut unit_table).unwrap();
-    trace!("Finished pruning units");
+    Ok(unit_table)
+}
 
-    let removed_ids = prune_unused_sockets(&mut unit_table);
-    trace!("Finished pruning sockets");
+/// Forget the WantedBy=/RequiredBy= of the unit, it only gets these through the symlinks of enable
+fn drop_install_section(unit: &mut Unit) {
+    let deps = &mut unit.common.dependencies;
+    let dropped: Vec<UnitId> = deps
+        .wanted_by
+        .drain(..)
+        .chain(deps.required_by.drain(..))
+        .collect();
+    let still_referenced = |id: &UnitId| {
+        deps.wants.contains(id)
+            || deps.requires.contains(id)
+            || deps.before.contains(id)
+            || deps.after.contains(id)
+    };
+    unit.common
+        .unit
+        .refs_by_name
+        .retain(|id| !dropped.contains(id) || still_referenced(id));
+}
 
-    cleanup_removed_ids(&mut unit_table, &removed_ids);
+/// Add the relations from the .wants/.requires symlinks as if the units had WantedBy=/RequiredBy= and point references to aliases
+/// to the real units
+fn apply_install_links(unit_table: &mut HashMap<UnitId, Unit>, install_links: InstallLinks) {
+    let aliases = install_links.aliases;
+    let resolve = |id: UnitId| aliases.get(&id).cloned().unwrap_or(id);
 
-    Ok(unit_table)
+    if !aliases.is_empty() {
+        for unit in unit_table.values_mut() {
+            let deps = &mut unit.common.dependencies;
+            for ids in &mut [
+                &mut deps.wants,
+                &mut deps.wanted_by,
+                &mut deps.requires,
+                &mut deps.required_by,
+                &mut deps.before,
+                &mut deps.after,
+                &mut unit.common.unit.refs_by_name,
+            ] {
+                for id in ids.iter_mut() {
+                    *id = resolve(id.clone());
+                }
+            }
+        }
+    }
+
+    for link in install_links.links {
+        let id = resolve(link.unit);
+        let by = resolve(link.by);
+        let required = link.required;
+        if !unit_table.contains_key(&by) {
+            warn!(
+                "Ignore the link to {} because {} does not exist",
+                id.name, by.name
+            );
+            continue;
+        }
+        match unit_table.get_mut(&id) {
+            Some(unit) => {
+                let deps = &mut unit.common.dependencies;
+                if required {
+                    deps.required_by.push(by.clone());
+                } else {
+                    deps.wanted_by.push(by.clone());
+                }
+                unit.common.unit.refs_by_name.push(by);
+            }
+            None => warn!(
+                "Ignore the link from {} because {} does not exist",
+                by.name, id.name
+            ),
+        }
+    }
+}
+
+/// Masked units are loaded from the first file that the mask shadows, so they can be shown and refuse to start. They
+/// do not pull in other units themselves. Returns the masked units for which no file exists.
+fn load_masked_units(
+    unit_table: &mut HashMap<UnitId, Unit>,
+    install_links: &InstallLinks,
+    unit_files: &UnitFiles,
+) -> Result<Vec<UnitId>, ParsingError> {
+    let mut missing = Vec::new();
+    for id in &install_links.masked {
+        let mut masked_unit = None;
+        let shadowed = unit_files
+            .shadowed
+            .get(id)
+            .map(Vec::as_slice)
+            .unwrap_or(&[]);
+        for path in shadowed {
+            if path.is_file() && !is_masked_link(path) {
+                masked_unit = parse_unit_file(path)?;
+                break;
+            }
+        }
+        match masked_unit {
+            Some(mut unit) => {
+                trace!("Unit {} is masked", id.name);
+                let common = &mut unit.common;
+                common.unit.masked = true;
+                common.unit.refs_by_name.clear();
+                common.dependencies = Dependencies::default();
+                unit_table.insert(id.clone(), unit);
+            }
+            None => {
+                trace!("Unit {} is masked and has no unit file", id.name);
+                unit_table.remove(id);
+                missing.push(id.clone());
+            }
+        }
+    }
+    Ok(missing)
+}
+
+fn is_masked_link(path: &PathBuf) -> bool {
+    std::fs::canonicalize(path)
+        .map(|target| target == std::path::Path::new("/dev/null"))
+        .unwrap_or(false)
+}
+
+/// Drop all references to units that do not exist, so a unit that wants a masked unit can still be loaded
+fn forget_missing_units(unit_table: &mut HashMap<UnitId, Unit>, missing: &Vec<UnitId>) {
+    cleanup_removed_ids(unit_table, missing);
+    for unit in unit_table.values_mut() {
+        unit.common
+            .unit
+            .refs_by_name
+            .retain(|id| !missing.contains(id));
+        match &mut unit.specific {
+            Specific::Service(specific) => specific.conf.sockets.retain(|id| !missing.contains(id)),
+            Specific::Socket(specific) => specific.conf.services.retain(|id| !missing.contains(id)),
+            Specific::Target(_) => {}
+        }
+    }
+}
+
+/// Remove the sockets that do not belong to any service (anymore) after pruning
+pub fn prune_sockets(unit_table: &mut HashMap<UnitId, Unit>) {
+    let removed_ids = prune_unused_sockets(unit_table);
+    trace!("Finished pruning sockets");
+
+    cleanup_removed_ids(unit_table, &removed_ids);
 }
 
 fn cleanup_removed_ids(
@@ -110,53 +319,189 @@ fn prune_unused_sockets(sockets: &mut UnitTable) -> Vec<UnitId> {
     ids_to_remove
 }
 
+/// Read the symlinks in a <unit>.wants/ or <unit>.requires/ dir
+fn parse_link_dir(dir: &PathBuf, install_links: &mut InstallLinks) -> Result<(), ParsingError> {
+    let dir_name = dir.file_name().unwrap().to_string_lossy().into_owned();
+    let (by_name, required) = match dir_name.strip_suffix(".wants") {
+        Some(name) => (name.to_owned(), false),
+        None => (dir_name.trim_end_matches(".requires").to_owned(), true),
+    };
+    let by: UnitId = match by_name.as_str().try_into() {
+        Ok(id) => id,
+        Err(e) => {
+            warn!("Ignore dir {:?}: {}", dir, e);
+            return Ok(());
+        }
+    };
+    let files = get_file_list(dir).map_err(|e| ParsingError::new(e, dir.clone()))?;
+    for entry in files {
+        let name = entry.file_name().to_string_lossy().into_owned();
+        match name.as_str().try_into() {
+            Ok(unit) => install_links.links.push(InstallLink {
+                unit,
+                by: by.clone(),
+                required,
+                target: std::fs::canonicalize(entry.path()).ok(),
+            }),
+            Err(e) => warn!("Ignore {:?}: {}", entry.path(), e),
+        }
+    }
+    Ok(())
+}
+
+/// Load the units that are only found as the target of a link in a .wants/.requires dir
+fn load_link_targets(
+    unit_table: &mut HashMap<UnitId, Unit>,
+    install_links: &InstallLinks,
+) -> Result<(), ParsingError> {
+    for link in &install_links.links {
+        if unit_table.contains_key(&link.unit)
+            || install_links.aliases.contains_key(&link.unit)
+            || install_links.masked.contains(&link.unit)
+        {
+            continue;
+        }
+        let target = match &link.target {
+            Some(target) if target.is_file() => target,
+            _ => continue,
+        };
+        if let Some(unit) = parse_unit_file(target)? {
+            if unit.id != link.unit {
+                warn!(
+                    "Ignore link {} in the dir of {} because it points to {:?}",
+                    link.unit.name, link.by.name, target
+                );
+                continue;
+            }
+            trace!(
+                "Load {} from the target of its link: {:?}",
+                unit.id.name,
+                target
+            );
+            unit_table.insert(unit.id.clone(), unit);
+        }
+    }
+    Ok(())
+}
+
+/// Parse a unit file. Returns None for files that are no supported unit type (like *.mount or *.conf files of drop-in dirs)
+fn parse_unit_file(path: &PathBuf) -> Result<Option<Unit>, ParsingError> {
+    let name = path.to_string_lossy();
+    if !(name.ends_with(".service") || name.ends_with(".socket") || name.ends_with(".target")) {
+        return Ok(None);
+    }
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| ParsingError::new(ParsingErrorReason::from(Box::new(e)), path.clone()))?;
+
+    let parsed_file = parse_file(&raw)
+        .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?;
+
+    let unit: Result<Unit, String> = if name.ends_with(".service") {
+        trace!("Service found: {:?}", path);
+        parse_service(parsed_file, path)
+            .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?
+            .try_into()
+    } else if name.ends_with(".socket") {
+        trace!("Socket found: {:?}", path);
+        parse_socket(parsed_file, path)
+            .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?
+            .try_into()
+    } else {
+        trace!("Target found: {:?}", path);
+        parse_target(parsed_file, path)
+            .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?
+            .try_into()
+    };
+    unit.map(Some)
+        .map_err(|err| ParsingError::new(ParsingErrorReason::Generic(err), path.clone()))
+}
+
+/// For symlinks: the unit is masked if it points to /dev/null, and it is an alias if it points to a file with another name
+fn check_symlink(entry: &std::fs::DirEntry, install_links: &mut InstallLinks) -> bool {
+    let is_symlink = entry
+        .file_type()
+        .map(|file_type| file_type.is_symlink())
+        .unwrap_or(false);
+    if !is_symlink {
+        return false;
+    }
+    let name = entry.file_name().to_string_lossy().into_owned();
+    let id: UnitId = match name.as_str().try_into() {
+        Ok(id) => id,
+        Err(_) => return false,
+    };
+    let target = match std::fs::canonicalize(entry.path()) {
+        Ok(target) => target,
+        Err(e) => {
+            warn!("Ignore broken symlink {:?}: {}", entry.path(), e);
+            return true;
+        }
+    };
+    if target == std::path::Path::new("/dev/null") {
+        trace!("{} is masked by {:?}", name, entry.path());
+        install_links.masked.push(id);
+        return true;
+    }
+    let target_name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if target_name != name {
+        if let Ok(target_id) = target_name.as_str().try_into() {
+            trace!("{} is an alias of {}", name, target_name);
+            install_links.aliases.insert(id, target_id);
+            return true;
+        }
+    }
+    false
+}
+
 fn parse_all_units(
     services: &mut std::collections::HashMap<UnitId, Unit>,
     sockets: &mut std::collections::HashMap<UnitId, Unit>,
     targets: &mut std::collections::HashMap<UnitId, Unit>,
+    install_links: &mut InstallLinks,
+    unit_files: &mut UnitFiles,
     path: &PathBuf,
 ) -> Result<(), ParsingError> {
     let files = get_file_list(path)
         .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?;
     for entry in files {
-        if entry.path().is_dir() {
-            parse_all_units(services, sockets, targets, path)?;
-        } else {
-            let raw = std::fs::read_to_string(&entry.path()).map_err(|e| {
-                ParsingError::new(ParsingErrorReason::from(Box::new(e)), path.clone())
-            })?;
-
-            let parsed_file = parse_file(&raw)
-                .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?;
-
-            if entry.path().to_str().unwrap().ends_with(".service") {
-                trace!("Service found: {:?}", entry.path());
-                let unit: Unit = parse_service(parsed_file, &entry.path())
-                    .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?
-                    .try_into()
-                    .map_err(|err| {
-                        ParsingError::new(ParsingErrorReason::Generic(err), path.clone())
-                    })?;
-                services.insert(unit.id.clone(), unit);
-            } else if entry.path().to_str().unwrap().ends_with(".socket") {
-                trace!("Socket found: {:?}", entry.path());
-                let unit: Unit = parse_socket(parsed_file, &entry.path())
-                    .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?
-                    .try_into()
-                    .map_err(|err| {
-                        ParsingError::new(ParsingErrorReason::Generic(err), path.clone())
-                    })?;
-                sockets.insert(unit.id.clone(), unit);
-            } else if entry.path().to_str().unwrap().ends_with(".target") {
-                trace!("Target found: {:?}", entry.path());
-                let unit: Unit = parse_target(parsed_file, &entry.path())
-                    .map_err(|e| ParsingError::new(ParsingErrorReason::from(e), path.clone()))?
-                    .try_into()
-                    .map_err(|err| {
-                        ParsingError::new(ParsingErrorReason::Generic(err), path.clone())
-                    })?;
-                targets.insert(unit.id.clone(), unit);
+        let entry_path = entry.path();
+        if entry_path.is_dir() {
+            let dir_name = entry.file_name().to_string_lossy().into_owned();
+            if dir_name.ends_with(".wants") || dir_name.ends_with(".requires") {
+                parse_link_dir(&entry_path, install_links)?;
+            } else {
+                parse_all_units(
+                    services,
+                    sockets,
+                    targets,
+                    install_links,
+                    unit_files,
+                    &entry_path,
+                )?;
             }
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Ok(id) = TryInto::<UnitId>::try_into(name.as_str()) {
+            if let Some(used) = unit_files.used.get(&id) {
+                trace!("{:?} is shadowed by {:?}", entry_path, used);
+                unit_files.shadowed.entry(id).or_default().push(entry_path);
+                continue;
+            }
+            unit_files.used.insert(id, entry_path.clone());
+        }
+        if check_symlink(&entry, install_links) {
+            // masked or an alias, nothing to parse
+        } else if let Some(unit) = parse_unit_file(&entry_path)? {
+            let table = match unit.id.kind {
+                UnitIdKind::Service => &mut *services,
+                UnitIdKind::Socket => &mut *sockets,
+                UnitIdKind::Target => &mut *targets,
+            };
+            table.insert(unit.id.clone(), unit);
         }
     }
     Ok(())
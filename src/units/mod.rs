@@ -2,6 +2,7 @@
 
 mod from_parsed_config;
 mod id;
+mod install;
 mod loading;
 mod status;
 mod unit;
@@ -9,6 +10,7 @@ mod unit_parsing;
 mod unitset_manipulation;
 
 pub use id::*;
+pub use install::*;
 pub use loading::*;
 pub use status::*;
 pub use unit::*;
@@ -14,12 +14,16 @@ pub enum UnitStatus {
 pub enum StatusStarted {
     Running,
     WaitingForSocket,
+    /// A oneshot service with RemainAfterExit=yes whose processes exited
+    Exited,
 }
 
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub enum StatusStopped {
     StoppedFinal,
     StoppedUnexpected,
+    /// A oneshot service without RemainAfterExit= that ran successfully
+    Finished,
 }
 
 impl UnitStatus {
@@ -35,4 +39,11 @@ impl UnitStatus {
             _ => false,
         }
     }
+    /// Units that require this unit can be started. Oneshot services that finished successfully count as well.
+    pub fn satisfies_requirement(&self) -> bool {
+        matches!(
+            self,
+            UnitStatus::Started(_) | UnitStatus::Stopped(StatusStopped::Finished, _)
+        )
+    }
 }
@@ -57,9 +57,10 @@ impl SocketState {
             });
         match open_res {
             Ok(_) => {
+                self.sock.reset_limits();
+                crate::socket_activation::arm_socket(id, &self.sock, run_info);
                 let mut status = status.write().unwrap();
                 *status = UnitStatus::Started(StatusStarted::Running);
-                run_info.notify_eventfds();
                 Ok(UnitStatus::Started(StatusStarted::Running))
             }
             Err(e) => {
@@ -78,6 +79,7 @@ impl SocketState {
         status: &RwLock<UnitStatus>,
         run_info: &RuntimeInfo,
     ) -> Result<(), UnitOperationError> {
+        run_info.reactor.deregister_unit(id);
         let close_result = self
             .sock
             .close_all(
@@ -110,6 +112,7 @@ impl SocketState {
         status: &RwLock<UnitStatus>,
         run_info: &RuntimeInfo,
     ) -> Result<(), UnitOperationError> {
+        run_info.reactor.deregister_unit(id);
         let close_result = self
             .sock
             .close_all(
@@ -146,9 +149,10 @@ impl SocketState {
             });
         match open_res {
             Ok(_) => {
+                self.sock.reset_limits();
+                crate::socket_activation::arm_socket(id, &self.sock, run_info);
                 let mut status = status.write().unwrap();
                 *status = UnitStatus::Started(StatusStarted::Running);
-                run_info.notify_eventfds();
                 Ok(())
             }
             Err(e) => {
@@ -162,6 +166,17 @@ impl SocketState {
 }
 
 impl ServiceState {
+    /// Oneshot services are done when starting them returns. They only stay active with RemainAfterExit=yes.
+    fn status_after_start(conf: &ServiceConfig) -> UnitStatus {
+        if conf.srcv_type != ServiceType::OneShot {
+            UnitStatus::Started(StatusStarted::Running)
+        } else if conf.remain_after_exit {
+            UnitStatus::Started(StatusStarted::Exited)
+        } else {
+            UnitStatus::Stopped(StatusStopped::Finished, vec![])
+        }
+    }
+
     fn activate(
         &mut self,
         id: &UnitId,
@@ -180,11 +195,12 @@ impl ServiceState {
             });
         match start_res {
             Ok(crate::services::StartResult::Started) => {
+                let new_status = Self::status_after_start(conf);
                 {
                     let mut status = status.write().unwrap();
-                    *status = UnitStatus::Started(StatusStarted::Running);
+                    *status = new_status.clone();
                 }
-                Ok(UnitStatus::Started(StatusStarted::Running))
+                Ok(new_status)
             }
             Ok(crate::services::StartResult::WaitingForSocket) => {
                 {
@@ -194,13 +210,9 @@ impl ServiceState {
                 // tell socket activation to listen to these sockets again
                 for socket_id in &conf.sockets {
                     if let Some(unit) = run_info.unit_table.get(socket_id) {
-                        if let Specific::Socket(sock) = &unit.specific {
-                            let mut_state = &mut *sock.state.write().unwrap();
-                            mut_state.sock.activated = false;
-                        }
+                        crate::socket_activation::rearm_socket(unit, run_info);
                     }
                 }
-                run_info.notify_eventfds();
                 Ok(UnitStatus::Started(StatusStarted::WaitingForSocket))
             }
             Err(e) => {
@@ -221,12 +233,15 @@ impl ServiceState {
     ) -> Result<(), UnitOperationError> {
         let kill_result = self
             .srvc
-            .kill(&conf, id.clone(), &id.name, run_info)
+            .kill(&conf, id.clone(), &id.name, run_info, false)
             .map_err(|e| UnitOperationError {
                 unit_name: id.name.clone(),
                 unit_id: id.clone(),
                 reason: UnitOperationErrorReason::ServiceStopError(e),
             });
+        // the pipes stay open, output that is still in them is read after the next start registers them again
+        run_info.reactor.deregister_unit(id);
+        crate::services::remove_runtime_directories(&conf.exec_config, &id.name, false);
         match &kill_result {
             Ok(_) => {
                 let mut status = status.write().unwrap();
@@ -249,7 +264,7 @@ impl ServiceState {
     ) -> Result<(), UnitOperationError> {
         let kill_result = self
             .srvc
-            .kill(&conf, id.clone(), &id.name, run_info)
+            .kill(&conf, id.clone(), &id.name, run_info, true)
             .map_err(|e| UnitOperationError {
                 unit_name: id.name.clone(),
                 unit_id: id.clone(),
@@ -258,10 +273,12 @@ impl ServiceState {
 
         // If killing failed, dont try to restart but fail early
         if let Err(error) = kill_result {
+            crate::services::remove_runtime_directories(&conf.exec_config, &id.name, false);
             let mut status = status.write().unwrap();
             *status = UnitStatus::Stopped(StatusStopped::StoppedFinal, vec![error.reason.clone()]);
             return Err(error);
         }
+        crate::services::remove_runtime_directories(&conf.exec_config, &id.name, true);
 
         // Restart and set the status according to the result
         let start_res = self
@@ -276,7 +293,7 @@ impl ServiceState {
             Ok(crate::services::StartResult::Started) => {
                 {
                     let mut status = status.write().unwrap();
-                    *status = UnitStatus::Started(StatusStarted::Running);
+                    *status = Self::status_after_start(conf);
                 }
                 Ok(())
             }
@@ -288,13 +305,9 @@ impl ServiceState {
                 // tell socket activation to listen to these sockets again
                 for socket_id in &conf.sockets {
                     if let Some(unit) = run_info.unit_table.get(socket_id) {
-                        if let Specific::Socket(sock) = &unit.specific {
-                            let mut_state = &mut *sock.state.write().unwrap();
-                            mut_state.sock.activated = false;
-                        }
+                        crate::socket_activation::rearm_socket(unit, run_info);
                     }
                 }
-                run_info.notify_eventfds();
                 Ok(())
             }
             Err(e) => {
@@ -408,7 +421,7 @@ impl Unit {
             .fold(Vec::new(), |mut acc, (id, status_locked)| {
                 let required = self.common.dependencies.requires.contains(id);
                 let ready = if required {
-                    status_locked.is_started()
+                    status_locked.satisfies_requirement()
                 } else {
                     **status_locked != UnitStatus::NeverStarted
                 };
@@ -446,7 +459,7 @@ impl Unit {
             .fold(Vec::new(), |mut acc, (id, status_locked)| {
                 let required = self.common.dependencies.requires.contains(id);
                 let ready = if required {
-                    status_locked.is_started()
+                    status_locked.satisfies_requirement()
                 } else {
                     **status_locked != UnitStatus::NeverStarted
                 };
@@ -542,6 +555,20 @@ impl Unit {
             }
         }
 
+        // only refuse when the unit would actually be started, a unit masked while running keeps its status
+        if self.common.unit.masked {
+            trace!("Unit {} is masked, refuse to start it", self.id.name);
+            *self.common.status.write().unwrap() = UnitStatus::Stopped(
+                StatusStopped::StoppedFinal,
+                vec![UnitOperationErrorReason::Masked],
+            );
+            return Err(UnitOperationError {
+                reason: UnitOperationErrorReason::Masked,
+                unit_name: self.id.name.clone(),
+                unit_id: self.id.clone(),
+            });
+        }
+
         self.state_transition_starting(run_info).map_err(|bad_ids| {
             trace!(
                 "Unit: {} ignores activation. Not all dependencies have been started (still waiting for: {:?})",
@@ -708,16 +735,26 @@ impl Unit {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct UnitConfig {
     pub description: String,
 
     /// This is needed for adding/removing units. All units in this set must be present
     /// or this unit is considered invalid os it has to be removed too / cannot be added.
     pub refs_by_name: Vec<UnitId>,
+
+    /// This unit may be started with the isolate mode (AllowIsolate=)
+    pub allow_isolate: bool,
+    /// This unit is not stopped when another unit is isolated (IgnoreOnIsolate=)
+    pub ignore_on_isolate: bool,
+
+    /// The unit is symlinked to /dev/null in a unit dir that takes precedence over the one with its file. It refuses to start.
+    pub masked: bool,
+    /// Files with the same name in later unit dirs that are overridden by this unit
+    pub shadowed_files: Vec<std::path::PathBuf>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq, Default)]
 /// This are the runtime dependencies. They are extended when the unit is added into the unit set
 /// so all dependencies go both ways.
 ///
@@ -756,6 +793,12 @@ impl Dependencies {
         ids.extend(self.required_by.iter().cloned());
         ids
     }
+    /// The units that were started after this one. They are stopped before this one.
+    pub fn stop_before_this(&self) -> Vec<UnitId> {
+        let mut ids = Vec::new();
+        ids.extend(self.before.iter().cloned());
+        ids
+    }
     pub fn start_before_this(&self) -> Vec<UnitId> {
         let mut ids = Vec::new();
         ids.extend(self.after.iter().cloned());
@@ -839,12 +882,19 @@ impl Dependencies {
 }
 
 /// Describes a single socket that should be opened. One Socket unit may contain multiple of these
-#[derive(Clone, Debug)]
+#[derive(Clone, Eq, PartialEq, Debug)]
 pub struct SingleSocketConfig {
     pub kind: SocketKind,
     pub specialized: SpecializedSocketConfig,
 }
 
+/// The names of the user and group that are allocated for a service with DynamicUser=yes
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DynamicUser {
+    pub user: String,
+    pub group: String,
+}
+
 /// All settings from the Exec section of a unit
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub struct ExecConfig {
@@ -853,6 +903,32 @@ pub struct ExecConfig {
     pub supplementary_groups: Vec<nix::unistd::Gid>,
     pub stdout_path: Option<StdIoOption>,
     pub stderr_path: Option<StdIoOption>,
+    pub stdin_option: Option<StdInOption>,
+    /// The terminal used for the tty stdio options. Defaults to /dev/console
+    pub tty_path: Option<std::path::PathBuf>,
+    /// Used instead of the unit name when logging the output of the service
+    pub syslog_identifier: Option<String>,
+    pub syslog_facility: u8,
+    /// Priority of output lines that have no priority prefix
+    pub syslog_level: u8,
+    /// Whether kernel style prefixes like "<3>" are parsed from the output lines
+    pub syslog_level_prefix: bool,
+    /// Output lines with a less important priority are dropped
+    pub log_level_max: Option<u8>,
+    pub sandbox: FilesystemSandbox,
+    pub no_new_privileges: bool,
+    /// DynamicUser=yes. The uid and gid are only allocated when the service is started
+    pub dynamic_user: Option<DynamicUser>,
+    /// Created before the service is started and owned by its user and group
+    pub directories: ExecDirectories,
+    /// The home directory of the user is already resolved if WorkingDirectory=~ was set
+    pub working_directory: Option<SandboxPath>,
+    pub process: ProcessSettings,
+    /// Merged with the DefaultLimit*= settings of the rustysd config when the service is started
+    pub limits: ResourceLimits,
+    pub namespaces: NamespaceSandbox,
+    pub capabilities: CapabilitySettings,
+    pub syscall_filter: SyscallFilter,
     pub environment: Option<EnvVars>,
 }
 
@@ -872,7 +948,8 @@ pub struct ServiceConfig {
     pub restart: ServiceRestart,
     pub accept: bool,
     pub notifyaccess: NotifyKind,
-    pub exec: Commandline,
+    /// Only oneshot services can have more than one
+    pub exec: Vec<Commandline>,
     pub stop: Vec<Commandline>,
     pub stoppost: Vec<Commandline>,
     pub startpre: Vec<Commandline>,
@@ -881,6 +958,10 @@ pub struct ServiceConfig {
     pub starttimeout: Option<Timeout>,
     pub stoptimeout: Option<Timeout>,
     pub generaltimeout: Option<Timeout>,
+    /// A oneshot service stays active after its processes exited
+    pub remain_after_exit: bool,
+    pub kill: KillConfig,
+    pub exit_status: ExitStatusConfig,
     pub exec_config: ExecConfig,
     pub platform_specific: PlatformSpecificServiceFields,
     pub dbus_name: Option<String>,
@@ -888,10 +969,13 @@ pub struct ServiceConfig {
 }
 
 /// The immutable config of a socket unit
+#[derive(Eq, PartialEq)]
 pub struct SocketConfig {
     pub sockets: Vec<SingleSocketConfig>,
     pub filedesc_name: String,
     pub services: Vec<UnitId>,
+    pub trigger_limit: RateLimit,
+    pub poll_limit: RateLimit,
 
     pub exec_config: ExecConfig,
 }
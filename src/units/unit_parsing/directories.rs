@@ -0,0 +1,93 @@
+//! Parse RuntimeDirectory=, StateDirectory=, CacheDirectory=, LogsDirectory= and ConfigurationDirectory= with their mode settings
+
+use super::get_single_value;
+use super::sandbox::list_values;
+use super::ParsedSection;
+use crate::units::*;
+use std::path::{Component, Path, PathBuf};
+
+const DIRECTORY_SETTINGS: [(ExecDirectoryKind, &str, &str); 5] = [
+    (
+        ExecDirectoryKind::Runtime,
+        "RUNTIMEDIRECTORY",
+        "RuntimeDirectory",
+    ),
+    (ExecDirectoryKind::State, "STATEDIRECTORY", "StateDirectory"),
+    (ExecDirectoryKind::Cache, "CACHEDIRECTORY", "CacheDirectory"),
+    (ExecDirectoryKind::Logs, "LOGSDIRECTORY", "LogsDirectory"),
+    (
+        ExecDirectoryKind::Configuration,
+        "CONFIGURATIONDIRECTORY",
+        "ConfigurationDirectory",
+    ),
+];
+
+/// The names must stay below the base dir, so only plain relative paths are allowed
+fn parse_directory_name(setting_name: &str, name: &str) -> Result<PathBuf, ParsingErrorReason> {
+    let path = Path::new(name);
+    if path
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+    {
+        Ok(path.to_owned())
+    } else {
+        Err(ParsingErrorReason::UnknownSetting(
+            setting_name.to_owned(),
+            name.to_owned(),
+        ))
+    }
+}
+
+pub fn parse_directories(
+    section: &mut ParsedSection,
+) -> Result<ExecDirectories, ParsingErrorReason> {
+    let mut directories = Vec::new();
+    for (kind, key, setting_name) in &DIRECTORY_SETTINGS {
+        let names = list_values(section, key)
+            .iter()
+            .map(|name| parse_directory_name(setting_name, name))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mode_key = format!("{}MODE", key);
+        let mode_name = format!("{}Mode", setting_name);
+        let mode = match get_single_value(section, &mode_key, &mode_name)? {
+            Some(mode) => u32::from_str_radix(&mode, 8)
+                .ok()
+                .filter(|mode| *mode <= 0o7777)
+                .ok_or(ParsingErrorReason::UnknownSetting(mode_name, mode))?,
+            None => 0o755,
+        };
+
+        if !names.is_empty() {
+            directories.push(ExecDirectory {
+                kind: *kind,
+                names,
+                mode,
+            });
+        }
+    }
+
+    let runtime_preserve = match get_single_value(
+        section,
+        "RUNTIMEDIRECTORYPRESERVE",
+        "RuntimeDirectoryPreserve",
+    )? {
+        Some(val) => match val.to_lowercase().as_str() {
+            "restart" => RuntimeDirectoryPreserve::Restart,
+            "yes" | "true" | "on" | "1" => RuntimeDirectoryPreserve::Yes,
+            "no" | "false" | "off" | "0" => RuntimeDirectoryPreserve::No,
+            _ => {
+                return Err(ParsingErrorReason::UnknownSetting(
+                    "RuntimeDirectoryPreserve".to_owned(),
+                    val,
+                ))
+            }
+        },
+        None => RuntimeDirectoryPreserve::No,
+    };
+
+    Ok(ExecDirectories {
+        directories,
+        runtime_preserve,
+    })
+}
@@ -0,0 +1,52 @@
+//! Parse SuccessExitStatus=, RestartPreventExitStatus= and RestartForceExitStatus=
+
+use super::kill::parse_signal;
+use super::sandbox::list_values;
+use super::ParsedSection;
+use crate::units::*;
+
+/// The values are exit codes (0-255) and signal names, separated by spaces
+fn parse_exit_status_set(
+    section: &mut ParsedSection,
+    key: &str,
+    setting_name: &str,
+) -> Result<ExitStatusSet, ParsingErrorReason> {
+    let mut set = ExitStatusSet::default();
+    for value in list_values(section, key) {
+        let valid = if value.chars().all(|c| c.is_ascii_digit()) {
+            value
+                .parse::<u8>()
+                .map(|code| set.exit_codes.push(i32::from(code)))
+                .is_ok()
+        } else {
+            parse_signal(&value)
+                .map(|sig| set.signals.push(sig))
+                .is_some()
+        };
+        if !valid {
+            return Err(ParsingErrorReason::UnknownSetting(
+                setting_name.to_owned(),
+                value,
+            ));
+        }
+    }
+    Ok(set)
+}
+
+pub fn parse_exit_status_section(
+    section: &mut ParsedSection,
+) -> Result<ExitStatusConfig, ParsingErrorReason> {
+    Ok(ExitStatusConfig {
+        success: parse_exit_status_set(section, "SUCCESSEXITSTATUS", "SuccessExitStatus")?,
+        restart_prevent: parse_exit_status_set(
+            section,
+            "RESTARTPREVENTEXITSTATUS",
+            "RestartPreventExitStatus",
+        )?,
+        restart_force: parse_exit_status_set(
+            section,
+            "RESTARTFORCEEXITSTATUS",
+            "RestartForceExitStatus",
+        )?,
+    })
+}
@@ -0,0 +1,76 @@
+//! Parse the settings of systemd.kill: KillMode=, KillSignal=, RestartKillSignal=, FinalKillSignal=, SendSIGHUP= and SendSIGKILL=
+
+use super::get_single_value;
+use super::ParsedSection;
+use crate::units::*;
+use nix::sys::signal::Signal;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+/// Signals can be given as "SIGTERM", "TERM" or as a number
+pub fn parse_signal(name: &str) -> Option<Signal> {
+    let name = name.trim();
+    if let Ok(number) = name.parse::<i32>() {
+        return Signal::try_from(number).ok();
+    }
+    let name = name.to_uppercase();
+    if name.starts_with("SIG") {
+        Signal::from_str(&name).ok()
+    } else {
+        Signal::from_str(&format!("SIG{}", name)).ok()
+    }
+}
+
+fn get_signal(
+    section: &mut ParsedSection,
+    key: &str,
+    setting_name: &str,
+) -> Result<Option<Signal>, ParsingErrorReason> {
+    match get_single_value(section, key, setting_name)? {
+        Some(val) => parse_signal(&val)
+            .map(Some)
+            .ok_or(ParsingErrorReason::UnknownSetting(
+                setting_name.to_owned(),
+                val,
+            )),
+        None => Ok(None),
+    }
+}
+
+pub fn parse_kill_section(section: &mut ParsedSection) -> Result<KillConfig, ParsingErrorReason> {
+    let defaults = KillConfig::default();
+    let mode = match get_single_value(section, "KILLMODE", "KillMode")? {
+        Some(val) => match val.as_str() {
+            "control-group" => KillMode::ControlGroup,
+            "mixed" => KillMode::Mixed,
+            "process" => KillMode::Process,
+            "none" => KillMode::None,
+            _ => {
+                return Err(ParsingErrorReason::UnknownSetting(
+                    "KillMode".to_owned(),
+                    val,
+                ))
+            }
+        },
+        None => defaults.mode,
+    };
+    let signal = get_signal(section, "KILLSIGNAL", "KillSignal")?.unwrap_or(defaults.signal);
+    let restart_signal = get_signal(section, "RESTARTKILLSIGNAL", "RestartKillSignal")?;
+    let final_signal =
+        get_signal(section, "FINALKILLSIGNAL", "FinalKillSignal")?.unwrap_or(defaults.final_signal);
+    let send_sighup = get_single_value(section, "SENDSIGHUP", "SendSIGHUP")?
+        .map(|val| string_to_bool(&val))
+        .unwrap_or(defaults.send_sighup);
+    let send_sigkill = get_single_value(section, "SENDSIGKILL", "SendSIGKILL")?
+        .map(|val| string_to_bool(&val))
+        .unwrap_or(defaults.send_sigkill);
+
+    Ok(KillConfig {
+        mode,
+        signal,
+        restart_signal,
+        final_signal,
+        send_sighup,
+        send_sigkill,
+    })
+}
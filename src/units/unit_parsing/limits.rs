@@ -0,0 +1,146 @@
+//! Parse the Limit*= settings. The same values are used for the DefaultLimit*= settings in the rustysd config.
+
+use super::get_single_value;
+use super::ParsedSection;
+use crate::units::*;
+
+/// The resource, the name after "Limit" in the setting and how its values are written
+pub const LIMIT_NAMES: [(LimitResource, &str); 16] = [
+    (LimitResource::Cpu, "CPU"),
+    (LimitResource::FileSize, "FSIZE"),
+    (LimitResource::Data, "DATA"),
+    (LimitResource::Stack, "STACK"),
+    (LimitResource::Core, "CORE"),
+    (LimitResource::Rss, "RSS"),
+    (LimitResource::NoFile, "NOFILE"),
+    (LimitResource::AddressSpace, "AS"),
+    (LimitResource::NProc, "NPROC"),
+    (LimitResource::MemLock, "MEMLOCK"),
+    (LimitResource::Locks, "LOCKS"),
+    (LimitResource::SigPending, "SIGPENDING"),
+    (LimitResource::MsgQueue, "MSGQUEUE"),
+    (LimitResource::Nice, "NICE"),
+    (LimitResource::RtPrio, "RTPRIO"),
+    (LimitResource::RtTime, "RTTIME"),
+];
+
+/// Sizes with the suffixes K, M, G, T, P and E (to the base 1024)
+fn parse_size(value: &str) -> Option<u64> {
+    let (number, factor) = match value.char_indices().last()? {
+        (idx, suffix) if suffix.is_ascii_alphabetic() => {
+            let exponent = match suffix.to_ascii_uppercase() {
+                'B' => 0,
+                'K' => 1,
+                'M' => 2,
+                'G' => 3,
+                'T' => 4,
+                'P' => 5,
+                'E' => 6,
+                _ => return None,
+            };
+            (&value[..idx], 1024u64.pow(exponent))
+        }
+        _ => (value, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(factor)
+}
+
+/// Time spans like "1h 30min" or "500ms". Plain numbers are in `default_unit` (which is in microseconds)
+fn parse_time_usec(value: &str, default_unit: u64) -> Option<u64> {
+    if let Ok(number) = value.parse::<u64>() {
+        return number.checked_mul(default_unit);
+    }
+    let mut sum: u64 = 0;
+    for part in value.split_whitespace() {
+        let split = part
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(part.len());
+        let (number, unit) = part.split_at(split);
+        let factor = match unit {
+            "us" | "usec" => 1,
+            "ms" | "msec" => 1_000,
+            "" | "s" | "sec" | "second" | "seconds" => 1_000_000,
+            "m" | "min" | "minute" | "minutes" => 60 * 1_000_000,
+            "h" | "hr" | "hrs" | "hour" | "hours" => 60 * 60 * 1_000_000,
+            "d" | "day" | "days" => 24 * 60 * 60 * 1_000_000,
+            _ => return None,
+        };
+        sum = sum.checked_add(number.parse::<u64>().ok()?.checked_mul(factor)?)?;
+    }
+    Some(sum)
+}
+
+fn parse_single_limit(resource: LimitResource, value: &str) -> Option<Option<u64>> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("infinity") {
+        return Some(None);
+    }
+    let limit = match resource {
+        LimitResource::FileSize
+        | LimitResource::Data
+        | LimitResource::Stack
+        | LimitResource::Core
+        | LimitResource::Rss
+        | LimitResource::AddressSpace
+        | LimitResource::MemLock
+        | LimitResource::MsgQueue => parse_size(value)?,
+        // seconds, rounded up
+        LimitResource::Cpu => {
+            let usec = parse_time_usec(value, 1_000_000)?;
+            usec.div_ceil(1_000_000)
+        }
+        LimitResource::RtTime => parse_time_usec(value, 1)?,
+        // "+N" and "-N" are nice levels, plain numbers are the raw limit (20 - nice level)
+        LimitResource::Nice if value.starts_with('+') || value.starts_with('-') => {
+            let nice = value.parse::<i64>().ok()?;
+            if !(-20..=19).contains(&nice) {
+                return None;
+            }
+            (20 - nice) as u64
+        }
+        _ => value.parse::<u64>().ok()?,
+    };
+    Some(Some(limit))
+}
+
+/// "soft:hard" or one value for both
+pub fn parse_limit_value(resource: LimitResource, value: &str) -> Result<ResourceLimit, String> {
+    let err = || format!("Invalid value for resource limit {:?}: {}", resource, value);
+    let (soft, hard) = match value.split_once(':') {
+        Some((soft, hard)) => (
+            parse_single_limit(resource, soft).ok_or_else(err)?,
+            parse_single_limit(resource, hard).ok_or_else(err)?,
+        ),
+        None => {
+            let limit = parse_single_limit(resource, value).ok_or_else(err)?;
+            (limit, limit)
+        }
+    };
+    // infinity is larger than every number
+    let soft_too_large = match (soft, hard) {
+        (Some(soft), Some(hard)) => soft > hard,
+        (None, Some(_)) => true,
+        _ => false,
+    };
+    if soft_too_large {
+        return Err(format!(
+            "The soft limit is larger than the hard limit for resource limit {:?}: {}",
+            resource, value
+        ));
+    }
+    Ok(ResourceLimit { soft, hard })
+}
+
+pub fn parse_limits(section: &mut ParsedSection) -> Result<ResourceLimits, ParsingErrorReason> {
+    let mut limits = ResourceLimits::default();
+    for (resource, name) in &LIMIT_NAMES {
+        let key = format!("LIMIT{}", name);
+        let setting_name = format!("Limit{}", name);
+        if let Some(value) = get_single_value(section, &key, &setting_name)? {
+            let limit = parse_limit_value(*resource, &value)
+                .map_err(|_| ParsingErrorReason::UnknownSetting(setting_name, value))?;
+            limits.set(*resource, limit);
+        }
+    }
+    Ok(limits)
+}
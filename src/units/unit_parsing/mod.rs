@@ -1,13 +1,26 @@
+mod directories;
+mod exit_status;
+mod kill;
+mod limits;
+mod process;
+mod sandbox;
 mod service_unit;
 mod socket_unit;
 mod target_unit;
 mod unit_parser;
 
+pub use directories::*;
+pub use exit_status::*;
+pub use kill::*;
+pub use limits::*;
+pub use process::*;
+pub use sandbox::*;
 pub use service_unit::*;
 pub use socket_unit::*;
 pub use target_unit::*;
 pub use unit_parser::*;
 
+use crate::signal_handler::ChildTermination;
 use std::path::PathBuf;
 
 pub struct ParsedCommonConfig {
@@ -35,6 +48,9 @@ pub struct ParsedUnitSection {
     pub requires: Vec<String>,
     pub before: Vec<String>,
     pub after: Vec<String>,
+
+    pub allow_isolate: bool,
+    pub ignore_on_isolate: bool,
 }
 #[derive(Clone)]
 pub struct ParsedSingleSocketConfig {
@@ -57,6 +73,8 @@ pub struct ParsedSocketSection {
     pub sockets: Vec<ParsedSingleSocketConfig>,
     pub filedesc_name: Option<String>,
     pub services: Vec<String>,
+    pub trigger_limit: RateLimit,
+    pub poll_limit: RateLimit,
 
     pub exec_section: ParsedExecSection,
 }
@@ -64,7 +82,8 @@ pub struct ParsedServiceSection {
     pub restart: ServiceRestart,
     pub accept: bool,
     pub notifyaccess: NotifyKind,
-    pub exec: Commandline,
+    /// Only oneshot services can have more than one
+    pub exec: Vec<Commandline>,
     pub stop: Vec<Commandline>,
     pub stoppost: Vec<Commandline>,
     pub startpre: Vec<Commandline>,
@@ -73,11 +92,14 @@ pub struct ParsedServiceSection {
     pub starttimeout: Option<Timeout>,
     pub stoptimeout: Option<Timeout>,
     pub generaltimeout: Option<Timeout>,
+    pub remain_after_exit: bool,
 
     pub dbus_name: Option<String>,
 
     pub sockets: Vec<String>,
 
+    pub kill: KillConfig,
+    pub exit_status: ExitStatusConfig,
     pub exec_section: ParsedExecSection,
 }
 
@@ -85,12 +107,34 @@ pub struct ParsedServiceSection {
 pub struct ParsedInstallSection {
     pub wanted_by: Vec<String>,
     pub required_by: Vec<String>,
+    /// Only used by enable, which creates a symlink with this name
+    pub alias: Vec<String>,
+    /// Units that are enabled/disabled together with this unit
+    pub also: Vec<String>,
 }
 pub struct ParsedExecSection {
     pub user: Option<String>,
     pub group: Option<String>,
     pub stdout_path: Option<StdIoOption>,
     pub stderr_path: Option<StdIoOption>,
+    pub stdin_option: Option<StdInOption>,
+    pub tty_path: Option<PathBuf>,
+    pub syslog_identifier: Option<String>,
+    pub syslog_facility: u8,
+    pub syslog_level: u8,
+    pub syslog_level_prefix: bool,
+    pub log_level_max: Option<u8>,
+    pub sandbox: FilesystemSandbox,
+    pub no_new_privileges: bool,
+    pub dynamic_user: bool,
+    pub directories: ExecDirectories,
+    /// "~" is the home directory of the user
+    pub working_directory: Option<SandboxPath>,
+    pub process: ProcessSettings,
+    pub limits: ResourceLimits,
+    pub namespaces: NamespaceSandbox,
+    pub capabilities: CapabilitySettings,
+    pub syscall_filter: SyscallFilter,
     pub supplementary_groups: Vec<String>,
     pub environment: Option<EnvVars>,
 }
@@ -117,16 +161,449 @@ pub enum ServiceRestart {
     No,
 }
 
+/// Exit codes and signals as listed in SuccessExitStatus=, RestartPreventExitStatus= and RestartForceExitStatus=
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct ExitStatusSet {
+    pub exit_codes: Vec<i32>,
+    pub signals: Vec<nix::sys::signal::Signal>,
+}
+
+impl ExitStatusSet {
+    pub fn contains(&self, termination: &ChildTermination) -> bool {
+        match termination {
+            ChildTermination::Exit(code) => self.exit_codes.contains(code),
+            ChildTermination::Signal(sig) => self.signals.contains(sig),
+        }
+    }
+}
+
+#[derive(Clone, Default, Eq, PartialEq, Debug)]
+pub struct ExitStatusConfig {
+    /// Additional exit codes and signals that count as a successful exit, exit code 0 always does
+    pub success: ExitStatusSet,
+    /// The service is not restarted on these, even with Restart=always
+    pub restart_prevent: ExitStatusSet,
+    /// The service is restarted on these, even with Restart=no
+    pub restart_force: ExitStatusSet,
+}
+
+impl ExitStatusConfig {
+    pub fn is_success(&self, termination: &ChildTermination) -> bool {
+        termination.success() || self.success.contains(termination)
+    }
+
+    pub fn should_restart(&self, restart: &ServiceRestart, termination: &ChildTermination) -> bool {
+        if self.restart_prevent.contains(termination) {
+            false
+        } else if self.restart_force.contains(termination) {
+            true
+        } else {
+            *restart == ServiceRestart::Always
+        }
+    }
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum Timeout {
     Duration(std::time::Duration),
     Infinity,
 }
 
+/// Allows `burst` events per `interval`. An interval or burst of zero disables the limit.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct RateLimit {
+    pub interval: Timeout,
+    pub burst: u64,
+}
+
 #[derive(Clone, Eq, PartialEq, Debug)]
 pub enum StdIoOption {
     File(PathBuf),
     AppendFile(PathBuf),
+    TruncateFile(PathBuf),
+    /// The output is read by rustysd and logged (and stored in the log store if that is enabled). This is the default.
+    Journal,
+    Null,
+    /// StandardOutput= gets the same target as stdin, StandardError= the same as stdout
+    Inherit,
+    Tty,
+    Kmsg,
+    /// Only usable with Accept=yes
+    Socket,
+    /// A filedescriptor of one of the sockets of the service selected by its FileDescriptorName=
+    Fd(String),
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum StdInOption {
+    Null,
+    Tty,
+    File(PathBuf),
+    /// Only usable with Accept=yes
+    Socket,
+    /// The text from StandardInputText=
+    Data(String),
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ProtectSystem {
+    No,
+    /// /usr and /boot are read only
+    Yes,
+    /// Like Yes but /etc is read only too
+    Full,
+    /// Everything except /dev, /proc and /sys is read only
+    Strict,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ProtectHome {
+    No,
+    /// /home, /root and /run/user are inaccessible
+    Yes,
+    ReadOnly,
+    /// An empty read only tmpfs is mounted over them
+    Tmpfs,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct SandboxPath {
+    pub path: PathBuf,
+    /// The path was prefixed with "-" so it is ignored if it does not exist
+    pub ignore_missing: bool,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct BindPath {
+    pub src: PathBuf,
+    pub dst: PathBuf,
+    /// Submounts of src are bound too (rbind, the default)
+    pub recursive: bool,
+    pub ignore_missing: bool,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct TemporaryFileSystem {
+    pub path: PathBuf,
+    /// The mount options like "ro,mode=0755"
+    pub options: String,
+}
+
+/// Which processes of a service are signaled when it is stopped
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum KillMode {
+    /// All processes of the service (the process group, or the cgroup if the cgroups feature is on)
+    ControlGroup,
+    /// The main process gets the KillSignal=, all remaining processes get the FinalKillSignal=
+    Mixed,
+    /// Only the main process
+    Process,
+    /// No process is signaled, only ExecStop= is run
+    None,
+}
+
+/// The settings from systemd.kill
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct KillConfig {
+    pub mode: KillMode,
+    pub signal: nix::sys::signal::Signal,
+    /// Used instead of the signal when the service is restarted
+    pub restart_signal: Option<nix::sys::signal::Signal>,
+    /// Sent if processes remain after TimeoutStopSec= and SendSIGKILL=yes
+    pub final_signal: nix::sys::signal::Signal,
+    /// Send SIGHUP right after the KillSignal=
+    pub send_sighup: bool,
+    pub send_sigkill: bool,
+}
+
+impl Default for KillConfig {
+    fn default() -> Self {
+        KillConfig {
+            mode: KillMode::ControlGroup,
+            signal: nix::sys::signal::Signal::SIGTERM,
+            restart_signal: None,
+            final_signal: nix::sys::signal::Signal::SIGKILL,
+            send_sighup: false,
+            send_sigkill: true,
+        }
+    }
+}
+
+/// The resources that can be limited with the Limit*= settings
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum LimitResource {
+    Cpu,
+    FileSize,
+    Data,
+    Stack,
+    Core,
+    Rss,
+    NoFile,
+    AddressSpace,
+    NProc,
+    MemLock,
+    Locks,
+    SigPending,
+    MsgQueue,
+    Nice,
+    RtPrio,
+    RtTime,
+}
+
+/// Soft and hard limit, None means infinity
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub struct ResourceLimit {
+    pub soft: Option<u64>,
+    pub hard: Option<u64>,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct ResourceLimits {
+    pub limits: Vec<(LimitResource, ResourceLimit)>,
+}
+
+impl ResourceLimits {
+    pub fn get(&self, resource: LimitResource) -> Option<ResourceLimit> {
+        self.limits
+            .iter()
+            .find(|(res, _)| *res == resource)
+            .map(|(_, limit)| *limit)
+    }
+
+    pub fn set(&mut self, resource: LimitResource, limit: ResourceLimit) {
+        self.limits.retain(|(res, _)| *res != resource);
+        self.limits.push((resource, limit));
+    }
+
+    /// The limits of `self` with the ones from `other` taking precedence
+    pub fn merged_with(&self, other: &ResourceLimits) -> ResourceLimits {
+        let mut merged = self.clone();
+        for (resource, limit) in &other.limits {
+            merged.set(*resource, *limit);
+        }
+        merged
+    }
+}
+
+/// CPUSchedulingPolicy=
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum CpuSchedulingPolicy {
+    Other,
+    Batch,
+    Idle,
+    Fifo,
+    RoundRobin,
+}
+
+/// IOSchedulingClass=
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum IoSchedulingClass {
+    None,
+    Realtime,
+    BestEffort,
+    Idle,
+}
+
+/// Personality=, the architecture reported by uname()
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Personality {
+    X86,
+    X86_64,
+    Arm,
+    Arm64,
+}
+
+/// Settings for the process that are applied right before the exec, for the main process and the helper commands
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct ProcessSettings {
+    /// chroot() into this directory. All other paths (except for the sandbox settings) are relative to it
+    pub root_directory: Option<PathBuf>,
+    pub umask: Option<u32>,
+    pub nice: Option<i32>,
+    pub cpu_scheduling_policy: Option<CpuSchedulingPolicy>,
+    pub cpu_scheduling_priority: Option<i32>,
+    /// The cpus the process may run on. Empty means all
+    pub cpu_affinity: Vec<usize>,
+    pub io_scheduling_class: Option<IoSchedulingClass>,
+    pub io_scheduling_priority: Option<u8>,
+    pub personality: Option<Personality>,
+}
+
+impl ProcessSettings {
+    pub fn is_enabled(&self) -> bool {
+        *self != ProcessSettings::default()
+    }
+}
+
+/// The kinds of directories that can be created for a service with RuntimeDirectory= etc.
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum ExecDirectoryKind {
+    Runtime,
+    State,
+    Cache,
+    Logs,
+    Configuration,
+}
+
+impl ExecDirectoryKind {
+    /// The directory the configured names are relative to
+    pub fn base_dir(self) -> &'static str {
+        match self {
+            ExecDirectoryKind::Runtime => "/run",
+            ExecDirectoryKind::State => "/var/lib",
+            ExecDirectoryKind::Cache => "/var/cache",
+            ExecDirectoryKind::Logs => "/var/log",
+            ExecDirectoryKind::Configuration => "/etc",
+        }
+    }
+
+    /// The env var that contains the full paths of the directories, separated by ':'
+    pub fn env_var(self) -> &'static str {
+        match self {
+            ExecDirectoryKind::Runtime => "RUNTIME_DIRECTORY",
+            ExecDirectoryKind::State => "STATE_DIRECTORY",
+            ExecDirectoryKind::Cache => "CACHE_DIRECTORY",
+            ExecDirectoryKind::Logs => "LOGS_DIRECTORY",
+            ExecDirectoryKind::Configuration => "CONFIGURATION_DIRECTORY",
+        }
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ExecDirectory {
+    pub kind: ExecDirectoryKind,
+    /// Relative to the base dir of the kind
+    pub names: Vec<PathBuf>,
+    /// Mode of the innermost directories, 0755 by default
+    pub mode: u32,
+}
+
+impl ExecDirectory {
+    pub fn paths(&self) -> Vec<PathBuf> {
+        self.names
+            .iter()
+            .map(|name| PathBuf::from(self.kind.base_dir()).join(name))
+            .collect()
+    }
+}
+
+/// When the runtime directories are removed
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum RuntimeDirectoryPreserve {
+    /// Whenever the service stops
+    No,
+    /// Never
+    Yes,
+    /// Only when the service is stopped, not when it is restarted
+    Restart,
+}
+
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ExecDirectories {
+    /// Only the kinds that have at least one directory configured
+    pub directories: Vec<ExecDirectory>,
+    pub runtime_preserve: RuntimeDirectoryPreserve,
+}
+
+impl Default for ExecDirectories {
+    fn default() -> Self {
+        ExecDirectories {
+            directories: Vec::new(),
+            runtime_preserve: RuntimeDirectoryPreserve::No,
+        }
+    }
+}
+
+/// The mount namespace based sandboxing of a service
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct FilesystemSandbox {
+    pub private_tmp: bool,
+    pub protect_system: ProtectSystem,
+    pub protect_home: ProtectHome,
+    pub read_write_paths: Vec<SandboxPath>,
+    pub read_only_paths: Vec<SandboxPath>,
+    pub inaccessible_paths: Vec<SandboxPath>,
+    pub bind_paths: Vec<BindPath>,
+    pub bind_read_only_paths: Vec<BindPath>,
+    pub temporary_file_systems: Vec<TemporaryFileSystem>,
+    /// A new /dev with only the basic pseudo devices like /dev/null
+    pub private_devices: bool,
+    /// /proc/sys, /sys and similar are read only
+    pub protect_kernel_tunables: bool,
+    /// The kernel modules are inaccessible
+    pub protect_kernel_modules: bool,
+    /// /proc/kmsg and /dev/kmsg are inaccessible
+    pub protect_kernel_logs: bool,
+    /// /sys/fs/cgroup is read only
+    pub protect_control_groups: bool,
+}
+
+impl Default for FilesystemSandbox {
+    fn default() -> Self {
+        FilesystemSandbox {
+            private_tmp: false,
+            protect_system: ProtectSystem::No,
+            protect_home: ProtectHome::No,
+            read_write_paths: Vec::new(),
+            read_only_paths: Vec::new(),
+            inaccessible_paths: Vec::new(),
+            bind_paths: Vec::new(),
+            bind_read_only_paths: Vec::new(),
+            temporary_file_systems: Vec::new(),
+            private_devices: false,
+            protect_kernel_tunables: false,
+            protect_kernel_modules: false,
+            protect_kernel_logs: false,
+            protect_control_groups: false,
+        }
+    }
+}
+
+impl FilesystemSandbox {
+    /// Whether the service needs its own mount namespace at all
+    pub fn is_enabled(&self) -> bool {
+        *self != FilesystemSandbox::default()
+    }
+}
+
+/// The namespaces (other than the mount namespace) a service gets and the namespaces it may create itself
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct NamespaceSandbox {
+    /// A new network namespace with only the loopback device
+    pub private_network: bool,
+    /// A new user namespace in which only root and the user/group of the service are mapped
+    pub private_users: bool,
+    /// A new UTS namespace so the hostname can not be changed for the whole system
+    pub protect_hostname: bool,
+    /// The CLONE_NEW* flags of the namespaces the service may still create. None means no restriction
+    pub restrict_namespaces: Option<u64>,
+}
+
+/// The capabilities of a service as bitmasks, the bit numbers are the capability numbers
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default)]
+pub struct CapabilitySettings {
+    /// None keeps the bounding set as it is, capabilities that are not in here are dropped from it
+    pub bounding_set: Option<u64>,
+    /// Capabilities that are kept after the user was changed and passed to the executed binary
+    pub ambient: u64,
+}
+
+/// The SystemCall*= settings. The names are only resolved to numbers when the filter is built in the forked child
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct SyscallFilter {
+    /// The SystemCallFilter= assignments in order. The bool is true for deny lists (prefixed with "~").
+    /// Names can be system calls or groups like "@system-service", optionally followed by ":errno"
+    pub rules: Vec<(bool, Vec<String>)>,
+    /// Denied system calls fail with this errno. If it is not set they kill the process
+    pub error_number: Option<i32>,
+    /// SystemCallArchitectures=native
+    pub native_arch_only: bool,
+}
+
+impl SyscallFilter {
+    pub fn is_enabled(&self) -> bool {
+        !self.rules.is_empty() || self.native_arch_only
+    }
 }
 
 #[derive(Clone, Eq, PartialEq, Debug)]
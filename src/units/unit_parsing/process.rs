@@ -0,0 +1,182 @@
+//! Parse the settings for the cwd, root directory, umask and scheduling of the processes of a service
+
+use super::get_single_value;
+use super::sandbox::list_values;
+use super::ParsedSection;
+use crate::units::*;
+use std::path::PathBuf;
+
+fn parse_number<T: std::str::FromStr + PartialOrd>(
+    section: &mut ParsedSection,
+    key: &str,
+    setting_name: &str,
+    min: T,
+    max: T,
+) -> Result<Option<T>, ParsingErrorReason> {
+    match get_single_value(section, key, setting_name)? {
+        Some(val) => match val.trim().parse::<T>() {
+            Ok(num) if num >= min && num <= max => Ok(Some(num)),
+            _ => Err(ParsingErrorReason::UnknownSetting(
+                setting_name.to_owned(),
+                val,
+            )),
+        },
+        None => Ok(None),
+    }
+}
+
+fn parse_choice<T: Copy>(
+    section: &mut ParsedSection,
+    key: &str,
+    setting_name: &str,
+    choices: &[(&str, T)],
+) -> Result<Option<T>, ParsingErrorReason> {
+    match get_single_value(section, key, setting_name)? {
+        Some(val) => choices
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(val.trim()))
+            .map(|(_, choice)| Some(*choice))
+            .ok_or(ParsingErrorReason::UnknownSetting(
+                setting_name.to_owned(),
+                val,
+            )),
+        None => Ok(None),
+    }
+}
+
+/// Entries are cpu indexes or ranges like "0-3"
+fn parse_cpu_affinity(section: &mut ParsedSection) -> Result<Vec<usize>, ParsingErrorReason> {
+    let mut cpus = Vec::new();
+    for entry in list_values(section, "CPUAFFINITY") {
+        // systemd also allows commas as separators
+        for part in entry.split(',').filter(|part| !part.is_empty()) {
+            let err =
+                || ParsingErrorReason::UnknownSetting("CPUAffinity".to_owned(), part.to_owned());
+            let (start, end) = match part.split_once('-') {
+                Some((start, end)) => (start, end),
+                None => (part, part),
+            };
+            let start = start.parse::<usize>().map_err(|_| err())?;
+            let end = end.parse::<usize>().map_err(|_| err())?;
+            if start > end {
+                return Err(err());
+            }
+            for cpu in start..=end {
+                if !cpus.contains(&cpu) {
+                    cpus.push(cpu);
+                }
+            }
+        }
+    }
+    Ok(cpus)
+}
+
+/// "-" ignores a missing directory, "~" is the home directory of the user
+pub fn parse_working_directory(
+    section: &mut ParsedSection,
+) -> Result<Option<SandboxPath>, ParsingErrorReason> {
+    match get_single_value(section, "WORKINGDIRECTORY", "WorkingDirectory")? {
+        Some(val) => {
+            let val = val.trim();
+            let ignore_missing = val.starts_with('-');
+            let path = val.trim_start_matches('-');
+            if path != "~" && !path.starts_with('/') {
+                return Err(ParsingErrorReason::UnknownSetting(
+                    "WorkingDirectory".to_owned(),
+                    val.to_owned(),
+                ));
+            }
+            Ok(Some(SandboxPath {
+                path: PathBuf::from(path),
+                ignore_missing,
+            }))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn parse_process_settings(
+    section: &mut ParsedSection,
+) -> Result<ProcessSettings, ParsingErrorReason> {
+    let root_directory = match get_single_value(section, "ROOTDIRECTORY", "RootDirectory")? {
+        Some(val) if val.starts_with('/') => Some(PathBuf::from(val)),
+        Some(val) => {
+            return Err(ParsingErrorReason::UnknownSetting(
+                "RootDirectory".to_owned(),
+                val,
+            ))
+        }
+        None => None,
+    };
+    let umask = match get_single_value(section, "UMASK", "UMask")? {
+        Some(val) => Some(
+            u32::from_str_radix(val.trim(), 8)
+                .ok()
+                .filter(|mask| *mask <= 0o777)
+                .ok_or(ParsingErrorReason::UnknownSetting("UMask".to_owned(), val))?,
+        ),
+        None => None,
+    };
+    let nice = parse_number(section, "NICE", "Nice", -20, 19)?;
+    let cpu_scheduling_policy = parse_choice(
+        section,
+        "CPUSCHEDULINGPOLICY",
+        "CPUSchedulingPolicy",
+        &[
+            ("other", CpuSchedulingPolicy::Other),
+            ("batch", CpuSchedulingPolicy::Batch),
+            ("idle", CpuSchedulingPolicy::Idle),
+            ("fifo", CpuSchedulingPolicy::Fifo),
+            ("rr", CpuSchedulingPolicy::RoundRobin),
+        ],
+    )?;
+    let cpu_scheduling_priority = parse_number(
+        section,
+        "CPUSCHEDULINGPRIORITY",
+        "CPUSchedulingPriority",
+        1,
+        99,
+    )?;
+    let cpu_affinity = parse_cpu_affinity(section)?;
+    let io_scheduling_class = parse_choice(
+        section,
+        "IOSCHEDULINGCLASS",
+        "IOSchedulingClass",
+        &[
+            ("none", IoSchedulingClass::None),
+            ("realtime", IoSchedulingClass::Realtime),
+            ("best-effort", IoSchedulingClass::BestEffort),
+            ("idle", IoSchedulingClass::Idle),
+        ],
+    )?;
+    let io_scheduling_priority = parse_number(
+        section,
+        "IOSCHEDULINGPRIORITY",
+        "IOSchedulingPriority",
+        0,
+        7,
+    )?;
+    let personality = parse_choice(
+        section,
+        "PERSONALITY",
+        "Personality",
+        &[
+            ("x86", Personality::X86),
+            ("x86-64", Personality::X86_64),
+            ("arm", Personality::Arm),
+            ("arm64", Personality::Arm64),
+        ],
+    )?;
+
+    Ok(ProcessSettings {
+        root_directory,
+        umask,
+        nice,
+        cpu_scheduling_policy,
+        cpu_scheduling_priority,
+        cpu_affinity,
+        io_scheduling_class,
+        io_scheduling_priority,
+        personality,
+    })
+}
@@ -0,0 +1,439 @@
+//! Parse the sandboxing and capability settings of the exec section
+
+use super::get_single_value;
+use super::ParsedSection;
+use crate::units::*;
+use std::path::PathBuf;
+
+fn get_bool(
+    section: &mut ParsedSection,
+    key: &str,
+    setting_name: &str,
+) -> Result<bool, ParsingErrorReason> {
+    Ok(get_single_value(section, key, setting_name)?
+        .map(|val| string_to_bool(&val))
+        .unwrap_or(false))
+}
+
+/// All values of a list setting split at whitespace. An empty assignment resets the list like in systemd.
+pub(super) fn list_values(section: &mut ParsedSection, key: &str) -> Vec<String> {
+    let mut values = Vec::new();
+    if let Some(vec) = section.remove(key) {
+        for (_, line) in vec {
+            if line.trim().is_empty() {
+                values.clear();
+            }
+            values.extend(line.split_whitespace().map(|x| x.to_owned()));
+        }
+    }
+    values
+}
+
+fn make_absolute_path(setting_name: &str, path: &str) -> Result<PathBuf, ParsingErrorReason> {
+    // in systemd "+" means relative to RootDirectory=. The sandbox is set up before the chroot and all its paths are
+    // relative to the host, so the prefix is ignored
+    let path = path.trim_start_matches('+');
+    if path.starts_with('/') {
+        Ok(PathBuf::from(path))
+    } else {
+        Err(ParsingErrorReason::UnknownSetting(
+            setting_name.to_owned(),
+            path.to_owned(),
+        ))
+    }
+}
+
+fn parse_paths(
+    section: &mut ParsedSection,
+    key: &str,
+    setting_name: &str,
+) -> Result<Vec<SandboxPath>, ParsingErrorReason> {
+    let mut paths = Vec::new();
+    for value in list_values(section, key) {
+        let ignore_missing = value.starts_with('-');
+        let path = make_absolute_path(setting_name, value.trim_start_matches('-'))?;
+        paths.push(SandboxPath {
+            path,
+            ignore_missing,
+        });
+    }
+    Ok(paths)
+}
+
+/// Entries look like "src[:dst[:rbind|norbind]]"
+fn parse_bind_paths(
+    section: &mut ParsedSection,
+    key: &str,
+    setting_name: &str,
+) -> Result<Vec<BindPath>, ParsingErrorReason> {
+    let mut paths = Vec::new();
+    for value in list_values(section, key) {
+        let ignore_missing = value.starts_with('-');
+        let parts: Vec<&str> = value.trim_start_matches('-').split(':').collect();
+        let src = make_absolute_path(setting_name, parts[0])?;
+        let dst = match parts.get(1) {
+            Some(dst) => make_absolute_path(setting_name, dst)?,
+            None => src.clone(),
+        };
+        let recursive = match parts.get(2) {
+            None | Some(&"rbind") => true,
+            Some(&"norbind") => false,
+            Some(_) => {
+                return Err(ParsingErrorReason::UnknownSetting(
+                    setting_name.to_owned(),
+                    value.clone(),
+                ))
+            }
+        };
+        if parts.len() > 3 {
+            return Err(ParsingErrorReason::UnknownSetting(
+                setting_name.to_owned(),
+                value.clone(),
+            ));
+        }
+        paths.push(BindPath {
+            src,
+            dst,
+            recursive,
+            ignore_missing,
+        });
+    }
+    Ok(paths)
+}
+
+pub fn parse_filesystem_sandbox(
+    section: &mut ParsedSection,
+) -> Result<FilesystemSandbox, ParsingErrorReason> {
+    let private_tmp = get_bool(section, "PRIVATETMP", "PrivateTmp")?;
+
+    let protect_system = match get_single_value(section, "PROTECTSYSTEM", "ProtectSystem")? {
+        None => ProtectSystem::No,
+        Some(val) => match val.as_str() {
+            "full" => ProtectSystem::Full,
+            "strict" => ProtectSystem::Strict,
+            _ => {
+                if string_to_bool(&val) {
+                    ProtectSystem::Yes
+                } else {
+                    ProtectSystem::No
+                }
+            }
+        },
+    };
+
+    let protect_home = match get_single_value(section, "PROTECTHOME", "ProtectHome")? {
+        None => ProtectHome::No,
+        Some(val) => match val.as_str() {
+            "read-only" => ProtectHome::ReadOnly,
+            "tmpfs" => ProtectHome::Tmpfs,
+            _ => {
+                if string_to_bool(&val) {
+                    ProtectHome::Yes
+                } else {
+                    ProtectHome::No
+                }
+            }
+        },
+    };
+
+    let read_write_paths = parse_paths(section, "READWRITEPATHS", "ReadWritePaths")?;
+    let read_only_paths = parse_paths(section, "READONLYPATHS", "ReadOnlyPaths")?;
+    let inaccessible_paths = parse_paths(section, "INACCESSIBLEPATHS", "InaccessiblePaths")?;
+    let bind_paths = parse_bind_paths(section, "BINDPATHS", "BindPaths")?;
+    let bind_read_only_paths = parse_bind_paths(section, "BINDREADONLYPATHS", "BindReadOnlyPaths")?;
+
+    let mut temporary_file_systems = Vec::new();
+    for value in list_values(section, "TEMPORARYFILESYSTEM") {
+        let (path, options) = match value.find(':') {
+            Some(pos) => (&value[..pos], &value[pos + 1..]),
+            None => (value.as_str(), ""),
+        };
+        temporary_file_systems.push(TemporaryFileSystem {
+            path: make_absolute_path("TemporaryFileSystem", path)?,
+            options: options.to_owned(),
+        });
+    }
+
+    let private_devices = get_bool(section, "PRIVATEDEVICES", "PrivateDevices")?;
+    let protect_kernel_tunables =
+        get_bool(section, "PROTECTKERNELTUNABLES", "ProtectKernelTunables")?;
+    let protect_kernel_modules = get_bool(section, "PROTECTKERNELMODULES", "ProtectKernelModules")?;
+    let protect_kernel_logs = get_bool(section, "PROTECTKERNELLOGS", "ProtectKernelLogs")?;
+    let protect_control_groups = get_bool(section, "PROTECTCONTROLGROUPS", "ProtectControlGroups")?;
+
+    Ok(FilesystemSandbox {
+        private_tmp,
+        protect_system,
+        protect_home,
+        read_write_paths,
+        read_only_paths,
+        inaccessible_paths,
+        bind_paths,
+        bind_read_only_paths,
+        temporary_file_systems,
+        private_devices,
+        protect_kernel_tunables,
+        protect_kernel_modules,
+        protect_kernel_logs,
+        protect_control_groups,
+    })
+}
+
+/// The CLONE_NEW* flags by the names used in RestrictNamespaces=
+const NAMESPACE_FLAGS: &[(&str, u64)] = &[
+    ("cgroup", 0x0200_0000),
+    ("ipc", 0x0800_0000),
+    ("net", 0x4000_0000),
+    ("mnt", 0x0002_0000),
+    ("pid", 0x2000_0000),
+    ("user", 0x1000_0000),
+    ("uts", 0x0400_0000),
+];
+
+/// All flags from NAMESPACE_FLAGS
+pub const ALL_NAMESPACE_FLAGS: u64 = 0x7E02_0000;
+
+pub fn parse_namespace_sandbox(
+    section: &mut ParsedSection,
+) -> Result<NamespaceSandbox, ParsingErrorReason> {
+    let private_network = get_bool(section, "PRIVATENETWORK", "PrivateNetwork")?;
+    let private_users = get_bool(section, "PRIVATEUSERS", "PrivateUsers")?;
+    let protect_hostname = get_bool(section, "PROTECTHOSTNAME", "ProtectHostname")?;
+
+    let restrict_namespaces =
+        match get_single_value(section, "RESTRICTNAMESPACES", "RestrictNamespaces")? {
+            None => None,
+            Some(val) => match val.as_str() {
+                "yes" | "true" | "1" => Some(0),
+                "no" | "false" | "0" => None,
+                _ => {
+                    let invert = val.starts_with('~');
+                    let mut flags = 0;
+                    for name in val.trim_start_matches('~').split_whitespace() {
+                        match NAMESPACE_FLAGS.iter().find(|(ns, _)| *ns == name) {
+                            Some((_, flag)) => flags |= flag,
+                            None => {
+                                return Err(ParsingErrorReason::UnknownSetting(
+                                    "RestrictNamespaces".to_owned(),
+                                    name.to_owned(),
+                                ))
+                            }
+                        }
+                    }
+                    if invert {
+                        Some(ALL_NAMESPACE_FLAGS & !flags)
+                    } else {
+                        Some(flags)
+                    }
+                }
+            },
+        };
+
+    Ok(NamespaceSandbox {
+        private_network,
+        private_users,
+        protect_hostname,
+        restrict_namespaces,
+    })
+}
+
+/// Parse a list of capability names into a bitmask. The second value is true if the list was prefixed with "~"
+fn parse_capability_list(
+    setting_name: &str,
+    value: &str,
+) -> Result<(u64, bool), ParsingErrorReason> {
+    let value = value.trim();
+    let invert = value.starts_with('~');
+    let mut mask = 0;
+    for name in value.trim_start_matches('~').split_whitespace() {
+        match crate::platform::capability_from_name(name) {
+            Some(cap) => mask |= 1 << cap,
+            None => {
+                return Err(ParsingErrorReason::UnknownSetting(
+                    setting_name.to_owned(),
+                    name.to_owned(),
+                ))
+            }
+        }
+    }
+    Ok((mask, invert))
+}
+
+pub fn parse_capabilities(
+    section: &mut ParsedSection,
+) -> Result<CapabilitySettings, ParsingErrorReason> {
+    let mut settings = CapabilitySettings::default();
+
+    // multiple assignments are merged, an empty one resets the setting
+    if let Some(vec) = section.remove("CAPABILITYBOUNDINGSET") {
+        for (_, line) in vec {
+            if line.trim().is_empty() {
+                settings.bounding_set = None;
+                continue;
+            }
+            let (mask, invert) = parse_capability_list("CapabilityBoundingSet", &line)?;
+            settings.bounding_set = if invert {
+                Some(!mask)
+            } else {
+                Some(settings.bounding_set.unwrap_or(0) | mask)
+            };
+        }
+    }
+    if let Some(vec) = section.remove("AMBIENTCAPABILITIES") {
+        for (_, line) in vec {
+            if line.trim().is_empty() {
+                settings.ambient = 0;
+                continue;
+            }
+            let (mask, invert) = parse_capability_list("AmbientCapabilities", &line)?;
+            if invert {
+                settings.ambient = crate::platform::ALL_CAPABILITIES & !mask;
+            } else {
+                settings.ambient |= mask;
+            }
+        }
+    }
+    Ok(settings)
+}
+
+/// The protection settings also drop the capabilities that would allow to undo them, like systemd does
+pub fn drop_protected_capabilities(caps: &mut CapabilitySettings, sandbox: &FilesystemSandbox) {
+    let mut dropped = Vec::new();
+    if sandbox.private_devices {
+        dropped.extend(&["CAP_MKNOD", "CAP_SYS_RAWIO"]);
+    }
+    if sandbox.protect_kernel_modules {
+        dropped.push("CAP_SYS_MODULE");
+    }
+    if sandbox.protect_kernel_logs {
+        dropped.push("CAP_SYSLOG");
+    }
+    let mask = dropped
+        .iter()
+        .filter_map(|name| crate::platform::capability_from_name(name))
+        .fold(0u64, |mask, cap| mask | 1 << cap);
+    if mask != 0 {
+        caps.bounding_set = Some(caps.bounding_set.unwrap_or(!0) & !mask);
+        caps.ambient &= !mask;
+    }
+}
+
+/// Error numbers by name, for SystemCallErrorNumber= and the ":errno" suffix of SystemCallFilter= entries
+const ERRNO_NAMES: &[(&str, i32)] = &[
+    ("EPERM", libc::EPERM),
+    ("ENOENT", libc::ENOENT),
+    ("ESRCH", libc::ESRCH),
+    ("EINTR", libc::EINTR),
+    ("EIO", libc::EIO),
+    ("ENXIO", libc::ENXIO),
+    ("E2BIG", libc::E2BIG),
+    ("ENOEXEC", libc::ENOEXEC),
+    ("EBADF", libc::EBADF),
+    ("ECHILD", libc::ECHILD),
+    ("EAGAIN", libc::EAGAIN),
+    ("ENOMEM", libc::ENOMEM),
+    ("EACCES", libc::EACCES),
+    ("EFAULT", libc::EFAULT),
+    ("EBUSY", libc::EBUSY),
+    ("EEXIST", libc::EEXIST),
+    ("EXDEV", libc::EXDEV),
+    ("ENODEV", libc::ENODEV),
+    ("ENOTDIR", libc::ENOTDIR),
+    ("EISDIR", libc::EISDIR),
+    ("EINVAL", libc::EINVAL),
+    ("ENFILE", libc::ENFILE),
+    ("EMFILE", libc::EMFILE),
+    ("ENOTTY", libc::ENOTTY),
+    ("EFBIG", libc::EFBIG),
+    ("ENOSPC", libc::ENOSPC),
+    ("ESPIPE", libc::ESPIPE),
+    ("EROFS", libc::EROFS),
+    ("EMLINK", libc::EMLINK),
+    ("EPIPE", libc::EPIPE),
+    ("ERANGE", libc::ERANGE),
+    ("ENOSYS", libc::ENOSYS),
+    ("ENOTSUP", libc::ENOTSUP),
+    ("EOPNOTSUPP", libc::EOPNOTSUPP),
+    ("EAFNOSUPPORT", libc::EAFNOSUPPORT),
+    ("EADDRINUSE", libc::EADDRINUSE),
+    ("ECONNREFUSED", libc::ECONNREFUSED),
+];
+
+/// An errno by name (like "EPERM") or number
+pub fn parse_errno(value: &str) -> Option<i32> {
+    let value = value.trim();
+    if let Ok(number) = value.parse::<i32>() {
+        // the errno of a seccomp filter has only 12 bits
+        return if (0..4096).contains(&number) {
+            Some(number)
+        } else {
+            None
+        };
+    }
+    ERRNO_NAMES
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, errno)| *errno)
+}
+
+pub fn parse_syscall_filter(
+    section: &mut ParsedSection,
+) -> Result<SyscallFilter, ParsingErrorReason> {
+    let mut filter = SyscallFilter::default();
+
+    if let Some(vec) = section.remove("SYSTEMCALLFILTER") {
+        for (_, line) in vec {
+            let line = line.trim();
+            if line.is_empty() {
+                filter.rules.clear();
+                continue;
+            }
+            let deny = line.starts_with('~');
+            let names: Vec<String> = line
+                .trim_start_matches('~')
+                .split_whitespace()
+                .map(|name| name.to_owned())
+                .collect();
+            // the system call names are checked when the filter is built, the errno can be checked right away
+            for name in &names {
+                if let Some(pos) = name.find(':') {
+                    if parse_errno(&name[pos + 1..]).is_none() {
+                        return Err(ParsingErrorReason::UnknownSetting(
+                            "SystemCallFilter".to_owned(),
+                            name.clone(),
+                        ));
+                    }
+                }
+            }
+            filter.rules.push((deny, names));
+        }
+    }
+
+    if let Some(val) = get_single_value(section, "SYSTEMCALLERRORNUMBER", "SystemCallErrorNumber")?
+    {
+        if val != "kill" {
+            match parse_errno(&val) {
+                Some(errno) => filter.error_number = Some(errno),
+                None => {
+                    return Err(ParsingErrorReason::UnknownSetting(
+                        "SystemCallErrorNumber".to_owned(),
+                        val,
+                    ))
+                }
+            }
+        }
+    }
+
+    for arch in list_values(section, "SYSTEMCALLARCHITECTURES") {
+        if arch == "native" {
+            filter.native_arch_only = true;
+        } else {
+            return Err(ParsingErrorReason::UnsupportedSetting(format!(
+                "SystemCallArchitectures: {}",
+                arch
+            )));
+        }
+    }
+
+    Ok(filter)
+}
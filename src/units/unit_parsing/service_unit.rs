@@ -25,15 +25,22 @@ pub fn parse_service(
         }
     }
 
-    let service_config = if let Some(service_config) = service_config {
+    let mut service_config = if let Some(service_config) = service_config {
         service_config
     } else {
         return Err(ParsingErrorReason::SectionNotFound("Service".to_owned()));
     };
 
+    let name = path.file_name().unwrap().to_str().unwrap().to_owned();
+    let exec_section = &mut service_config.exec_section;
+    if exec_section.dynamic_user && exec_section.user.is_none() {
+        // the dynamic user is named like the service if User= is not set
+        exec_section.user = Some(name.trim_end_matches(".service").to_owned());
+    }
+
     Ok(ParsedServiceConfig {
         common: ParsedCommonConfig {
-            name: path.file_name().unwrap().to_str().unwrap().to_owned(),
+            name,
             unit: unit_config.unwrap_or_else(Default::default),
             install: install_config.unwrap_or_else(Default::default),
         },
@@ -41,7 +48,7 @@ pub fn parse_service(
     })
 }
 
-fn parse_timeout(descr: &str) -> Timeout {
+pub fn parse_timeout(descr: &str) -> Timeout {
     if descr.to_uppercase() == "INFINITY" {
         Timeout::Infinity
     } else {
@@ -155,8 +162,11 @@ fn parse_service_section(
     let notify_access = section.remove("NOTIFYACCESS");
     let srcv_type = section.remove("TYPE");
     let accept = section.remove("ACCEPT");
+    let remain_after_exit = section.remove("REMAINAFTEREXIT");
     let dbus_name = section.remove("BUSNAME");
 
+    let kill = super::parse_kill_section(&mut section)?;
+    let exit_status = super::parse_exit_status_section(&mut section)?;
     let exec_config = super::parse_exec_section(&mut section)?;
 
     if !section.is_empty() {
@@ -205,20 +215,6 @@ fn parse_service_section(
         None => None,
     };
 
-    let exec = match exec {
-        Some(mut vec) => {
-            if vec.len() == 1 {
-                parse_cmdline(&vec.remove(0).1)?
-            } else {
-                return Err(ParsingErrorReason::SettingTooManyValues(
-                    "ExecStart".to_owned(),
-                    super::map_tupels_to_second(vec),
-                ));
-            }
-        }
-        None => return Err(ParsingErrorReason::MissingSetting("ExecStart".to_owned())),
-    };
-
     let srcv_type = match srcv_type {
         Some(vec) => {
             if vec.len() == 1 {
@@ -254,6 +250,21 @@ fn parse_service_section(
         None => ServiceType::Simple,
     };
 
+    // only oneshot services can have multiple ExecStart=, they are run one after the other
+    let exec = match exec {
+        Some(vec) => {
+            if vec.len() == 1 || srcv_type == ServiceType::OneShot {
+                parse_cmdlines(&vec)?
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "ExecStart".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => return Err(ParsingErrorReason::MissingSetting("ExecStart".to_owned())),
+    };
+
     let notifyaccess = match notify_access {
         Some(vec) => {
             if vec.len() == 1 {
@@ -332,6 +343,19 @@ fn parse_service_section(
         }
         None => false,
     };
+    let remain_after_exit = match remain_after_exit {
+        Some(vec) => {
+            if vec.len() == 1 {
+                string_to_bool(&vec[0].1)
+            } else {
+                return Err(ParsingErrorReason::SettingTooManyValues(
+                    "RemainAfterExit".to_owned(),
+                    super::map_tupels_to_second(vec),
+                ));
+            }
+        }
+        None => false,
+    };
     let dbus_name = match dbus_name {
         Some(vec) => {
             if vec.len() == 1 {
@@ -357,6 +381,7 @@ fn parse_service_section(
         notifyaccess,
         restart,
         accept,
+        remain_after_exit,
         dbus_name,
         exec,
         stop,
@@ -367,6 +392,8 @@ fn parse_service_section(
         stoptimeout,
         generaltimeout,
         sockets: map_tupels_to_second(sockets.unwrap_or_default()),
+        kill,
+        exit_status,
         exec_section: exec_config,
     })
 }
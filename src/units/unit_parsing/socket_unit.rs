@@ -62,6 +62,28 @@ fn parse_unix_addr(addr: &str) -> Result<String, ()> {
     }
 }
 
+fn parse_limit_interval(
+    section: &mut ParsedSection,
+    key: &str,
+    name: &str,
+) -> Result<Option<Timeout>, ParsingErrorReason> {
+    Ok(get_single_value(section, key, name)?.map(|value| parse_timeout(&value)))
+}
+
+fn parse_limit_burst(
+    section: &mut ParsedSection,
+    key: &str,
+    name: &str,
+) -> Result<Option<u64>, ParsingErrorReason> {
+    match get_single_value(section, key, name)? {
+        None => Ok(None),
+        Some(value) => value
+            .parse::<u64>()
+            .map(Some)
+            .map_err(|_| ParsingErrorReason::UnknownSetting(name.to_owned(), value)),
+    }
+}
+
 fn parse_socket_section(
     mut section: ParsedSection,
 ) -> Result<ParsedSocketSection, ParsingErrorReason> {
@@ -71,6 +93,16 @@ fn parse_socket_section(
     let datagrams = section.remove("LISTENDATAGRAM");
     let seqpacks = section.remove("LISTENSEQUENTIALPACKET");
     let fifos = section.remove("LISTENFIFO");
+    let trigger_limit_interval = parse_limit_interval(
+        &mut section,
+        "TRIGGERLIMITINTERVALSEC",
+        "TriggerLimitIntervalSec",
+    )?;
+    let trigger_limit_burst =
+        parse_limit_burst(&mut section, "TRIGGERLIMITBURST", "TriggerLimitBurst")?;
+    let poll_limit_interval =
+        parse_limit_interval(&mut section, "POLLLIMITINTERVALSEC", "PollLimitIntervalSec")?;
+    let poll_limit_burst = parse_limit_burst(&mut section, "POLLLIMITBURST", "PollLimitBurst")?;
 
     let exec_config = super::parse_exec_section(&mut section)?;
 
@@ -99,6 +131,18 @@ fn parse_socket_section(
         .map(|vec| super::map_tupels_to_second(vec))
         .unwrap_or_default();
 
+    // defaults are the same as systemd uses
+    let trigger_limit = RateLimit {
+        interval: trigger_limit_interval
+            .unwrap_or(Timeout::Duration(std::time::Duration::from_secs(2))),
+        burst: trigger_limit_burst.unwrap_or(200),
+    };
+    let poll_limit = RateLimit {
+        interval: poll_limit_interval
+            .unwrap_or(Timeout::Duration(std::time::Duration::from_secs(2))),
+        burst: poll_limit_burst.unwrap_or(150),
+    };
+
     let mut socket_kinds: Vec<(u32, SocketKind)> = Vec::new();
     if let Some(mut streams) = streams {
         for _ in 0..streams.len() {
@@ -188,6 +232,8 @@ fn parse_socket_section(
         filedesc_name: fdname,
         services,
         sockets: socket_configs,
+        trigger_limit,
+        poll_limit,
         exec_section: exec_config,
     })
 }
@@ -103,6 +103,8 @@ pub fn parse_unit_section(
     let after = section.remove("AFTER");
     let before = section.remove("BEFORE");
     let description = section.remove("DESCRIPTION");
+    let allow_isolate = get_single_value(&mut section, "ALLOWISOLATE", "AllowIsolate")?;
+    let ignore_on_isolate = get_single_value(&mut section, "IGNOREONISOLATE", "IgnoreOnIsolate")?;
 
     if !section.is_empty() {
         return Err(ParsingErrorReason::UnusedSetting(
@@ -116,21 +118,100 @@ pub fn parse_unit_section(
         requires: map_tupels_to_second(requires.unwrap_or_default()),
         after: map_tupels_to_second(after.unwrap_or_default()),
         before: map_tupels_to_second(before.unwrap_or_default()),
+        allow_isolate: allow_isolate.map(|x| string_to_bool(&x)).unwrap_or(false),
+        ignore_on_isolate: ignore_on_isolate
+            .map(|x| string_to_bool(&x))
+            .unwrap_or(false),
     })
 }
 
-fn make_stdio_option(setting: &str) -> Result<StdIoOption, ParsingErrorReason> {
+/// Remove a setting that may only appear once from the section
+pub fn get_single_value(
+    section: &mut ParsedSection,
+    key: &str,
+    setting_name: &str,
+) -> Result<Option<String>, ParsingErrorReason> {
+    match section.remove(key) {
+        None => Ok(None),
+        Some(mut vec) => {
+            if vec.len() == 1 {
+                Ok(Some(vec.remove(0).1))
+            } else if vec.len() > 1 {
+                Err(ParsingErrorReason::SettingTooManyValues(
+                    setting_name.into(),
+                    super::map_tupels_to_second(vec),
+                ))
+            } else {
+                Ok(None)
+            }
+        }
+    }
+}
+
+fn parse_priority_setting(
+    setting_name: &str,
+    value: Option<String>,
+) -> Result<Option<u8>, ParsingErrorReason> {
+    match value {
+        None => Ok(None),
+        Some(value) => match crate::log_store::parse_priority(&value) {
+            Some(prio) => Ok(Some(prio)),
+            None => Err(ParsingErrorReason::UnknownSetting(
+                setting_name.into(),
+                value,
+            )),
+        },
+    }
+}
+
+fn make_stdio_option(setting_name: &str, setting: &str) -> Result<StdIoOption, ParsingErrorReason> {
     if setting.starts_with("file:") {
         let p = setting.trim_start_matches("file:");
         Ok(StdIoOption::File(p.into()))
     } else if setting.starts_with("append:") {
         let p = setting.trim_start_matches("append:");
         Ok(StdIoOption::AppendFile(p.into()))
+    } else if setting.starts_with("truncate:") {
+        let p = setting.trim_start_matches("truncate:");
+        Ok(StdIoOption::TruncateFile(p.into()))
+    } else if setting.starts_with("fd:") {
+        let name = setting.trim_start_matches("fd:");
+        Ok(StdIoOption::Fd(name.into()))
     } else {
-        return Err(ParsingErrorReason::UnsupportedSetting(format!(
-            "StandardOutput: {}",
-            setting
-        )));
+        match setting {
+            // rustysd always prints the output on its own stdout so the +console variants are the same
+            "journal" | "journal+console" | "syslog" | "syslog+console" => Ok(StdIoOption::Journal),
+            "kmsg" | "kmsg+console" => Ok(StdIoOption::Kmsg),
+            "null" => Ok(StdIoOption::Null),
+            "inherit" => Ok(StdIoOption::Inherit),
+            "tty" => Ok(StdIoOption::Tty),
+            "socket" => Ok(StdIoOption::Socket),
+            _ => Err(ParsingErrorReason::UnsupportedSetting(format!(
+                "{}: {}",
+                setting_name, setting
+            ))),
+        }
+    }
+}
+
+fn make_stdin_option(
+    setting: &str,
+    text: Option<String>,
+) -> Result<StdInOption, ParsingErrorReason> {
+    if setting.starts_with("file:") {
+        let p = setting.trim_start_matches("file:");
+        Ok(StdInOption::File(p.into()))
+    } else {
+        match setting {
+            "null" => Ok(StdInOption::Null),
+            "tty" => Ok(StdInOption::Tty),
+            "socket" => Ok(StdInOption::Socket),
+            "data" => Ok(StdInOption::Data(text.unwrap_or_default())),
+            _ => Err(ParsingErrorReason::UnsupportedSetting(format!(
+                "StandardInput: {}",
+                setting
+            ))),
+        }
     }
 }
 
@@ -141,6 +222,53 @@ pub fn parse_exec_section(
     let group = section.remove("GROUP");
     let stdout = section.remove("STANDARDOUTPUT");
     let stderr = section.remove("STANDARDERROR");
+    let stdin = get_single_value(section, "STANDARDINPUT", "StandardInput")?;
+    let stdin_text = section.remove("STANDARDINPUTTEXT");
+    let tty_path = get_single_value(section, "TTYPATH", "TTYPath")?.map(PathBuf::from);
+    let syslog_identifier = get_single_value(section, "SYSLOGIDENTIFIER", "SyslogIdentifier")?;
+    let syslog_facility = get_single_value(section, "SYSLOGFACILITY", "SyslogFacility")?;
+    let syslog_level = get_single_value(section, "SYSLOGLEVEL", "SyslogLevel")?;
+    let syslog_level_prefix = get_single_value(section, "SYSLOGLEVELPREFIX", "SyslogLevelPrefix")?;
+    let log_level_max = get_single_value(section, "LOGLEVELMAX", "LogLevelMax")?;
+    let mut sandbox = super::parse_filesystem_sandbox(section)?;
+    let mut no_new_privileges = get_single_value(section, "NONEWPRIVILEGES", "NoNewPrivileges")?
+        .map(|val| string_to_bool(&val))
+        .unwrap_or(false);
+    let dynamic_user = get_single_value(section, "DYNAMICUSER", "DynamicUser")?
+        .map(|val| string_to_bool(&val))
+        .unwrap_or(false);
+    let directories = super::parse_directories(section)?;
+    let working_directory = super::parse_working_directory(section)?;
+    let process = super::parse_process_settings(section)?;
+    let limits = super::parse_limits(section)?;
+    if dynamic_user {
+        // like systemd: the uid might be reused by another service later, so the service must not leave files or setuid binaries behind
+        if sandbox.protect_system == ProtectSystem::No {
+            sandbox.protect_system = ProtectSystem::Strict;
+        }
+        if sandbox.protect_home == ProtectHome::No {
+            sandbox.protect_home = ProtectHome::ReadOnly;
+        }
+        sandbox.private_tmp = true;
+        no_new_privileges = true;
+    }
+    if sandbox.is_enabled() {
+        // the directories must stay writable even with e.g. ProtectSystem=strict. Configuration directories are not meant to be changed by the service.
+        for dir in &directories.directories {
+            if dir.kind != ExecDirectoryKind::Configuration {
+                sandbox
+                    .read_write_paths
+                    .extend(dir.paths().into_iter().map(|path| SandboxPath {
+                        path,
+                        ignore_missing: false,
+                    }));
+            }
+        }
+    }
+    let namespaces = super::parse_namespace_sandbox(section)?;
+    let mut capabilities = super::parse_capabilities(section)?;
+    super::drop_protected_capabilities(&mut capabilities, &sandbox);
+    let syscall_filter = super::parse_syscall_filter(section)?;
     let supplementary_groups = section.remove("SUPPLEMENTARYGROUPS");
     let environment = section.remove("ENVIRONMENT");
 
@@ -191,7 +319,7 @@ pub fn parse_exec_section(
         }
     };
     let stdout_path = if let Some(p) = stdout_path {
-        Some(make_stdio_option(&p)?)
+        Some(make_stdio_option("StandardOutput", &p)?)
     } else {
         None
     };
@@ -212,11 +340,44 @@ pub fn parse_exec_section(
         }
     };
     let stderr_path = if let Some(p) = stderr_path {
-        Some(make_stdio_option(&p)?)
+        Some(make_stdio_option("StandardError", &p)?)
     } else {
         None
     };
 
+    // every StandardInputText= line is one line of the data
+    let stdin_text = stdin_text.map(|lines| {
+        lines.iter().fold(String::new(), |mut acc, (_id, line)| {
+            acc.push_str(line);
+            acc.push('\n');
+            acc
+        })
+    });
+    let stdin_option = match stdin {
+        Some(setting) => Some(make_stdin_option(&setting, stdin_text)?),
+        // like systemd: setting a text without StandardInput= implies StandardInput=data
+        None => stdin_text.map(StdInOption::Data),
+    };
+
+    let syslog_facility = match syslog_facility {
+        None => crate::log_store::FACILITY_DAEMON,
+        Some(facility) => match crate::log_store::parse_facility(&facility) {
+            Some(facility) => facility,
+            None => {
+                return Err(ParsingErrorReason::UnknownSetting(
+                    "SyslogFacility".into(),
+                    facility,
+                ))
+            }
+        },
+    };
+    let syslog_level =
+        parse_priority_setting("SyslogLevel", syslog_level)?.unwrap_or(crate::log_store::PRIO_INFO);
+    let syslog_level_prefix = syslog_level_prefix
+        .map(|prefix| string_to_bool(&prefix))
+        .unwrap_or(true);
+    let log_level_max = parse_priority_setting("LogLevelMax", log_level_max)?;
+
     let supplementary_groups = match supplementary_groups {
         None => Vec::new(),
         Some(vec) => vec.iter().fold(Vec::new(), |mut acc, (_id, list)| {
@@ -238,6 +399,23 @@ pub fn parse_exec_section(
         group,
         stderr_path,
         stdout_path,
+        stdin_option,
+        tty_path,
+        syslog_identifier,
+        syslog_facility,
+        syslog_level,
+        syslog_level_prefix,
+        log_level_max,
+        sandbox,
+        no_new_privileges,
+        dynamic_user,
+        directories,
+        working_directory,
+        process,
+        limits,
+        namespaces,
+        capabilities,
+        syscall_filter,
         supplementary_groups,
         environment,
     })
@@ -248,6 +426,8 @@ pub fn parse_install_section(
 ) -> Result<ParsedInstallSection, ParsingErrorReason> {
     let wantedby = section.remove("WANTEDBY");
     let requiredby = section.remove("REQUIREDBY");
+    let alias = section.remove("ALIAS");
+    let also = section.remove("ALSO");
 
     if !section.is_empty() {
         return Err(ParsingErrorReason::UnusedSetting(
@@ -258,6 +438,8 @@ pub fn parse_install_section(
     Ok(ParsedInstallSection {
         wanted_by: map_tupels_to_second(wantedby.unwrap_or_default()),
         required_by: map_tupels_to_second(requiredby.unwrap_or_default()),
+        alias: map_tupels_to_second(alias.unwrap_or_default()),
+        also: map_tupels_to_second(also.unwrap_or_default()),
     })
 }
 
@@ -21,9 +21,11 @@ pub enum UnitOperationErrorReason {
     GenericStopError(String),
     SocketOpenError(String),
     SocketCloseError(String),
+    SocketTriggerLimitHit,
     ServiceStartError(ServiceErrorReason),
     ServiceStopError(ServiceErrorReason),
     DependencyError(Vec<UnitId>),
+    Masked,
 }
 
 impl std::fmt::Display for UnitOperationError {
@@ -71,6 +73,13 @@ impl std::fmt::Display for UnitOperationError {
                     self.unit_name, self.unit_id, msg
                 )?;
             }
+            UnitOperationErrorReason::SocketTriggerLimitHit => {
+                write!(
+                    f,
+                    "Socket {} (ID {}) was triggered too often and stopped listening",
+                    self.unit_name, self.unit_id
+                )?;
+            }
             UnitOperationErrorReason::DependencyError(ids) => {
                 write!(
                     f,
@@ -78,6 +87,13 @@ impl std::fmt::Display for UnitOperationError {
                     self.unit_name, self.unit_id, ids
                 )?;
             }
+            UnitOperationErrorReason::Masked => {
+                write!(
+                    f,
+                    "Unit {} (ID {}) is masked and can not be started",
+                    self.unit_name, self.unit_id
+                )?;
+            }
         }
         Ok(())
     }
@@ -105,7 +121,7 @@ pub fn unstarted_deps(id: &UnitId, run_info: &RuntimeInfo) -> Vec<UnitId> {
             let elem_unit = run_info.unit_table.get(elem).unwrap();
             let status_locked = elem_unit.common.status.read().unwrap();
             let ready = if required {
-                status_locked.is_started()
+                status_locked.satisfies_requirement()
             } else {
                 *status_locked != UnitStatus::NeverStarted
             };
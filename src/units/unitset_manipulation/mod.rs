@@ -4,6 +4,8 @@ mod activate;
 mod deactivate;
 mod insert_new;
 mod locking;
+mod ordering;
+mod reload;
 mod remove;
 mod sanity_check;
 
@@ -11,5 +13,7 @@ pub use activate::*;
 pub use deactivate::*;
 pub use insert_new::*;
 pub use locking::*;
+pub use ordering::*;
+pub use reload::*;
 pub use remove::*;
 pub use sanity_check::*;
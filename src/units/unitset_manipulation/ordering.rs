@@ -0,0 +1,40 @@
+use crate::runtime_info::*;
+use crate::units::*;
+
+/// Order the units so every unit comes after the units returned by must_come_first that are in the list too. Cycles are broken up
+/// by taking the remaining units in their original order.
+pub fn order_units<F: Fn(&Unit) -> Vec<UnitId>>(
+    mut ids: Vec<UnitId>,
+    unit_table: &UnitTable,
+    must_come_first: F,
+) -> Vec<UnitId> {
+    let mut ordered = Vec::with_capacity(ids.len());
+    while !ids.is_empty() {
+        let next = ids
+            .iter()
+            .position(|id| {
+                let first = unit_table.get(id).map(&must_come_first).unwrap_or_default();
+                !ids.iter().any(|other| first.contains(other))
+            })
+            .unwrap_or(0);
+        ordered.push(ids.remove(next));
+    }
+    ordered
+}
+
+/// The order in which the units are stopped, on shutdown and for the stop jobs of a transaction. Units that were started after a unit
+/// or that require it are stopped before it.
+pub fn stop_order(ids: Vec<UnitId>, unit_table: &UnitTable) -> Vec<UnitId> {
+    order_units(ids, unit_table, |unit| {
+        let mut ids = unit.common.dependencies.stop_before_this();
+        ids.extend(unit.common.dependencies.kill_before_this());
+        ids
+    })
+}
+
+/// The order in which the start jobs of a transaction are run, by the after relations
+pub fn start_order(ids: Vec<UnitId>, unit_table: &UnitTable) -> Vec<UnitId> {
+    order_units(ids, unit_table, |unit| {
+        unit.common.dependencies.start_before_this()
+    })
+}
@@ -0,0 +1,198 @@
+use log::trace;
+
+use crate::runtime_info::*;
+use crate::units::*;
+
+/// A change found by a reload that could not be applied yet because the unit was not stopped
+pub enum PendingReload {
+    /// The unit file changed. Only the configs of this newly loaded unit are used, its state is thrown away
+    Changed(Box<Unit>),
+    /// The unit file was removed
+    Removed,
+}
+
+/// The names of the units grouped by what the reload did with them
+#[derive(Default, Debug)]
+pub struct ReloadResult {
+    pub added: Vec<String>,
+    pub changed: Vec<String>,
+    pub removed: Vec<String>,
+    /// Units that were running while their file changed or was removed. The change is applied when they are stopped
+    pub needs_restart: Vec<String>,
+}
+
+fn is_inactive(unit: &Unit) -> bool {
+    let status = unit.common.status.read().unwrap();
+    status.is_stopped() || *status == UnitStatus::NeverStarted
+}
+
+fn same_config(old: &Unit, new: &Unit) -> bool {
+    let mut old_deps = old.common.dependencies.clone();
+    let mut new_deps = new.common.dependencies.clone();
+    old_deps.dedup();
+    new_deps.dedup();
+
+    let same_specific = match (&old.specific, &new.specific) {
+        (Specific::Service(old), Specific::Service(new)) => old.conf == new.conf,
+        (Specific::Socket(old), Specific::Socket(new)) => old.conf == new.conf,
+        (Specific::Target(_), Specific::Target(_)) => true,
+        _ => false,
+    };
+    old.common.unit == new.common.unit && old_deps == new_deps && same_specific
+}
+
+/// Replace the configs of the unit but keep its status and state. The other units still have the reverse edges of the old
+/// dependencies, these are replaced with the ones of the new dependencies.
+fn swap_config(id: &UnitId, new_unit: Unit, unit_table: &mut UnitTable) {
+    trace!("Swap in the new config of unit: {}", id.name);
+    let deps = new_unit.common.dependencies.clone();
+    for other in unit_table.values_mut().filter(|other| other.id != *id) {
+        let other_deps = &mut other.common.dependencies;
+        other_deps.remove_id(id);
+        let reverse_edges = [
+            (&deps.wants, &mut other_deps.wanted_by),
+            (&deps.wanted_by, &mut other_deps.wants),
+            (&deps.requires, &mut other_deps.required_by),
+            (&deps.required_by, &mut other_deps.requires),
+            (&deps.before, &mut other_deps.after),
+            (&deps.after, &mut other_deps.before),
+        ];
+        for (edges, reverse) in reverse_edges {
+            if edges.contains(&other.id) {
+                reverse.push(id.clone());
+            }
+        }
+        other.dedup_dependencies();
+    }
+
+    let unit = unit_table.get_mut(id).unwrap();
+    unit.common.unit = new_unit.common.unit;
+    unit.common.dependencies = deps;
+    match (&mut unit.specific, new_unit.specific) {
+        (Specific::Service(old), Specific::Service(new)) => old.conf = new.conf,
+        (Specific::Socket(old), Specific::Socket(new)) => old.conf = new.conf,
+        _ => {}
+    }
+}
+
+/// Parse all unit files again and compare them to the loaded units.
+///
+/// 1. New units are added. Besides the units needed for the target unit all units that are loaded right now are kept
+/// 1. Stopped units get the new configs, running units keep theirs until they are stopped
+/// 1. Units whose file was removed are removed if they and the units requiring them are stopped
+///
+/// The status and state of the units are kept, so no pids or filedescriptors are lost.
+pub fn reload_all_units(run_info: &mut RuntimeInfo) -> Result<ReloadResult, String> {
+    let mut new_units = load_all_unit_files(
+        &run_info.config.unit_dirs,
+        run_info.config.apply_install_sections(),
+    )
+    .map_err(|e| format!("Error while loading unit definitons: {:?}", e))?;
+
+    let mut keep_ids: Vec<UnitId> = run_info
+        .unit_table
+        .keys()
+        .filter(|id| new_units.contains_key(id))
+        .cloned()
+        .collect();
+    if let Some(target) = new_units
+        .keys()
+        .find(|id| id.name == run_info.config.target_unit)
+    {
+        keep_ids.push(target.clone());
+    }
+    prune_units_not_needed_by(keep_ids, &mut new_units);
+    prune_sockets(&mut new_units);
+
+    // everything pending from an earlier reload is decided again
+    run_info.pending_reload.clear();
+    let mut result = ReloadResult::default();
+
+    let removed_ids: Vec<UnitId> = run_info
+        .unit_table
+        .keys()
+        .filter(|id| !new_units.contains_key(id))
+        .cloned()
+        .collect();
+
+    let added_ids: Vec<UnitId> = new_units
+        .keys()
+        .filter(|id| !run_info.unit_table.contains_key(id))
+        .cloned()
+        .collect();
+    let mut added_units = UnitTable::new();
+    for id in added_ids {
+        let unit = new_units.remove(&id).unwrap();
+        result.added.push(id.name.clone());
+        added_units.insert(id, unit);
+    }
+    insert_new_units(added_units, run_info)?;
+
+    // the stopped units are swapped first. Running units that only differ in the reverse edges to them are up to date afterwards
+    let (inactive, active): (Vec<_>, Vec<_>) = new_units
+        .into_iter()
+        .partition(|(id, _)| is_inactive(&run_info.unit_table[id]));
+    for (id, new_unit) in inactive {
+        if !same_config(&run_info.unit_table[&id], &new_unit) {
+            swap_config(&id, new_unit, &mut run_info.unit_table);
+            result.changed.push(id.name.clone());
+        }
+    }
+    for (id, new_unit) in active {
+        if !same_config(&run_info.unit_table[&id], &new_unit) {
+            trace!("Unit {} changed while running", id.name);
+            result.needs_restart.push(id.name.clone());
+            run_info
+                .pending_reload
+                .insert(id, PendingReload::Changed(Box::new(new_unit)));
+        }
+    }
+
+    for id in removed_ids {
+        if !run_info.unit_table.contains_key(&id) {
+            // was removed together with a unit that referenced it
+            result.removed.push(id.name.clone());
+            continue;
+        }
+        match remove_unit_with_dependencies(id.clone(), run_info) {
+            Ok(()) => result.removed.push(id.name.clone()),
+            Err(e) => {
+                trace!("Can not remove unit {} yet: {}", id.name, e);
+                result.needs_restart.push(id.name.clone());
+                run_info.pending_reload.insert(id, PendingReload::Removed);
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Apply the changes of the last reload to the units that have been stopped since
+pub fn apply_pending_reloads(run_info: &mut RuntimeInfo) {
+    let mut ids: Vec<UnitId> = run_info.pending_reload.keys().cloned().collect();
+    // swap the changed configs first, they might no longer reference the removed units
+    ids.sort_by_key(|id| matches!(run_info.pending_reload[id], PendingReload::Removed));
+    for id in ids {
+        let inactive = match run_info.unit_table.get(&id) {
+            Some(unit) => is_inactive(unit),
+            None => {
+                run_info.pending_reload.remove(&id);
+                continue;
+            }
+        };
+        if !inactive {
+            continue;
+        }
+        match run_info.pending_reload.remove(&id).unwrap() {
+            PendingReload::Changed(new_unit) => {
+                swap_config(&id, *new_unit, &mut run_info.unit_table);
+            }
+            PendingReload::Removed => {
+                if let Err(e) = remove_unit_with_dependencies(id.clone(), run_info) {
+                    trace!("Can not remove unit {} yet: {}", id.name, e);
+                    run_info.pending_reload.insert(id, PendingReload::Removed);
+                }
+            }
+        }
+    }
+}
@@ -18,12 +18,17 @@ pub fn remove_unit_with_dependencies(
             &mut depending_by_name_ids,
         );
     }
-    for id in depending_by_name_ids {
-        check_deactivated_recursive(id, run_info)?;
+    for id in &depending_by_name_ids {
+        check_deactivated_recursive(id.clone(), run_info)?;
     }
 
     remove_with_depending_units(remove_id.clone(), &mut run_info.unit_table);
 
+    // stop listening on the filedescriptors of all removed units
+    for id in std::iter::once(remove_id).chain(depending_by_name_ids) {
+        run_info.reactor.deregister_unit(&id);
+    }
+
     Ok(())
 }
 